@@ -0,0 +1,59 @@
+//! Compares updating the context of a job with a 1MB payload through `Storage::update`, which
+//! re-encodes the whole job, and `RedisStorage::update_context`, which only writes the context.
+//!
+//! Run with `REDIS_URL=redis://127.0.0.1/ cargo bench --bench update_context`.
+
+use apalis::prelude::Storage;
+use apalis_redis::{connect, Config, RedisStorage};
+use criterion::{criterion_group, criterion_main, Criterion};
+use tokio::runtime::Runtime;
+
+const PAYLOAD_SIZE: usize = 1024 * 1024;
+
+fn update_context(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
+    let (storage, job) = rt.block_on(async {
+        let conn = connect(redis_url).await.unwrap();
+        let mut storage: RedisStorage<String> = RedisStorage::new_with_config(
+            conn,
+            Config::default().set_namespace("apalis::bench::update_context"),
+        );
+        storage.clear().await.unwrap();
+        let parts = storage.push("x".repeat(PAYLOAD_SIZE)).await.unwrap();
+        let job = storage.fetch_by_id(&parts.task_id).await.unwrap().unwrap();
+        (storage, job)
+    });
+
+    let mut group = c.benchmark_group("1MB payload");
+    group.bench_function("update", |b| {
+        b.to_async(&rt).iter(|| {
+            let mut storage = storage.clone();
+            let mut job = job.clone();
+            async move {
+                job.parts.context.set_priority(1);
+                storage.update(job).await.unwrap();
+            }
+        })
+    });
+    group.bench_function("update_context", |b| {
+        b.to_async(&rt).iter(|| {
+            let mut storage = storage.clone();
+            let mut context = job.parts.context.clone();
+            let task_id = job.parts.task_id.clone();
+            async move {
+                context.set_priority(1);
+                storage.update_context(&task_id, context).await.unwrap();
+            }
+        })
+    });
+    group.finish();
+
+    rt.block_on(async {
+        let mut storage = storage.clone();
+        storage.clear().await.unwrap();
+    });
+}
+
+criterion_group!(benches, update_context);
+criterion_main!(benches);
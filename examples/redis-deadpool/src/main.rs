@@ -2,9 +2,9 @@ use std::time::Duration;
 
 use anyhow::Result;
 use apalis::prelude::*;
-use apalis_redis::RedisStorage;
+use apalis_redis::{RedisConn, RedisStorage};
 
-use deadpool_redis::{Config, Connection, Runtime};
+use deadpool_redis::{Config, Runtime};
 use email_service::{send_email, Email};
 use tracing::info;
 
@@ -18,8 +18,7 @@ async fn main() -> Result<()> {
 
     let cfg = Config::from_url("redis://127.0.0.1/");
     let pool = cfg.create_pool(Some(Runtime::Tokio1)).unwrap();
-    let conn = pool.get().await.unwrap();
-    let mut storage = RedisStorage::new_with_config(conn, config);
+    let mut storage = RedisStorage::new_with_config(RedisConn::Pooled(pool.clone()), config);
     // This can be in another part of the program
     produce_jobs(&mut storage).await?;
 
@@ -41,7 +40,7 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn produce_jobs(storage: &mut RedisStorage<Email, Connection>) -> Result<()> {
+async fn produce_jobs(storage: &mut RedisStorage<Email, RedisConn>) -> Result<()> {
     for index in 0..10 {
         storage
             .push(Email {
@@ -23,8 +23,6 @@ struct RedisMq<T, C = JsonCodec<Vec<u8>>> {
     codec: PhantomData<C>,
 }
 
-type RedisMqMessage<Req> = (Req, RedisMqContext);
-
 #[derive(Clone, Debug, Serialize, Deserialize, Default)]
 pub struct RedisMqContext {
     max_attempts: usize,
@@ -1,31 +1,14 @@
-use std::{sync::Arc, time::Duration};
+use std::time::Duration;
 
 use anyhow::Result;
 use apalis::prelude::*;
+use apalis_core::codec::msgpack::MessagePackCodec;
 use apalis_redis::RedisStorage;
 
 use apalis_redis::ConnectionManager;
 use email_service::{send_email, Email};
-use serde::{Deserialize, Serialize};
 use tracing::info;
 
-struct MessagePack;
-
-impl Codec for MessagePack {
-    type Compact = Vec<u8>;
-    type Error = Error;
-    fn encode<T: Serialize>(input: T) -> Result<Vec<u8>, Self::Error> {
-        rmp_serde::to_vec(&input).map_err(|e| Error::SourceError(Arc::new(Box::new(e))))
-    }
-
-    fn decode<O>(compact: Vec<u8>) -> Result<O, Self::Error>
-    where
-        O: for<'de> Deserialize<'de>,
-    {
-        rmp_serde::from_slice(&compact).map_err(|e| Error::SourceError(Arc::new(Box::new(e))))
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<()> {
     std::env::set_var("RUST_LOG", "debug");
@@ -37,7 +20,7 @@ async fn main() -> Result<()> {
         .await
         .expect("Could not connect");
     let config = apalis_redis::Config::default().set_namespace("apalis_redis-with-msg-pack");
-    let storage = RedisStorage::new_with_codec::<MessagePack>(conn, config);
+    let storage = RedisStorage::new_with_codec::<MessagePackCodec>(conn, config);
     // This can be in another part of the program
     produce_jobs(storage.clone()).await?;
 
@@ -59,7 +42,7 @@ async fn main() -> Result<()> {
 }
 
 async fn produce_jobs(
-    mut storage: RedisStorage<Email, ConnectionManager, MessagePack>,
+    mut storage: RedisStorage<Email, ConnectionManager, MessagePackCodec>,
 ) -> Result<()> {
     for index in 0..10 {
         storage
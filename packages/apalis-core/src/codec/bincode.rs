@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use crate::codec::Codec;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+
+/// The version of the payload layout, written as the first byte of every payload
+const VERSION: u8 = 1;
+
+/// Bincode encoding and decoding
+///
+/// Bincode is compact but not self-describing: fields are written in declaration order without
+/// their names, so adding, removing or reordering fields of a job, or of the context of a backend,
+/// makes the jobs already stored undecodable. Prefer it for queues where producers and consumers
+/// are deployed together, and drain the queue before changing the job types.
+///
+/// Payloads start with a version byte so that payloads written with another layout fail to
+/// decode with a clear error instead of producing garbage.
+#[derive(Debug, Clone, Default)]
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    type Compact = Vec<u8>;
+    type Error = Error;
+    fn encode<T: Serialize>(input: T) -> Result<Vec<u8>, Self::Error> {
+        let mut bytes = vec![VERSION];
+        bincode::serialize_into(&mut bytes, &input).map_err(|e| Error::SourceError(Arc::new(e)))?;
+        Ok(bytes)
+    }
+
+    fn decode<O>(compact: Vec<u8>) -> Result<O, Self::Error>
+    where
+        O: for<'de> Deserialize<'de>,
+    {
+        match compact.split_first() {
+            Some((&VERSION, payload)) => {
+                bincode::deserialize(payload).map_err(|e| Error::SourceError(Arc::new(e)))
+            }
+            Some((version, _)) => Err(Error::SourceError(Arc::new(
+                format!("unsupported bincode payload version {version}, expected {VERSION}").into(),
+            ))),
+            None => Err(Error::SourceError(Arc::new("empty bincode payload".into()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::Request;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        street: String,
+        zip: Option<u32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Customer {
+        id: u64,
+        name: String,
+        addresses: Vec<Address>,
+        tags: Option<Vec<String>>,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    struct Context {
+        priority: i64,
+        lock_by: Option<String>,
+    }
+
+    fn customer() -> Customer {
+        Customer {
+            id: 42,
+            name: "Ada".to_owned(),
+            addresses: vec![
+                Address {
+                    street: "1 Main St".to_owned(),
+                    zip: Some(12345),
+                },
+                Address {
+                    street: "2 Side St".to_owned(),
+                    zip: None,
+                },
+            ],
+            tags: Some(vec!["vip".to_owned()]),
+        }
+    }
+
+    #[test]
+    fn nested_struct_round_trips() {
+        let bytes = BincodeCodec::encode(customer()).unwrap();
+        assert_eq!(bytes[0], VERSION);
+        let decoded: Customer = BincodeCodec::decode(bytes).unwrap();
+        assert_eq!(decoded, customer());
+    }
+
+    #[test]
+    fn request_round_trips() {
+        let mut req: Request<Customer, Context> = Request::new(customer());
+        req.parts.context.priority = 3;
+        req.parts.attempt.increment();
+        let bytes = BincodeCodec::encode(&req).unwrap();
+        let decoded: Request<Customer, Context> = BincodeCodec::decode(bytes).unwrap();
+        assert_eq!(decoded.args, customer());
+        assert_eq!(decoded.parts.task_id, req.parts.task_id);
+        assert_eq!(decoded.parts.attempt.current(), 1);
+        assert_eq!(decoded.parts.context, req.parts.context);
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut bytes = BincodeCodec::encode(customer()).unwrap();
+        bytes[0] = VERSION + 1;
+        let err = BincodeCodec::decode::<Customer>(bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Encountered an error during streaming"));
+        let Error::SourceError(source) = err else {
+            panic!("expected a source error");
+        };
+        assert!(source
+            .to_string()
+            .contains("unsupported bincode payload version"));
+        assert!(BincodeCodec::decode::<Customer>(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::codec::Codec;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+
+/// Marks compressed payloads. `0xC1` is never used by MessagePack and cannot start valid UTF-8,
+/// so it cannot be confused with the output of the bundled codecs.
+const MAGIC: u8 = 0xC1;
+/// The version of the compressed format
+const VERSION: u8 = 1;
+/// The zstd compression level
+const LEVEL: i32 = 3;
+
+/// Wraps a codec and compresses its output using zstd.
+///
+/// Payloads that were not written by this codec are passed to the inner codec unchanged,
+/// allowing compression to be enabled on a storage that already has jobs.
+#[derive(Debug, Clone, Default)]
+pub struct CompressedCodec<C> {
+    _c: PhantomData<C>,
+}
+
+impl<C> Codec for CompressedCodec<C>
+where
+    C: Codec<Compact = Vec<u8>>,
+{
+    type Compact = Vec<u8>;
+    type Error = Error;
+    fn encode<T: Serialize>(input: T) -> Result<Vec<u8>, Self::Error> {
+        let bytes = C::encode(input).map_err(|e| Error::SourceError(Arc::new(e.into())))?;
+        let mut compressed = vec![MAGIC, VERSION];
+        zstd::stream::copy_encode(bytes.as_slice(), &mut compressed, LEVEL)
+            .map_err(|e| Error::SourceError(Arc::new(Box::new(e))))?;
+        Ok(compressed)
+    }
+
+    fn decode<O>(compact: Vec<u8>) -> Result<O, Self::Error>
+    where
+        O: for<'de> Deserialize<'de>,
+    {
+        let bytes = match compact.as_slice() {
+            [MAGIC, VERSION, compressed @ ..] => zstd::stream::decode_all(compressed)
+                .map_err(|e| Error::SourceError(Arc::new(Box::new(e))))?,
+            _ => compact,
+        };
+        C::decode(bytes).map_err(|e| Error::SourceError(Arc::new(e.into())))
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use crate::codec::json::JsonCodec;
+
+    use super::*;
+
+    type Compressed = CompressedCodec<JsonCodec<Vec<u8>>>;
+
+    #[test]
+    fn compressible_payload_shrinks() {
+        let payload = "<p>Hello world</p>".repeat(1000);
+        let plain = JsonCodec::<Vec<u8>>::encode(&payload).unwrap();
+        let compressed = Compressed::encode(&payload).unwrap();
+        assert!(compressed.len() < plain.len());
+        let decoded: String = Compressed::decode(compressed).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn random_payload_round_trips() {
+        let mut state: u64 = 42;
+        let payload: Vec<u8> = (0..4096)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect();
+        let compressed = Compressed::encode(&payload).unwrap();
+        let decoded: Vec<u8> = Compressed::decode(compressed).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn uncompressed_payload_passes_through() {
+        let plain = JsonCodec::<Vec<u8>>::encode("legacy").unwrap();
+        let decoded: String = Compressed::decode(plain).unwrap();
+        assert_eq!(decoded, "legacy");
+    }
+}
@@ -48,3 +48,15 @@ impl<Compact> Codec for NoopCodec<Compact> {
 /// Encoding for tasks using json
 #[cfg(feature = "json")]
 pub mod json;
+
+/// Encoding for tasks using MessagePack
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+/// Encoding for tasks using bincode
+#[cfg(feature = "bincode")]
+pub mod bincode;
+
+/// Compression for the output of other codecs
+#[cfg(feature = "compression")]
+pub mod compressed;
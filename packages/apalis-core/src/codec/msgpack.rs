@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use crate::codec::Codec;
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+
+/// MessagePack encoding and decoding
+#[derive(Debug, Clone, Default)]
+pub struct MessagePackCodec;
+
+impl Codec for MessagePackCodec {
+    type Compact = Vec<u8>;
+    type Error = Error;
+    fn encode<T: Serialize>(input: T) -> Result<Vec<u8>, Self::Error> {
+        rmp_serde::to_vec_named(&input).map_err(|e| Error::SourceError(Arc::new(Box::new(e))))
+    }
+
+    fn decode<O>(compact: Vec<u8>) -> Result<O, Self::Error>
+    where
+        O: for<'de> Deserialize<'de>,
+    {
+        rmp_serde::from_slice(&compact).map_err(|e| Error::SourceError(Arc::new(Box::new(e))))
+    }
+}
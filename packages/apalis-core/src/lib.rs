@@ -211,7 +211,6 @@ pub mod test_utils {
     ///    }
     ///}
     /// ````
-
     impl<B, Req, Res, Ctx> TestWrapper<B, Request<Req, Ctx>, Res>
     where
         B: Backend<Request<Req, Ctx>> + Send + Sync + 'static + Clone,
@@ -300,6 +299,7 @@ pub mod test_utils {
         }
 
         /// Gets the current state of results
+        #[allow(clippy::type_complexity, deprecated)]
         pub fn try_execute_next(
             &mut self,
         ) -> Result<Option<(TaskId, Result<String, String>)>, TryRecvError> {
@@ -1,7 +1,4 @@
-use std::{
-    fmt::{self, Debug, Formatter},
-    sync::Arc,
-};
+use std::fmt::{self, Debug, Formatter};
 
 use futures::{future::BoxFuture, Future, FutureExt};
 use tower::{Layer, Service};
@@ -19,6 +16,7 @@ use crate::{
 use self::shutdown::Shutdown;
 
 /// A monitor for coordinating and managing a collection of workers.
+#[derive(Default)]
 pub struct Monitor {
     futures: Vec<BoxFuture<'static, ()>>,
     workers: Vec<Worker<Context>>,
@@ -172,18 +170,6 @@ impl Monitor {
     }
 }
 
-impl Default for Monitor {
-    fn default() -> Self {
-        Self {
-            shutdown: Shutdown::new(),
-            futures: Vec::new(),
-            terminator: None,
-            event_handler: Arc::default(),
-            workers: Vec::new(),
-        }
-    }
-}
-
 impl Monitor {
     /// Creates a new monitor instance.
     ///
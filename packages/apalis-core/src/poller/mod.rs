@@ -8,6 +8,7 @@ pub mod controller;
 pub mod stream;
 
 /// A poller type that allows fetching from a stream and a heartbeat future that can be used to do periodic tasks
+#[allow(clippy::manual_non_exhaustive)]
 pub struct Poller<S, L = Identity> {
     /// The stream of jobs
     pub stream: S,
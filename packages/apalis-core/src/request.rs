@@ -35,7 +35,8 @@ pub struct Parts<Ctx> {
     #[serde(skip)]
     pub data: Extensions,
 
-    /// The request's attempts
+    /// The request's attempts, a job encoded without it has not been attempted yet
+    #[serde(default)]
     pub attempt: Attempt,
 
     /// The Context stored by the storage
@@ -109,10 +110,11 @@ impl<T, Ctx> std::ops::DerefMut for Request<T, Ctx> {
 }
 
 /// Represents the state of a job/task
-#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, std::cmp::Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, std::cmp::Eq, Default)]
 pub enum State {
     /// Job is pending
     #[serde(alias = "Latest")]
+    #[default]
     Pending,
     /// Job is in the queue but not ready for execution
     Scheduled,
@@ -126,12 +128,6 @@ pub enum State {
     Killed,
 }
 
-impl Default for State {
-    fn default() -> Self {
-        State::Pending
-    }
-}
-
 impl FromStr for State {
     type Err = Error;
 
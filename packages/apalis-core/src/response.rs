@@ -17,6 +17,7 @@ use crate::{
 /// - `attempt`: An `Attempt` representing how many attempts were made to complete the task.
 /// - `_priv`: A private marker field to prevent external construction of the `Response`.
 #[derive(Debug, Clone)]
+#[allow(clippy::manual_non_exhaustive)]
 pub struct Response<Res> {
     /// The result from a task
     pub inner: Result<Res, Error>,
@@ -125,8 +126,7 @@ impl IntoResponse for bool {
     fn into_response(self) -> std::result::Result<Self, Error> {
         match self {
             true => Ok(true),
-            false => Err(Error::Failed(Arc::new(Box::new(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            false => Err(Error::Failed(Arc::new(Box::new(std::io::Error::other(
                 "Job returned false",
             ))))),
         }
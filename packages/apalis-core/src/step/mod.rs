@@ -242,10 +242,12 @@ where
     fn call(&mut self, req: Request<StepRequest<Compact, Index>, Ctx>) -> Self::Future {
         let transformed_req: Request<Current, Ctx> = {
             Request::new_with_parts(
-                Encode::decode(req.args.step).expect(&format!(
-                    "Could not decode step, expecting {}",
-                    std::any::type_name::<Current>()
-                )),
+                Encode::decode(req.args.step).unwrap_or_else(|_| {
+                    panic!(
+                        "Could not decode step, expecting {}",
+                        std::any::type_name::<Current>()
+                    )
+                }),
                 req.parts,
             )
         };
@@ -62,6 +62,7 @@ pub trait Storage: Backend<Request<Self::Job, Self::Context>> {
     fn len(&mut self) -> impl Future<Output = Result<i64, Self::Error>> + Send;
 
     /// Fetch a job given an id
+    #[allow(clippy::type_complexity)]
     fn fetch_by_id(
         &mut self,
         job_id: &TaskId,
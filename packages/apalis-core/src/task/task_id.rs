@@ -29,6 +29,12 @@ impl Default for TaskId {
     }
 }
 
+impl From<Ulid> for TaskId {
+    fn from(ulid: Ulid) -> Self {
+        TaskId(ulid)
+    }
+}
+
 impl FromStr for TaskId {
     type Err = ulid::DecodeError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
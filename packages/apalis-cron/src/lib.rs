@@ -126,7 +126,7 @@ where
     }
 }
 
-fn build_stream<Tz: TimeZone, Req>(
+fn build_stream<Tz, Req>(
     timezone: &Tz,
     schedule: &Schedule,
 ) -> RequestStream<Request<Req, CronContext<Tz>>>
@@ -0,0 +1,92 @@
+-- KEYS[1]: this consumer's inflight set
+-- KEYS[2]: the done jobs set
+-- KEYS[3]: the job data hash
+-- KEYS[4]: the unique job hash
+-- KEYS[5]: the failed jobs set
+-- KEYS[6]: the completed jobs list
+-- KEYS[7]: the job fetch time hash
+-- KEYS[8]: the job duration hash
+-- KEYS[9]: the job result hash
+-- KEYS[10]: the job group hash
+-- KEYS[11]: the group stats hash
+-- KEYS[12]: the done details set
+-- KEYS[13]: the job error hash
+-- KEYS[14]: the job priority hash
+
+-- ARGV[1]: the job ID
+-- ARGV[2]: the current time
+-- ARGV[3]: the result of the job, or the error reason if it failed
+-- ARGV[4]: the unique key of the job, empty if it has none
+-- ARGV[5]: the outcome of the job, either "done" or "failed"
+-- ARGV[6]: the current time in milliseconds
+-- ARGV[7]: "1" to record the details of a done job in the done details set
+-- ARGV[8]: the id of the worker acknowledging the job
+-- ARGV[9]: the attempts made at the job, empty if unknown
+-- ARGV[10]: the max number of ids kept in the completed jobs list, 0 to keep none
+
+-- Returns: a status as the first element, "ok" if the job was acknowledged, "not_found" if it is
+-- not in this consumer's inflight set
+
+-- Remove the job from this consumer's inflight set
+local removed = redis.call("srem", KEYS[1], ARGV[1])
+if removed == 1 then
+  if ARGV[5] == "failed" then
+    -- Push the job on to the failed jobs set
+    redis.call("zadd", KEYS[5], ARGV[2], ARGV[1])
+
+    -- A failed job has no result, keep its error instead
+    redis.call("hdel", KEYS[9], ARGV[1])
+    if ARGV[3] ~= "" then
+      redis.call("hset", KEYS[13], ARGV[1], ARGV[3])
+    end
+  else
+    -- Push the job on to the done jobs set
+    redis.call("zadd", KEYS[2], ARGV[2], ARGV[1])
+
+    -- Notify completed job subscribers, dropping the oldest ids nobody took
+    local backlog = tonumber(ARGV[10])
+    if backlog > 0 then
+      redis.call("rpush", KEYS[6], ARGV[1])
+      redis.call("ltrim", KEYS[6], -backlog, -1)
+    end
+
+    -- Keep the result of the job, unless one was set while it ran
+    redis.call("hsetnx", KEYS[9], ARGV[1], ARGV[3])
+
+    -- A done job is never enqueued again
+    redis.call("hdel", KEYS[14], ARGV[1])
+  end
+  -- Record how long the job ran since it was fetched
+  local duration_ms = nil
+  local started_at = tonumber(redis.call("hget", KEYS[7], ARGV[1]))
+  if started_at then
+    duration_ms = math.max(tonumber(ARGV[6]) - started_at, 0)
+    redis.call("hset", KEYS[8], ARGV[1], duration_ms)
+    redis.call("hdel", KEYS[7], ARGV[1])
+  end
+
+  if ARGV[5] == "done" and ARGV[7] == "1" then
+    redis.call("zadd", KEYS[12], ARGV[2], cjson.encode({
+      task_id = ARGV[1],
+      worker_id = ARGV[8],
+      attempts = tonumber(ARGV[9]),
+      duration_ms = duration_ms,
+      done_at = tonumber(ARGV[2])
+    }))
+  end
+
+  -- Release the unique key if this job still holds it, whether it is done or failed
+  if ARGV[4] ~= "" and redis.call("hget", KEYS[4], ARGV[4]) == ARGV[1] then
+    redis.call("hdel", KEYS[4], ARGV[4])
+  end
+
+  -- Count the job as finished in its group
+  local group = redis.call("hget", KEYS[10], ARGV[1])
+  if group then
+    redis.call("hincrby", KEYS[11], group .. ":" .. ARGV[5], 1)
+    redis.call("hdel", KEYS[10], ARGV[1])
+  end
+  return {"ok"}
+end
+
+return {"not_found"}
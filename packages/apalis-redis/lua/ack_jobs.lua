@@ -0,0 +1,88 @@
+-- KEYS[1]: this consumer's inflight set
+-- KEYS[2]: the done jobs set
+-- KEYS[3]: the job data hash
+-- KEYS[4]: the unique job hash
+-- KEYS[5]: the completed jobs list
+-- KEYS[6]: the job fetch time hash
+-- KEYS[7]: the job duration hash
+-- KEYS[8]: the job result hash
+-- KEYS[9]: the job group hash
+-- KEYS[10]: the group stats hash
+-- KEYS[11]: the done details set
+-- KEYS[12]: the job priority hash
+
+-- ARGV[1]: the current time
+-- ARGV[2]: the current time in milliseconds
+-- ARGV[3]: "1" to record the details of the jobs in the done details set
+-- ARGV[4]: the id of the worker acknowledging the jobs
+-- ARGV[5]: the max number of ids kept in the completed jobs list, 0 to keep none
+-- ARGV[6..]: groups of the job ID, the job data with its updated attempts, empty to keep the
+-- stored data, the result of the job, its unique key, empty if it has none, and the attempts made
+-- at the job
+
+-- Returns: the number of jobs that were acknowledged
+
+local backlog = tonumber(ARGV[5])
+local count = 0
+
+for i = 6, table.getn(ARGV), 5 do
+  local id = ARGV[i]
+
+  -- Remove the job from this consumer's inflight set
+  if redis.call("srem", KEYS[1], id) == 1 then
+    if ARGV[i + 1] ~= "" then
+      redis.call("hset", KEYS[3], id, ARGV[i + 1])
+    end
+
+    -- Push the job on to the done jobs set
+    redis.call("zadd", KEYS[2], ARGV[1], id)
+
+    -- Notify completed job subscribers, dropping the oldest ids nobody took
+    if backlog > 0 then
+      redis.call("rpush", KEYS[5], id)
+      redis.call("ltrim", KEYS[5], -backlog, -1)
+    end
+
+    -- Keep the result of the job, unless one was set while it ran
+    redis.call("hsetnx", KEYS[8], id, ARGV[i + 2])
+
+    -- A done job is never enqueued again
+    redis.call("hdel", KEYS[12], id)
+
+    -- Record how long the job ran since it was fetched
+    local duration_ms = nil
+    local started_at = tonumber(redis.call("hget", KEYS[6], id))
+    if started_at then
+      duration_ms = math.max(tonumber(ARGV[2]) - started_at, 0)
+      redis.call("hset", KEYS[7], id, duration_ms)
+      redis.call("hdel", KEYS[6], id)
+    end
+
+    if ARGV[3] == "1" then
+      redis.call("zadd", KEYS[11], ARGV[1], cjson.encode({
+        task_id = id,
+        worker_id = ARGV[4],
+        attempts = tonumber(ARGV[i + 4]),
+        duration_ms = duration_ms,
+        done_at = tonumber(ARGV[1])
+      }))
+    end
+
+    -- Release the unique key if this job still holds it
+    local unique_key = ARGV[i + 3]
+    if unique_key ~= "" and redis.call("hget", KEYS[4], unique_key) == id then
+      redis.call("hdel", KEYS[4], unique_key)
+    end
+
+    -- Count the job as done in its group
+    local group = redis.call("hget", KEYS[9], id)
+    if group then
+      redis.call("hincrby", KEYS[10], group .. ":done", 1)
+      redis.call("hdel", KEYS[9], id)
+    end
+
+    count = count + 1
+  end
+end
+
+return count
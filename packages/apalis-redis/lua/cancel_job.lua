@@ -0,0 +1,43 @@
+-- KEYS[1]: the active job set
+-- KEYS[2]: the scheduled job set
+-- KEYS[3]: the job data hash
+-- KEYS[4]: the job priority hash
+-- KEYS[5]: the job expiry hash
+-- KEYS[6]: the job fetch time hash
+-- KEYS[7]: the job progress hash
+-- KEYS[8]: the job error hash
+-- KEYS[9]: the job duration hash
+-- KEYS[10]: the job result hash
+-- KEYS[11]: the job group hash
+-- KEYS[12]: the group stats hash
+-- KEYS[13]: the job context hash
+
+-- ARGV[1]: the job ID
+
+-- Returns: 1 if the job was pending and removed, 0 otherwise
+
+-- Only pending jobs are cancelled, inflight jobs are left to their worker
+local removed = redis.call("zrem", KEYS[1], ARGV[1]) + redis.call("zrem", KEYS[2], ARGV[1])
+
+if removed > 0 then
+  -- Deleting the data also releases the unique key held by the job
+  redis.call("hdel", KEYS[3], ARGV[1])
+  redis.call("hdel", KEYS[4], ARGV[1])
+  redis.call("hdel", KEYS[5], ARGV[1])
+  redis.call("hdel", KEYS[6], ARGV[1])
+  redis.call("hdel", KEYS[7], ARGV[1])
+  redis.call("hdel", KEYS[8], ARGV[1])
+  redis.call("hdel", KEYS[9], ARGV[1])
+  redis.call("hdel", KEYS[10], ARGV[1])
+  redis.call("hdel", KEYS[13], ARGV[1])
+
+  -- A cancelled job will never complete, so it counts as failed in its group
+  local group = redis.call("hget", KEYS[11], ARGV[1])
+  if group then
+    redis.call("hincrby", KEYS[12], group .. ":failed", 1)
+    redis.call("hdel", KEYS[11], ARGV[1])
+  end
+  return 1
+end
+
+return 0
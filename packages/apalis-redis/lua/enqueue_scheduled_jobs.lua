@@ -1,6 +1,7 @@
 -- KEYS[1]: the scheduled jobs set
--- KEYS[2]: the active job list
+-- KEYS[2]: the active job set
 -- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
 
 -- ARGV[1]: the current timestamp
 -- ARGV[2]: the max number of jobs to schedule
@@ -12,8 +13,11 @@ local job_ids = redis.call("zrangebyscore", KEYS[1], 0, ARGV[1], "LIMIT", 0, ARG
 local count = table.getn(job_ids)
 
 if count > 0 then
-  -- Push them on to the active list
-  redis.call("rpush", KEYS[2], unpack(job_ids))
+  -- Push them on to the active set
+  for _,job_id in ipairs(job_ids) do
+    local priority = tonumber(redis.call("hget", KEYS[4], job_id)) or 0
+    redis.call("zadd", KEYS[2], -priority, job_id)
+  end
 
   -- Remove the jobs from the scheduled set
   redis.call("zremrangebyrank", KEYS[1], 0, count - 1)
@@ -1,13 +1,27 @@
 -- KEYS[1]: the active consumers set
--- KEYS[2]: the active job list
+-- KEYS[2]: the active job set
 -- KEYS[3]: this consumer's inflight set
 -- KEYS[4]: the job data hash
 -- KEYS[5]: the signal list
+-- KEYS[6]: the job expiry hash
+-- KEYS[7]: the dead jobs set
+-- KEYS[8]: the job fetch time hash, in milliseconds
+-- KEYS[9]: the dead letter list
+-- KEYS[10]: the dead reason hash
+-- KEYS[11]: the job context hash
+-- KEYS[12]: the job group hash
+-- KEYS[13]: the group stats hash
+-- KEYS[14]: the job priority hash
 
 -- ARGV[1]: the max number of jobs to get
 -- ARGV[2]: this consumer's inflight set
+-- ARGV[3]: the current time
+-- ARGV[4]: the current time in milliseconds
+-- ARGV[5]: the fetch order, either "fifo" or "lifo"
+-- ARGV[6]: the max number of ids kept in the dead letter list, 0 to keep none
 
--- Returns: the jobs
+-- Returns: the ids, data and contexts of the jobs, interleaved, where the context is nil unless it
+-- was updated apart from the data, and the ids of the jobs that expired
 
 -- Ensure the consumer is registered
 local registered = redis.call("zscore", KEYS[1], ARGV[2])
@@ -15,25 +29,95 @@ if not registered then
   error("consumer not registered")
 end
 
--- Get the jobs out of the active job list
-local job_ids = redis.call("lrange", KEYS[2], 0, ARGV[1] - 1)
+-- Nothing to fetch, a stop of -1 would otherwise take the whole active job set
+local limit = tonumber(ARGV[1])
+if limit < 1 then
+  return {{}, {}}
+end
+
+-- Get the highest priority jobs out of the active job set
+local job_ids
+if ARGV[5] == "lifo" then
+  -- Take the most recent jobs of each priority first, jobs of equal priority are ordered by ID,
+  -- which increases in the order the jobs were pushed
+  local min = "-inf"
+  job_ids = {}
+  while table.getn(job_ids) < limit do
+    local top = redis.call("zrangebyscore", KEYS[2], min, "+inf", "WITHSCORES", "LIMIT", 0, 1)
+    if table.getn(top) == 0 then
+      break
+    end
+    local score = top[2]
+    local ids = redis.call("zrevrangebyscore", KEYS[2], score, score, "LIMIT", 0, limit - table.getn(job_ids))
+    for _,job_id in ipairs(ids) do
+      table.insert(job_ids, job_id)
+    end
+    min = "(" .. score
+  end
+else
+  job_ids = redis.call("zrange", KEYS[2], 0, limit - 1)
+end
 local count = table.getn(job_ids)
 local results = {}
+local expired_ids = {}
 
 if count > 0 then
-  -- Add the jobs to this consumer's inflight set
-  redis.call("sadd", KEYS[3], unpack(job_ids))
+  -- Remove the jobs from the active job set, FIFO jobs are the head of the set
+  if ARGV[5] == "lifo" then
+    redis.call("zrem", KEYS[2], unpack(job_ids))
+  else
+    redis.call("zremrangebyrank", KEYS[2], 0, count - 1)
+  end
+
+  -- Drop any jobs that expired while waiting in the active set
+  local now = tonumber(ARGV[3])
+  local live_ids = {}
+  for _,job_id in ipairs(job_ids) do
+    local expires_at = tonumber(redis.call("hget", KEYS[6], job_id))
+    if expires_at and expires_at < now then
+      redis.call("zadd", KEYS[7], now, job_id)
+      redis.call("hset", KEYS[10], job_id, "expired")
+      if tonumber(ARGV[6]) > 0 then
+        redis.call("rpush", KEYS[9], job_id)
+        redis.call("ltrim", KEYS[9], -tonumber(ARGV[6]), -1)
+      end
+      redis.call("hdel", KEYS[6], job_id)
+      redis.call("hdel", KEYS[14], job_id)
+      -- Count the job as failed in its group
+      local group = redis.call("hget", KEYS[12], job_id)
+      if group then
+        redis.call("hincrby", KEYS[13], group .. ":failed", 1)
+        redis.call("hdel", KEYS[12], job_id)
+      end
+      table.insert(expired_ids, job_id)
+    else
+      table.insert(live_ids, job_id)
+    end
+  end
+
+  if table.getn(live_ids) > 0 then
+    -- Add the jobs to this consumer's inflight set
+    redis.call("sadd", KEYS[3], unpack(live_ids))
 
-  -- Remove the jobs from the active job list
-  redis.call("ltrim", KEYS[2], count, -1)
+    -- Stamp when the jobs were fetched so that stale jobs can be reenqueued
+    for _,job_id in ipairs(live_ids) do
+      redis.call("hset", KEYS[8], job_id, ARGV[4])
+    end
 
-  -- Return the job data
-  results = redis.call("hmget", KEYS[4], unpack(job_ids))
+    -- Return the job ids with their data and context
+    local data = redis.call("hmget", KEYS[4], unpack(live_ids))
+    local contexts = redis.call("hmget", KEYS[11], unpack(live_ids))
+    for i,job_id in ipairs(live_ids) do
+      table.insert(results, job_id)
+      table.insert(results, data[i])
+      table.insert(results, contexts[i])
+    end
+  end
 end
 
 -- Signal to the other consumers to wait
-if count < tonumber(ARGV[1]) then
+if count < limit then
   redis.call("del", KEYS[5])
 end
 
-return results
+return {results, expired_ids}
@@ -1,9 +1,20 @@
 -- KEYS[1]: this consumer's inflight set
 -- KEYS[2]: the dead jobs set
--- KEYS[3]: the job data hash
+-- KEYS[3]: the unique job hash
+-- KEYS[4]: the dead letter list
+-- KEYS[5]: the job error hash
+-- KEYS[6]: the dead reason hash
+-- KEYS[7]: the job group hash
+-- KEYS[8]: the group stats hash
+-- KEYS[9]: the job fetch time hash
+-- KEYS[10]: the job priority hash
+-- KEYS[11]: the job progress hash
 -- ARGV[1]: the job ID
 -- ARGV[2]: the current time
--- ARGV[3]: the result of the job
+-- ARGV[3]: the unique key of the job, empty if it has none
+-- ARGV[4]: the error of the failed attempt, empty if unknown
+-- ARGV[5]: why the job died
+-- ARGV[6]: the max number of ids kept in the dead letter list, 0 to keep none
 -- Returns: nil
 -- Remove the job from this consumer's inflight set
 local removed = redis.call("srem", KEYS[1], ARGV[1])
@@ -11,10 +22,35 @@ local removed = redis.call("srem", KEYS[1], ARGV[1])
 if removed == 1 then
     -- Push the job on to the dead jobs set
     redis.call("zadd", KEYS[2], ARGV[2], ARGV[1])
+    redis.call("hset", KEYS[6], ARGV[1], ARGV[5])
+    redis.call("hdel", KEYS[9], ARGV[1])
+    redis.call("hdel", KEYS[11], ARGV[1])
+    -- Replaying the job restores its priority from its data
+    redis.call("hdel", KEYS[10], ARGV[1])
 
-    -- Save the result of the job
-    local ns = "::result"
-    redis.call("hmset", KEYS[3] .. ns, ARGV[1], ARGV[3])
+    -- Notify dead letter subscribers, dropping the oldest ids nobody took
+    local backlog = tonumber(ARGV[6])
+    if backlog > 0 then
+        redis.call("rpush", KEYS[4], ARGV[1])
+        redis.call("ltrim", KEYS[4], -backlog, -1)
+    end
+
+    -- Keep the error of the failed attempt
+    if ARGV[4] ~= "" then
+        redis.call("hset", KEYS[5], ARGV[1], ARGV[4])
+    end
+
+    -- Release the unique key if this job still holds it
+    if ARGV[3] ~= "" and redis.call("hget", KEYS[3], ARGV[3]) == ARGV[1] then
+        redis.call("hdel", KEYS[3], ARGV[3])
+    end
+
+    -- Count the job as failed in its group
+    local group = redis.call("hget", KEYS[7], ARGV[1])
+    if group then
+        redis.call("hincrby", KEYS[8], group .. ":failed", 1)
+        redis.call("hdel", KEYS[7], ARGV[1])
+    end
 
     return 1
 end
@@ -0,0 +1,27 @@
+-- KEYS[1]: the active job set
+-- KEYS[2]: the job priority hash
+-- KEYS[3]: the signal list
+
+-- Returns: the number of jobs moved from a list holding the active jobs into the active job set,
+-- 0 if the active jobs are already held in a sorted set
+
+-- Queues created before the active jobs were sorted by priority hold them in a list
+if redis.call("type", KEYS[1]).ok ~= "list" then
+  return 0
+end
+
+local job_ids = redis.call("lrange", KEYS[1], 0, -1)
+redis.call("del", KEYS[1])
+
+for _, job_id in ipairs(job_ids) do
+  local priority = tonumber(redis.call("hget", KEYS[2], job_id)) or 0
+  redis.call("zadd", KEYS[1], -priority, job_id)
+end
+
+if table.getn(job_ids) > 0 then
+  -- Signal that there are jobs in the queue
+  redis.call("del", KEYS[3])
+  redis.call("lpush", KEYS[3], 1)
+end
+
+return table.getn(job_ids)
@@ -1,22 +1,40 @@
 -- KEYS[1]: the job data hash
--- KEYS[2]: the active job list
+-- KEYS[2]: the active job set
 -- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
+-- KEYS[5]: the job expiry hash
 
 -- ARGV[1]: the job ID
 -- ARGV[2]: the serialized job data
+-- ARGV[3]: the job priority
+-- ARGV[4]: the time after which the job expires, 0 if it never expires
 
--- Returns: 1 if the job was newly enqueued, 0 if it already exists
+-- Returns: a status as the first element, "ok" if the job was newly enqueued, "conflict" if a job
+-- with the same ID already exists
 
 -- Set job data in hash
 local set = redis.call("hsetnx", KEYS[1], ARGV[1], ARGV[2])
 
 if set == 1 then
-  -- If it was set, push the job on to the active list
-  redis.call("rpush", KEYS[2], ARGV[1])
+  -- Remember the priority so that the job can be re-enqueued with it
+  local priority = tonumber(ARGV[3])
+  if priority ~= 0 then
+    redis.call("hset", KEYS[4], ARGV[1], priority)
+  end
+
+  -- Remember when the job expires so that it can be dropped when fetched
+  if tonumber(ARGV[4]) ~= 0 then
+    redis.call("hset", KEYS[5], ARGV[1], ARGV[4])
+  end
+
+  -- If it was set, push the job on to the active set
+  redis.call("zadd", KEYS[2], -priority, ARGV[1])
 
   -- Signal that there are jobs in the queue
   redis.call("del", KEYS[3])
   redis.call("lpush", KEYS[3], 1)
+
+  return {"ok"}
 end
 
-return set
+return {"conflict"}
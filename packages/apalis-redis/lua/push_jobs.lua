@@ -0,0 +1,50 @@
+-- KEYS[1]: the job data hash
+-- KEYS[2]: the active job set
+-- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
+-- KEYS[5]: the job expiry hash
+-- KEYS[6]: the job group hash
+-- KEYS[7]: the group stats hash
+
+-- ARGV[]: groups of the job ID, the serialized job data, the job priority, the time after which
+-- the job expires, 0 if it never expires, and the group of the job, empty if it has none
+
+-- Returns: the number of jobs that were newly enqueued
+
+local count = 0
+
+for i = 1, table.getn(ARGV), 5 do
+  -- Set job data in hash
+  local set = redis.call("hsetnx", KEYS[1], ARGV[i], ARGV[i + 1])
+
+  if set == 1 then
+    -- Remember the priority so that the job can be re-enqueued with it
+    local priority = tonumber(ARGV[i + 2])
+    if priority ~= 0 then
+      redis.call("hset", KEYS[4], ARGV[i], priority)
+    end
+
+    -- Remember when the job expires so that it can be dropped when fetched
+    if tonumber(ARGV[i + 3]) ~= 0 then
+      redis.call("hset", KEYS[5], ARGV[i], ARGV[i + 3])
+    end
+
+    -- Count the job as a member of its group until it finishes
+    if ARGV[i + 4] ~= "" then
+      redis.call("hset", KEYS[6], ARGV[i], ARGV[i + 4])
+      redis.call("hincrby", KEYS[7], ARGV[i + 4] .. ":total", 1)
+    end
+
+    -- If it was set, push the job on to the active set
+    redis.call("zadd", KEYS[2], -priority, ARGV[i])
+    count = count + 1
+  end
+end
+
+if count > 0 then
+  -- Signal that there are jobs in the queue
+  redis.call("del", KEYS[3])
+  redis.call("lpush", KEYS[3], 1)
+end
+
+return count
@@ -0,0 +1,56 @@
+-- KEYS[1]: the job data hash
+-- KEYS[2]: the active job set
+-- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
+-- KEYS[5]: the job expiry hash
+-- KEYS[6]: the unique job hash
+-- KEYS[7]: the done jobs set
+-- KEYS[8]: the dead jobs set
+-- KEYS[9]: the failed jobs set
+
+-- ARGV[1]: the job ID
+-- ARGV[2]: the serialized job data
+-- ARGV[3]: the job priority
+-- ARGV[4]: the time after which the job expires, 0 if it never expires
+-- ARGV[5]: the unique key of the job
+
+-- Returns: the ID of the job holding the unique key
+
+-- Skip the push if a job holding the unique key is still pending
+local existing = redis.call("hget", KEYS[6], ARGV[5])
+if existing then
+  local finished = redis.call("hexists", KEYS[1], existing) == 0
+    or redis.call("zscore", KEYS[7], existing)
+    or redis.call("zscore", KEYS[8], existing)
+    or redis.call("zscore", KEYS[9], existing)
+  if not finished then
+    return existing
+  end
+end
+
+-- Set job data in hash
+local set = redis.call("hsetnx", KEYS[1], ARGV[1], ARGV[2])
+
+if set == 1 then
+  -- Claim the unique key for this job
+  redis.call("hset", KEYS[6], ARGV[5], ARGV[1])
+
+  local priority = tonumber(ARGV[3])
+  if priority ~= 0 then
+    redis.call("hset", KEYS[4], ARGV[1], priority)
+  end
+
+  -- Remember when the job expires so that it can be dropped when fetched
+  if tonumber(ARGV[4]) ~= 0 then
+    redis.call("hset", KEYS[5], ARGV[1], ARGV[4])
+  end
+
+  -- If it was set, push the job on to the active set
+  redis.call("zadd", KEYS[2], -priority, ARGV[1])
+
+  -- Signal that there are jobs in the queue
+  redis.call("del", KEYS[3])
+  redis.call("lpush", KEYS[3], 1)
+end
+
+return ARGV[1]
@@ -1,18 +1,23 @@
 -- KEYS[1]: this consumer's inflight set
--- KEYS[2]: the active jobs list
+-- KEYS[2]: the active jobs set
 -- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
 
 -- ARGV[]: the list of job IDs
 
--- Returns: nil
+-- Returns: the number of jobs that were re-enqueued
+
+local count = 0
 
 for _,job_id in ipairs(ARGV) do
   -- Remove the jobs from this consumer's inflight set
   local removed = redis.call("srem", KEYS[1], job_id)
 
   if removed == 1 then
-    -- Push the job back into the active jobs list
-    redis.call("rpush", KEYS[2], job_id)
+    -- Push the job back into the active jobs set
+    local priority = tonumber(redis.call("hget", KEYS[4], job_id)) or 0
+    redis.call("zadd", KEYS[2], -priority, job_id)
+    count = count + 1
   end
 end
 
@@ -20,4 +25,4 @@ end
 redis.call("del", KEYS[3])
 redis.call("lpush", KEYS[3], 1)
 
-return true
+return count
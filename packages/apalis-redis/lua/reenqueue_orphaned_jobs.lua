@@ -1,24 +1,29 @@
 -- KEYS[1]: the consumer set
--- KEYS[2]: the active job list
+-- KEYS[2]: the active job set
 -- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
 
 -- ARGV[1]: the timestamp before which a consumer is considered expired
 -- ARGV[2]: the max number of jobs to process in a given run
 
--- Returns: 0 if all orphaned jobs have been rescheduled, 1 if there are more to process
+-- Returns: the inflight set of the expired consumer followed by the job ID, for each reenqueued job
 
 -- Find expired consumers
 local consumers = redis.call("zrangebyscore", KEYS[1], 0, ARGV[1], "LIMIT", 0, ARGV[2])
 redis.replicate_commands()
 -- Pull jobs from the consumer's inflight set and reschedule up to limit
 local limit = tonumber(ARGV[2])
+local orphaned = {}
 for _,consumer in ipairs(consumers) do
   local jobs = redis.call("spop", consumer, limit)
   local count = table.getn(jobs)
 
-  -- Push any orphaned jobs on to the message list
-  if count > 0 then
-    redis.call("rpush", KEYS[2], unpack(jobs))
+  -- Push any orphaned jobs on to the active set
+  for _,job_id in ipairs(jobs) do
+    local priority = tonumber(redis.call("hget", KEYS[4], job_id)) or 0
+    redis.call("zadd", KEYS[2], -priority, job_id)
+    table.insert(orphaned, consumer)
+    table.insert(orphaned, job_id)
   end
 
   -- Delete the consumer if all of its jobs have been rescheduled
@@ -42,4 +47,4 @@ if processed > 0 then
   redis.call("lpush", KEYS[3], 1)
 end
 
-return processed
+return orphaned
@@ -0,0 +1,48 @@
+-- KEYS[1]: the consumer set
+-- KEYS[2]: the active job set
+-- KEYS[3]: the signal list
+-- KEYS[4]: the job priority hash
+-- KEYS[5]: the job fetch time hash
+
+-- ARGV[1]: the time in milliseconds before which an inflight job is considered stale
+-- ARGV[2]: the max number of jobs to process in a given run
+
+-- Returns: the number of jobs that were reenqueued
+
+local consumers = redis.call("zrange", KEYS[1], 0, -1)
+redis.replicate_commands()
+local stale_since = tonumber(ARGV[1])
+local limit = tonumber(ARGV[2])
+local count = 0
+
+for _,consumer in ipairs(consumers) do
+  local jobs = redis.call("smembers", consumer)
+
+  -- Push any jobs fetched before the cutoff back on to the active set
+  for _,job_id in ipairs(jobs) do
+    if count >= limit then
+      break
+    end
+    local fetched_at = tonumber(redis.call("hget", KEYS[5], job_id))
+    if fetched_at and fetched_at < stale_since then
+      redis.call("srem", consumer, job_id)
+      redis.call("hdel", KEYS[5], job_id)
+      local priority = tonumber(redis.call("hget", KEYS[4], job_id)) or 0
+      redis.call("zadd", KEYS[2], -priority, job_id)
+      count = count + 1
+    end
+  end
+
+  -- Don't keep looping if we can't process any more jobs
+  if count >= limit then
+    break
+  end
+end
+
+if count > 0 then
+  -- Signal that there are jobs in the queue
+  redis.call("del", KEYS[3])
+  redis.call("lpush", KEYS[3], 1)
+end
+
+return count
@@ -2,9 +2,22 @@
 
 -- ARGV[1]: the current time
 -- ARGV[2]: this consumer's inflight set
+-- ARGV[3]: consumers last seen before this time are pruned, 0 to keep them
 
 -- Returns: nil
 
 -- Update the consumer in the active consumer set
 redis.call("zadd", KEYS[1], ARGV[1], ARGV[2])
+
+-- Prune consumers that have not been seen for a while, those still holding inflight jobs are left
+-- for the orphaned jobs scan to reenqueue
+if tonumber(ARGV[3]) > 0 then
+  local expired = redis.call("zrangebyscore", KEYS[1], "-inf", "(" .. ARGV[3], "LIMIT", 0, 100)
+  for _,consumer in ipairs(expired) do
+    if redis.call("scard", consumer) == 0 then
+      redis.call("zrem", KEYS[1], consumer)
+    end
+  end
+end
+
 return true
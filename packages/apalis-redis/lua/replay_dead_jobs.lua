@@ -0,0 +1,47 @@
+-- KEYS[1]: the dead jobs set
+-- KEYS[2]: the active job set
+-- KEYS[3]: the signal list
+-- KEYS[4]: the job data hash
+-- KEYS[5]: the job priority hash
+-- KEYS[6]: the job expiry hash
+-- KEYS[7]: the job error hash
+-- KEYS[8]: the dead reason hash
+
+-- ARGV[]: groups of the job ID, the job data with its attempts reset, empty to keep the stored
+-- data, and the job priority, empty if the data could not be decoded
+
+-- Returns: the number of jobs that were moved back to the active set
+
+local count = 0
+
+for i = 1, table.getn(ARGV), 3 do
+  local id = ARGV[i]
+
+  -- Only jobs that are still dead are replayed
+  if redis.call("zrem", KEYS[1], id) == 1 then
+    if ARGV[i + 1] ~= "" then
+      redis.call("hset", KEYS[4], id, ARGV[i + 1])
+    end
+
+    -- A replayed job no longer expires and starts without the error it died with
+    redis.call("hdel", KEYS[6], id)
+    redis.call("hdel", KEYS[7], id)
+    redis.call("hdel", KEYS[8], id)
+
+    -- Dead jobs no longer hold a priority so that it can't leak, restore it from the data
+    local priority = tonumber(ARGV[i + 2]) or tonumber(redis.call("hget", KEYS[5], id)) or 0
+    if priority ~= 0 then
+      redis.call("hset", KEYS[5], id, priority)
+    end
+    redis.call("zadd", KEYS[2], -priority, id)
+    count = count + 1
+  end
+end
+
+if count > 0 then
+  -- Signal that there are jobs in the queue
+  redis.call("del", KEYS[3])
+  redis.call("lpush", KEYS[3], 1)
+end
+
+return count
@@ -1,29 +1,64 @@
 -- KEYS[1]: this consumer's inflight set
 -- KEYS[2]: the scheduled jobs set
 -- KEYS[3]: the job data hash
+-- KEYS[4]: the job error hash
+-- KEYS[5]: the job result hash
+-- KEYS[6]: the retry budget set
+-- KEYS[7]: the job context hash
+-- KEYS[8]: the job fetch time hash
 
 -- ARGV[1]: the job ID
 -- ARGV[2]: the time at which to retry
--- ARGV[3]: the result of the job
+-- ARGV[3]: the job, with the context it is retried with
+-- ARGV[4]: the error of the failed attempt, empty if unknown
+-- ARGV[5]: the number of retries allowed per window, 0 for no retry budget
+-- ARGV[6]: the retry budget window in milliseconds
+-- ARGV[7]: the current time in milliseconds
 
--- Returns: nil
+-- Returns: a status as the first element, "ok" if the job was scheduled for a retry, "not_found" if
+-- it is not in this consumer's inflight set
 
 -- Remove the job from this consumer's inflight set
 local removed = redis.call("srem", KEYS[1], ARGV[1])
 
 if removed == 1 then
+  local retry_at = tonumber(ARGV[2])
+  local budget = tonumber(ARGV[5])
+
+  -- Take a slot of the retry budget, deferring the retry until one frees up
+  if budget > 0 then
+    local window = tonumber(ARGV[6])
+    local now = tonumber(ARGV[7])
+    redis.call("zremrangebyscore", KEYS[6], "-inf", now - window)
+    local at = math.max(retry_at * 1000, now)
+    local taken = redis.call("zcount", KEYS[6], "(" .. (at - window), "+inf")
+    if taken >= budget then
+      -- The slot of the oldest of the last `budget` retries frees up one window after it
+      local last = redis.call("zcard", KEYS[6]) - budget
+      local oldest = redis.call("zrange", KEYS[6], last, last, "withscores")
+      at = math.max(at, tonumber(oldest[2]) + window)
+      retry_at = math.ceil(at / 1000)
+    end
+    redis.call("zadd", KEYS[6], at, ARGV[1] .. ":" .. at)
+  end
+
   -- Push the job on to the scheduled set
-  redis.call("zadd", KEYS[2], ARGV[2], ARGV[1])
+  redis.call("zadd", KEYS[2], retry_at, ARGV[1])
 
-  local job = redis.call('HGET', KEYS[3], ARGV[1])
+  -- Save the job so that its context, such as its total attempts, is kept for the next attempt
+  redis.call("hset", KEYS[3], ARGV[1], ARGV[3])
+  redis.call("hdel", KEYS[7], ARGV[1])
+  redis.call("hdel", KEYS[8], ARGV[1])
 
-  -- Reset the job data
-  redis.call("hset", KEYS[3], ARGV[1], job)
+  -- Drop any result set by the failed attempt
+  redis.call("hdel", KEYS[5], ARGV[1])
 
-  -- Save the result of the job
-  local ns = "::result"
-  redis.call("hmset", KEYS[3] .. ns, ARGV[1], ARGV[3] )
+  -- Keep the error of the failed attempt
+  if ARGV[4] ~= "" then
+    redis.call("hset", KEYS[4], ARGV[1], ARGV[4])
+  end
 
+  return {"ok"}
 end
 
-return removed
+return {"not_found"}
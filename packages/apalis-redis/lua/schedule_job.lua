@@ -1,9 +1,13 @@
 -- KEYS[1]: the job data hash
 -- KEYS[2]: the scheduled set
+-- KEYS[3]: the job priority hash
+-- KEYS[4]: the job expiry hash
 
 -- ARGV[1]: the job ID
 -- ARGV[2]: the serialized job data
 -- ARGV[3]: the time to schedule the job
+-- ARGV[4]: the job priority
+-- ARGV[5]: the time after which the job expires, 0 if it never expires
 
 -- Returns: 1 if the job was newly scheduled, 0 if it already exists
 
@@ -12,4 +16,19 @@ redis.call("HDEL", KEYS[1], ARGV[1])
 -- Set job data in hash
 local set = redis.call("hsetnx", KEYS[1], ARGV[1], ARGV[2])
 redis.call("zadd", KEYS[2], ARGV[3], ARGV[1])
+
+-- Remember the priority for when the job is moved to the active set
+local priority = tonumber(ARGV[4])
+if priority ~= 0 then
+  redis.call("hset", KEYS[3], ARGV[1], priority)
+else
+  redis.call("hdel", KEYS[3], ARGV[1])
+end
+
+-- Remember when the job expires so that it can be dropped when fetched
+if tonumber(ARGV[5]) ~= 0 then
+  redis.call("hset", KEYS[4], ARGV[1], ARGV[5])
+else
+  redis.call("hdel", KEYS[4], ARGV[1])
+end
 return set
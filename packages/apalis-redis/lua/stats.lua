@@ -5,7 +5,7 @@
 -- by redis:
 -- https://redis.io/docs/latest/commands/eval/#:~:text=Important%3A%20to,in%20the%20database
 
--- KEYS[1]: the pending jobs set ( aka active job list )
+-- KEYS[1]: the pending jobs set ( aka active job set )
 -- KEYS[2]: the consumer set
 -- KEYS[3]: the dead jobs set
 -- KEYS[4]: the failed jobs set
@@ -28,7 +28,7 @@ for _, consumer_inflight_set in ipairs(consumers) do
     running_count = running_count + redis.call("SCARD", consumer_inflight_set)
 end
 
-local pending_count = redis.call('LLEN', pending_jobs_set)
+local pending_count = redis.call('ZCARD', pending_jobs_set)
 local dead_count = redis.call('ZCARD', dead_jobs_set)
 local failed_count = redis.call('ZCARD', failed_jobs_set)
 local success_count = redis.call('ZCARD', success_jobs_set)
@@ -0,0 +1,34 @@
+-- KEYS[1]: the done jobs set
+-- KEYS[2]: the job data hash
+-- KEYS[3]: the job priority hash
+-- KEYS[4]: the job expiry hash
+-- KEYS[5]: the job fetch time hash
+-- KEYS[6]: the job progress hash
+-- KEYS[7]: the job error hash
+-- KEYS[8]: the job duration hash
+-- KEYS[9]: the job result hash
+-- KEYS[10]: the done details set
+-- KEYS[11]: the job context hash
+
+-- ARGV[1]: jobs done at or before this time are trimmed
+
+-- Returns: the number of trimmed jobs
+
+local job_ids = redis.call("zrangebyscore", KEYS[1], "-inf", ARGV[1])
+
+for _, id in ipairs(job_ids) do
+  redis.call("hdel", KEYS[2], id)
+  redis.call("hdel", KEYS[3], id)
+  redis.call("hdel", KEYS[4], id)
+  redis.call("hdel", KEYS[5], id)
+  redis.call("hdel", KEYS[6], id)
+  redis.call("hdel", KEYS[7], id)
+  redis.call("hdel", KEYS[8], id)
+  redis.call("hdel", KEYS[9], id)
+  redis.call("hdel", KEYS[11], id)
+end
+
+redis.call("zremrangebyscore", KEYS[1], "-inf", ARGV[1])
+redis.call("zremrangebyscore", KEYS[10], "-inf", ARGV[1])
+
+return table.getn(job_ids)
@@ -0,0 +1,15 @@
+-- KEYS[1]: the job data hash
+-- KEYS[2]: the job context hash
+
+-- ARGV[1]: the job ID
+-- ARGV[2]: the encoded context of the job
+
+-- Returns: 1 if the context was stored, 0 if the job does not exist
+
+-- Contexts are only kept for jobs that exist, so vacuums can find them by the job data
+if redis.call("hexists", KEYS[1], ARGV[1]) == 0 then
+  return 0
+end
+
+redis.call("hset", KEYS[2], ARGV[1], ARGV[2])
+return 1
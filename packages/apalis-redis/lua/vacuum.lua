@@ -3,6 +3,18 @@
 -- Define the keys
 local done_list_key = KEYS[1]
 local data_hash = KEYS[2]
+local priority_hash = KEYS[3]
+local expiry_hash = KEYS[4]
+local fetched_hash = KEYS[5]
+local progress_hash = KEYS[6]
+local error_hash = KEYS[7]
+local duration_hash = KEYS[8]
+local result_hash = KEYS[9]
+local done_details_set = KEYS[10]
+local context_hash = KEYS[11]
+
+-- ARGV[1]: "1" to only count the done jobs without deleting them
+local dry_run = ARGV[1] == "1"
 
 -- Iterate through done_list
 local done_list_ids = redis.call('ZRANGE', done_list_key, 0, -1)
@@ -13,14 +25,27 @@ local removed_items_count = 0
 for _, id in ipairs(done_list_ids) do
 
     local is_member = redis.call('HEXISTS', data_hash, id)
-    if is_member == 1 then
+    if is_member == 1 and dry_run then
+        removed_items_count = removed_items_count + 1
+    elseif is_member == 1 then
         -- Remove entry from data_hash
         redis.call('HDEL', data_hash, id)
+        redis.call('HDEL', priority_hash, id)
+        redis.call('HDEL', expiry_hash, id)
+        redis.call('HDEL', fetched_hash, id)
+        redis.call('HDEL', progress_hash, id)
+        redis.call('HDEL', error_hash, id)
+        redis.call('HDEL', duration_hash, id)
+        redis.call('HDEL', result_hash, id)
+        redis.call('HDEL', context_hash, id)
         removed_items_count = removed_items_count + 1
     end
 end
 
 -- Clean the done_list
-redis.call('DEL', done_list_key)
+if not dry_run then
+    redis.call('DEL', done_list_key)
+    redis.call('DEL', done_details_set)
+end
 
 return removed_items_count
@@ -0,0 +1,55 @@
+-- KEYS[1]: the active job set
+-- KEYS[2]: the scheduled job set
+-- KEYS[3]: the done jobs set
+-- KEYS[4]: the failed jobs set
+-- KEYS[5]: the dead jobs set
+-- KEYS[6]: the active consumers set
+-- KEYS[7]: the job data hash
+-- KEYS[8]: the job priority hash
+-- KEYS[9]: the job expiry hash
+-- KEYS[10]: the job fetch time hash
+-- KEYS[11]: the job progress hash
+-- KEYS[12]: the job error hash
+-- KEYS[13]: the job duration hash
+-- KEYS[14]: the job result hash
+-- KEYS[15]: the dead reason hash
+-- KEYS[16]: the job context hash
+
+-- ARGV[1]: "1" to only count the orphaned jobs without deleting them
+-- ARGV[2...]: the IDs of the jobs to check
+
+-- Returns: the number of jobs whose data is not referenced by any set
+
+local dry_run = ARGV[1] == "1"
+local inflight_sets = redis.call("zrange", KEYS[6], 0, -1)
+local count = 0
+
+for i = 2, table.getn(ARGV) do
+  local id = ARGV[i]
+  -- Jobs deleted since they were scanned are skipped
+  local referenced = redis.call("hexists", KEYS[7], id) == 0
+    or redis.call("zscore", KEYS[1], id)
+    or redis.call("zscore", KEYS[2], id)
+    or redis.call("zscore", KEYS[3], id)
+    or redis.call("zscore", KEYS[4], id)
+    or redis.call("zscore", KEYS[5], id)
+  if not referenced then
+    for _, inflight_set in ipairs(inflight_sets) do
+      if redis.call("sismember", inflight_set, id) == 1 then
+        referenced = true
+        break
+      end
+    end
+  end
+
+  if not referenced then
+    if not dry_run then
+      for k = 7, 16 do
+        redis.call("hdel", KEYS[k], id)
+      end
+    end
+    count = count + 1
+  end
+end
+
+return count
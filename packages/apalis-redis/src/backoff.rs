@@ -0,0 +1,217 @@
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::RedisStorageError;
+
+/// Computes how long a failed job waits before it is retried
+///
+/// Retries are scheduled with a precision of one second, a delay with a fractional part is
+/// rounded up to the next whole second.
+pub trait BackoffStrategy: Debug + Send + Sync {
+    /// Get the delay before the next retry given the number of attempts made so far
+    fn next_delay(&self, attempt: usize) -> Duration;
+}
+
+/// Retries jobs after the same delay on every attempt
+#[derive(Debug, Clone, Default)]
+pub struct FixedBackoff {
+    delay: Duration,
+}
+
+impl FixedBackoff {
+    /// Build a new fixed backoff
+    pub fn new(delay: Duration) -> Self {
+        Self { delay }
+    }
+}
+
+impl BackoffStrategy for FixedBackoff {
+    fn next_delay(&self, _attempt: usize) -> Duration {
+        self.delay
+    }
+}
+
+/// Doubles the delay on every attempt, up to a maximum.
+///
+/// A jitter factor between `0.0` and `1.0` randomly shortens each delay by up to that fraction
+/// to avoid retrying many jobs at the same time.
+#[derive(Debug)]
+pub struct ExponentialBackoff {
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+    rng: AtomicU64,
+}
+
+impl ExponentialBackoff {
+    /// Build a new exponential backoff without jitter
+    pub fn new(base: Duration, max: Duration) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            base,
+            max,
+            jitter: 0.0,
+            rng: AtomicU64::new(seed),
+        }
+    }
+
+    /// Set the jitter factor, clamped between `0.0` and `1.0`
+    pub fn set_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seed the random number generator used for jitter, useful for deterministic tests
+    pub fn set_seed(self, seed: u64) -> Self {
+        self.rng.store(seed, Ordering::Relaxed);
+        self
+    }
+
+    /// Get the base delay
+    pub fn get_base(&self) -> &Duration {
+        &self.base
+    }
+
+    /// Get the maximum delay
+    pub fn get_max(&self) -> &Duration {
+        &self.max
+    }
+
+    /// Get the jitter factor
+    pub fn get_jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// SplitMix64, returns a number in `[0, 1)`
+    fn next_random(&self) -> f64 {
+        let mut z = self
+            .rng
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl BackoffStrategy for ExponentialBackoff {
+    fn next_delay(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(u32::MAX as usize) as u32;
+        let delay = 2u32
+            .checked_pow(exponent)
+            .and_then(|factor| self.base.checked_mul(factor))
+            .map_or(self.max, |delay| delay.min(self.max));
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - self.jitter * self.next_random())
+    }
+}
+
+/// Spaces out the calls of a poller while Redis cannot be reached, see
+/// [Config::set_reconnect_backoff](crate::Config::set_reconnect_backoff)
+#[derive(Debug)]
+pub(crate) struct ReconnectBackoff {
+    backoff: Option<ExponentialBackoff>,
+    failures: usize,
+}
+
+impl ReconnectBackoff {
+    pub(crate) fn new(bounds: Option<(Duration, Duration)>) -> Self {
+        Self {
+            backoff: bounds.map(|(min, max)| ExponentialBackoff::new(min, max)),
+            failures: 0,
+        }
+    }
+
+    /// Record the outcome of a call and get how long to wait before the next one.
+    ///
+    /// Only connection errors back off, a success resets the wait to its minimum.
+    pub(crate) fn record<R>(&mut self, res: &Result<R, RedisStorageError>) -> Option<Duration> {
+        let backoff = self.backoff.as_ref()?;
+        match res {
+            Ok(_) => {
+                self.failures = 0;
+                None
+            }
+            Err(e) if e.is_connection_error() => {
+                self.failures += 1;
+                Some(backoff.next_delay(self.failures))
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Record the outcome of a call and wait before the next one if it could not reach Redis
+    pub(crate) async fn wait_after<R>(&mut self, res: &Result<R, RedisStorageError>) {
+        if let Some(delay) = self.record(res) {
+            apalis_core::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_backoff_is_constant() {
+        let backoff = FixedBackoff::new(Duration::from_secs(3));
+        assert_eq!(backoff.next_delay(1), Duration::from_secs(3));
+        assert_eq!(backoff.next_delay(10), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn exponential_backoff_doubles_up_to_max() {
+        let backoff = ExponentialBackoff::new(Duration::from_secs(1), Duration::from_secs(10));
+        assert_eq!(backoff.next_delay(0), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(2), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(4), Duration::from_secs(8));
+        assert_eq!(backoff.next_delay(5), Duration::from_secs(10));
+        assert_eq!(backoff.next_delay(usize::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn exponential_backoff_jitter_is_seeded() {
+        let build = || {
+            ExponentialBackoff::new(Duration::from_secs(8), Duration::from_secs(60))
+                .set_jitter(0.5)
+                .set_seed(42)
+        };
+        let first = build();
+        let second = build();
+        for attempt in 1..5 {
+            let delay = first.next_delay(attempt);
+            assert_eq!(delay, second.next_delay(attempt));
+            let max = Duration::from_secs(8 * 2u64.pow(attempt as u32 - 1));
+            assert!(delay <= max && delay >= max / 2);
+        }
+    }
+
+    #[test]
+    fn reconnect_backoff_only_grows_on_connection_errors() {
+        let mut reconnect = ReconnectBackoff::new(Some((
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+        )));
+        let dropped: Result<(), _> = Err(RedisStorageError::Connection(
+            std::io::Error::from(std::io::ErrorKind::ConnectionReset).into(),
+        ));
+        let logic: Result<(), _> = Err(RedisStorageError::Script(crate::ScriptError::NotFound));
+        assert_eq!(reconnect.record(&dropped), Some(Duration::from_millis(100)));
+        assert_eq!(reconnect.record(&dropped), Some(Duration::from_millis(200)));
+        assert_eq!(reconnect.record(&logic), None);
+        assert_eq!(reconnect.record(&dropped), Some(Duration::from_millis(300)));
+        assert_eq!(reconnect.record(&Ok(())), None);
+        assert_eq!(reconnect.record(&dropped), Some(Duration::from_millis(100)));
+
+        let mut disabled = ReconnectBackoff::new(None);
+        assert_eq!(disabled.record(&dropped), None);
+    }
+}
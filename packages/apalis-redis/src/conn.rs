@@ -0,0 +1,104 @@
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::{Cmd, Pipeline, RedisFuture, Value};
+
+/// A connection used by a [RedisStorage](crate::RedisStorage) that is either shared or checked
+/// out of a pool for every command.
+///
+/// A single [ConnectionManager] multiplexes every fetch and ack over one socket, a pool lets them
+/// run on separate connections under heavy concurrency.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum RedisConn {
+    /// A single multiplexed connection
+    Single(ConnectionManager),
+    /// A `deadpool-redis` pool, a connection is checked out for each command or pipeline
+    #[cfg(feature = "deadpool")]
+    Pooled(deadpool_redis::Pool),
+    /// A connection to a Redis Cluster, see [connect_cluster](crate::connect_cluster)
+    #[cfg(feature = "cluster")]
+    Cluster(redis::cluster_async::ClusterConnection),
+}
+
+impl std::fmt::Debug for RedisConn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RedisConn::Single(_) => f.debug_tuple("Single").finish(),
+            #[cfg(feature = "deadpool")]
+            RedisConn::Pooled(pool) => f.debug_tuple("Pooled").field(&pool.status()).finish(),
+            #[cfg(feature = "cluster")]
+            RedisConn::Cluster(_) => f.debug_tuple("Cluster").finish(),
+        }
+    }
+}
+
+impl From<ConnectionManager> for RedisConn {
+    fn from(conn: ConnectionManager) -> Self {
+        RedisConn::Single(conn)
+    }
+}
+
+#[cfg(feature = "deadpool")]
+impl From<deadpool_redis::Pool> for RedisConn {
+    fn from(pool: deadpool_redis::Pool) -> Self {
+        RedisConn::Pooled(pool)
+    }
+}
+
+#[cfg(feature = "cluster")]
+impl From<redis::cluster_async::ClusterConnection> for RedisConn {
+    fn from(conn: redis::cluster_async::ClusterConnection) -> Self {
+        RedisConn::Cluster(conn)
+    }
+}
+
+#[cfg(feature = "deadpool")]
+fn pool_error(e: deadpool_redis::PoolError) -> redis::RedisError {
+    match e {
+        deadpool_redis::PoolError::Backend(e) => e,
+        e => redis::RedisError::from(std::io::Error::other(e.to_string())),
+    }
+}
+
+impl ConnectionLike for RedisConn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConn::Single(conn) => conn.req_packed_command(cmd),
+            #[cfg(feature = "deadpool")]
+            RedisConn::Pooled(pool) => Box::pin(async move {
+                let mut conn = pool.get().await.map_err(pool_error)?;
+                conn.req_packed_command(cmd).await
+            }),
+            #[cfg(feature = "cluster")]
+            RedisConn::Cluster(conn) => conn.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConn::Single(conn) => conn.req_packed_commands(cmd, offset, count),
+            #[cfg(feature = "deadpool")]
+            RedisConn::Pooled(pool) => Box::pin(async move {
+                let mut conn = pool.get().await.map_err(pool_error)?;
+                conn.req_packed_commands(cmd, offset, count).await
+            }),
+            #[cfg(feature = "cluster")]
+            RedisConn::Cluster(conn) => conn.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConn::Single(conn) => conn.get_db(),
+            // The pool does not expose its database until a connection is checked out
+            #[cfg(feature = "deadpool")]
+            RedisConn::Pooled(_) => 0,
+            #[cfg(feature = "cluster")]
+            RedisConn::Cluster(conn) => conn.get_db(),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use apalis_core::error::BoxDynError;
+use apalis_core::task::task_id::TaskId;
+use redis::RedisError;
+use std::time::Duration;
+
+use crate::DeadReason;
+
+/// A status returned by a Lua script of a [RedisStorage](crate::RedisStorage) for an operation it
+/// did not perform
+///
+/// Scripts reply with their status as the first element, `"ok"` when the operation succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[non_exhaustive]
+pub enum ScriptError {
+    /// A job with the same id already exists
+    #[error("conflict: a job with the same id already exists")]
+    Conflict,
+    /// The job is not held by the worker, it may have been reenqueued, killed or acknowledged
+    ///
+    /// Acknowledging a job the storage moved back to the active set, because its worker stopped
+    /// sending keep-alives or it was inflight past the visibility timeout, returns this. Workers
+    /// ignore it, the job runs again from the active set.
+    #[error("not found: the job is not inflight with this worker")]
+    NotFound,
+}
+
+impl ScriptError {
+    /// Parse the status of a script reply, `Ok` for `"ok"` and `None` for an unknown status
+    pub(crate) fn from_status(status: &str) -> Option<Result<(), Self>> {
+        match status {
+            "ok" => Some(Ok(())),
+            "conflict" => Some(Err(ScriptError::Conflict)),
+            "not_found" => Some(Err(ScriptError::NotFound)),
+            _ => None,
+        }
+    }
+}
+
+/// Errors returned by a [RedisStorage](crate::RedisStorage)
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum RedisStorageError {
+    /// No job with the given id exists in the queue
+    #[error("Job not found: `{0}`")]
+    NotFound(TaskId),
+    /// A job or its result could not be encoded
+    #[error("Encode error: {0}")]
+    Encode(BoxDynError),
+    /// A job could not be decoded, or Redis returned data in an unexpected shape
+    #[error("Decode error: {0}")]
+    Codec(BoxDynError),
+    /// An encoded job is larger than the configured limit
+    #[error("Payload too large: encoded job is {size} bytes, the limit is {limit} bytes")]
+    PayloadTooLarge {
+        /// The size of the encoded job
+        size: usize,
+        /// The limit set with [Config::set_max_payload_bytes](crate::Config::set_max_payload_bytes)
+        limit: usize,
+    },
+    /// A job was pushed with more attempts than it is allowed
+    #[error(
+        "Too many attempts: job has been attempted {attempts} times, the limit is {max_attempts}"
+    )]
+    TooManyAttempts {
+        /// The attempts the job was pushed with
+        attempts: usize,
+        /// The maximum number of attempts of the job
+        max_attempts: usize,
+    },
+    /// A delay or retention could not be converted to a timestamp
+    #[error("Invalid duration: {0}")]
+    InvalidDuration(BoxDynError),
+    /// A cron expression could not be parsed or has no upcoming occurrence
+    #[error("Invalid cron expression: {0}")]
+    InvalidCron(BoxDynError),
+    /// A script did not perform the operation, see [ScriptError]
+    #[error("Script error: {0}")]
+    Script(#[from] ScriptError),
+    /// A job was not done before the timeout given to
+    /// [RedisStorage::push_and_wait](crate::RedisStorage::push_and_wait)
+    #[error("Timed out waiting for job: `{0}`")]
+    Timeout(TaskId),
+    /// A job waited on by [RedisStorage::push_and_wait](crate::RedisStorage::push_and_wait) was
+    /// acknowledged as failed or killed
+    #[error("Job failed: `{task_id}`: {}", error.as_deref().unwrap_or("no error recorded"))]
+    JobFailed {
+        /// The id of the job
+        task_id: TaskId,
+        /// Why the job was killed, `None` if it was acknowledged as failed
+        dead_reason: Option<DeadReason>,
+        /// The error of the last attempt of the job, if one was recorded
+        error: Option<String>,
+    },
+    /// A job of a multiplexed storage has a type it does not decode
+    #[error("Unexpected job type: {0:?}")]
+    UnexpectedJobType(Option<String>),
+    /// The connection failed or Redis returned an error, including errors raised by scripts
+    #[error("Connection error: {0}")]
+    Connection(#[from] RedisError),
+}
+
+/// The error a job fails with when its handler runs longer than the timeout of the job, see
+/// [RedisContext::set_timeout](crate::RedisContext::set_timeout)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
+#[error("Job timed out after {0:?}")]
+pub struct JobTimedOut(pub Duration);
+
+impl RedisStorageError {
+    /// Whether Redis could not be reached, such as a dropped or refused connection or a timeout,
+    /// as opposed to an error returned by Redis, a script or the storage itself
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            RedisStorageError::Connection(e) => {
+                e.is_io_error()
+                    || e.is_connection_dropped()
+                    || e.is_connection_refusal()
+                    || e.is_timeout()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a script found that the job is no longer inflight with the worker, see
+    /// [ScriptError::NotFound]
+    pub fn is_not_inflight(&self) -> bool {
+        matches!(self, RedisStorageError::Script(ScriptError::NotFound))
+    }
+}
@@ -1,5 +1,8 @@
+use crate::multiplexed::{decode_job, MultiplexedDecoder};
+use crate::JobState;
 use crate::RedisContext;
 use crate::RedisStorage;
+use crate::RedisStorageError;
 use apalis_core::backend::BackendExpose;
 use apalis_core::backend::Stat;
 use apalis_core::backend::WorkerState;
@@ -9,7 +12,7 @@ use apalis_core::request::Request;
 use apalis_core::request::State;
 use apalis_core::worker::Worker;
 use apalis_core::worker::WorkerId;
-use redis::{ErrorKind, Value};
+use redis::Value;
 use serde::{de::DeserializeOwned, Serialize};
 
 type RedisCodec = JsonCodec<Vec<u8>>;
@@ -19,8 +22,8 @@ where
     T: 'static + Serialize + DeserializeOwned + Send + Unpin + Sync,
 {
     type Request = Request<T, RedisContext>;
-    type Error = redis::RedisError;
-    async fn stats(&self) -> Result<Stat, redis::RedisError> {
+    type Error = RedisStorageError;
+    async fn stats(&self) -> Result<Stat, RedisStorageError> {
         let mut conn = self.get_connection().clone();
         let queue = self.get_config();
 
@@ -47,33 +50,15 @@ where
         &self,
         status: &State,
         page: i32,
-    ) -> Result<Vec<Self::Request>, redis::RedisError> {
+    ) -> Result<Vec<Self::Request>, RedisStorageError> {
         let mut conn = self.get_connection().clone();
         let queue = self.get_config();
-        match status {
-            State::Pending | State::Scheduled => {
-                let active_jobs_list = &queue.active_jobs_list();
-                let job_data_hash = &queue.job_data_hash();
-                let ids: Vec<String> = redis::cmd("LRANGE")
-                    .arg(active_jobs_list)
-                    .arg(((page - 1) * 10).to_string())
-                    .arg((page * 10).to_string())
-                    .query_async(&mut conn)
-                    .await?;
-
-                if ids.is_empty() {
-                    return Ok(Vec::new());
-                }
-                let data: Option<Value> = redis::cmd("HMGET")
-                    .arg(job_data_hash)
-                    .arg(&ids)
-                    .query_async(&mut conn)
-                    .await?;
-
-                let jobs: Vec<Request<T, RedisContext>> =
-                    deserialize_multiple_jobs::<_, RedisCodec>(data.as_ref()).unwrap();
-                Ok(jobs)
-            }
+        let state = match status {
+            State::Pending => JobState::Active,
+            State::Scheduled => JobState::Scheduled,
+            State::Done => JobState::Done,
+            State::Failed => JobState::Failed,
+            State::Killed => JobState::Dead,
             State::Running => {
                 let consumers_set = &queue.consumers_set();
                 let job_data_hash = &queue.job_data_hash();
@@ -104,85 +89,18 @@ where
                         .await?;
 
                     let jobs: Vec<Request<T, RedisContext>> =
-                        deserialize_multiple_jobs::<_, RedisCodec>(data.as_ref()).unwrap();
+                        deserialize_multiple_jobs::<_, RedisCodec>(data.as_ref(), self.multiplexed)
+                            .unwrap();
                     all_jobs.extend(jobs);
                 }
 
-                Ok(all_jobs)
+                return Ok(all_jobs);
             }
-            State::Done => {
-                let done_jobs_set = &queue.done_jobs_set();
-                let job_data_hash = &queue.job_data_hash();
-                let ids: Vec<String> = redis::cmd("ZRANGE")
-                    .arg(done_jobs_set)
-                    .arg(((page - 1) * 10).to_string())
-                    .arg((page * 10).to_string())
-                    .query_async(&mut conn)
-                    .await?;
-
-                if ids.is_empty() {
-                    return Ok(Vec::new());
-                }
-                let data: Option<Value> = redis::cmd("HMGET")
-                    .arg(job_data_hash)
-                    .arg(&ids)
-                    .query_async(&mut conn)
-                    .await?;
-
-                let jobs: Vec<Request<T, RedisContext>> =
-                    deserialize_multiple_jobs::<_, RedisCodec>(data.as_ref()).unwrap();
-                Ok(jobs)
-            }
-            // State::Retry => Ok(Vec::new()),
-            State::Failed => {
-                let failed_jobs_set = &queue.failed_jobs_set();
-                let job_data_hash = &queue.job_data_hash();
-                let ids: Vec<String> = redis::cmd("ZRANGE")
-                    .arg(failed_jobs_set)
-                    .arg(((page - 1) * 10).to_string())
-                    .arg((page * 10).to_string())
-                    .query_async(&mut conn)
-                    .await?;
-                if ids.is_empty() {
-                    return Ok(Vec::new());
-                }
-                let data: Option<Value> = redis::cmd("HMGET")
-                    .arg(job_data_hash)
-                    .arg(&ids)
-                    .query_async(&mut conn)
-                    .await?;
-                let jobs: Vec<Request<T, RedisContext>> =
-                    deserialize_multiple_jobs::<_, RedisCodec>(data.as_ref()).unwrap();
-
-                Ok(jobs)
-            }
-            State::Killed => {
-                let dead_jobs_set = &queue.dead_jobs_set();
-                let job_data_hash = &queue.job_data_hash();
-                let ids: Vec<String> = redis::cmd("ZRANGE")
-                    .arg(dead_jobs_set)
-                    .arg(((page - 1) * 10).to_string())
-                    .arg((page * 10).to_string())
-                    .query_async(&mut conn)
-                    .await?;
-
-                if ids.is_empty() {
-                    return Ok(Vec::new());
-                }
-                let data: Option<Value> = redis::cmd("HMGET")
-                    .arg(job_data_hash)
-                    .arg(&ids)
-                    .query_async(&mut conn)
-                    .await?;
-
-                let jobs: Vec<Request<T, RedisContext>> =
-                    deserialize_multiple_jobs::<_, RedisCodec>(data.as_ref()).unwrap();
-
-                Ok(jobs)
-            }
-        }
+        };
+        self.list_jobs_by_state(state, page.max(1) as usize, 10)
+            .await
     }
-    async fn list_workers(&self) -> Result<Vec<Worker<WorkerState>>, redis::RedisError> {
+    async fn list_workers(&self) -> Result<Vec<Worker<WorkerState>>, RedisStorageError> {
         let queue = self.get_config();
         let consumers_set = &queue.consumers_set();
         let mut conn = self.get_connection().clone();
@@ -206,6 +124,7 @@ where
 
 fn deserialize_multiple_jobs<T, C: Codec<Compact = Vec<u8>>>(
     jobs: Option<&Value>,
+    multiplexed: Option<MultiplexedDecoder<T>>,
 ) -> Option<Vec<Request<T, RedisContext>>>
 where
     T: DeserializeOwned,
@@ -227,9 +146,7 @@ where
             .iter()
             .filter_map(|v| match v {
                 Value::BulkString(data) => {
-                    let inner = C::decode(data.to_vec())
-                        .map_err(|e| (ErrorKind::IoError, "Decode error", e.into().to_string()))
-                        .unwrap();
+                    let inner = decode_job::<T, C>(multiplexed, data.to_vec()).unwrap();
                     Some(inner)
                 }
                 _ => None,
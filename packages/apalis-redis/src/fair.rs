@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+
+use apalis_core::backend::Backend;
+use apalis_core::codec::json::JsonCodec;
+use apalis_core::codec::Codec;
+use apalis_core::layers::{Ack, AckLayer, Stack};
+use apalis_core::poller::controller::Controller;
+use apalis_core::poller::stream::BackendStream;
+use apalis_core::poller::Poller;
+use apalis_core::request::{Request, RequestStream};
+use apalis_core::response::Response;
+use apalis_core::task::task_id::TaskId;
+use apalis_core::worker::Worker;
+use futures::{SinkExt, Stream, StreamExt};
+use redis::aio::{ConnectionLike, ConnectionManager};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::storage::AckSender;
+use crate::timeout::JobTimeoutLayer;
+use crate::{RedisContext, RedisPollError, RedisStorage, RedisStorageError, ScriptError};
+
+/// Serves the jobs of several namespaces to one worker, taking turns between them by weight
+///
+/// Each namespace is polled by its own [RedisStorage] with its own config, keep-alives and
+/// inflight set, and the results of its jobs are acknowledged to it. The combined stream takes up
+/// to `weight` jobs from a namespace before moving on to the next one that has jobs, so a busy
+/// namespace cannot starve the others, such as the namespaces of several tenants.
+///
+/// Not to be confused with [MultiplexedJob](crate::MultiplexedJob), which shares a single
+/// namespace between job types.
+///
+/// ```rust,no_run
+/// # use apalis_redis::{Config, MultiplexedStorage, RedisStorage};
+/// # async fn example(conn: redis::aio::ConnectionManager) {
+/// let storage: MultiplexedStorage<String> = MultiplexedStorage::new()
+///     .add(
+///         RedisStorage::new_with_config(conn.clone(), Config::default().set_namespace("big")),
+///         3,
+///     )
+///     .add(
+///         RedisStorage::new_with_config(conn, Config::default().set_namespace("small")),
+///         1,
+///     );
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MultiplexedStorage<T, Conn = ConnectionManager, C = JsonCodec<Vec<u8>>> {
+    storages: Vec<(RedisStorage<T, Conn, C>, usize)>,
+    controller: Controller,
+}
+
+impl<T, Conn: Clone, C> Clone for MultiplexedStorage<T, Conn, C> {
+    fn clone(&self) -> Self {
+        Self {
+            storages: self.storages.clone(),
+            controller: self.controller.clone(),
+        }
+    }
+}
+
+impl<T, Conn, C> Default for MultiplexedStorage<T, Conn, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, Conn, C> MultiplexedStorage<T, Conn, C> {
+    /// Create a storage serving no namespace yet
+    pub fn new() -> Self {
+        Self {
+            storages: Vec::new(),
+            controller: Controller::new(),
+        }
+    }
+
+    /// Serve the namespace of `storage`, taking up to `weight` of its jobs per turn.
+    ///
+    /// A weight of 0 is treated as 1.
+    pub fn add(mut self, storage: RedisStorage<T, Conn, C>, weight: usize) -> Self {
+        self.storages.push((storage, weight.max(1)));
+        self
+    }
+
+    /// Get the storages of the served namespaces along with their weight, in the order they
+    /// were added
+    pub fn storages(&self) -> &[(RedisStorage<T, Conn, C>, usize)] {
+        &self.storages
+    }
+
+    /// Get the controller of the combined stream
+    pub fn get_controller(&self) -> &Controller {
+        &self.controller
+    }
+}
+
+impl<T, Conn, C> Backend<Request<T, RedisContext>> for MultiplexedStorage<T, Conn, C>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + Sync + 'static,
+{
+    type Stream = BackendStream<RequestStream<Request<T, RedisContext>>>;
+
+    type Layer = Stack<JobTimeoutLayer, AckLayer<MultiplexedAck, T, RedisContext, C>>;
+
+    type Codec = C;
+
+    fn poll(
+        self,
+        worker: &Worker<apalis_core::worker::Context>,
+    ) -> Poller<Self::Stream, Self::Layer> {
+        let routes = Arc::new(Mutex::new(HashMap::new()));
+        let mut streams = Vec::with_capacity(self.storages.len());
+        let mut heartbeats = Vec::with_capacity(self.storages.len());
+        let mut senders = Vec::with_capacity(self.storages.len());
+        for (index, (storage, weight)) in self.storages.into_iter().enumerate() {
+            let (stream, heartbeat, sender) = storage.poll_with_ack(worker);
+            let routes = routes.clone();
+            // Remember the namespace of each job so that its result is acknowledged to it
+            let stream = stream.inspect(move |res| {
+                if let Ok(Some(req)) = res {
+                    routes
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert(req.parts.task_id.clone(), index);
+                }
+            });
+            streams.push((stream.boxed(), weight));
+            heartbeats.push(heartbeat);
+            senders.push(sender);
+        }
+        let stream: RequestStream<Request<T, RedisContext>> =
+            WeightedRoundRobin::new(streams).boxed();
+        let heartbeat = async move {
+            futures::future::join_all(heartbeats).await;
+        };
+        let layer = Stack::new(
+            JobTimeoutLayer,
+            AckLayer::new(MultiplexedAck { senders, routes }),
+        );
+        Poller::new_with_layer(
+            BackendStream::new(stream, self.controller),
+            heartbeat,
+            layer,
+        )
+    }
+}
+
+/// Acknowledges the results of the jobs of a [MultiplexedStorage] to the namespace each job was
+/// fetched from
+#[derive(Clone, Debug)]
+pub struct MultiplexedAck {
+    senders: Vec<AckSender>,
+    routes: Arc<Mutex<HashMap<TaskId, usize>>>,
+}
+
+impl<T, Res, C> Ack<T, Res, C> for MultiplexedAck
+where
+    Res: Serialize + Sync,
+    C: Codec<Compact = Vec<u8>> + Send,
+{
+    type Context = RedisContext;
+    type AckError = RedisPollError;
+
+    async fn ack(&mut self, ctx: &RedisContext, res: &Response<Res>) -> Result<(), RedisPollError> {
+        let index = self
+            .routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&res.task_id)
+            .ok_or_else(|| RedisPollError::AckError(ScriptError::NotFound.into()))?;
+        let inner = match &res.inner {
+            Ok(result) => Ok(C::encode(result)
+                .map_err(|e| RedisPollError::AckError(RedisStorageError::Encode(e.into())))?),
+            Err(e) => Err(e.clone()),
+        };
+        let res = Response::new(inner, res.task_id.clone(), res.attempt.clone());
+        self.senders[index]
+            .send((ctx.clone(), res))
+            .await
+            .map_err(RedisPollError::EnqueueError)
+    }
+}
+
+/// Takes up to the weight of each stream in turn, skipping the streams that have nothing ready
+struct WeightedRoundRobin<S> {
+    streams: Vec<(Option<S>, usize)>,
+    current: usize,
+    taken: usize,
+}
+
+impl<S> WeightedRoundRobin<S> {
+    fn new(streams: Vec<(S, usize)>) -> Self {
+        Self {
+            streams: streams
+                .into_iter()
+                .map(|(stream, weight)| (Some(stream), weight))
+                .collect(),
+            current: 0,
+            taken: 0,
+        }
+    }
+
+    fn next_turn(&mut self) {
+        self.current = (self.current + 1) % self.streams.len();
+        self.taken = 0;
+    }
+}
+
+impl<S: Stream + Unpin> Stream for WeightedRoundRobin<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        let this = self.get_mut();
+        if this.streams.is_empty() {
+            return Poll::Ready(None);
+        }
+        for _ in 0..this.streams.len() {
+            let (slot, weight) = &mut this.streams[this.current];
+            let weight = *weight;
+            match slot.as_mut().map(|stream| stream.poll_next_unpin(cx)) {
+                Some(Poll::Ready(Some(item))) => {
+                    this.taken += 1;
+                    if this.taken >= weight {
+                        this.next_turn();
+                    }
+                    return Poll::Ready(Some(item));
+                }
+                Some(Poll::Ready(None)) => {
+                    *slot = None;
+                    this.next_turn();
+                }
+                Some(Poll::Pending) | None => this.next_turn(),
+            }
+        }
+        if this.streams.iter().all(|(slot, _)| slot.is_none()) {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_takes_turns_by_weight() {
+        let busy = stream::iter(vec!["a"; 6]);
+        let small = stream::iter(vec!["b"; 2]);
+        let items: Vec<_> = WeightedRoundRobin::new(vec![(busy, 2), (small, 1)])
+            .collect()
+            .await;
+        assert_eq!(items, ["a", "a", "b", "a", "a", "b", "a", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_skips_streams_without_ready_jobs() {
+        let pending = stream::pending::<&str>();
+        let ready = stream::iter(vec!["b"; 3]).chain(stream::pending());
+        let mut streams = WeightedRoundRobin::new(vec![(pending.boxed(), 1), (ready.boxed(), 1)]);
+        for _ in 0..3 {
+            assert_eq!(streams.next().await, Some("b"));
+        }
+    }
+}
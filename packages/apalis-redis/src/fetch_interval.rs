@@ -0,0 +1,115 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The wait between two fetches, doubled after every empty fetch up to a cap
+///
+/// Clones share the current wait, so that the poll stream sees what the fetch loop records. It
+/// also tracks since when the fetches have been empty.
+#[derive(Debug, Clone)]
+pub(crate) struct FetchInterval {
+    min: Duration,
+    max: Duration,
+    current: Arc<AtomicU64>,
+    empty_since: Arc<Mutex<Option<Instant>>>,
+}
+
+impl FetchInterval {
+    /// Build an interval starting at `min` and backing off up to `max`
+    pub(crate) fn new(min: Duration, max: Duration) -> Self {
+        let max = max.max(min);
+        Self {
+            min,
+            max,
+            current: Arc::new(AtomicU64::new(as_nanos(min))),
+            empty_since: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Build an interval that never backs off
+    pub(crate) fn fixed(interval: Duration) -> Self {
+        Self::new(interval, interval)
+    }
+
+    /// Get the wait before the next fetch
+    pub(crate) fn current(&self) -> Duration {
+        Duration::from_nanos(self.current.load(Ordering::Relaxed))
+    }
+
+    /// Back off after a fetch returned no jobs, or snap back to the minimum once it did
+    ///
+    /// Returns how long the fetches have been empty if this one was, zero for the first empty
+    /// fetch after jobs were fetched.
+    pub(crate) fn record(&self, fetched: usize) -> Option<Duration> {
+        let next = if fetched > 0 {
+            self.min
+        } else {
+            self.current().saturating_mul(2).clamp(self.min, self.max)
+        };
+        self.current.store(as_nanos(next), Ordering::Relaxed);
+        let mut empty_since = self.empty_since.lock().unwrap_or_else(|e| e.into_inner());
+        if fetched > 0 {
+            *empty_since = None;
+            None
+        } else {
+            Some(empty_since.get_or_insert_with(Instant::now).elapsed())
+        }
+    }
+}
+
+fn as_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_fetches_back_off_up_to_the_cap() {
+        let interval = FetchInterval::new(Duration::from_millis(100), Duration::from_millis(500));
+        let waits: Vec<_> = (0..5)
+            .map(|_| {
+                interval.record(0);
+                interval.current()
+            })
+            .collect();
+        assert_eq!(
+            waits,
+            [200, 400, 500, 500, 500]
+                .map(Duration::from_millis)
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn fetched_jobs_reset_the_wait() {
+        let interval = FetchInterval::new(Duration::from_millis(100), Duration::from_secs(5));
+        let shared = interval.clone();
+        for _ in 0..4 {
+            interval.record(0);
+        }
+        assert_eq!(shared.current(), Duration::from_millis(1600));
+        interval.record(3);
+        assert_eq!(shared.current(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn empty_fetches_report_how_long_they_lasted() {
+        let interval = FetchInterval::fixed(Duration::from_millis(100));
+        let first = interval.record(0).expect("an empty fetch is reported");
+        std::thread::sleep(Duration::from_millis(10));
+        let second = interval.record(0).expect("an empty fetch is reported");
+        assert!(second >= first + Duration::from_millis(10));
+        assert_eq!(interval.record(1), None);
+        assert!(interval.record(0).expect("an empty fetch is reported") < second);
+    }
+
+    #[test]
+    fn fixed_interval_never_backs_off() {
+        let interval = FetchInterval::fixed(Duration::from_millis(100));
+        interval.record(0);
+        interval.record(0);
+        assert_eq!(interval.current(), Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use apalis_core::worker::WorkerId;
+use futures::{Stream, StreamExt};
+
+use crate::fetch_interval::FetchInterval;
+
+/// A random offset added to each poll so that workers started together drift apart
+#[derive(Debug, Clone)]
+pub(crate) struct Jitter {
+    max: Duration,
+    state: u64,
+}
+
+impl Jitter {
+    /// Build a jitter of up to `max` seeded from the worker and the current time
+    pub(crate) fn new(max: Duration, worker_id: &WorkerId) -> Self {
+        let mut hasher = DefaultHasher::new();
+        worker_id.to_string().hash(&mut hasher);
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        Self::new_with_seed(max, hasher.finish())
+    }
+
+    fn new_with_seed(max: Duration, seed: u64) -> Self {
+        Self { max, state: seed }
+    }
+
+    /// Get a random offset in `[0, max)`, always zero without jitter
+    pub(crate) fn next_offset(&mut self) -> Duration {
+        if self.max.is_zero() {
+            return Duration::ZERO;
+        }
+        self.max.mul_f64(self.next_random())
+    }
+
+    /// SplitMix64, returns a number in `[0, 1)`
+    fn next_random(&mut self) -> f64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A stream ticking every current wait of `interval` plus a fresh offset of the jitter
+pub(crate) fn jittered_interval(
+    interval: FetchInterval,
+    jitter: Jitter,
+) -> impl Stream<Item = ()> + Send + Unpin {
+    futures::stream::unfold(jitter, move |mut jitter| {
+        let wait = interval.current() + jitter.next_offset();
+        async move {
+            apalis_core::sleep(wait).await;
+            Some(((), jitter))
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let max = Duration::from_millis(250);
+        let mut jitter = Jitter::new_with_seed(max, 42);
+        let offsets: Vec<_> = (0..100).map(|_| jitter.next_offset()).collect();
+        assert!(offsets.iter().all(|offset| *offset < max));
+        assert!(offsets.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn jitter_is_seeded_per_stream() {
+        let max = Duration::from_secs(1);
+        let mut first = Jitter::new_with_seed(max, 1);
+        let mut second = Jitter::new_with_seed(max, 2);
+        let mut replay = Jitter::new_with_seed(max, 1);
+        let first: Vec<_> = (0..10).map(|_| first.next_offset()).collect();
+        let second: Vec<_> = (0..10).map(|_| second.next_offset()).collect();
+        let replay: Vec<_> = (0..10).map(|_| replay.next_offset()).collect();
+        assert_eq!(first, replay);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn zero_jitter_is_disabled() {
+        let mut jitter = Jitter::new(Duration::ZERO, &WorkerId::new("worker"));
+        assert_eq!(jitter.next_offset(), Duration::ZERO);
+    }
+}
@@ -33,12 +33,62 @@
 //! }
 //! ```
 
+mod backoff;
+mod clock;
+mod conn;
+mod error;
 mod expose;
+mod fair;
+mod fetch_interval;
+mod jitter;
+mod listener;
+#[cfg(feature = "memory")]
+mod memory;
+mod multiplexed;
+mod rate_limit;
+mod script;
 mod storage;
+mod task_id;
+mod timeout;
+#[cfg(feature = "tls")]
+mod tls;
+pub use backoff::{BackoffStrategy, ExponentialBackoff, FixedBackoff};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use conn::RedisConn;
+pub use error::{JobTimedOut, RedisStorageError, ScriptError};
+pub use fair::{MultiplexedAck, MultiplexedStorage};
+pub use listener::JobEventListener;
+#[cfg(feature = "memory")]
+pub use memory::MemoryStorage;
+pub use multiplexed::MultiplexedJob;
+#[cfg(feature = "cluster")]
+pub use redis::cluster_async::ClusterConnection;
 pub use redis::{aio::ConnectionManager, RedisError};
+pub use script::{RedisScripts, ScriptName};
 pub use storage::connect;
+#[cfg(feature = "cluster")]
+pub use storage::connect_cluster;
 pub use storage::Config;
+pub use storage::ContextProfile;
+pub use storage::DeadReason;
+pub use storage::DecodeErrorPolicy;
+pub use storage::DoneDetails;
+pub use storage::FetchOrder;
+pub use storage::GroupStatus;
+pub use storage::JobState;
+pub use storage::Metadata;
+pub use storage::OrphanedJob;
+pub use storage::PushOutcome;
+pub use storage::QueueStats;
 pub use storage::RedisContext;
 pub use storage::RedisPollError;
 pub use storage::RedisQueueInfo;
 pub use storage::RedisStorage;
+pub use storage::TickReport;
+pub use storage::VacuumReport;
+pub use storage::WorkerInfo;
+pub use storage::PUSH_JOB_SCRIPT;
+pub use task_id::{TaskIdEncoding, TaskIdFactory, UlidFactory};
+pub use timeout::{JobTimeoutLayer, JobTimeoutService};
+#[cfg(feature = "tls")]
+pub use tls::{connect_tls, TlsConfig};
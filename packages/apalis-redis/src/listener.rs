@@ -0,0 +1,52 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use apalis_core::task::task_id::TaskId;
+use apalis_core::worker::WorkerId;
+
+use crate::DeadReason;
+
+/// Observes the lifecycle of the jobs of a [RedisStorage](crate::RedisStorage), see
+/// [RedisStorage::with_listener](crate::RedisStorage::with_listener)
+///
+/// Every method defaults to doing nothing, so a listener only implements the transitions it
+/// cares about. Methods are called after the transition is stored in Redis, from the task that
+/// made it, and should return quickly.
+pub trait JobEventListener: Debug + Send + Sync {
+    /// A job was fetched by `worker_id` and moved to its inflight set
+    fn on_fetched(&self, _task_id: &TaskId, _worker_id: &WorkerId) {}
+
+    /// A job was acknowledged as done or failed
+    ///
+    /// With ack batching every job of a flushed batch is reported.
+    fn on_acked(&self, _task_id: &TaskId) {}
+
+    /// A failed job was scheduled for another attempt
+    fn on_retried(&self, _task_id: &TaskId) {}
+
+    /// A job was moved to the dead set
+    ///
+    /// Jobs that expire while waiting in the active set are not reported.
+    fn on_killed(&self, _task_id: &TaskId, _reason: DeadReason) {}
+
+    /// An inflight job was moved back to the active set by
+    /// [RedisStorage::reenqueue_orphaned](crate::RedisStorage::reenqueue_orphaned),
+    /// [RedisStorage::reenqueue_active](crate::RedisStorage::reenqueue_active),
+    /// [RedisStorage::requeue](crate::RedisStorage::requeue) or
+    /// [RedisStorage::shutdown](crate::RedisStorage::shutdown)
+    fn on_reenqueued(&self, _task_id: &TaskId) {}
+
+    /// A fetch of the poll stream of `worker_id` returned no jobs
+    ///
+    /// `empty_for` is how long the fetches of the stream have been empty, zero for the first empty
+    /// fetch after jobs were fetched, so an autoscaler can scale down once the queue has been
+    /// empty for long enough. Fetches made with
+    /// [RedisStorage::fetch_next](crate::RedisStorage::fetch_next) are not reported.
+    fn on_empty_poll(&self, _worker_id: &WorkerId, _empty_for: Duration) {}
+}
+
+/// The listener of a storage that has none
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct NoopListener;
+
+impl JobEventListener for NoopListener {}
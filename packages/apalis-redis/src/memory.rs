@@ -0,0 +1,759 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use apalis_core::backend::Backend;
+use apalis_core::codec::json::JsonCodec;
+use apalis_core::codec::Codec;
+use apalis_core::error::{BoxDynError, Error};
+use apalis_core::layers::{Ack, AckLayer};
+use apalis_core::poller::controller::Controller;
+use apalis_core::poller::stream::BackendStream;
+use apalis_core::poller::Poller;
+use apalis_core::request::{Parts, Request, RequestStream};
+use apalis_core::response::Response;
+use apalis_core::storage::Storage;
+use apalis_core::task::task_id::TaskId;
+use apalis_core::worker::{Context, Worker, WorkerId};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{Config, DeadReason, DecodeErrorPolicy, JobState, RedisContext, RedisStorageError};
+
+/// An in-memory storage with the semantics of a [RedisStorage](crate::RedisStorage), for testing
+/// workers without a Redis server
+///
+/// Jobs go through the same states: pushed jobs wait in the active set, highest priority first
+/// and oldest first within a priority, scheduled jobs are moved there once due, fetched jobs are
+/// held inflight by their worker until they are acknowledged as done, retried after the
+/// configured [BackoffStrategy](crate::BackoffStrategy) or killed. Jobs are encoded with
+/// [JsonCodec], so a job that does not round-trip through JSON fails here as it would in Redis.
+///
+/// The storage only lives as long as the process and its clones share the same jobs. A worker
+/// that starts polling takes back the jobs left inflight under its id, but keep-alives, rate
+/// limits, ack batching and the other features that only make sense across processes are not
+/// emulated.
+pub struct MemoryStorage<T> {
+    state: Arc<Mutex<MemoryState>>,
+    config: Config,
+    controller: Controller,
+    job_type: PhantomData<T>,
+}
+
+/// The sets and hashes of a queue
+#[derive(Debug, Default)]
+struct MemoryState {
+    data: HashMap<TaskId, Vec<u8>>,
+    /// Pending jobs by descending priority, then by push order
+    active: BTreeMap<(Reverse<i64>, u64), TaskId>,
+    /// Scheduled jobs by due time, then by schedule order
+    scheduled: BTreeMap<(i64, u64), TaskId>,
+    priority: HashMap<TaskId, i64>,
+    inflight: HashMap<String, HashSet<TaskId>>,
+    done: HashSet<TaskId>,
+    dead: HashMap<TaskId, DeadReason>,
+    errors: HashMap<TaskId, String>,
+    results: HashMap<TaskId, Vec<u8>>,
+    seq: u64,
+}
+
+impl MemoryState {
+    fn next_seq(&mut self) -> u64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    /// Add a job that is not stored yet to the active set, like `push_job.lua`
+    fn push(&mut self, task_id: TaskId, job: Vec<u8>, priority: i64) -> bool {
+        if self.data.contains_key(&task_id) {
+            return false;
+        }
+        self.data.insert(task_id.clone(), job);
+        self.priority.insert(task_id.clone(), priority);
+        self.activate(task_id);
+        true
+    }
+
+    /// Add a job to the active set with the priority it was pushed with
+    fn activate(&mut self, task_id: TaskId) {
+        let priority = self.priority.get(&task_id).copied().unwrap_or_default();
+        let seq = self.next_seq();
+        self.active.insert((Reverse(priority), seq), task_id);
+    }
+
+    fn schedule(&mut self, task_id: TaskId, on: i64) {
+        let seq = self.next_seq();
+        self.scheduled.insert((on, seq), task_id);
+    }
+
+    /// Remove a job from the inflight set of `worker_id`, returning whether it held it
+    fn release(&mut self, worker_id: &WorkerId, task_id: &TaskId) -> bool {
+        self.inflight
+            .get_mut(&worker_id.to_string())
+            .is_some_and(|inflight| inflight.remove(task_id))
+    }
+
+    fn state_of(&self, task_id: &TaskId) -> JobState {
+        if self.active.values().any(|id| id == task_id) {
+            JobState::Active
+        } else if self.scheduled.values().any(|id| id == task_id) {
+            JobState::Scheduled
+        } else if self.done.contains(task_id) {
+            JobState::Done
+        } else if self.dead.contains_key(task_id) {
+            JobState::Dead
+        } else if self
+            .inflight
+            .values()
+            .any(|inflight| inflight.contains(task_id))
+        {
+            JobState::Inflight
+        } else {
+            JobState::Unknown
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for MemoryStorage<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryStorage")
+            .field("job_type", &std::any::type_name::<T>())
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<T> Clone for MemoryStorage<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            config: self.config.clone(),
+            controller: self.controller.clone(),
+            job_type: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for MemoryStorage<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> MemoryStorage<T> {
+    /// Create an empty storage with the default config
+    pub fn new() -> Self {
+        Self::new_with_config(Config::default())
+    }
+
+    /// Create an empty storage with a custom config
+    ///
+    /// The poll interval, buffer size, backoff and attempt limits apply as they do to a
+    /// [RedisStorage](crate::RedisStorage), the keys and connection settings are ignored.
+    pub fn new_with_config(config: Config) -> Self {
+        Self {
+            state: Arc::default(),
+            config,
+            controller: Controller::new(),
+            job_type: PhantomData,
+        }
+    }
+
+    /// Get the config used by the storage
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the controller shared by this storage, its clones and the workers polling them
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Move up to `count` scheduled jobs that are due to the active set
+    pub fn enqueue_scheduled(&self, count: usize) -> usize {
+        let now = self.config.get_clock().now().timestamp();
+        let mut state = self.state();
+        let due: Vec<_> = state
+            .scheduled
+            .range(..(now + 1, 0))
+            .take(count)
+            .map(|(key, task_id)| (*key, task_id.clone()))
+            .collect();
+        for (key, task_id) in &due {
+            state.scheduled.remove(key);
+            state.activate(task_id.clone());
+        }
+        due.len()
+    }
+
+    /// Get the encoded result of a job, see
+    /// [RedisStorage::get_result](crate::RedisStorage::get_result)
+    pub fn get_result(&self, task_id: &TaskId) -> Option<Vec<u8>> {
+        self.state().results.get(task_id).cloned()
+    }
+
+    /// Get why a dead job was killed, `None` if the job is not dead
+    pub fn dead_reason(&self, task_id: &TaskId) -> Option<DeadReason> {
+        self.state().dead.get(task_id).copied()
+    }
+
+    /// Kill a job held by `worker_id`, see [RedisStorage::kill](crate::RedisStorage::kill)
+    pub fn kill(&self, worker_id: &WorkerId, task_id: &TaskId, error: &BoxDynError) {
+        self.kill_job(worker_id, task_id, DeadReason::Killed, &error.to_string());
+    }
+
+    /// Move the jobs left inflight by `worker_id` back to the active set, returning how many moved
+    ///
+    /// This is done when a worker starts polling, so jobs lost by a worker that died are resumed.
+    pub fn reenqueue_inflight(&self, worker_id: &WorkerId) -> usize {
+        let mut state = self.state();
+        let inflight = state
+            .inflight
+            .remove(&worker_id.to_string())
+            .unwrap_or_default();
+        let count = inflight.len();
+        for task_id in inflight {
+            state.activate(task_id);
+        }
+        count
+    }
+
+    fn kill_job(&self, worker_id: &WorkerId, task_id: &TaskId, reason: DeadReason, error: &str) {
+        let mut state = self.state();
+        if state.release(worker_id, task_id) {
+            state.dead.insert(task_id.clone(), reason);
+            state.errors.insert(task_id.clone(), error.to_owned());
+        }
+    }
+}
+
+impl<T> MemoryStorage<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn decode(job: Vec<u8>) -> Result<Request<T, RedisContext>, RedisStorageError> {
+        JsonCodec::<Vec<u8>>::decode(job).map_err(|e| RedisStorageError::Codec(e.into()))
+    }
+
+    fn encode(req: &Request<T, RedisContext>) -> Result<Vec<u8>, RedisStorageError> {
+        JsonCodec::<Vec<u8>>::encode(req).map_err(|e| RedisStorageError::Encode(e.into()))
+    }
+
+    /// Fetch up to `count` jobs from the active set into the inflight set of `worker_id`, see
+    /// [RedisStorage::fetch_next](crate::RedisStorage::fetch_next)
+    ///
+    /// Jobs that expired while waiting are killed with [DeadReason::Expired] instead. A job that
+    /// fails to decode is handled as set by [Config::set_on_decode_error].
+    pub fn fetch_next(
+        &self,
+        worker_id: &WorkerId,
+        count: usize,
+    ) -> Result<Vec<Request<T, RedisContext>>, RedisStorageError> {
+        let now = self.config.get_clock().now().timestamp();
+        let mut state = self.state();
+        let mut jobs = Vec::new();
+        while jobs.len() < count {
+            let Some((_, task_id)) = state.active.pop_first() else {
+                break;
+            };
+            let Some(job) = state.data.get(&task_id).cloned() else {
+                continue;
+            };
+            // Hold the job before decoding it, so a job that fails to decode is not lost
+            state
+                .inflight
+                .entry(worker_id.to_string())
+                .or_default()
+                .insert(task_id.clone());
+            let mut req = match Self::decode(job) {
+                Ok(req) => req,
+                Err(e) if self.config.get_on_decode_error() == DecodeErrorPolicy::Quarantine => {
+                    state.release(worker_id, &task_id);
+                    state.dead.insert(task_id.clone(), DeadReason::DecodeFailed);
+                    state.errors.insert(task_id, e.to_string());
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            if req.parts.context.expires_at().is_some_and(|at| at <= now) {
+                state.release(worker_id, &task_id);
+                state.dead.insert(task_id, DeadReason::Expired);
+                continue;
+            }
+            req.parts.context.set_lock_by(Some(worker_id.clone()));
+            jobs.push(req);
+        }
+        Ok(jobs)
+    }
+
+    /// Schedule a failed job held by `worker_id` for another attempt, or kill it once it has no
+    /// attempts left, see [RedisStorage::retry](crate::RedisStorage::retry)
+    pub fn retry(&self, worker_id: &WorkerId, task_id: &TaskId) -> Result<(), RedisStorageError> {
+        self.retry_job(worker_id, task_id, None)
+    }
+
+    fn retry_job(
+        &self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+        last_error: Option<&str>,
+    ) -> Result<(), RedisStorageError> {
+        let job = self.state().data.get(task_id).cloned();
+        let mut req =
+            Self::decode(job.ok_or_else(|| RedisStorageError::NotFound(task_id.clone()))?)?;
+        let attempt = req.parts.attempt.current();
+        let max_attempts = self.config.max_attempts_of(&req.parts.context);
+        if attempt >= max_attempts {
+            self.kill_job(
+                worker_id,
+                task_id,
+                DeadReason::MaxRetriesExceeded,
+                last_error.unwrap_or(&format!("Max retries of {max_attempts} exceeded")),
+            );
+            return Ok(());
+        }
+        if let Some(max) = self.config.count_total_attempt(&mut req.parts.context) {
+            self.kill_job(
+                worker_id,
+                task_id,
+                DeadReason::MaxRetriesExceeded,
+                last_error.unwrap_or(&format!("Absolute max attempts of {max} exceeded")),
+            );
+            return Ok(());
+        }
+        let retry_at =
+            self.config.get_clock().now() + self.config.get_backoff().next_delay(attempt);
+        let job = Self::encode(&req)?;
+        let mut state = self.state();
+        if !state.release(worker_id, task_id) {
+            return Err(crate::ScriptError::NotFound.into());
+        }
+        state.data.insert(task_id.clone(), job);
+        if let Some(last_error) = last_error {
+            state.errors.insert(task_id.clone(), last_error.to_owned());
+        }
+        state.schedule(task_id.clone(), retry_at.timestamp());
+        Ok(())
+    }
+}
+
+impl<T> Backend<Request<T, RedisContext>> for MemoryStorage<T>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
+{
+    type Stream = BackendStream<RequestStream<Request<T, RedisContext>>>;
+
+    type Layer = AckLayer<MemoryStorage<T>, T, RedisContext, JsonCodec<Vec<u8>>>;
+
+    type Codec = JsonCodec<Vec<u8>>;
+
+    fn poll(self, worker: &Worker<Context>) -> Poller<Self::Stream, Self::Layer> {
+        self.reenqueue_inflight(worker.id());
+        let layer = AckLayer::new(self.clone());
+        let controller = self.controller.clone();
+        let worker_id = worker.id().clone();
+        let poll_interval = self.config.get_poll_interval().to_owned();
+        let enqueue_scheduled = self.config.get_enqueue_scheduled().to_owned();
+        let buffer_size = self.config.get_buffer_size();
+        let heartbeat = {
+            let storage = self.clone();
+            async move {
+                let mut enqueue_scheduled_stm = apalis_core::interval::interval(enqueue_scheduled);
+                while enqueue_scheduled_stm.next().await.is_some() {
+                    storage.enqueue_scheduled(buffer_size);
+                }
+            }
+        };
+        let stream = futures::stream::unfold((self, false), move |(storage, idle)| {
+            let worker_id = worker_id.clone();
+            async move {
+                if idle {
+                    apalis_core::sleep(poll_interval).await;
+                }
+                let res = storage.fetch_next(&worker_id, buffer_size);
+                let idle = !matches!(&res, Ok(jobs) if !jobs.is_empty());
+                Some((res, (storage, idle)))
+            }
+        })
+        .flat_map(|res| {
+            futures::stream::iter(match res {
+                Ok(jobs) => jobs.into_iter().map(|job| Ok(Some(job))).collect(),
+                Err(e) => vec![Err(Error::SourceError(Arc::new(Box::new(e))))],
+            })
+        })
+        .boxed();
+        Poller::new_with_layer(BackendStream::new(stream, controller), heartbeat, layer)
+    }
+}
+
+impl<T, Res> Ack<T, Res, JsonCodec<Vec<u8>>> for MemoryStorage<T>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
+    Res: Serialize + Sync,
+{
+    type Context = RedisContext;
+    type AckError = RedisStorageError;
+
+    async fn ack(
+        &mut self,
+        ctx: &RedisContext,
+        res: &Response<Res>,
+    ) -> Result<(), RedisStorageError> {
+        let worker_id = ctx.lock_by().expect("job is not locked by a worker");
+        if let Some(mut task) = self.fetch_by_id(&res.task_id).await? {
+            task.parts.attempt = res.attempt.clone();
+            self.update(task).await?;
+        }
+        match &res.inner {
+            Ok(result) => {
+                let result = JsonCodec::<Vec<u8>>::encode(result)
+                    .map_err(|e| RedisStorageError::Encode(e.into()))?;
+                let mut state = self.state();
+                if !state.release(worker_id, &res.task_id) {
+                    return Err(crate::ScriptError::NotFound.into());
+                }
+                state.done.insert(res.task_id.clone());
+                state.results.insert(res.task_id.clone(), result);
+            }
+            Err(Error::Abort(e)) => {
+                self.kill_job(worker_id, &res.task_id, DeadReason::Killed, &e.to_string());
+            }
+            Err(e) => self.retry_job(worker_id, &res.task_id, Some(&e.to_string()))?,
+        }
+        Ok(())
+    }
+}
+
+impl<T> Storage for MemoryStorage<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static,
+{
+    type Job = T;
+    type Error = RedisStorageError;
+    type Context = RedisContext;
+    type Compact = Vec<u8>;
+
+    async fn push_request(
+        &mut self,
+        mut req: Request<T, RedisContext>,
+    ) -> Result<Parts<RedisContext>, RedisStorageError> {
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = Self::encode(&req)?;
+        self.state()
+            .push(req.parts.task_id.clone(), job, req.parts.context.priority());
+        Ok(req.parts)
+    }
+
+    async fn push_raw_request(
+        &mut self,
+        req: Request<Vec<u8>, RedisContext>,
+    ) -> Result<Parts<RedisContext>, RedisStorageError> {
+        let job =
+            JsonCodec::<Vec<u8>>::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        if !self
+            .state()
+            .push(req.parts.task_id.clone(), job, req.parts.context.priority())
+        {
+            return Err(crate::ScriptError::Conflict.into());
+        }
+        Ok(req.parts)
+    }
+
+    async fn schedule_request(
+        &mut self,
+        mut req: Request<T, RedisContext>,
+        on: i64,
+    ) -> Result<Parts<RedisContext>, RedisStorageError> {
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = Self::encode(&req)?;
+        let mut state = self.state();
+        state.data.insert(req.parts.task_id.clone(), job);
+        state
+            .priority
+            .insert(req.parts.task_id.clone(), req.parts.context.priority());
+        state.schedule(req.parts.task_id.clone(), on);
+        Ok(req.parts)
+    }
+
+    async fn len(&mut self) -> Result<i64, RedisStorageError> {
+        Ok(self.state().active.len() as i64)
+    }
+
+    async fn fetch_by_id(
+        &mut self,
+        task_id: &TaskId,
+    ) -> Result<Option<Request<T, RedisContext>>, RedisStorageError> {
+        let (job, last_error, job_state) = {
+            let state = self.state();
+            let Some(job) = state.data.get(task_id).cloned() else {
+                return Ok(None);
+            };
+            (
+                job,
+                state.errors.get(task_id).cloned(),
+                state.state_of(task_id),
+            )
+        };
+        let mut req = Self::decode(job)?;
+        if last_error.is_some() {
+            req.parts.context.set_last_error(last_error);
+        }
+        req.parts.data.insert(job_state);
+        Ok(Some(req))
+    }
+
+    async fn update(&mut self, mut job: Request<T, RedisContext>) -> Result<(), RedisStorageError> {
+        self.config.apply_context_profile(&mut job.parts.context);
+        let bytes = Self::encode(&job)?;
+        self.state().data.insert(job.parts.task_id, bytes);
+        Ok(())
+    }
+
+    async fn reschedule(
+        &mut self,
+        mut job: Request<T, RedisContext>,
+        wait: Duration,
+    ) -> Result<(), RedisStorageError> {
+        let worker_id = job
+            .parts
+            .context
+            .lock_by()
+            .cloned()
+            .expect("job is not locked by a worker");
+        if let Some(max) = self.config.count_total_attempt(&mut job.parts.context) {
+            self.kill_job(
+                &worker_id,
+                &job.parts.task_id,
+                DeadReason::MaxRetriesExceeded,
+                &format!("Absolute max attempts of {max} exceeded"),
+            );
+            return Ok(());
+        }
+        self.config.apply_context_profile(&mut job.parts.context);
+        let bytes = Self::encode(&job)?;
+        let on = (self.config.get_clock().now() + wait).timestamp();
+        let task_id = job.parts.task_id;
+        let mut state = self.state();
+        state.release(&worker_id, &task_id);
+        state.data.insert(task_id.clone(), bytes);
+        state.schedule(task_id, on);
+        Ok(())
+    }
+
+    async fn is_empty(&mut self) -> Result<bool, RedisStorageError> {
+        Ok(self.state().active.is_empty())
+    }
+
+    async fn vacuum(&mut self) -> Result<usize, RedisStorageError> {
+        let mut state = self.state();
+        let done: Vec<_> = state.done.drain().collect();
+        for task_id in &done {
+            state.data.remove(task_id);
+            state.priority.remove(task_id);
+            state.errors.remove(task_id);
+            state.results.remove(task_id);
+        }
+        Ok(done.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use apalis_core::generic_storage_test;
+    use apalis_core::test_utils::apalis_test_service_fn;
+    use apalis_core::test_utils::TestWrapper;
+    use chrono::Utc;
+    use email_service::Email;
+
+    generic_storage_test!(setup);
+
+    use super::*;
+
+    async fn setup<T>() -> MemoryStorage<T> {
+        MemoryStorage::new_with_config(
+            Config::default()
+                .set_poll_interval(Duration::from_millis(50))
+                .set_enqueue_scheduled(Duration::from_millis(500)),
+        )
+    }
+
+    fn email(to: &str) -> Email {
+        Email {
+            to: to.to_owned(),
+            text: "Test background job from apalis".to_owned(),
+            subject: "Background email job".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetches_by_priority_then_push_order() {
+        let mut storage = setup::<Email>().await;
+        let worker_id = WorkerId::new("test-worker");
+        let mut low = Request::<_, RedisContext>::new(email("low@example.com"));
+        low.parts.context.set_priority(-1);
+        let mut high = Request::<_, RedisContext>::new(email("high@example.com"));
+        high.parts.context.set_priority(5);
+        storage.push_request(low).await.unwrap();
+        storage.push(email("first@example.com")).await.unwrap();
+        storage.push_request(high).await.unwrap();
+        storage.push(email("second@example.com")).await.unwrap();
+
+        let jobs = storage.fetch_next(&worker_id, 10).unwrap();
+        let to: Vec<_> = jobs.iter().map(|job| job.args.to.as_str()).collect();
+        assert_eq!(
+            to,
+            [
+                "high@example.com",
+                "first@example.com",
+                "second@example.com",
+                "low@example.com"
+            ]
+        );
+        assert!(storage.is_empty().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_jobs_are_enqueued_once_due() {
+        let mut storage = setup::<Email>().await;
+        let now = Utc::now().timestamp();
+        storage
+            .schedule(email("later@example.com"), now + 3600)
+            .await
+            .unwrap();
+        storage
+            .schedule(email("due@example.com"), now - 1)
+            .await
+            .unwrap();
+
+        assert_eq!(storage.enqueue_scheduled(10), 1);
+        assert_eq!(storage.len().await.unwrap(), 1);
+        let jobs = storage
+            .fetch_next(&WorkerId::new("test-worker"), 10)
+            .unwrap();
+        assert_eq!(jobs[0].args.to, "due@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_failed_jobs_are_killed_after_max_attempts() {
+        let mut storage = setup::<Email>().await;
+        storage.config = storage.config.clone().set_max_attempts(2);
+        let worker_id = WorkerId::new("test-worker");
+        let parts = storage.push(email("fail@example.com")).await.unwrap();
+
+        for attempt in 1..=2 {
+            let job = storage.fetch_next(&worker_id, 1).unwrap().remove(0);
+            job.parts.attempt.increment();
+            let res = Response::new(
+                Err(Error::Failed(Arc::new("oh no!".into()))),
+                parts.task_id.clone(),
+                job.parts.attempt.clone(),
+            );
+            Ack::<Email, (), JsonCodec<Vec<u8>>>::ack(&mut storage, &job.parts.context, &res)
+                .await
+                .unwrap();
+            if attempt == 1 {
+                assert_eq!(storage.enqueue_scheduled(10), 1);
+            }
+        }
+
+        assert_eq!(
+            storage.dead_reason(&parts.task_id),
+            Some(DeadReason::MaxRetriesExceeded)
+        );
+        let job = storage.fetch_by_id(&parts.task_id).await.unwrap().unwrap();
+        assert_eq!(job.parts.attempt.current(), 2);
+        assert_eq!(job.parts.context.last_error(), Some("FailedError: oh no!"));
+    }
+
+    #[tokio::test]
+    async fn test_retried_jobs_are_done_once_they_succeed() {
+        let mut storage = setup::<Email>().await;
+        let worker_id = WorkerId::new("test-worker");
+        let parts = storage.push(email("retry@example.com")).await.unwrap();
+
+        let job = storage.fetch_next(&worker_id, 1).unwrap().remove(0);
+        let fetched = storage.fetch_by_id(&parts.task_id).await.unwrap().unwrap();
+        assert_eq!(
+            fetched.parts.data.get::<JobState>(),
+            Some(&JobState::Inflight)
+        );
+        job.parts.attempt.increment();
+        let res = Response::new(
+            Err(Error::Failed(Arc::new("oh no!".into()))),
+            parts.task_id.clone(),
+            job.parts.attempt.clone(),
+        );
+        Ack::<Email, (), JsonCodec<Vec<u8>>>::ack(&mut storage, &job.parts.context, &res)
+            .await
+            .unwrap();
+        let job = storage.fetch_by_id(&parts.task_id).await.unwrap().unwrap();
+        assert_eq!(job.parts.data.get::<JobState>(), Some(&JobState::Scheduled));
+
+        assert_eq!(storage.enqueue_scheduled(10), 1);
+        let job = storage.fetch_next(&worker_id, 1).unwrap().remove(0);
+        let res = Response::success((), parts.task_id.clone(), job.parts.attempt.clone());
+        Ack::<Email, (), JsonCodec<Vec<u8>>>::ack(&mut storage, &job.parts.context, &res)
+            .await
+            .unwrap();
+        let job = storage.fetch_by_id(&parts.task_id).await.unwrap().unwrap();
+        assert_eq!(job.parts.data.get::<JobState>(), Some(&JobState::Done));
+    }
+
+    #[tokio::test]
+    async fn test_jobs_that_fail_to_decode_are_quarantined() {
+        let mut storage = setup::<Email>().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_on_decode_error(DecodeErrorPolicy::Quarantine);
+        let worker_id = WorkerId::new("test-worker");
+        let bad = Request::<Vec<u8>, RedisContext>::new(b"not an email".to_vec());
+        let bad = storage.push_raw_request(bad).await.unwrap();
+        storage.push(email("good@example.com")).await.unwrap();
+
+        let jobs = storage.fetch_next(&worker_id, 10).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].args.to, "good@example.com");
+        assert_eq!(
+            storage.dead_reason(&bad.task_id),
+            Some(DeadReason::DecodeFailed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jobs_that_fail_to_decode_stay_inflight() {
+        let mut storage = setup::<Email>().await;
+        let worker_id = WorkerId::new("test-worker");
+        let bad = Request::<Vec<u8>, RedisContext>::new(b"not an email".to_vec());
+        storage.push_raw_request(bad).await.unwrap();
+
+        assert!(storage.fetch_next(&worker_id, 10).is_err());
+        assert!(storage.is_empty().await.unwrap());
+        assert_eq!(storage.reenqueue_inflight(&worker_id), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ack_of_a_job_not_inflight_is_not_found() {
+        let mut storage = setup::<Email>().await;
+        let parts = storage.push(email("done@example.com")).await.unwrap();
+        let mut ctx = RedisContext::default();
+        ctx.set_lock_by(Some(WorkerId::new("test-worker")));
+        let res = Response::success((), parts.task_id, parts.attempt);
+        let err = Ack::<Email, (), JsonCodec<Vec<u8>>>::ack(&mut storage, &ctx, &res)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            RedisStorageError::Script(crate::ScriptError::NotFound)
+        ));
+    }
+}
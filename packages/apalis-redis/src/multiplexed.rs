@@ -0,0 +1,91 @@
+use crate::{RedisContext, RedisStorageError};
+use apalis_core::codec::Codec;
+use apalis_core::error::BoxDynError;
+use apalis_core::request::Request;
+use serde::de::{DeserializeOwned, IgnoredAny};
+
+/// A job enum fetched from a namespace shared by several job types, see
+/// [RedisStorage::new_multiplexed](crate::RedisStorage::new_multiplexed).
+///
+/// Each job is pushed by a typed storage and tagged with its
+/// [Config::set_job_type](crate::Config::set_job_type), which selects the variant it decodes into.
+///
+/// ```rust
+/// # use apalis_core::codec::Codec;
+/// # use apalis_core::request::Request;
+/// # use apalis_redis::{MultiplexedJob, RedisContext};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Email(String);
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct Sms(String);
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// enum Notification {
+///     Email(Email),
+///     Sms(Sms),
+/// }
+///
+/// impl MultiplexedJob for Notification {
+///     fn decode_job<C: Codec<Compact = Vec<u8>>>(
+///         job_type: &str,
+///         job: Vec<u8>,
+///     ) -> Option<Result<Request<Self, RedisContext>, C::Error>> {
+///         match job_type {
+///             "email" => Some(C::decode(job).map(|req: Request<Email, RedisContext>| {
+///                 Request::new_with_parts(Notification::Email(req.args), req.parts)
+///             })),
+///             "sms" => Some(C::decode(job).map(|req: Request<Sms, RedisContext>| {
+///                 Request::new_with_parts(Notification::Sms(req.args), req.parts)
+///             })),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait MultiplexedJob: Sized {
+    /// Decode a job of the given type, returns `None` for types this enum does not handle
+    fn decode_job<C: Codec<Compact = Vec<u8>>>(
+        job_type: &str,
+        job: Vec<u8>,
+    ) -> Option<Result<Request<Self, RedisContext>, C::Error>>;
+}
+
+/// Decodes a job of a multiplexed storage by its job type
+pub(crate) type MultiplexedDecoder<T> =
+    fn(&str, Vec<u8>) -> Option<Result<Request<T, RedisContext>, BoxDynError>>;
+
+pub(crate) fn decode_multiplexed<T, C>(
+    job_type: &str,
+    job: Vec<u8>,
+) -> Option<Result<Request<T, RedisContext>, BoxDynError>>
+where
+    T: MultiplexedJob,
+    C: Codec<Compact = Vec<u8>>,
+{
+    T::decode_job::<C>(job_type, job).map(|res| res.map_err(Into::into))
+}
+
+/// Decode a job with the codec, or by its job type if the storage is multiplexed
+pub(crate) fn decode_job<T, C>(
+    multiplexed: Option<MultiplexedDecoder<T>>,
+    job: Vec<u8>,
+) -> Result<Request<T, RedisContext>, RedisStorageError>
+where
+    T: DeserializeOwned,
+    C: Codec<Compact = Vec<u8>>,
+{
+    let Some(decode) = multiplexed else {
+        return C::decode(job).map_err(|e| RedisStorageError::Codec(e.into()));
+    };
+    let untyped: Request<IgnoredAny, RedisContext> =
+        C::decode(job.clone()).map_err(|e| RedisStorageError::Codec(e.into()))?;
+    let job_type = untyped.parts.context.job_type().map(ToOwned::to_owned);
+    match job_type
+        .as_deref()
+        .and_then(|job_type| decode(job_type, job))
+    {
+        Some(res) => res.map_err(RedisStorageError::Codec),
+        None => Err(RedisStorageError::UnexpectedJobType(job_type)),
+    }
+}
@@ -0,0 +1,84 @@
+use std::time::{Duration, Instant};
+
+/// A token bucket limiting how many jobs a worker fetches per window
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBucket {
+    max: usize,
+    per: Duration,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Build a full bucket allowing `max` jobs every `per`
+    pub(crate) fn new(max: usize, per: Duration) -> Self {
+        Self::new_at(max, per, Instant::now())
+    }
+
+    fn new_at(max: usize, per: Duration, now: Instant) -> Self {
+        Self {
+            max,
+            per,
+            tokens: max as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Get the number of jobs that can be fetched now
+    pub(crate) fn available(&mut self) -> usize {
+        self.available_at(Instant::now())
+    }
+
+    fn available_at(&mut self, now: Instant) -> usize {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+        if self.per.is_zero() {
+            self.tokens = self.max as f64;
+        } else {
+            let refill = elapsed.as_secs_f64() / self.per.as_secs_f64() * self.max as f64;
+            self.tokens = (self.tokens + refill).min(self.max as f64);
+        }
+        self.tokens.floor() as usize
+    }
+
+    /// Take tokens for the jobs that were fetched
+    pub(crate) fn take(&mut self, count: usize) {
+        self.tokens = (self.tokens - count as f64).max(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_paces_fetches() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(2, Duration::from_secs(1), start);
+        let mut pending = 10;
+        let mut fetched_at = Vec::new();
+        let mut now = start;
+        while pending > 0 {
+            let count = bucket.available_at(now).min(pending);
+            bucket.take(count);
+            pending -= count;
+            fetched_at.extend(std::iter::repeat(now - start).take(count));
+            now += Duration::from_millis(100);
+        }
+        // The first two jobs are fetched at once, then one every 500ms
+        assert_eq!(fetched_at.len(), 10);
+        for (i, at) in fetched_at.iter().enumerate() {
+            let expected = Duration::from_millis(500 * i.saturating_sub(1) as u64);
+            assert_eq!(*at, expected, "job {i} fetched after {at:?}");
+        }
+    }
+
+    #[test]
+    fn token_bucket_does_not_exceed_max() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new_at(3, Duration::from_secs(1), start);
+        assert_eq!(bucket.available_at(start + Duration::from_secs(60)), 3);
+        bucket.take(3);
+        assert_eq!(bucket.available_at(start + Duration::from_secs(60)), 0);
+    }
+}
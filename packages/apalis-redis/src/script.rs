@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use redis::aio::ConnectionLike;
+use redis::{
+    ErrorKind, FromRedisValue, RedisError, RedisResult, Script, ScriptInvocation, ToRedisArgs,
+    Value,
+};
+
+use crate::error::ScriptError;
+
+/// A Lua script run by a [RedisStorage](crate::RedisStorage), see [RedisScripts]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ScriptName {
+    /// Acknowledges a job as done or failed
+    AckJob,
+    /// Acknowledges a batch of jobs as done
+    AckJobs,
+    /// Cancels a pending job
+    CancelJob,
+    /// Moves the scheduled jobs that are due to the active set
+    EnqueueScheduled,
+    /// Fetches jobs into the inflight set of a worker
+    GetJobs,
+    /// Moves a job to the dead set
+    KillJob,
+    /// Moves the active jobs of a queue created before they were sorted by priority to a sorted set
+    MigrateActive,
+    /// Pushes a job, see [PUSH_JOB_SCRIPT](crate::PUSH_JOB_SCRIPT)
+    PushJob,
+    /// Pushes a batch of jobs
+    PushJobs,
+    /// Pushes a job unless its unique key is held
+    PushUniqueJob,
+    /// Moves inflight jobs back to the active set
+    ReenqueueActive,
+    /// Moves the jobs of workers that stopped sending keep-alives back to the active set
+    ReenqueueOrphaned,
+    /// Moves jobs inflight for longer than the visibility timeout back to the active set
+    ReenqueueStale,
+    /// Registers a worker and records its keep-alive
+    RegisterConsumer,
+    /// Moves dead jobs back to the active set
+    ReplayDead,
+    /// Schedules a failed job for another attempt
+    RetryJob,
+    /// Schedules a job
+    ScheduleJob,
+    /// Counts the jobs in each state
+    Stats,
+    /// Deletes done jobs older than the retention
+    TrimDone,
+    /// Stores the context of a job apart from its data
+    UpdateContext,
+    /// Deletes the data of done jobs
+    Vacuum,
+    /// Deletes the data of jobs that no set references
+    VacuumOrphaned,
+}
+
+impl ScriptName {
+    /// Get the name of the script, as recorded by the `redis_script` tracing span
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptName::AckJob => "ack_job",
+            ScriptName::AckJobs => "ack_jobs",
+            ScriptName::CancelJob => "cancel_job",
+            ScriptName::EnqueueScheduled => "enqueue_scheduled_jobs",
+            ScriptName::GetJobs => "get_jobs",
+            ScriptName::KillJob => "kill_job",
+            ScriptName::MigrateActive => "migrate_active_jobs",
+            ScriptName::PushJob => "push_job",
+            ScriptName::PushJobs => "push_jobs",
+            ScriptName::PushUniqueJob => "push_unique_job",
+            ScriptName::ReenqueueActive => "reenqueue_active_jobs",
+            ScriptName::ReenqueueOrphaned => "reenqueue_orphaned_jobs",
+            ScriptName::ReenqueueStale => "reenqueue_stale_jobs",
+            ScriptName::RegisterConsumer => "register_consumer",
+            ScriptName::ReplayDead => "replay_dead_jobs",
+            ScriptName::RetryJob => "retry_job",
+            ScriptName::ScheduleJob => "schedule_job",
+            ScriptName::Stats => "stats",
+            ScriptName::TrimDone => "trim_done",
+            ScriptName::UpdateContext => "update_context",
+            ScriptName::Vacuum => "vacuum",
+            ScriptName::VacuumOrphaned => "vacuum_orphaned",
+        }
+    }
+
+    /// Get the source of the script bundled with the crate
+    ///
+    /// The comments at its top list the keys and arguments the script is invoked with and the
+    /// reply the storage expects, which a replacement must honor.
+    pub fn bundled(&self) -> &'static str {
+        match self {
+            ScriptName::AckJob => include_str!("../lua/ack_job.lua"),
+            ScriptName::AckJobs => include_str!("../lua/ack_jobs.lua"),
+            ScriptName::CancelJob => include_str!("../lua/cancel_job.lua"),
+            ScriptName::EnqueueScheduled => include_str!("../lua/enqueue_scheduled_jobs.lua"),
+            ScriptName::GetJobs => include_str!("../lua/get_jobs.lua"),
+            ScriptName::KillJob => include_str!("../lua/kill_job.lua"),
+            ScriptName::MigrateActive => include_str!("../lua/migrate_active_jobs.lua"),
+            ScriptName::PushJob => include_str!("../lua/push_job.lua"),
+            ScriptName::PushJobs => include_str!("../lua/push_jobs.lua"),
+            ScriptName::PushUniqueJob => include_str!("../lua/push_unique_job.lua"),
+            ScriptName::ReenqueueActive => include_str!("../lua/reenqueue_active_jobs.lua"),
+            ScriptName::ReenqueueOrphaned => include_str!("../lua/reenqueue_orphaned_jobs.lua"),
+            ScriptName::ReenqueueStale => include_str!("../lua/reenqueue_stale_jobs.lua"),
+            ScriptName::RegisterConsumer => include_str!("../lua/register_consumer.lua"),
+            ScriptName::ReplayDead => include_str!("../lua/replay_dead_jobs.lua"),
+            ScriptName::RetryJob => include_str!("../lua/retry_job.lua"),
+            ScriptName::ScheduleJob => include_str!("../lua/schedule_job.lua"),
+            ScriptName::Stats => include_str!("../lua/stats.lua"),
+            ScriptName::TrimDone => include_str!("../lua/trim_done.lua"),
+            ScriptName::UpdateContext => include_str!("../lua/update_context.lua"),
+            ScriptName::Vacuum => include_str!("../lua/vacuum.lua"),
+            ScriptName::VacuumOrphaned => include_str!("../lua/vacuum_orphaned.lua"),
+        }
+    }
+}
+
+/// Replacements for the Lua scripts a [RedisStorage](crate::RedisStorage) runs, see
+/// [RedisStorage::with_scripts](crate::RedisStorage::with_scripts)
+///
+/// This is an escape hatch for behaviors the crate does not cover, such as incrementing a metric
+/// when a job is acknowledged. Scripts that are not replaced run the bundled source. A
+/// replacement is invoked with the keys and arguments documented at the top of
+/// [ScriptName::bundled] and must reply in the same shape, a good start is to copy the bundled
+/// script and extend it. Replacements are not checked until they run, or until
+/// [RedisStorage::load_scripts](crate::RedisStorage::load_scripts) loads them.
+#[derive(Clone, Debug, Default)]
+pub struct RedisScripts {
+    overrides: HashMap<ScriptName, Cow<'static, str>>,
+}
+
+impl RedisScripts {
+    /// Run the bundled scripts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the source of `script`
+    pub fn set(mut self, script: ScriptName, code: impl Into<Cow<'static, str>>) -> Self {
+        self.overrides.insert(script, code.into());
+        self
+    }
+
+    /// Get the source `script` runs, the replacement if it was set and the bundled one otherwise
+    pub fn get(&self, script: ScriptName) -> &str {
+        self.overrides
+            .get(&script)
+            .map_or(script.bundled(), |code| code.as_ref())
+    }
+}
+
+/// A Lua script that carries its name so that its invocations can be traced
+#[derive(Clone, Debug)]
+pub(crate) struct NamedScript {
+    name: &'static str,
+    code: Cow<'static, str>,
+    script: Script,
+}
+
+impl NamedScript {
+    pub(crate) fn new(name: &'static str, code: impl Into<Cow<'static, str>>) -> Self {
+        let code = code.into();
+        Self {
+            name,
+            script: Script::new(&code),
+            code,
+        }
+    }
+
+    /// Build `script` from its replacement in `scripts`, or from its bundled source
+    pub(crate) fn of(script: ScriptName, scripts: &RedisScripts) -> Self {
+        match scripts.overrides.get(&script) {
+            Some(code) => Self::new(script.name(), code.clone()),
+            None => Self::new(script.name(), script.bundled()),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub(crate) fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The SHA1 Redis refers to the script by once it is loaded
+    pub(crate) fn hash(&self) -> &str {
+        self.script.get_hash()
+    }
+
+    /// Start an invocation with its first key
+    pub(crate) fn key<T: ToRedisArgs>(&self, key: T) -> NamedInvocation<'_> {
+        NamedInvocation {
+            name: self.name,
+            keys: 1,
+            invocation: self.script.key(key),
+        }
+    }
+}
+
+/// The status a script replies with as the first element of its reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ScriptStatus(pub(crate) Result<(), ScriptError>);
+
+impl FromRedisValue for ScriptStatus {
+    fn from_redis_value(v: &Value) -> RedisResult<Self> {
+        let reply: Vec<Value> = redis::from_redis_value(v)?;
+        let status: String = match reply.first() {
+            Some(status) => redis::from_redis_value(status)?,
+            None => {
+                return Err(RedisError::from((
+                    ErrorKind::TypeError,
+                    "script replied without a status",
+                )))
+            }
+        };
+        ScriptError::from_status(&status)
+            .map(ScriptStatus)
+            .ok_or_else(|| {
+                RedisError::from((
+                    ErrorKind::TypeError,
+                    "script replied with an unknown status",
+                    status,
+                ))
+            })
+    }
+}
+
+/// A prepared call of a [NamedScript]
+pub(crate) struct NamedInvocation<'a> {
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    name: &'static str,
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    keys: usize,
+    invocation: ScriptInvocation<'a>,
+}
+
+impl NamedInvocation<'_> {
+    pub(crate) fn key<T: ToRedisArgs>(&mut self, key: T) -> &mut Self {
+        self.invocation.key(key);
+        self.keys += 1;
+        self
+    }
+
+    pub(crate) fn arg<T: ToRedisArgs>(&mut self, arg: T) -> &mut Self {
+        self.invocation.arg(arg);
+        self
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) async fn invoke_async<T: FromRedisValue>(
+        &self,
+        conn: &mut impl ConnectionLike,
+    ) -> RedisResult<T> {
+        self.invocation.invoke_async(conn).await
+    }
+
+    /// Invoke the script inside a `redis_script` debug span recording the script name, the number
+    /// of keys and the elapsed time. Nothing is timed unless a subscriber enables the span.
+    #[cfg(feature = "tracing")]
+    pub(crate) async fn invoke_async<T: FromRedisValue>(
+        &self,
+        conn: &mut impl ConnectionLike,
+    ) -> RedisResult<T> {
+        use tracing::Instrument;
+
+        let span = tracing::debug_span!(
+            "redis_script",
+            script = self.name,
+            keys = self.keys,
+            elapsed_ms = tracing::field::Empty,
+        );
+        if span.is_disabled() {
+            return self.invocation.invoke_async(conn).await;
+        }
+        let start = std::time::Instant::now();
+        let res = self
+            .invocation
+            .invoke_async(conn)
+            .instrument(span.clone())
+            .await;
+        span.record("elapsed_ms", start.elapsed().as_secs_f64() * 1000.0);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invocation_counts_keys() {
+        let script = NamedScript::new("push_job", "return KEYS[1]");
+        let mut invocation = script.key("a");
+        invocation.key("b").arg(1).key("c").arg("d");
+        assert_eq!(invocation.name, "push_job");
+        assert_eq!(invocation.keys, 3);
+    }
+
+    #[test]
+    fn replaced_scripts_fall_back_to_the_bundled_ones() {
+        let scripts = RedisScripts::new().set(ScriptName::AckJob, "return {'ok'}");
+        let ack_job = NamedScript::of(ScriptName::AckJob, &scripts);
+        assert_eq!(ack_job.name(), "ack_job");
+        assert_eq!(ack_job.code(), "return {'ok'}");
+        let retry_job = NamedScript::of(ScriptName::RetryJob, &scripts);
+        assert_eq!(retry_job.code(), include_str!("../lua/retry_job.lua"));
+        assert_eq!(scripts.get(ScriptName::RetryJob), retry_job.code());
+    }
+
+    #[test]
+    fn script_status_is_parsed() {
+        let reply = |status: &str| Value::Array(vec![Value::BulkString(status.into())]);
+        assert_eq!(
+            ScriptStatus::from_redis_value(&reply("ok")).unwrap(),
+            ScriptStatus(Ok(()))
+        );
+        assert_eq!(
+            ScriptStatus::from_redis_value(&reply("conflict")).unwrap(),
+            ScriptStatus(Err(ScriptError::Conflict))
+        );
+        assert_eq!(
+            ScriptStatus::from_redis_value(&reply("not_found")).unwrap(),
+            ScriptStatus(Err(ScriptError::NotFound))
+        );
+        assert!(ScriptStatus::from_redis_value(&reply("unknown")).is_err());
+        assert!(ScriptStatus::from_redis_value(&Value::Array(vec![])).is_err());
+        assert!(ScriptStatus::from_redis_value(&Value::Int(1)).is_err());
+    }
+}
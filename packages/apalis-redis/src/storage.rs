@@ -1,6 +1,19 @@
+use crate::backoff::{BackoffStrategy, FixedBackoff, ReconnectBackoff};
+use crate::clock::{Clock, SystemClock};
+use crate::fetch_interval::FetchInterval;
+use crate::jitter::{jittered_interval, Jitter};
+use crate::listener::{JobEventListener, NoopListener};
+use crate::multiplexed::{decode_job, decode_multiplexed, MultiplexedDecoder, MultiplexedJob};
+use crate::rate_limit::TokenBucket;
+use crate::script::{NamedScript, RedisScripts, ScriptName, ScriptStatus};
+use crate::task_id::{TaskIdEncoding, TaskIdFactory, UlidFactory};
+use crate::timeout::{is_timeout, JobTimeoutLayer};
+#[cfg(feature = "deadpool")]
+use crate::RedisConn;
+use crate::{RedisStorageError, ScriptError};
 use apalis_core::codec::json::JsonCodec;
 use apalis_core::error::{BoxDynError, Error};
-use apalis_core::layers::{Ack, AckLayer};
+use apalis_core::layers::{Ack, AckLayer, Stack};
 use apalis_core::poller::controller::Controller;
 use apalis_core::poller::stream::BackendStream;
 use apalis_core::poller::Poller;
@@ -8,23 +21,32 @@ use apalis_core::request::{Parts, Request, RequestStream};
 use apalis_core::response::Response;
 use apalis_core::service_fn::FromRequest;
 use apalis_core::storage::Storage;
+use apalis_core::task::attempt::Attempt;
 use apalis_core::task::namespace::Namespace;
 use apalis_core::task::task_id::TaskId;
 use apalis_core::worker::{Event, Worker, WorkerId};
 use apalis_core::{backend::Backend, codec::Codec};
 use chrono::{DateTime, Utc};
 use futures::channel::mpsc::{self, SendError, Sender};
-use futures::{select, FutureExt, SinkExt, StreamExt, TryFutureExt};
+use futures::future::BoxFuture;
+use futures::{select, Future, FutureExt, SinkExt, Stream, StreamExt, TryFutureExt};
 use log::*;
 use redis::aio::ConnectionLike;
 use redis::ErrorKind;
-use redis::{aio::ConnectionManager, Client, IntoConnectionInfo, RedisError, Script, Value};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use redis::{aio::ConnectionManager, Client, IntoConnectionInfo, RedisError, Value};
+#[cfg(feature = "cluster")]
+use redis::{cluster::ClusterClient, cluster_async::ClusterConnection};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
 use std::any::type_name;
+use std::collections::HashMap;
 use std::fmt::{self, Debug};
 use std::io;
 use std::num::TryFromIntError;
-use std::time::SystemTime;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Instant, SystemTime};
 use std::{marker::PhantomData, time::Duration};
 
 /// Shorthand to create a client and connect
@@ -34,30 +56,101 @@ pub async fn connect<S: IntoConnectionInfo>(redis: S) -> Result<ConnectionManage
     Ok(conn)
 }
 
+/// Shorthand to create a cluster client and connect to the given nodes
+///
+/// Every script touches several keys of one namespace, so they must hash to the same slot. The
+/// namespace is used as-is in key names, wrap it in a hash tag such as `{emails}` with
+/// [Config::set_namespace] when running against a cluster.
+#[cfg(feature = "cluster")]
+pub async fn connect_cluster(nodes: Vec<String>) -> Result<ClusterConnection, RedisError> {
+    let client = ClusterClient::new(nodes)?;
+    let conn = client.get_async_connection().await?;
+    Ok(conn)
+}
+
 const ACTIVE_JOBS_LIST: &str = "{queue}:active";
+const COMPLETED_JOBS_LIST: &str = "{queue}:completed";
 const CONSUMERS_SET: &str = "{queue}:consumers";
+const DEAD_LETTER_LIST: &str = "{queue}:dead_letter";
 const DEAD_JOBS_SET: &str = "{queue}:dead";
+const DEAD_REASON_HASH: &str = "{queue}:dead_reason";
+const DONE_DETAILS_SET: &str = "{queue}:done_details";
 const DONE_JOBS_SET: &str = "{queue}:done";
 const FAILED_JOBS_SET: &str = "{queue}:failed";
+const GROUP_STATS_HASH: &str = "{queue}:groups";
 const INFLIGHT_JOB_SET: &str = "{queue}:inflight";
+const JOB_CONTEXT_HASH: &str = "{queue}:context";
 const JOB_DATA_HASH: &str = "{queue}:data";
+const JOB_DURATION_HASH: &str = "{queue}:duration";
+const JOB_ERROR_HASH: &str = "{queue}:errors";
+const JOB_EXPIRY_HASH: &str = "{queue}:expiry";
+const JOB_FETCHED_HASH: &str = "{queue}:fetched";
+const JOB_GROUP_HASH: &str = "{queue}:group";
+const JOB_PRIORITY_HASH: &str = "{queue}:priority";
+const JOB_PROGRESS_HASH: &str = "{queue}:progress";
+const JOB_RESULT_HASH: &str = "{queue}:result";
+const RETRY_BUDGET_SET: &str = "{queue}:retry_budget";
 const SCHEDULED_JOBS_SET: &str = "{queue}:scheduled";
 const SIGNAL_LIST: &str = "{queue}:signal";
+const UNIQUE_JOBS_HASH: &str = "{queue}:unique";
+
+/// The Lua script a [RedisStorage] pushes jobs with, for producers that are not written in Rust.
+///
+/// Load it with `SCRIPT LOAD` and run it with `EVALSHA`, passing the keys returned by these
+/// [Config] accessors in order:
+///
+/// 1. [Config::job_data_hash]
+/// 2. [Config::active_jobs_list]
+/// 3. [Config::signal_list]
+/// 4. [Config::job_priority_hash]
+/// 5. [Config::job_expiry_hash]
+///
+/// and the arguments:
+///
+/// 1. a unique job id, a ULID by default
+/// 2. the encoded job
+/// 3. the priority of the job, `0` by default
+/// 4. the unix timestamp after which the job expires, `0` if it never expires
+///
+/// The script replies with `["ok"]` once the job is enqueued, or `["conflict"]` if a job with the
+/// same id already exists, see [ScriptError].
+///
+/// With the default [JsonCodec] a job is a JSON object holding the job arguments and its parts,
+/// where the `task_id` repeats the job id and every field of the `context` may be omitted:
+///
+/// ```json
+/// {
+///   "args": { "to": "user@example.com" },
+///   "parts": { "task_id": "01J8Z3Q5ZC3V4XNTKQ1W6M8E2B", "context": {} }
+/// }
+/// ```
+///
+/// See [RedisStorage::encode_external] to get the exact payload a Rust producer would push.
+pub const PUSH_JOB_SCRIPT: &str = include_str!("../lua/push_job.lua");
 
 /// Represents redis key names for various components of the RedisStorage.
 ///
 /// This struct defines keys used in Redis to manage jobs and their lifecycle in the storage.
 #[derive(Clone, Debug)]
 pub struct RedisQueueInfo {
-    /// Key for the list of currently active jobs.
+    /// Key for the sorted set of currently active jobs, ordered by priority.
     pub active_jobs_list: String,
 
+    /// Key for the list of completed jobs waiting to be consumed by a completed stream.
+    pub completed_jobs_list: String,
+
     /// Key for the set of active consumers.
     pub consumers_set: String,
 
     /// Key for the set of jobs that are no longer retryable.
     pub dead_jobs_set: String,
 
+    /// Key for the list of dead jobs waiting to be consumed by a dead letter stream.
+    pub dead_letter_list: String,
+
+    /// Key for the hash storing why each dead job died.
+    pub dead_reason_hash: String,
+
     /// Key for the set of jobs that have completed successfully.
     pub done_jobs_set: String,
 
@@ -70,43 +163,343 @@ pub struct RedisQueueInfo {
     /// Key for the hash storing data for each job.
     pub job_data_hash: String,
 
+    /// Key for the hash storing how long each acknowledged job ran, in milliseconds.
+    pub job_duration_hash: String,
+
+    /// Key for the hash storing the last error of each failed job.
+    pub job_error_hash: String,
+
+    /// Key for the hash storing the expiry time of each job.
+    pub job_expiry_hash: String,
+
+    /// Key for the hash storing the time in milliseconds each inflight job was fetched.
+    pub job_fetched_hash: String,
+
+    /// Key for the hash storing the priority of each job.
+    pub job_priority_hash: String,
+
+    /// Key for the hash storing the progress of each job.
+    pub job_progress_hash: String,
+
+    /// Key for the hash storing the result of each completed job.
+    pub job_result_hash: String,
+
     /// Key for the set of jobs scheduled for future execution.
     pub scheduled_jobs_set: String,
 
     /// Key for the list used for signaling and communication between consumers and producers.
     pub signal_list: String,
+
+    /// Key for the hash mapping unique keys to the job holding them.
+    pub unique_jobs_hash: String,
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct RedisScript {
-    done_job: Script,
-    enqueue_scheduled: Script,
-    get_jobs: Script,
-    kill_job: Script,
-    push_job: Script,
-    reenqueue_active: Script,
-    reenqueue_orphaned: Script,
-    register_consumer: Script,
-    retry_job: Script,
-    schedule_job: Script,
-    vacuum: Script,
-    pub(crate) stats: Script,
+    ack_job: NamedScript,
+    ack_jobs: NamedScript,
+    cancel_job: NamedScript,
+    enqueue_scheduled: NamedScript,
+    get_jobs: NamedScript,
+    kill_job: NamedScript,
+    migrate_active: NamedScript,
+    push_job: NamedScript,
+    push_jobs: NamedScript,
+    push_unique_job: NamedScript,
+    reenqueue_active: NamedScript,
+    reenqueue_orphaned: NamedScript,
+    reenqueue_stale: NamedScript,
+    register_consumer: NamedScript,
+    replay_dead: NamedScript,
+    retry_job: NamedScript,
+    schedule_job: NamedScript,
+    trim_done: NamedScript,
+    update_context: NamedScript,
+    vacuum: NamedScript,
+    vacuum_orphaned: NamedScript,
+    pub(crate) stats: NamedScript,
+}
+
+impl RedisScript {
+    fn new(scripts: &RedisScripts) -> Self {
+        Self {
+            ack_job: NamedScript::of(ScriptName::AckJob, scripts),
+            ack_jobs: NamedScript::of(ScriptName::AckJobs, scripts),
+            cancel_job: NamedScript::of(ScriptName::CancelJob, scripts),
+            enqueue_scheduled: NamedScript::of(ScriptName::EnqueueScheduled, scripts),
+            get_jobs: NamedScript::of(ScriptName::GetJobs, scripts),
+            kill_job: NamedScript::of(ScriptName::KillJob, scripts),
+            migrate_active: NamedScript::of(ScriptName::MigrateActive, scripts),
+            push_job: NamedScript::of(ScriptName::PushJob, scripts),
+            push_jobs: NamedScript::of(ScriptName::PushJobs, scripts),
+            push_unique_job: NamedScript::of(ScriptName::PushUniqueJob, scripts),
+            reenqueue_active: NamedScript::of(ScriptName::ReenqueueActive, scripts),
+            reenqueue_orphaned: NamedScript::of(ScriptName::ReenqueueOrphaned, scripts),
+            reenqueue_stale: NamedScript::of(ScriptName::ReenqueueStale, scripts),
+            register_consumer: NamedScript::of(ScriptName::RegisterConsumer, scripts),
+            replay_dead: NamedScript::of(ScriptName::ReplayDead, scripts),
+            retry_job: NamedScript::of(ScriptName::RetryJob, scripts),
+            schedule_job: NamedScript::of(ScriptName::ScheduleJob, scripts),
+            trim_done: NamedScript::of(ScriptName::TrimDone, scripts),
+            update_context: NamedScript::of(ScriptName::UpdateContext, scripts),
+            vacuum: NamedScript::of(ScriptName::Vacuum, scripts),
+            vacuum_orphaned: NamedScript::of(ScriptName::VacuumOrphaned, scripts),
+            stats: NamedScript::of(ScriptName::Stats, scripts),
+        }
+    }
+
+    fn all(&self) -> [&NamedScript; 22] {
+        [
+            &self.ack_job,
+            &self.ack_jobs,
+            &self.cancel_job,
+            &self.enqueue_scheduled,
+            &self.get_jobs,
+            &self.kill_job,
+            &self.migrate_active,
+            &self.push_job,
+            &self.push_jobs,
+            &self.push_unique_job,
+            &self.reenqueue_active,
+            &self.reenqueue_orphaned,
+            &self.reenqueue_stale,
+            &self.register_consumer,
+            &self.replay_dead,
+            &self.retry_job,
+            &self.schedule_job,
+            &self.trim_done,
+            &self.update_context,
+            &self.vacuum,
+            &self.vacuum_orphaned,
+            &self.stats,
+        ]
+    }
 }
 
 /// The context for a redis storage job
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// Self-describing codecs such as the default [JsonCodec] leave unset fields out of the stored
+/// job, while codecs that are not, such as bincode, write every field in order.
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct RedisContext {
-    max_attempts: usize,
+    #[serde(default)]
+    max_attempts: Option<usize>,
+    #[serde(default)]
     lock_by: Option<WorkerId>,
+    #[serde(default)]
     run_at: Option<SystemTime>,
+    #[serde(default)]
+    priority: i64,
+    #[serde(default)]
+    expires_at: Option<i64>,
+    #[serde(default)]
+    unique_key: Option<String>,
+    #[serde(default)]
+    progress: u8,
+    #[serde(default)]
+    last_error: Option<String>,
+    #[serde(default)]
+    job_type: Option<String>,
+    #[serde(default)]
+    cron: Option<String>,
+    #[serde(default)]
+    duration_ms: Option<u64>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    #[serde(default)]
+    total_attempts: usize,
+    #[serde(default)]
+    group_id: Option<String>,
+    #[serde(default)]
+    timeout: Option<Duration>,
 }
 
-impl Default for RedisContext {
-    fn default() -> Self {
-        Self {
-            max_attempts: 5,
-            lock_by: None,
-            run_at: None,
+impl Serialize for RedisContext {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        // Fields can only be left out of formats that write their names
+        let skip_unset = serializer.is_human_readable();
+        let mut state = serializer.serialize_struct("RedisContext", 15)?;
+        macro_rules! field {
+            ($name:ident, $unset:expr) => {
+                if skip_unset && $unset {
+                    state.skip_field(stringify!($name))?;
+                } else {
+                    state.serialize_field(stringify!($name), &self.$name)?;
+                }
+            };
+        }
+        field!(max_attempts, self.max_attempts.is_none());
+        field!(lock_by, self.lock_by.is_none());
+        field!(run_at, self.run_at.is_none());
+        field!(priority, false);
+        field!(expires_at, self.expires_at.is_none());
+        field!(unique_key, self.unique_key.is_none());
+        field!(progress, self.progress == 0);
+        field!(last_error, self.last_error.is_none());
+        field!(job_type, self.job_type.is_none());
+        field!(cron, self.cron.is_none());
+        field!(duration_ms, self.duration_ms.is_none());
+        field!(metadata, self.metadata.is_empty());
+        field!(total_attempts, self.total_attempts == 0);
+        field!(group_id, self.group_id.is_none());
+        field!(timeout, self.timeout.is_none());
+        state.end()
+    }
+}
+
+impl RedisContext {
+    /// Get the maximum number of attempts of the job, if it overrides [Config::set_max_attempts]
+    pub fn max_attempts(&self) -> Option<usize> {
+        self.max_attempts
+    }
+
+    /// Set the maximum number of attempts of the job.
+    ///
+    /// A failed job is retried until it has been attempted this many times, then it is killed.
+    /// `None` falls back to [Config::set_max_attempts].
+    pub fn set_max_attempts(&mut self, max_attempts: Option<usize>) {
+        self.max_attempts = max_attempts;
+    }
+
+    /// Get the priority of the job
+    pub fn priority(&self) -> i64 {
+        self.priority
+    }
+
+    /// Set the priority of the job.
+    ///
+    /// Jobs with a higher priority are fetched first.
+    pub fn set_priority(&mut self, priority: i64) {
+        self.priority = priority;
+    }
+
+    /// Get the unix timestamp after which the job is no longer delivered
+    pub fn expires_at(&self) -> Option<i64> {
+        self.expires_at
+    }
+
+    /// Set the unix timestamp after which the job is no longer delivered.
+    ///
+    /// Expired jobs are moved to the dead set when they are fetched.
+    pub fn set_expires_at(&mut self, expires_at: Option<i64>) {
+        self.expires_at = expires_at;
+    }
+
+    /// Get the unique key of a job pushed with [RedisStorage::push_unique]
+    pub fn unique_key(&self) -> Option<&str> {
+        self.unique_key.as_deref()
+    }
+
+    /// Get the progress of the job as a percentage, as last reported with
+    /// [RedisStorage::set_progress]
+    pub fn progress(&self) -> u8 {
+        self.progress
+    }
+
+    /// Get the error of the last failed attempt of the job, if it was retried or killed
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    /// Get the type the job was pushed as, see [Config::set_job_type]
+    pub fn job_type(&self) -> Option<&str> {
+        self.job_type.as_deref()
+    }
+
+    /// Get the cron expression of a recurring job scheduled with [RedisStorage::schedule_cron]
+    pub fn cron(&self) -> Option<&str> {
+        self.cron.as_deref()
+    }
+
+    /// Get how long the last acknowledged attempt of the job ran in milliseconds, from when it was
+    /// fetched until it was acknowledged. Only set on jobs fetched by id.
+    pub fn duration_ms(&self) -> Option<u64> {
+        self.duration_ms
+    }
+
+    /// Get how many times the job was retried or rescheduled, see
+    /// [Config::set_absolute_max_attempts]
+    ///
+    /// Unlike the attempts of the job this is never reset, not even when a dead job is replayed.
+    pub fn total_attempts(&self) -> usize {
+        self.total_attempts
+    }
+
+    /// Get the group of a job pushed with [RedisStorage::push_group]
+    pub fn group_id(&self) -> Option<&str> {
+        self.group_id.as_deref()
+    }
+
+    /// Get how long a handler may run the job before the attempt fails
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Set how long a handler may run the job before the attempt fails.
+    ///
+    /// A job that runs longer is retried like any failed job, once it is out of attempts it is
+    /// killed with [DeadReason::Timeout]. See [JobTimeoutLayer].
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Get the metadata the job was pushed with, see [Metadata]
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Set a metadata entry of the job, such as a trace or tenant id
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.metadata.insert(key.into(), value.into());
+    }
+
+    #[cfg(feature = "memory")]
+    pub(crate) fn lock_by(&self) -> Option<&WorkerId> {
+        self.lock_by.as_ref()
+    }
+
+    #[cfg(feature = "memory")]
+    pub(crate) fn set_lock_by(&mut self, lock_by: Option<WorkerId>) {
+        self.lock_by = lock_by;
+    }
+
+    #[cfg(feature = "memory")]
+    pub(crate) fn set_last_error(&mut self, last_error: Option<String>) {
+        self.last_error = last_error;
+    }
+}
+
+/// Key-value pairs attached to a job as a request extension that are kept with the job.
+///
+/// The extensions of a request are not encoded, they are lost once the job is pushed. A
+/// [Metadata] extension is the exception: its entries are merged into [RedisContext::metadata]
+/// when the request is pushed or scheduled, and a fetched job carries them again as a [Metadata]
+/// extension, so that a handler can take them with `Data<Metadata>`.
+///
+/// ```rust
+/// # use apalis_core::request::Request;
+/// # use apalis_redis::{Metadata, RedisContext};
+/// let mut req: Request<String, RedisContext> = Request::new("job".to_owned());
+/// req.parts
+///     .data
+///     .insert(Metadata::from([("tenant_id".to_owned(), "acme".to_owned())]));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata(pub HashMap<String, String>);
+
+impl<const N: usize> From<[(String, String); N]> for Metadata {
+    fn from(entries: [(String, String); N]) -> Self {
+        Metadata(HashMap::from(entries))
+    }
+}
+
+impl Metadata {
+    /// Merge the [Metadata] extension of a request into its context
+    fn apply(parts: &mut Parts<RedisContext>) {
+        if let Some(Metadata(metadata)) = parts.data.get::<Metadata>() {
+            parts.context.metadata.extend(metadata.clone());
         }
     }
 }
@@ -122,15 +515,21 @@ impl<Req> FromRequest<Request<Req, RedisContext>> for RedisContext {
 pub enum RedisPollError {
     /// Error during a keep-alive heartbeat.
     #[error("KeepAlive heartbeat encountered an error: `{0}`")]
-    KeepAliveError(RedisError),
+    KeepAliveError(RedisStorageError),
+
+    /// The keep-alive heartbeat failed too many consecutive times, the worker is stopped.
+    ///
+    /// See [Config::set_max_keepalive_failures].
+    #[error("KeepAlive heartbeat failed {0} consecutive times, stopping the worker: `{1}`")]
+    KeepAliveExhausted(usize, RedisStorageError),
 
     /// Error during enqueueing scheduled tasks.
     #[error("EnqueueScheduled heartbeat encountered an error: `{0}`")]
-    EnqueueScheduledError(RedisError),
+    EnqueueScheduledError(RedisStorageError),
 
     /// Error during polling for the next task or message.
     #[error("PollNext heartbeat encountered an error: `{0}`")]
-    PollNextError(RedisError),
+    PollNextError(RedisStorageError),
 
     /// Error during enqueueing tasks for worker consumption.
     #[error("Enqueue for worker consumption encountered an error: `{0}`")]
@@ -138,11 +537,329 @@ pub enum RedisPollError {
 
     /// Error during acknowledgment of tasks.
     #[error("Ack heartbeat encountered an error: `{0}`")]
-    AckError(RedisError),
+    AckError(RedisStorageError),
 
     /// Error during re-enqueuing orphaned tasks.
     #[error("ReenqueueOrphaned heartbeat encountered an error: `{0}`")]
-    ReenqueueOrphanedError(RedisError),
+    ReenqueueOrphanedError(RedisStorageError),
+
+    /// Error during re-enqueuing tasks past the visibility timeout.
+    #[error("ReenqueueStale heartbeat encountered an error: `{0}`")]
+    ReenqueueStaleError(RedisStorageError),
+
+    /// Error during trimming done jobs past the retention window.
+    #[error("TrimDone heartbeat encountered an error: `{0}`")]
+    TrimDoneError(RedisStorageError),
+
+    /// Error during moving the active jobs of an older queue to a sorted set.
+    #[error("MigrateActive encountered an error: `{0}`")]
+    MigrateActiveError(RedisStorageError),
+}
+
+/// The state of a job as tracked by the Redis keys of a [RedisStorage]
+///
+/// A job fetched with [Storage::fetch_by_id] carries its state as a request extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JobState {
+    /// Job is waiting in the active set to be fetched
+    Active,
+    /// Job is waiting in the scheduled set to be moved to the active set
+    Scheduled,
+    /// Job was completed successfully
+    Done,
+    /// Job failed and is waiting to be retried, or was acknowledged with [RedisStorage::ack_failed]
+    Failed,
+    /// Job is no longer retryable
+    Dead,
+    /// Job was fetched and is held in the inflight set of a worker
+    Inflight,
+    /// Job is in none of the sets, such as a job reenqueued while it was being fetched
+    Unknown,
+}
+
+/// Why a job was moved to the dead set
+///
+/// Dead jobs listed with [RedisStorage::list_jobs_by_state] carry their reason as a request extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeadReason {
+    /// Job failed on its last allowed attempt
+    MaxRetriesExceeded,
+    /// Job expired before it was fetched, see [RedisContext::set_expires_at]
+    Expired,
+    /// Job was aborted by its handler or killed with [RedisStorage::kill]
+    Killed,
+    /// Job could not be decoded, or has a type a multiplexed storage does not decode
+    DecodeFailed,
+    /// Job ran longer than its timeout on its last allowed attempt, see
+    /// [RedisContext::set_timeout]
+    Timeout,
+}
+
+impl DeadReason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeadReason::MaxRetriesExceeded => "max_retries_exceeded",
+            DeadReason::Expired => "expired",
+            DeadReason::Killed => "killed",
+            DeadReason::DecodeFailed => "decode_failed",
+            DeadReason::Timeout => "timeout",
+        }
+    }
+
+    fn parse(reason: &str) -> Option<Self> {
+        match reason {
+            "max_retries_exceeded" => Some(DeadReason::MaxRetriesExceeded),
+            "expired" => Some(DeadReason::Expired),
+            "killed" => Some(DeadReason::Killed),
+            "decode_failed" => Some(DeadReason::DecodeFailed),
+            "timeout" => Some(DeadReason::Timeout),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DeadReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The order in which jobs of equal priority are fetched from the active set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FetchOrder {
+    /// Oldest jobs first, like a queue
+    #[default]
+    Fifo,
+    /// Most recent jobs first, like a stack
+    Lifo,
+}
+
+/// What a worker does with a fetched job that its codec fails to decode
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DecodeErrorPolicy {
+    /// Return the error from the fetch, which stops the stream of jobs
+    #[default]
+    StopStream,
+    /// Kill the job, keeping its payload in the job data hash, and carry on with the other jobs
+    Quarantine,
+}
+
+/// The optional fields of a [RedisContext] that are persisted with a job, see
+/// [Config::set_context_profile]
+///
+/// Every job is stored as a single entry of the job data hash, so each persisted field grows the
+/// memory used per job. With a self-describing codec such as the default [JsonCodec], fields that
+/// are unset are not written, and the fields below can be left out even when they are set: the
+/// progress, last error and duration are also kept in their own hashes and are still returned by
+/// [RedisStorage::fetch_by_id], while metadata that is left out is lost. A high-volume workload
+/// that only needs the attempts of its jobs can use [ContextProfile::minimal].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ContextProfile {
+    progress: bool,
+    last_error: bool,
+    duration_ms: bool,
+    metadata: bool,
+}
+
+impl Default for ContextProfile {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl ContextProfile {
+    /// Persist every field of the context, the default
+    pub fn full() -> Self {
+        Self {
+            progress: true,
+            last_error: true,
+            duration_ms: true,
+            metadata: true,
+        }
+    }
+
+    /// Persist none of the optional fields of the context
+    pub fn minimal() -> Self {
+        Self {
+            progress: false,
+            last_error: false,
+            duration_ms: false,
+            metadata: false,
+        }
+    }
+
+    /// Set whether the progress of a job is persisted with it
+    pub fn set_progress(mut self, persist: bool) -> Self {
+        self.progress = persist;
+        self
+    }
+
+    /// Set whether the error of the last attempt is persisted with a job
+    pub fn set_last_error(mut self, persist: bool) -> Self {
+        self.last_error = persist;
+        self
+    }
+
+    /// Set whether the duration of a job is persisted with it
+    pub fn set_duration_ms(mut self, persist: bool) -> Self {
+        self.duration_ms = persist;
+        self
+    }
+
+    /// Set whether the [Metadata] of a job is persisted with it
+    pub fn set_metadata(mut self, persist: bool) -> Self {
+        self.metadata = persist;
+        self
+    }
+
+    /// Clear the fields of `ctx` that are not persisted, so that the codec skips them
+    fn apply(&self, ctx: &mut RedisContext) {
+        if !self.progress {
+            ctx.progress = 0;
+        }
+        if !self.last_error {
+            ctx.last_error = None;
+        }
+        if !self.duration_ms {
+            ctx.duration_ms = None;
+        }
+        if !self.metadata {
+            ctx.metadata.clear();
+        }
+    }
+}
+
+/// The outcome of a job acknowledged by `ack_job.lua`, carrying the encoded result or error reason
+enum AckStatus {
+    Done(Vec<u8>),
+    Failed(Vec<u8>),
+}
+
+/// The sender a polled storage receives the results of its jobs through
+pub(crate) type AckSender = Sender<(RedisContext, Response<Vec<u8>>)>;
+
+/// What was recorded about a done job when [Config::set_done_details] is enabled, see
+/// [RedisStorage::list_done_details]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DoneDetails {
+    /// The id of the job
+    pub task_id: TaskId,
+    /// The worker that acknowledged the job
+    pub worker_id: WorkerId,
+    /// The attempts made at the job, `None` for jobs acknowledged with
+    /// [RedisStorage::ack_by_id]
+    pub attempts: Option<usize>,
+    /// How long the job ran from when it was fetched, `None` if its fetch time was not recorded
+    pub duration_ms: Option<u64>,
+    /// The unix timestamp the job was done at
+    pub done_at: i64,
+}
+
+/// The encoding of [DoneDetails] written by the ack scripts
+#[derive(Deserialize)]
+struct RawDoneDetails {
+    task_id: String,
+    worker_id: String,
+    attempts: Option<usize>,
+    duration_ms: Option<u64>,
+    done_at: i64,
+}
+
+/// A successful ack buffered by the poller until it is flushed with `ack_jobs.lua`
+#[derive(Debug)]
+struct PendingAck {
+    worker_id: WorkerId,
+    task_id: TaskId,
+    attempt: Attempt,
+    result: Vec<u8>,
+    unique_key: Option<String>,
+}
+
+/// The outcome of pushing a job with [RedisStorage::push_with_outcome] or
+/// [RedisStorage::push_unique]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PushOutcome {
+    /// The id of the pushed job, or of the existing job if the push was skipped
+    pub task_id: TaskId,
+    /// Whether the job was newly enqueued, `false` if a job with the same id or unique key was
+    /// already present
+    pub was_new: bool,
+}
+
+/// Counts of the jobs of a group pushed with [RedisStorage::push_group], see
+/// [RedisStorage::group_status]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GroupStatus {
+    /// Jobs pushed to the group
+    pub total: usize,
+    /// Jobs of the group completed successfully
+    pub done: usize,
+    /// Jobs of the group that were killed, cancelled or acknowledged as failed
+    pub failed: usize,
+}
+
+impl GroupStatus {
+    /// Whether every job of the group has finished, successfully or not
+    pub fn is_complete(&self) -> bool {
+        self.total > 0 && self.done + self.failed >= self.total
+    }
+}
+
+/// Counts of the jobs in each state of a [RedisStorage]
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct QueueStats {
+    /// Jobs waiting in the active set to be fetched
+    pub pending: usize,
+    /// Jobs waiting in the scheduled set
+    pub scheduled: usize,
+    /// Jobs currently being processed by a registered worker
+    pub inflight: usize,
+    /// Jobs completed successfully
+    pub done: usize,
+    /// Jobs that failed and are waiting to be retried
+    pub failed: usize,
+    /// Jobs that are no longer retryable
+    pub dead: usize,
+}
+
+/// What a [RedisStorage::vacuum_with_report] removed, or would remove in a dry run
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VacuumReport {
+    /// Done jobs whose data was deleted
+    pub done: usize,
+    /// Jobs whose data was not referenced by any set or inflight set, such as jobs left behind
+    /// by a crash in the middle of an operation
+    pub orphaned: usize,
+}
+
+/// How many jobs a [RedisStorage::tick] moved back to the active set
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TickReport {
+    /// Scheduled jobs that were due
+    pub scheduled: usize,
+    /// Inflight jobs of workers that stopped sending keep-alives
+    pub orphaned: usize,
+}
+
+/// A worker registered with a [RedisStorage], see [RedisStorage::list_workers]
+#[derive(Clone, Debug, Serialize)]
+pub struct WorkerInfo {
+    /// The id of the worker
+    pub id: WorkerId,
+    /// The timestamp of the worker's last keep-alive
+    pub last_seen: i64,
+    /// The number of jobs the worker currently holds inflight
+    pub inflight_count: usize,
+}
+
+/// A job moved back to the active set because its worker stopped sending keep-alives, see
+/// [RedisStorage::reenqueue_orphaned]
+#[derive(Clone, Debug, Serialize)]
+pub struct OrphanedJob {
+    /// The id of the reenqueued job
+    pub task_id: TaskId,
+    /// The worker that held the job when it stopped sending keep-alives
+    pub worker_id: WorkerId,
 }
 
 /// Config for a [RedisStorage]
@@ -154,6 +871,32 @@ pub struct Config {
     enqueue_scheduled: Duration,
     reenqueue_orphaned_after: Duration,
     namespace: String,
+    backoff: Arc<dyn BackoffStrategy>,
+    default_ttl: Option<Duration>,
+    visibility_timeout: Option<Duration>,
+    max_payload_bytes: usize,
+    stream_backlog: usize,
+    task_id_factory: Arc<dyn TaskIdFactory>,
+    task_id_encoding: TaskIdEncoding,
+    rate_limit: Option<(usize, Duration)>,
+    done_retention: Duration,
+    max_concurrent: Option<usize>,
+    job_type: Option<String>,
+    ack_batching: bool,
+    done_details: bool,
+    max_keepalive_failures: usize,
+    consumer_ttl: Option<Duration>,
+    fetch_jitter: Duration,
+    adaptive_fetch: Option<(Duration, Duration)>,
+    reconnect_backoff: Option<(Duration, Duration)>,
+    context_profile: ContextProfile,
+    max_attempts: usize,
+    absolute_max_attempts: Option<usize>,
+    retry_budget: Option<(usize, Duration)>,
+    fetch_order: FetchOrder,
+    on_decode_error: DecodeErrorPolicy,
+    orphaned_jobs_sender: Option<Sender<OrphanedJob>>,
+    clock: Arc<dyn Clock>,
 }
 
 impl Default for Config {
@@ -165,6 +908,32 @@ impl Default for Config {
             enqueue_scheduled: Duration::from_secs(30),
             reenqueue_orphaned_after: Duration::from_secs(300),
             namespace: String::from("apalis_redis"),
+            backoff: Arc::new(FixedBackoff::default()),
+            default_ttl: None,
+            visibility_timeout: None,
+            max_payload_bytes: 0,
+            stream_backlog: 10_000,
+            task_id_factory: Arc::new(UlidFactory),
+            task_id_encoding: TaskIdEncoding::Ulid,
+            rate_limit: None,
+            done_retention: Duration::ZERO,
+            max_concurrent: None,
+            job_type: None,
+            ack_batching: true,
+            done_details: false,
+            max_keepalive_failures: 5,
+            consumer_ttl: None,
+            fetch_jitter: Duration::ZERO,
+            adaptive_fetch: None,
+            reconnect_backoff: None,
+            context_profile: ContextProfile::full(),
+            max_attempts: 5,
+            absolute_max_attempts: None,
+            retry_budget: None,
+            fetch_order: FetchOrder::Fifo,
+            on_decode_error: DecodeErrorPolicy::StopStream,
+            orphaned_jobs_sender: None,
+            clock: Arc::new(SystemClock),
         }
     }
 }
@@ -195,7 +964,10 @@ impl Config {
         &self.namespace
     }
 
-    /// get the poll interval
+    /// set the poll interval
+    ///
+    /// A worker fetches the next batch of jobs once it has taken every job of the previous batch,
+    /// the interval is the minimum delay between two fetches
     pub fn set_poll_interval(mut self, poll_interval: Duration) -> Self {
         self.poll_interval = poll_interval;
         self
@@ -225,994 +997,7178 @@ impl Config {
         self
     }
 
-    /// Returns the Redis key for the list of pending jobs associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
-    ///
-    /// # Returns
-    /// A `String` representing the Redis key for the pending jobs list.
-    pub fn active_jobs_list(&self) -> String {
-        ACTIVE_JOBS_LIST.replace("{queue}", &self.namespace)
+    /// get the backoff strategy used when retrying jobs
+    pub fn get_backoff(&self) -> &dyn BackoffStrategy {
+        self.backoff.as_ref()
     }
 
-    /// Returns the Redis key for the set of consumers associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
+    /// set the backoff strategy used when retrying jobs
     ///
-    /// # Returns
-    /// A `String` representing the Redis key for the consumers set.
-    pub fn consumers_set(&self) -> String {
-        CONSUMERS_SET.replace("{queue}", &self.namespace)
+    /// Defaults to retrying immediately
+    pub fn set_backoff(mut self, backoff: impl BackoffStrategy + 'static) -> Self {
+        self.backoff = Arc::new(backoff);
+        self
     }
 
-    /// Returns the Redis key for the set of dead jobs associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
-    ///
-    /// # Returns
-    /// A `String` representing the Redis key for the dead jobs set.
-    pub fn dead_jobs_set(&self) -> String {
-        DEAD_JOBS_SET.replace("{queue}", &self.namespace)
+    /// get the factory generating the ids of pushed and scheduled jobs
+    pub fn get_task_id_factory(&self) -> &dyn TaskIdFactory {
+        self.task_id_factory.as_ref()
     }
 
-    /// Returns the Redis key for the set of done jobs associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
+    /// set the factory generating the ids of pushed and scheduled jobs
     ///
-    /// # Returns
-    /// A `String` representing the Redis key for the done jobs set.
-    pub fn done_jobs_set(&self) -> String {
-        DONE_JOBS_SET.replace("{queue}", &self.namespace)
+    /// Defaults to a random [TaskId]. Requests pushed with an explicit id keep it
+    pub fn set_task_id_factory(mut self, factory: impl TaskIdFactory + 'static) -> Self {
+        self.task_id_factory = Arc::new(factory);
+        self
     }
 
-    /// Returns the Redis key for the set of failed jobs associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
-    ///
-    /// # Returns
-    /// A `String` representing the Redis key for the failed jobs set.
-    pub fn failed_jobs_set(&self) -> String {
-        FAILED_JOBS_SET.replace("{queue}", &self.namespace)
+    /// get the clock the storage reads the current time from
+    pub fn get_clock(&self) -> &dyn Clock {
+        self.clock.as_ref()
     }
 
-    /// Returns the Redis key for the set of inflight jobs associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
+    /// set the clock the storage reads the current time from
     ///
-    /// # Returns
-    /// A `String` representing the Redis key for the inflight jobs set.
-    pub fn inflight_jobs_set(&self) -> String {
-        INFLIGHT_JOB_SET.replace("{queue}", &self.namespace)
+    /// Defaults to the system time. A [MockClock](crate::MockClock) makes scheduling, expiry
+    /// and orphan detection deterministic in tests. Workers still wait on real timers between
+    /// polls, only the timestamps compared in Redis follow the clock.
+    pub fn set_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
     }
 
-    /// Returns the Redis key for the hash storing job data associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
-    ///
-    /// # Returns
-    /// A `String` representing the Redis key for the job data hash.
-    pub fn job_data_hash(&self) -> String {
-        JOB_DATA_HASH.replace("{queue}", &self.namespace)
+    /// get how task ids are written in Redis
+    pub fn get_task_id_encoding(&self) -> TaskIdEncoding {
+        self.task_id_encoding
     }
 
-    /// Returns the Redis key for the set of scheduled jobs associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
+    /// set how task ids are written in Redis
     ///
-    /// # Returns
-    /// A `String` representing the Redis key for the scheduled jobs set.
-    pub fn scheduled_jobs_set(&self) -> String {
-        SCHEDULED_JOBS_SET.replace("{queue}", &self.namespace)
+    /// [TaskIdEncoding::Base62] writes shorter ids to save memory on large queues. Producers that
+    /// push jobs with [PUSH_JOB_SCRIPT] must use the same encoding. Defaults to
+    /// [TaskIdEncoding::Ulid]
+    pub fn set_task_id_encoding(mut self, encoding: TaskIdEncoding) -> Self {
+        self.task_id_encoding = encoding;
+        self
     }
 
-    /// Returns the Redis key for the list of signals associated with the queue.
-    /// The key is dynamically generated using the namespace of the queue.
-    ///
-    /// # Returns
-    /// A `String` representing the Redis key for the signal list.
-    pub fn signal_list(&self) -> String {
-        SIGNAL_LIST.replace("{queue}", &self.namespace)
+    /// get the time to live applied to pushed jobs without an expiry
+    pub fn get_default_ttl(&self) -> Option<&Duration> {
+        self.default_ttl.as_ref()
     }
 
-    /// Gets the reenqueue_orphaned_after duration.
-    pub fn reenqueue_orphaned_after(&self) -> Duration {
-        self.reenqueue_orphaned_after
+    /// set the time to live applied to pushed jobs without an expiry
+    ///
+    /// Defaults to no expiry
+    pub fn set_default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
     }
 
-    /// Gets a mutable reference to the reenqueue_orphaned_after.
-    pub fn reenqueue_orphaned_after_mut(&mut self) -> &mut Duration {
-        &mut self.reenqueue_orphaned_after
+    /// get the time a job can stay inflight before it is reenqueued
+    pub fn get_visibility_timeout(&self) -> Option<&Duration> {
+        self.visibility_timeout.as_ref()
     }
 
-    /// Occasionally some workers die, or abandon jobs because of panics.
-    /// This is the time a task takes before its back to the queue
+    /// set the time a job can stay inflight before it is reenqueued, even if its worker is alive
     ///
-    /// Defaults to 5 minutes
-    pub fn set_reenqueue_orphaned_after(mut self, after: Duration) -> Self {
-        self.reenqueue_orphaned_after = after;
+    /// Stale jobs are checked on the `enqueue_scheduled` interval. Defaults to no timeout
+    pub fn set_visibility_timeout(mut self, visibility_timeout: Duration) -> Self {
+        self.visibility_timeout = Some(visibility_timeout);
         self
     }
-}
 
-/// Represents a [Storage] that uses Redis for storage.
-pub struct RedisStorage<T, Conn = ConnectionManager, C = JsonCodec<Vec<u8>>> {
-    conn: Conn,
-    job_type: PhantomData<T>,
-    pub(super) scripts: RedisScript,
-    controller: Controller,
-    config: Config,
-    codec: PhantomData<C>,
-}
+    /// get how long done jobs are kept before they are trimmed
+    pub fn get_done_retention(&self) -> &Duration {
+        &self.done_retention
+    }
 
-impl<T, Conn, C> fmt::Debug for RedisStorage<T, Conn, C> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("RedisStorage")
-            .field("conn", &"ConnectionManager")
-            .field("job_type", &std::any::type_name::<T>())
-            .field("scripts", &self.scripts)
-            .field("config", &self.config)
-            .finish()
+    /// set how long done jobs are kept before they are trimmed, along with their data
+    ///
+    /// Done jobs are trimmed on the `enqueue_scheduled` interval. Defaults to zero, which keeps
+    /// done jobs until [Storage::vacuum] is called
+    pub fn set_done_retention(mut self, retention: Duration) -> Self {
+        self.done_retention = retention;
+        self
     }
-}
 
-impl<T, Conn: Clone, C> Clone for RedisStorage<T, Conn, C> {
-    fn clone(&self) -> Self {
-        Self {
-            conn: self.conn.clone(),
-            job_type: PhantomData,
-            scripts: self.scripts.clone(),
-            controller: self.controller.clone(),
-            config: self.config.clone(),
-            codec: self.codec,
-        }
+    /// get the maximum number of jobs a worker fetches per window
+    pub fn get_rate_limit(&self) -> Option<(usize, Duration)> {
+        self.rate_limit
     }
-}
 
-impl<T: Serialize + DeserializeOwned, Conn> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
-    /// Start a new connection
-    pub fn new(conn: Conn) -> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
-        Self::new_with_codec::<JsonCodec<Vec<u8>>>(
-            conn,
-            Config::default().set_namespace(type_name::<T>()),
-        )
+    /// set the maximum number of jobs a worker fetches every `per`, independent of `buffer_size`
+    ///
+    /// The limit is enforced by each worker with a token bucket, polls are skipped while it is
+    /// empty. Defaults to no limit
+    pub fn set_rate_limit(mut self, max: usize, per: Duration) -> Self {
+        self.rate_limit = Some((max, per));
+        self
     }
 
-    /// Start a connection with a custom config
-    pub fn new_with_config(
-        conn: Conn,
-        config: Config,
-    ) -> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
-        Self::new_with_codec::<JsonCodec<Vec<u8>>>(conn, config)
+    /// get the maximum number of jobs a worker holds inflight
+    pub fn get_max_concurrent(&self) -> Option<usize> {
+        self.max_concurrent
     }
 
-    /// Start a new connection providing custom config and a codec
-    pub fn new_with_codec<K>(conn: Conn, config: Config) -> RedisStorage<T, Conn, K>
-    where
-        K: Codec + Sync + Send + 'static,
-    {
-        RedisStorage {
-            conn,
-            job_type: PhantomData,
-            controller: Controller::new(),
-            config,
-            codec: PhantomData::<K>,
-            scripts: RedisScript {
-                done_job: redis::Script::new(include_str!("../lua/done_job.lua")),
-                push_job: redis::Script::new(include_str!("../lua/push_job.lua")),
-                retry_job: redis::Script::new(include_str!("../lua/retry_job.lua")),
-                enqueue_scheduled: redis::Script::new(include_str!(
-                    "../lua/enqueue_scheduled_jobs.lua"
-                )),
-                get_jobs: redis::Script::new(include_str!("../lua/get_jobs.lua")),
-                register_consumer: redis::Script::new(include_str!("../lua/register_consumer.lua")),
-                kill_job: redis::Script::new(include_str!("../lua/kill_job.lua")),
-                reenqueue_active: redis::Script::new(include_str!(
-                    "../lua/reenqueue_active_jobs.lua"
-                )),
-                reenqueue_orphaned: redis::Script::new(include_str!(
-                    "../lua/reenqueue_orphaned_jobs.lua"
-                )),
-                schedule_job: redis::Script::new(include_str!("../lua/schedule_job.lua")),
-                vacuum: redis::Script::new(include_str!("../lua/vacuum.lua")),
-                stats: redis::Script::new(include_str!("../lua/stats.lua")),
-            },
-        }
+    /// set the maximum number of jobs a worker holds inflight, independent of `buffer_size`
+    ///
+    /// A worker only fetches as many jobs as it has free slots. Defaults to no limit
+    pub fn set_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = Some(max_concurrent);
+        self
     }
 
-    /// Get current connection
-    pub fn get_connection(&self) -> &Conn {
-        &self.conn
+    /// get the type pushed jobs are tagged with, `None` means the name of the job's Rust type
+    pub fn get_job_type(&self) -> Option<&str> {
+        self.job_type.as_deref()
     }
 
-    /// Get the config used by the storage
-    pub fn get_config(&self) -> &Config {
-        &self.config
+    /// set the type pushed jobs are tagged with in [RedisContext::job_type]
+    ///
+    /// Storages of different job types can share a namespace with distinct job types, and a
+    /// storage built with [RedisStorage::new_multiplexed] decodes each job by its type.
+    /// Defaults to the name of the job's Rust type
+    pub fn set_job_type(mut self, job_type: &str) -> Self {
+        self.job_type = Some(job_type.to_owned());
+        self
     }
-}
 
-impl<T, Conn, C> RedisStorage<T, Conn, C> {
-    /// Get the underlying codec details
-    pub fn get_codec(&self) -> &PhantomData<C> {
-        &self.codec
+    /// get whether the poller batches successful acks
+    pub fn get_ack_batching(&self) -> bool {
+        self.ack_batching
     }
-}
 
-impl<T, Conn, C> Backend<Request<T, RedisContext>> for RedisStorage<T, Conn, C>
-where
-    T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
-    Conn: ConnectionLike + Send + Sync + 'static,
-    C: Codec<Compact = Vec<u8>> + Send + 'static,
-{
-    type Stream = BackendStream<RequestStream<Request<T, RedisContext>>>;
+    /// set whether the poller batches successful acks
+    ///
+    /// Batched acks are flushed in a single round trip every `buffer_size` acks or every half of
+    /// `keep_alive`, and when the worker shuts down. Failed jobs are always acknowledged
+    /// immediately. Jobs with a buffered ack free their [Config::set_max_concurrent] slot right
+    /// away. Disable it to mark jobs as done as soon as they complete. Defaults to `true`
+    pub fn set_ack_batching(mut self, ack_batching: bool) -> Self {
+        self.ack_batching = ack_batching;
+        self
+    }
 
-    type Layer = AckLayer<Sender<(RedisContext, Response<Vec<u8>>)>, T, RedisContext, C>;
+    /// get whether the details of done jobs are recorded
+    pub fn get_done_details(&self) -> bool {
+        self.done_details
+    }
 
-    type Codec = C;
+    /// set whether the details of done jobs are recorded
+    ///
+    /// When enabled, acking a job as done also adds its [DoneDetails] to a sorted set scored by
+    /// the time it was done, read with [RedisStorage::list_done_details] without looking up the
+    /// data of each job. The done set itself keeps holding bare ids. The details are trimmed and
+    /// vacuumed along with the done jobs. Defaults to `false` to save memory
+    pub fn set_done_details(mut self, done_details: bool) -> Self {
+        self.done_details = done_details;
+        self
+    }
 
-    fn poll(
-        mut self,
-        worker: &Worker<apalis_core::worker::Context>,
-    ) -> Poller<Self::Stream, Self::Layer> {
-        let (mut tx, rx) = mpsc::channel(self.config.buffer_size);
-        let (ack, ack_rx) =
-            mpsc::channel::<(RedisContext, Response<Vec<u8>>)>(self.config.buffer_size);
-        let layer = AckLayer::new(ack);
-        let controller = self.controller.clone();
-        let config = self.config.clone();
-        let stream: RequestStream<Request<T, RedisContext>> = Box::pin(rx);
-        let worker = worker.clone();
-        let heartbeat = async move {
-            // Lets reenqueue any jobs that belonged to this worker in case of a death
-            if let Err(e) = self
-                .reenqueue_orphaned((config.buffer_size * 10) as i32, Utc::now())
-                .await
-            {
-                worker.emit(Event::Error(Box::new(
-                    RedisPollError::ReenqueueOrphanedError(e),
-                )));
-            }
+    /// get the maximum random delay added to each poll
+    pub fn get_fetch_jitter(&self) -> &Duration {
+        &self.fetch_jitter
+    }
 
-            let mut reenqueue_orphaned_stm =
-                apalis_core::interval::interval(config.poll_interval).fuse();
+    /// set the maximum random delay added to each poll
+    ///
+    /// Every wait of `poll_interval` is extended by a random offset in `[0, jitter)`, drawn from
+    /// a generator seeded for each worker. Workers deployed at the same time then drift apart
+    /// instead of fetching in lockstep, which smooths the load on Redis. Defaults to zero
+    pub fn set_fetch_jitter(mut self, jitter: Duration) -> Self {
+        self.fetch_jitter = jitter;
+        self
+    }
 
-            let mut keep_alive_stm = apalis_core::interval::interval(config.keep_alive).fuse();
+    /// get the bounds of the adaptive wait between fetches
+    pub fn get_adaptive_fetch(&self) -> Option<(Duration, Duration)> {
+        self.adaptive_fetch
+    }
 
-            let mut enqueue_scheduled_stm =
-                apalis_core::interval::interval(config.enqueue_scheduled).fuse();
+    /// set the bounds of the adaptive wait between fetches, replacing `poll_interval` for fetching
+    ///
+    /// The wait starts at `min` and doubles after every fetch that returns no jobs, up to `max`.
+    /// It snaps back to `min` as soon as a fetch returns jobs, so an idle queue is polled rarely
+    /// while a busy one is drained without delay. `min` should not be zero, a zero wait never
+    /// backs off. Orphaned jobs are still reenqueued every `poll_interval`. Defaults to a fixed
+    /// `poll_interval`
+    pub fn set_adaptive_fetch(mut self, min: Duration, max: Duration) -> Self {
+        self.adaptive_fetch = Some((min, max));
+        self
+    }
 
-            let mut poll_next_stm = apalis_core::interval::interval(config.poll_interval).fuse();
+    /// get the bounds of the wait between calls of a poller that cannot reach Redis
+    pub fn get_reconnect_backoff(&self) -> Option<(Duration, Duration)> {
+        self.reconnect_backoff
+    }
 
-            let mut ack_stream = ack_rx.fuse();
+    /// set the bounds of the wait between calls of a poller that cannot reach Redis
+    ///
+    /// When a keep-alive, fetch, ack or other heartbeat call fails with a connection error, see
+    /// [RedisStorageError::is_connection_error], the poller waits `min` before its next call,
+    /// doubling the wait after every further connection error up to `max`. The wait resets to
+    /// `min` after the next successful call. Errors returned by Redis or the scripts do not back
+    /// off. Keep-alive failures still count towards [Config::set_max_keepalive_failures]. Defaults
+    /// to retrying on the usual intervals
+    pub fn set_reconnect_backoff(mut self, min: Duration, max: Duration) -> Self {
+        self.reconnect_backoff = Some((min, max));
+        self
+    }
 
-            if let Err(e) = self.keep_alive(worker.id()).await {
-                worker.emit(Event::Error(Box::new(RedisPollError::KeepAliveError(e))));
-            }
+    /// Build the wait between fetches, adaptive if it was set
+    fn fetch_interval(&self) -> FetchInterval {
+        match self.adaptive_fetch {
+            Some((min, max)) => FetchInterval::new(min, max),
+            None => FetchInterval::fixed(self.poll_interval),
+        }
+    }
 
-            loop {
-                select! {
-                    _ = keep_alive_stm.next() => {
-                        if let Err(e) = self.keep_alive(worker.id()).await {
-                            worker.emit(Event::Error(Box::new(RedisPollError::KeepAliveError(e))));
-                        }
-                    }
-                    _ = enqueue_scheduled_stm.next() => {
-                        if let Err(e) = self.enqueue_scheduled(config.buffer_size).await {
-                            worker.emit(Event::Error(Box::new(RedisPollError::EnqueueScheduledError(e))));
-                        }
-                    }
-                    _ = poll_next_stm.next() => {
-                        if worker.is_ready() {
-                            let res = self.fetch_next(worker.id()).await;
-                            match res {
-                                Err(e) => {
-                                    worker.emit(Event::Error(Box::new(RedisPollError::PollNextError(e))));
-                                }
-                                Ok(res) => {
-                                    for job in res {
-                                        if let Err(e) = tx.send(Ok(Some(job))).await {
-                                            worker.emit(Event::Error(Box::new(RedisPollError::EnqueueError(e))));
-                                        }
-                                    }
-                                }
-                            }
-                        } else {
-                            continue;
-                        }
+    /// get the optional fields of the context that are persisted with a job
+    pub fn get_context_profile(&self) -> ContextProfile {
+        self.context_profile
+    }
 
-                    }
-                    id_to_ack = ack_stream.next() => {
-                        if let Some((ctx, res)) = id_to_ack {
-                            if let Err(e) = self.ack(&ctx, &res).await {
-                                worker.emit(Event::Error(Box::new(RedisPollError::AckError(e))));
-                            }
-                        }
-                    }
-                    _ = reenqueue_orphaned_stm.next() => {
-                        let dead_since = Utc::now()
-                            - chrono::Duration::from_std(config.reenqueue_orphaned_after).unwrap();
-                        if let Err(e) = self.reenqueue_orphaned((config.buffer_size * 10) as i32, dead_since).await {
-                            worker.emit(Event::Error(Box::new(RedisPollError::ReenqueueOrphanedError(e))));
-                        }
-                    }
-                };
-            }
-        };
-        Poller::new_with_layer(
-            BackendStream::new(stream, controller),
-            heartbeat.boxed(),
-            layer,
-        )
+    /// set the optional fields of the context that are persisted with a job, see [ContextProfile]
+    ///
+    /// Leaving out fields a workload does not need shrinks every entry of the job data hash.
+    /// Defaults to [ContextProfile::full]
+    pub fn set_context_profile(mut self, context_profile: ContextProfile) -> Self {
+        self.context_profile = context_profile;
+        self
     }
-}
 
-impl<T, Conn, C, Res> Ack<T, Res, C> for RedisStorage<T, Conn, C>
-where
-    T: Sync + Send + Serialize + DeserializeOwned + Unpin + 'static,
-    Conn: ConnectionLike + Send + Sync + 'static,
-    C: Codec<Compact = Vec<u8>> + Send + 'static,
-    Res: Serialize + Sync + Send + 'static,
-{
-    type Context = RedisContext;
-    type AckError = RedisError;
-    async fn ack(&mut self, ctx: &Self::Context, res: &Response<Res>) -> Result<(), RedisError> {
-        // Lets update the number of attempts
-        // TODO: move attempts to its own key
-        let mut task = self
-            .fetch_by_id(&res.task_id)
-            .await?
-            .expect("must be a valid task");
-        task.parts.attempt = res.attempt.clone();
-        self.update(task).await?;
-        // End of expensive update
+    /// get the order in which jobs of equal priority are fetched
+    pub fn get_fetch_order(&self) -> FetchOrder {
+        self.fetch_order
+    }
 
-        let inflight_set = format!(
-            "{}:{}",
-            self.config.inflight_jobs_set(),
-            ctx.lock_by.clone().unwrap()
-        );
+    /// set the order in which jobs of equal priority are fetched
+    ///
+    /// Jobs with a higher priority are always fetched first. Within a priority [FetchOrder::Lifo]
+    /// fetches the most recently pushed jobs first, as ordered by their task ids, which the default
+    /// [UlidFactory] generates in the order jobs are pushed, even within a millisecond. Defaults
+    /// to [FetchOrder::Fifo]
+    pub fn set_fetch_order(mut self, fetch_order: FetchOrder) -> Self {
+        self.fetch_order = fetch_order;
+        self
+    }
 
-        let now: i64 = Utc::now().timestamp();
-        let task_id = res.task_id.to_string();
-        match &res.inner {
-            Ok(success_res) => {
-                let done_job = self.scripts.done_job.clone();
-                let done_jobs_set = &self.config.done_jobs_set();
-                done_job
-                    .key(inflight_set)
-                    .key(done_jobs_set)
-                    .key(self.config.job_data_hash())
-                    .arg(task_id)
-                    .arg(now)
-                    .arg(C::encode(success_res).map_err(Into::into).unwrap())
-                    .invoke_async(&mut self.conn)
-                    .await
-            }
-            Err(e) => match e {
-                Error::Abort(e) => {
-                    let worker_id = ctx.lock_by.as_ref().unwrap();
-                    self.kill(worker_id, &res.task_id, &e).await
-                }
-                _ => {
-                    if ctx.max_attempts > res.attempt.current() {
-                        let worker_id = ctx.lock_by.as_ref().unwrap();
-                        self.retry(worker_id, &res.task_id).await.map(|_| ())
-                    } else {
-                        let worker_id = ctx.lock_by.as_ref().unwrap();
+    /// get what is done with fetched jobs that fail to decode
+    pub fn get_on_decode_error(&self) -> DecodeErrorPolicy {
+        self.on_decode_error
+    }
 
-                        self.kill(
-                            worker_id,
-                            &res.task_id,
-                            &(Box::new(io::Error::new(
-                                io::ErrorKind::Interrupted,
-                                format!("Max retries of {} exceeded", ctx.max_attempts),
-                            )) as BoxDynError),
-                        )
-                        .await
-                    }
-                }
-            },
-        }
+    /// set what is done with fetched jobs that fail to decode
+    ///
+    /// With [DecodeErrorPolicy::Quarantine] a job whose payload is not understood by the codec is
+    /// logged and moved to the dead jobs set with its payload preserved, so that a single poisoned
+    /// job does not stop the worker. Its unique key, if any, is not released. Defaults to
+    /// [DecodeErrorPolicy::StopStream]
+    pub fn set_on_decode_error(mut self, policy: DecodeErrorPolicy) -> Self {
+        self.on_decode_error = policy;
+        self
     }
-}
 
-impl<T, Conn, C> RedisStorage<T, Conn, C>
-where
-    T: DeserializeOwned + Send + Unpin + Send + Sync + 'static,
-    Conn: ConnectionLike + Send + Sync + 'static,
-    C: Codec<Compact = Vec<u8>>,
-{
-    async fn fetch_next(
-        &mut self,
-        worker_id: &WorkerId,
-    ) -> Result<Vec<Request<T, RedisContext>>, RedisError> {
-        let fetch_jobs = self.scripts.get_jobs.clone();
-        let consumers_set = self.config.consumers_set();
-        let active_jobs_list = self.config.active_jobs_list();
-        let job_data_hash = self.config.job_data_hash();
-        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
-        let signal_list = self.config.signal_list();
-        let namespace = &self.config.namespace;
+    /// get the maximum number of attempts of jobs that do not set their own
+    pub fn get_max_attempts(&self) -> usize {
+        self.max_attempts
+    }
 
-        let result = fetch_jobs
-            .key(&consumers_set)
-            .key(&active_jobs_list)
-            .key(&inflight_set)
-            .key(&job_data_hash)
-            .key(&signal_list)
-            .arg(self.config.buffer_size) // No of jobs to fetch
-            .arg(&inflight_set)
-            .invoke_async::<Vec<Value>>(&mut self.conn)
-            .await;
+    /// set the maximum number of attempts of jobs that do not set their own
+    ///
+    /// A failed job is retried until it has been attempted this many times, then it is killed. A
+    /// job pushed with [RedisStorage::push_with_retries] or with
+    /// [RedisContext::set_max_attempts] uses its own limit instead. Defaults to `5`
+    pub fn set_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
 
-        match result {
-            Ok(jobs) => {
-                let mut processed = vec![];
-                for job in jobs {
-                    let bytes = deserialize_job(&job)?;
-                    let mut request: Request<T, RedisContext> =
-                        C::decode(bytes.clone()).map_err(|e| build_error(&e.into().to_string()))?;
-                    request.parts.context.lock_by = Some(worker_id.clone());
-                    request.parts.namespace = Some(Namespace(namespace.clone()));
-                    processed.push(request)
-                }
-                Ok(processed)
-            }
-            Err(e) => {
-                warn!("An error occurred during streaming jobs: {e}");
-                if matches!(e.kind(), ErrorKind::ResponseError)
-                    && e.to_string().contains("consumer not registered script")
-                {
-                    self.keep_alive(worker_id).await?;
-                }
-                Err(e)
-            }
-        }
+    /// get the number of retries and reschedules after which a job is killed
+    pub fn get_absolute_max_attempts(&self) -> Option<usize> {
+        self.absolute_max_attempts
     }
-}
-
-fn build_error(message: &str) -> RedisError {
-    RedisError::from(io::Error::new(io::ErrorKind::InvalidData, message))
-}
 
-fn deserialize_job(job: &Value) -> Result<&Vec<u8>, RedisError> {
-    match job {
-        Value::BulkString(bytes) => Ok(bytes),
-        Value::Array(val) | Value::Set(val) => val
-            .first()
-            .and_then(|val| {
-                if let Value::BulkString(bytes) = val {
-                    Some(bytes)
-                } else {
-                    None
-                }
-            })
-            .ok_or(build_error("Value::Bulk: Invalid data returned by storage")),
-        _ => Err(build_error("unknown result type for next message")),
+    /// set the number of retries and reschedules after which a job is killed
+    ///
+    /// Every retry and reschedule of a job counts towards [RedisContext::total_attempts], which
+    /// unlike its attempts is never reset. A retry or reschedule that would exceed the limit kills
+    /// the job with [DeadReason::MaxRetriesExceeded] instead, whatever the per-job limit or the
+    /// retry logic says, as a safety valve against jobs that are retried forever. Defaults to no
+    /// limit
+    pub fn set_absolute_max_attempts(mut self, absolute_max_attempts: usize) -> Self {
+        self.absolute_max_attempts = Some(absolute_max_attempts);
+        self
     }
-}
 
-impl<T, Conn: ConnectionLike, C> RedisStorage<T, Conn, C> {
-    async fn keep_alive(&mut self, worker_id: &WorkerId) -> Result<(), RedisError> {
-        let register_consumer = self.scripts.register_consumer.clone();
-        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
-        let consumers_set = self.config.consumers_set();
+    /// get the number of retries allowed across the queue in a rolling window
+    pub fn get_retry_budget(&self) -> Option<(usize, Duration)> {
+        self.retry_budget
+    }
 
-        let now: i64 = Utc::now().timestamp();
+    /// set the number of retries allowed across the queue in a rolling `window`, to cap the load
+    /// of retries during an incident
+    ///
+    /// Retries are counted in a sliding window shared by every worker of the namespace. Once
+    /// `count` retries are due within a window, further retries are not dropped but deferred,
+    /// each rescheduled to when the window has room for it. Deferring a retry does not change
+    /// its attempts, so the per-job maximum set with [Config::set_max_attempts] or
+    /// [RedisStorage::push_with_retries] still decides whether a failed job is retried or killed;
+    /// the budget only decides when the retry runs. A `count` of zero is raised to one, as a
+    /// budget that allows no retries would defer them forever. Defaults to no budget
+    pub fn set_retry_budget(mut self, count: usize, window: Duration) -> Self {
+        self.retry_budget = Some((count.max(1), window));
+        self
+    }
 
-        register_consumer
-            .key(consumers_set)
-            .arg(now)
-            .arg(inflight_set)
-            .invoke_async(&mut self.conn)
-            .await
+    /// get the number of consecutive keep-alive failures after which the worker is stopped
+    pub fn get_max_keepalive_failures(&self) -> usize {
+        self.max_keepalive_failures
     }
-}
 
-impl<T, Conn, C> Storage for RedisStorage<T, Conn, C>
-where
-    T: Serialize + DeserializeOwned + Send + 'static + Unpin + Sync,
-    Conn: ConnectionLike + Send + Sync + 'static,
-    C: Codec<Compact = Vec<u8>> + Send + 'static,
-{
-    type Job = T;
-    type Error = RedisError;
-    type Context = RedisContext;
+    /// set the number of consecutive keep-alive failures after which the worker is stopped
+    ///
+    /// Each failure is emitted as [RedisPollError::KeepAliveError] and retried on the next
+    /// `keep_alive` tick. Once the limit is reached the poller emits
+    /// [RedisPollError::KeepAliveExhausted] and stops the worker, so a worker that can no longer
+    /// reach Redis does not keep running without making progress. `0` retries forever.
+    /// Defaults to `5`
+    pub fn set_max_keepalive_failures(mut self, max_keepalive_failures: usize) -> Self {
+        self.max_keepalive_failures = max_keepalive_failures;
+        self
+    }
 
-    type Compact = Vec<u8>;
+    /// get how long a consumer that is no longer kept alive stays registered
+    pub fn get_consumer_ttl(&self) -> Option<Duration> {
+        self.consumer_ttl
+    }
 
-    async fn push_request(
-        &mut self,
-        req: Request<T, RedisContext>,
-    ) -> Result<Parts<Self::Context>, RedisError> {
-        let conn = &mut self.conn;
-        let push_job = self.scripts.push_job.clone();
-        let job_data_hash = self.config.job_data_hash();
-        let active_jobs_list = self.config.active_jobs_list();
-        let signal_list = self.config.signal_list();
+    /// set how long a consumer that is no longer kept alive stays registered
+    ///
+    /// Every keep-alive prunes consumers last seen longer than `ttl` ago from the consumers set, so
+    /// that workers that died do not pile up in [RedisStorage::list_workers] and the orphaned jobs
+    /// scan. Consumers that still hold inflight jobs are kept until those are reenqueued. A value
+    /// of about 10 times `keep_alive` is a reasonable choice. Defaults to keeping every consumer
+    pub fn set_consumer_ttl(mut self, ttl: Duration) -> Self {
+        self.consumer_ttl = Some(ttl);
+        self
+    }
 
-        let job = C::encode(&req)
-            .map_err(|e| (ErrorKind::IoError, "Encode error", e.into().to_string()))?;
-        push_job
-            .key(job_data_hash)
-            .key(active_jobs_list)
-            .key(signal_list)
-            .arg(req.parts.task_id.to_string())
-            .arg(job)
-            .invoke_async(conn)
-            .await?;
-        Ok(req.parts)
+    /// get the maximum size in bytes of an encoded job, `0` means no limit
+    pub fn get_max_payload_bytes(&self) -> usize {
+        self.max_payload_bytes
     }
 
-    async fn push_raw_request(
-        &mut self,
-        req: Request<Self::Compact, Self::Context>,
-    ) -> Result<Parts<Self::Context>, Self::Error> {
-        let conn = &mut self.conn;
-        let push_job = self.scripts.push_job.clone();
-        let job_data_hash = self.config.job_data_hash();
-        let active_jobs_list = self.config.active_jobs_list();
-        let signal_list = self.config.signal_list();
+    /// set the maximum size in bytes of an encoded job, larger jobs are rejected when pushed or
+    /// scheduled
+    ///
+    /// Defaults to `0`, no limit
+    pub fn set_max_payload_bytes(mut self, max_payload_bytes: usize) -> Self {
+        self.max_payload_bytes = max_payload_bytes;
+        self
+    }
 
-        let job = C::encode(&req)
-            .map_err(|e| (ErrorKind::IoError, "Encode error", e.into().to_string()))?;
-        push_job
-            .key(job_data_hash)
-            .key(active_jobs_list)
-            .key(signal_list)
-            .arg(req.parts.task_id.to_string())
-            .arg(job)
-            .invoke_async(conn)
-            .await?;
-        Ok(req.parts)
+    /// get the max number of ids kept for [RedisStorage::completed_stream] and
+    /// [RedisStorage::dead_letter_stream]
+    pub fn get_stream_backlog(&self) -> usize {
+        self.stream_backlog
     }
 
-    async fn schedule_request(
-        &mut self,
-        req: Request<Self::Job, RedisContext>,
-        on: i64,
-    ) -> Result<Parts<Self::Context>, RedisError> {
-        let schedule_job = self.scripts.schedule_job.clone();
-        let job_data_hash = self.config.job_data_hash();
-        let scheduled_jobs_set = self.config.scheduled_jobs_set();
-        let job = C::encode(&req)
-            .map_err(|e| (ErrorKind::IoError, "Encode error", e.into().to_string()))?;
-        schedule_job
-            .key(job_data_hash)
-            .key(scheduled_jobs_set)
-            .arg(req.parts.task_id.to_string())
-            .arg(job)
-            .arg(on)
-            .invoke_async(&mut self.conn)
-            .await?;
-        Ok(req.parts)
+    /// set the max number of ids kept for [RedisStorage::completed_stream] and
+    /// [RedisStorage::dead_letter_stream] while no subscriber takes them, the oldest ids are
+    /// dropped past it and `0` keeps none
+    ///
+    /// Defaults to `10_000`
+    pub fn set_stream_backlog(mut self, stream_backlog: usize) -> Self {
+        self.stream_backlog = stream_backlog;
+        self
     }
 
-    async fn len(&mut self) -> Result<i64, RedisError> {
-        let pending_jobs: i64 = redis::cmd("LLEN")
-            .arg(self.config.active_jobs_list())
-            .query_async(&mut self.conn)
-            .await?;
+    /// Returns the Redis key for the sorted set of pending jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the pending jobs set.
+    pub fn active_jobs_list(&self) -> String {
+        ACTIVE_JOBS_LIST.replace("{queue}", &self.namespace)
+    }
 
-        Ok(pending_jobs)
+    /// Returns the Redis key for the list of completed jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the completed jobs list.
+    pub fn completed_jobs_list(&self) -> String {
+        COMPLETED_JOBS_LIST.replace("{queue}", &self.namespace)
     }
 
-    async fn fetch_by_id(
-        &mut self,
-        job_id: &TaskId,
-    ) -> Result<Option<Request<Self::Job, RedisContext>>, RedisError> {
-        let data: Value = redis::cmd("HMGET")
-            .arg(self.config.job_data_hash())
-            .arg(job_id.to_string())
-            .query_async(&mut self.conn)
-            .await?;
-        let bytes = deserialize_job(&data)?;
+    /// Returns the Redis key for the set of consumers associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the consumers set.
+    pub fn consumers_set(&self) -> String {
+        CONSUMERS_SET.replace("{queue}", &self.namespace)
+    }
 
-        let inner: Request<T, RedisContext> = C::decode(bytes.to_vec())
-            .map_err(|e| (ErrorKind::IoError, "Decode error", e.into().to_string()))?;
-        Ok(Some(inner))
+    /// Returns the Redis key for the set of dead jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the dead jobs set.
+    pub fn dead_jobs_set(&self) -> String {
+        DEAD_JOBS_SET.replace("{queue}", &self.namespace)
     }
-    async fn update(&mut self, job: Request<T, RedisContext>) -> Result<(), RedisError> {
-        let task_id = job.parts.task_id.to_string();
-        let bytes = C::encode(&job)
-            .map_err(|e| (ErrorKind::IoError, "Encode error", e.into().to_string()))?;
-        let _: i64 = redis::cmd("HSET")
-            .arg(self.config.job_data_hash())
-            .arg(task_id)
-            .arg(bytes)
-            .query_async(&mut self.conn)
-            .await?;
-        Ok(())
+
+    /// Returns the Redis key for the hash storing the [DeadReason] of each dead job associated
+    /// with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the dead reason hash.
+    pub fn dead_reason_hash(&self) -> String {
+        DEAD_REASON_HASH.replace("{queue}", &self.namespace)
     }
 
-    async fn reschedule(
-        &mut self,
-        job: Request<T, RedisContext>,
-        wait: Duration,
-    ) -> Result<(), RedisError> {
-        let schedule_job = self.scripts.schedule_job.clone();
-        let job_id = &job.parts.task_id;
-        let worker_id = &job.parts.context.lock_by.clone().unwrap();
-        let job = C::encode(&job)
-            .map_err(|e| (ErrorKind::IoError, "Encode error", e.into().to_string()))?;
-        let job_data_hash = self.config.job_data_hash();
-        let scheduled_jobs_set = self.config.scheduled_jobs_set();
-        let on: i64 = Utc::now().timestamp();
-        let wait: i64 = wait
-            .as_secs()
-            .try_into()
-            .map_err(|e: TryFromIntError| (ErrorKind::IoError, "Duration error", e.to_string()))?;
-        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
-        let failed_jobs_set = self.config.failed_jobs_set();
-        redis::cmd("SREM")
-            .arg(inflight_set)
-            .arg(job_id.to_string())
-            .query_async(&mut self.conn)
-            .await?;
-        redis::cmd("ZADD")
-            .arg(failed_jobs_set)
-            .arg(on)
-            .arg(job_id.to_string())
-            .query_async(&mut self.conn)
-            .await?;
-        schedule_job
-            .key(job_data_hash)
-            .key(scheduled_jobs_set)
-            .arg(job_id.to_string())
-            .arg(job)
-            .arg(on + wait)
-            .invoke_async(&mut self.conn)
-            .await
+    /// Returns the Redis key for the list of dead letters associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the dead letter list.
+    pub fn dead_letter_list(&self) -> String {
+        DEAD_LETTER_LIST.replace("{queue}", &self.namespace)
     }
-    async fn is_empty(&mut self) -> Result<bool, RedisError> {
-        self.len().map_ok(|res| res == 0).await
+
+    /// Returns the Redis key for the set of done jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the done jobs set.
+    pub fn done_jobs_set(&self) -> String {
+        DONE_JOBS_SET.replace("{queue}", &self.namespace)
     }
 
-    async fn vacuum(&mut self) -> Result<usize, RedisError> {
-        let vacuum_script = self.scripts.vacuum.clone();
-        vacuum_script
-            .key(self.config.done_jobs_set())
-            .key(self.config.job_data_hash())
-            .invoke_async(&mut self.conn)
-            .await
+    /// Returns the Redis key for the set of the details of done jobs associated with the queue,
+    /// see [Config::set_done_details].
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the done details set.
+    pub fn done_details_set(&self) -> String {
+        DONE_DETAILS_SET.replace("{queue}", &self.namespace)
     }
-}
 
-impl<T, Conn, C> RedisStorage<T, Conn, C>
-where
-    Conn: ConnectionLike + Send + Sync + 'static,
-    C: Codec<Compact = Vec<u8>> + Send + 'static,
-{
-    /// Attempt to retry a job
-    pub async fn retry(&mut self, worker_id: &WorkerId, task_id: &TaskId) -> Result<i32, RedisError>
-    where
-        T: Send + DeserializeOwned + Serialize + Unpin + Sync + 'static,
-    {
-        let retry_job = self.scripts.retry_job.clone();
-        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
-        let scheduled_jobs_set = self.config.scheduled_jobs_set();
-        let job_data_hash = self.config.job_data_hash();
-        let job_fut = self.fetch_by_id(task_id);
-        let now: i64 = Utc::now().timestamp();
-        let res = job_fut.await?;
-        let conn = &mut self.conn;
-        match res {
-            Some(job) => {
-                let attempt = &job.parts.attempt;
-                let max_attempts = &job.parts.context.max_attempts;
-                if &attempt.current() >= &max_attempts {
-                    self.kill(
-                        worker_id,
-                        task_id,
-                        &(Box::new(io::Error::new(
-                            io::ErrorKind::Interrupted,
-                            format!("Max retries of {} exceeded", max_attempts),
-                        )) as BoxDynError),
-                    )
-                    .await?;
-                    return Ok(1);
-                }
-                let job = C::encode(job)
-                    .map_err(|e| (ErrorKind::IoError, "Encode error", e.into().to_string()))?;
+    /// Returns the Redis key for the set of failed jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the failed jobs set.
+    pub fn failed_jobs_set(&self) -> String {
+        FAILED_JOBS_SET.replace("{queue}", &self.namespace)
+    }
 
-                let res: Result<i32, RedisError> = retry_job
-                    .key(inflight_set)
-                    .key(scheduled_jobs_set)
-                    .key(job_data_hash)
-                    .arg(task_id.to_string())
-                    .arg(now)
-                    .arg(job)
-                    .invoke_async(conn)
-                    .await;
-                match res {
-                    Ok(count) => Ok(count),
-                    Err(e) => Err(e),
-                }
-            }
-            None => Err(RedisError::from((ErrorKind::ResponseError, "Id not found"))),
-        }
+    /// Returns the Redis key for the set of inflight jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the inflight jobs set.
+    pub fn inflight_jobs_set(&self) -> String {
+        INFLIGHT_JOB_SET.replace("{queue}", &self.namespace)
     }
 
-    /// Attempt to kill a job
+    /// Returns the Redis key for the sorted set counting the retries of the queue against its
+    /// retry budget, see [Config::set_retry_budget].
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the retry budget set.
+    pub fn retry_budget_set(&self) -> String {
+        RETRY_BUDGET_SET.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing the contexts of jobs associated with the queue
+    /// that were updated apart from their data, see [RedisStorage::update_context].
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job context hash.
+    pub fn job_context_hash(&self) -> String {
+        JOB_CONTEXT_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing job data associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job data hash.
+    pub fn job_data_hash(&self) -> String {
+        JOB_DATA_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing how long acknowledged jobs associated with the
+    /// queue ran, in milliseconds.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job duration hash.
+    pub fn job_duration_hash(&self) -> String {
+        JOB_DURATION_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing the last error of failed jobs associated with the
+    /// queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job error hash.
+    pub fn job_error_hash(&self) -> String {
+        JOB_ERROR_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing job expiry times associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job expiry hash.
+    pub fn job_expiry_hash(&self) -> String {
+        JOB_EXPIRY_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing the time in milliseconds inflight jobs associated
+    /// with the queue were fetched.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job fetch time hash.
+    pub fn job_fetched_hash(&self) -> String {
+        JOB_FETCHED_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing job priorities associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job priority hash.
+    pub fn job_priority_hash(&self) -> String {
+        JOB_PRIORITY_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing the group of unfinished jobs pushed with
+    /// [RedisStorage::push_group] associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job group hash.
+    pub fn job_group_hash(&self) -> String {
+        JOB_GROUP_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash counting the total, done and failed jobs of each group
+    /// associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the group stats hash.
+    pub fn group_stats_hash(&self) -> String {
+        GROUP_STATS_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing job progress associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job progress hash.
+    pub fn job_progress_hash(&self) -> String {
+        JOB_PROGRESS_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash storing the result of completed jobs associated with the
+    /// queue, see [RedisStorage::get_result].
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the job result hash.
+    pub fn job_result_hash(&self) -> String {
+        JOB_RESULT_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the set of scheduled jobs associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the scheduled jobs set.
+    pub fn scheduled_jobs_set(&self) -> String {
+        SCHEDULED_JOBS_SET.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the list of signals associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the signal list.
+    pub fn signal_list(&self) -> String {
+        SIGNAL_LIST.replace("{queue}", &self.namespace)
+    }
+
+    /// Returns the Redis key for the hash of unique keys associated with the queue.
+    /// The key is dynamically generated using the namespace of the queue.
+    ///
+    /// # Returns
+    /// A `String` representing the Redis key for the unique jobs hash.
+    pub fn unique_jobs_hash(&self) -> String {
+        UNIQUE_JOBS_HASH.replace("{queue}", &self.namespace)
+    }
+
+    /// Gets the reenqueue_orphaned_after duration.
+    pub fn reenqueue_orphaned_after(&self) -> Duration {
+        self.reenqueue_orphaned_after
+    }
+
+    /// Gets a mutable reference to the reenqueue_orphaned_after.
+    pub fn reenqueue_orphaned_after_mut(&mut self) -> &mut Duration {
+        &mut self.reenqueue_orphaned_after
+    }
+
+    /// Occasionally some workers die, or abandon jobs because of panics.
+    /// This is the time a task takes before its back to the queue
+    ///
+    /// Defaults to 5 minutes
+    pub fn set_reenqueue_orphaned_after(mut self, after: Duration) -> Self {
+        self.reenqueue_orphaned_after = after;
+        self
+    }
+
+    /// set a channel notified of every job the workers reenqueue as orphaned
+    ///
+    /// Orphaned jobs usually mean that a worker crashed. Each one is logged, and also sent to this
+    /// channel when it is set. Notifications are dropped when the channel is full or closed so
+    /// that a slow receiver does not hold back the worker.
+    pub fn set_orphaned_jobs_sender(mut self, sender: Sender<OrphanedJob>) -> Self {
+        self.orphaned_jobs_sender = Some(sender);
+        self
+    }
+
+    fn notify_orphaned(&mut self, orphaned: Vec<OrphanedJob>) {
+        for job in orphaned {
+            warn!(
+                "Reenqueued job {} orphaned by worker {}",
+                job.task_id, job.worker_id
+            );
+            if let Some(sender) = self.orphaned_jobs_sender.as_mut() {
+                if let Err(e) = sender.try_send(job) {
+                    warn!("Failed to notify about an orphaned job: {e}");
+                }
+            }
+        }
+    }
+
+    fn new_request<T>(&self, job: T) -> Request<T, RedisContext> {
+        let mut req = Request::new(job);
+        req.parts.task_id = self.task_id_factory.next_id();
+        req
+    }
+
+    /// The id of a job as it is stored in Redis
+    fn task_key(&self, task_id: &TaskId) -> String {
+        self.task_id_encoding.encode(task_id)
+    }
+
+    fn apply_job_type<T>(&self, ctx: &mut RedisContext) {
+        if ctx.job_type.is_none() {
+            ctx.job_type = Some(
+                self.job_type
+                    .clone()
+                    .unwrap_or_else(|| type_name::<T>().to_owned()),
+            );
+        }
+    }
+
+    pub(crate) fn apply_context_profile(&self, ctx: &mut RedisContext) {
+        self.context_profile.apply(ctx);
+    }
+
+    fn apply_default_ttl(&self, ctx: &mut RedisContext) {
+        if let (None, Some(ttl)) = (ctx.expires_at, self.default_ttl) {
+            ctx.expires_at = Some(self.clock.now().timestamp() + ttl.as_secs() as i64);
+        }
+    }
+
+    /// Count a retry or reschedule of a job, returning the limit it exceeds if any
+    pub(crate) fn count_total_attempt(&self, ctx: &mut RedisContext) -> Option<usize> {
+        ctx.total_attempts += 1;
+        self.absolute_max_attempts
+            .filter(|max| ctx.total_attempts > *max)
+    }
+
+    pub(crate) fn max_attempts_of(&self, ctx: &RedisContext) -> usize {
+        ctx.max_attempts.unwrap_or(self.max_attempts)
+    }
+
+    fn check_payload_size(&self, job: &[u8]) -> Result<(), RedisStorageError> {
+        if self.max_payload_bytes > 0 && job.len() > self.max_payload_bytes {
+            return Err(RedisStorageError::PayloadTooLarge {
+                size: job.len(),
+                limit: self.max_payload_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Represents a [Storage] that uses Redis for storage.
+pub struct RedisStorage<T, Conn = ConnectionManager, C = JsonCodec<Vec<u8>>> {
+    conn: Conn,
+    job_type: PhantomData<T>,
+    pub(super) scripts: RedisScript,
+    controller: Controller,
+    maintenance: Arc<AtomicBool>,
+    config: Config,
+    codec: PhantomData<C>,
+    pub(super) multiplexed: Option<MultiplexedDecoder<T>>,
+    pending_acks: Arc<Mutex<Vec<PendingAck>>>,
+    listener: Arc<dyn JobEventListener>,
+}
+
+impl<T, Conn, C> fmt::Debug for RedisStorage<T, Conn, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStorage")
+            .field("conn", &"ConnectionManager")
+            .field("job_type", &std::any::type_name::<T>())
+            .field("scripts", &self.scripts)
+            .field("config", &self.config)
+            .field("multiplexed", &self.multiplexed.is_some())
+            .field("listener", &self.listener)
+            .finish()
+    }
+}
+
+impl<T, Conn: Clone, C> Clone for RedisStorage<T, Conn, C> {
+    fn clone(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+            job_type: PhantomData,
+            scripts: self.scripts.clone(),
+            controller: self.controller.clone(),
+            maintenance: self.maintenance.clone(),
+            config: self.config.clone(),
+            codec: self.codec,
+            multiplexed: self.multiplexed,
+            pending_acks: self.pending_acks.clone(),
+            listener: self.listener.clone(),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned, Conn> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
+    /// Start a new connection
+    pub fn new(conn: Conn) -> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
+        Self::new_with_codec::<JsonCodec<Vec<u8>>>(
+            conn,
+            Config::default().set_namespace(type_name::<T>()),
+        )
+    }
+
+    /// Start a connection with a custom config
+    pub fn new_with_config(
+        conn: Conn,
+        config: Config,
+    ) -> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
+        Self::new_with_codec::<JsonCodec<Vec<u8>>>(conn, config)
+    }
+
+    /// Start a connection with the default config and a custom namespace
+    pub fn new_with_namespace(
+        conn: Conn,
+        namespace: &str,
+    ) -> RedisStorage<T, Conn, JsonCodec<Vec<u8>>> {
+        Self::new_with_config(conn, Config::default().set_namespace(namespace))
+    }
+
+    /// Start a storage that fetches the jobs of several types pushed to one namespace.
+    ///
+    /// Each job is decoded into a variant of `T` by its [RedisContext::job_type], jobs of a type
+    /// `T` does not handle are killed with [RedisStorageError::UnexpectedJobType] instead of
+    /// failing the poll. Push jobs with typed storages sharing the namespace, each tagged with
+    /// [Config::set_job_type].
+    pub fn new_multiplexed(conn: Conn, config: Config) -> RedisStorage<T, Conn, JsonCodec<Vec<u8>>>
+    where
+        T: MultiplexedJob,
+    {
+        let mut storage = Self::new_with_codec::<JsonCodec<Vec<u8>>>(conn, config);
+        storage.multiplexed = Some(decode_multiplexed::<T, JsonCodec<Vec<u8>>>);
+        storage
+    }
+
+    /// Start a new connection providing custom config and a codec
+    pub fn new_with_codec<K>(conn: Conn, config: Config) -> RedisStorage<T, Conn, K>
+    where
+        K: Codec + Sync + Send + 'static,
+    {
+        RedisStorage {
+            conn,
+            job_type: PhantomData,
+            controller: Controller::new(),
+            maintenance: Arc::new(AtomicBool::new(false)),
+            config,
+            codec: PhantomData::<K>,
+            multiplexed: None,
+            pending_acks: Arc::new(Mutex::new(Vec::new())),
+            listener: Arc::new(NoopListener),
+            scripts: RedisScript::new(&RedisScripts::default()),
+        }
+    }
+
+    /// Get current connection
+    pub fn get_connection(&self) -> &Conn {
+        &self.conn
+    }
+
+    /// Get the config used by the storage
+    pub fn get_config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Get the controller shared by this storage, its clones and the workers polling them.
+    ///
+    /// [Controller::unplug] pauses the workers: they stop fetching jobs until [Controller::plug]
+    /// resumes them, while keep-alives, acks and scheduled jobs carry on. Pausing does not affect
+    /// jobs that were already fetched, those being processed run to completion and are
+    /// acknowledged, so an operator can pause, wait for the inflight jobs to drain and resume
+    /// after maintenance. [Controller::stop] ends the job stream for good.
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> RedisStorage<T> {
+    /// Connect to the Redis at the `REDIS_URL` environment variable and start a storage of
+    /// `namespace`
+    ///
+    /// ```rust,no_run
+    /// # use apalis_redis::RedisStorage;
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Email;
+    /// # async fn run() -> Result<(), apalis_redis::RedisError> {
+    /// let storage: RedisStorage<Email> = RedisStorage::from_env("emails").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Fails with an [ErrorKind::InvalidClientConfig] error when `REDIS_URL` is not set.
+    pub async fn from_env(namespace: &str) -> Result<Self, RedisError> {
+        let url = std::env::var("REDIS_URL").map_err(|e| {
+            RedisError::from((
+                ErrorKind::InvalidClientConfig,
+                "REDIS_URL is not set",
+                e.to_string(),
+            ))
+        })?;
+        let conn = connect(url).await?;
+        Ok(Self::new_with_namespace(conn, namespace))
+    }
+}
+
+#[cfg(feature = "deadpool")]
+impl<T: Serialize + DeserializeOwned> RedisStorage<T, RedisConn, JsonCodec<Vec<u8>>> {
+    /// Start a new storage that checks out a connection from the pool for every operation
+    pub fn new_with_pool(pool: deadpool_redis::Pool) -> Self {
+        Self::new(RedisConn::Pooled(pool))
+    }
+}
+
+impl<T, Conn, C> RedisStorage<T, Conn, C> {
+    /// Run the Lua scripts replaced in `scripts` instead of the bundled ones, see [RedisScripts].
+    ///
+    /// Every clone made afterwards runs the same scripts. Defaults to the bundled scripts.
+    pub fn with_scripts(mut self, scripts: RedisScripts) -> Self {
+        self.scripts = RedisScript::new(&scripts);
+        self
+    }
+
+    /// Report the lifecycle transitions of this storage's jobs to `listener`.
+    ///
+    /// The listener is shared by the clones of the storage, including the ones polled by
+    /// workers. Defaults to a listener that does nothing.
+    pub fn with_listener(mut self, listener: impl JobEventListener + 'static) -> Self {
+        self.listener = Arc::new(listener);
+        self
+    }
+
+    /// Move the storage to `namespace`, keeping the rest of its config, connection and scripts.
+    ///
+    /// The storage gets its own controller and maintenance mode, so pausing it does not pause the
+    /// clones left in the old namespace.
+    ///
+    /// ```rust,no_run
+    /// # use apalis_redis::RedisStorage;
+    /// # async fn example(conn: redis::aio::ConnectionManager) {
+    /// let emails: RedisStorage<String> = RedisStorage::new_with_namespace(conn, "emails");
+    /// let reports = emails.clone().namespaced("reports");
+    /// # }
+    /// ```
+    pub fn namespaced(mut self, namespace: &str) -> Self {
+        self.config.namespace = namespace.to_string();
+        self.controller = Controller::new();
+        self.maintenance = Arc::new(AtomicBool::new(false));
+        self.pending_acks = Arc::new(Mutex::new(Vec::new()));
+        self
+    }
+
+    /// Get the underlying codec details
+    pub fn get_codec(&self) -> &PhantomData<C> {
+        &self.codec
+    }
+
+    /// Stop the workers polling this storage and its clones from fetching new jobs, to drain a
+    /// worker before it restarts.
+    ///
+    /// Jobs that were already fetched are still handed to the worker, and their acks, retries
+    /// and kills go through as usual, as do keep-alives and scheduled jobs. Once
+    /// [RedisStorage::list_workers] shows no inflight jobs for the worker it can be restarted.
+    ///
+    /// This differs from [Controller::unplug], which also holds back the jobs fetched but not yet
+    /// taken by the worker, so they stay inflight until the worker is resumed. Maintenance mode
+    /// is local to this process: nothing is written to Redis, so the workers of other processes
+    /// keep fetching from the queue.
+    pub fn enter_maintenance(&self) {
+        self.maintenance.store(true, Ordering::Relaxed);
+    }
+
+    /// Let the workers polling this storage and its clones fetch jobs again after
+    /// [RedisStorage::enter_maintenance]
+    pub fn exit_maintenance(&self) {
+        self.maintenance.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether fetching is stopped by [RedisStorage::enter_maintenance]
+    pub fn is_in_maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::Relaxed)
+    }
+}
+
+impl<T, Conn, C> Backend<Request<T, RedisContext>> for RedisStorage<T, Conn, C>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + 'static,
+{
+    type Stream = BackendStream<RequestStream<Request<T, RedisContext>>>;
+
+    type Layer = Stack<JobTimeoutLayer, AckLayer<AckSender, T, RedisContext, C>>;
+
+    type Codec = C;
+
+    fn poll(
+        self,
+        worker: &Worker<apalis_core::worker::Context>,
+    ) -> Poller<Self::Stream, Self::Layer> {
+        let (stream, heartbeat, ack) = self.poll_with_ack(worker);
+        let layer = Stack::new(JobTimeoutLayer, AckLayer::new(ack));
+        Poller::new_with_layer(stream, heartbeat, layer)
+    }
+}
+
+impl<T, Conn, C> RedisStorage<T, Conn, C>
+where
+    T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + 'static,
+{
+    /// Poll the storage for `worker`, returning the stream of jobs, the heartbeat and the sender
+    /// the results of the jobs are acknowledged through
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn poll_with_ack(
+        mut self,
+        worker: &Worker<apalis_core::worker::Context>,
+    ) -> (
+        BackendStream<RequestStream<Request<T, RedisContext>>>,
+        BoxFuture<'static, ()>,
+        AckSender,
+    ) {
+        let (mut tx, rx) = mpsc::channel(self.config.buffer_size);
+        let (ack, ack_rx) =
+            mpsc::channel::<(RedisContext, Response<Vec<u8>>)>(self.config.buffer_size);
+        let controller = self.controller.clone();
+        let mut config = self.config.clone();
+        // Jobs sent to the stream that the worker has not taken yet
+        let buffered = Arc::new(AtomicUsize::new(0));
+        let stream: RequestStream<Request<T, RedisContext>> = Box::pin(rx.inspect({
+            let buffered = buffered.clone();
+            move |_| {
+                buffered.fetch_sub(1, Ordering::Relaxed);
+            }
+        }));
+        let worker = worker.clone();
+        let heartbeat = async move {
+            // Queues created before the active jobs were sorted by priority hold them in a list
+            if let Err(e) = self.migrate_active_jobs().await {
+                worker.emit(Event::Error(Box::new(RedisPollError::MigrateActiveError(
+                    e,
+                ))));
+            }
+
+            // Lets reenqueue any jobs that belonged to this worker in case of a death
+            match self
+                .reenqueue_orphaned_jobs((config.buffer_size * 10) as i32, config.clock.now())
+                .await
+            {
+                Ok(orphaned) => config.notify_orphaned(orphaned),
+                Err(e) => {
+                    worker.emit(Event::Error(Box::new(
+                        RedisPollError::ReenqueueOrphanedError(e),
+                    )));
+                }
+            }
+
+            let mut reenqueue_orphaned_stm =
+                apalis_core::interval::interval(config.poll_interval).fuse();
+
+            let mut keep_alive_stm = apalis_core::interval::interval(config.keep_alive).fuse();
+
+            let mut enqueue_scheduled_stm =
+                apalis_core::interval::interval(config.enqueue_scheduled).fuse();
+
+            let fetch_interval = config.fetch_interval();
+            let mut poll_next_stm = jittered_interval(
+                fetch_interval.clone(),
+                Jitter::new(config.fetch_jitter, worker.id()),
+            )
+            .fuse();
+
+            let mut rate_limit = config
+                .rate_limit
+                .map(|(max, per)| TokenBucket::new(max, per));
+
+            let mut ack_stream = ack_rx.fuse();
+
+            let mut flush_acks_stm = apalis_core::interval::interval(config.keep_alive / 2).fuse();
+
+            let mut keep_alive_failures = 0;
+
+            let mut reconnect = ReconnectBackoff::new(config.reconnect_backoff);
+
+            if let Err(e) = self.keep_alive(worker.id()).await {
+                keep_alive_failures += 1;
+                worker.emit(Event::Error(Box::new(RedisPollError::KeepAliveError(e))));
+            }
+
+            loop {
+                select! {
+                    _ = keep_alive_stm.next() => {
+                        let res = self.keep_alive(worker.id()).await;
+                        let delay = reconnect.record(&res);
+                        match res {
+                            Ok(()) => keep_alive_failures = 0,
+                            Err(e) => {
+                                keep_alive_failures += 1;
+                                if config.max_keepalive_failures != 0
+                                    && keep_alive_failures >= config.max_keepalive_failures
+                                {
+                                    worker.emit(Event::Error(Box::new(
+                                        RedisPollError::KeepAliveExhausted(keep_alive_failures, e),
+                                    )));
+                                    self.controller.stop();
+                                    worker.stop();
+                                    break;
+                                }
+                                worker.emit(Event::Error(Box::new(RedisPollError::KeepAliveError(e))));
+                            }
+                        }
+                        if let Some(delay) = delay {
+                            apalis_core::sleep(delay).await;
+                        }
+                    }
+                    _ = enqueue_scheduled_stm.next() => {
+                        let res = self.enqueue_scheduled(config.buffer_size).await;
+                        reconnect.wait_after(&res).await;
+                        if let Err(e) = res {
+                            worker.emit(Event::Error(Box::new(RedisPollError::EnqueueScheduledError(e))));
+                        }
+                        if let Some(visibility_timeout) = config.visibility_timeout {
+                            let stale_since = config.clock.now()
+                                - chrono::Duration::from_std(visibility_timeout).unwrap();
+                            if let Err(e) = self.reenqueue_stale((config.buffer_size * 10) as i32, stale_since).await {
+                                worker.emit(Event::Error(Box::new(RedisPollError::ReenqueueStaleError(e))));
+                            }
+                        }
+                        if !config.done_retention.is_zero() {
+                            if let Err(e) = self.trim_done(config.done_retention).await {
+                                worker.emit(Event::Error(Box::new(RedisPollError::TrimDoneError(e))));
+                            }
+                        }
+                    }
+                    _ = poll_next_stm.next() => {
+                        if worker.is_ready()
+                            && self.controller.is_plugged()
+                            && !self.is_in_maintenance()
+                        {
+                            // Wait for the worker to take the previous batch before fetching more
+                            if buffered.load(Ordering::Relaxed) > 0 {
+                                continue;
+                            }
+                            let count = rate_limit
+                                .as_mut()
+                                .map_or(config.buffer_size, |bucket| bucket.available().min(config.buffer_size));
+                            let count = self.free_slots(worker.id(), count).await;
+                            reconnect.wait_after(&count).await;
+                            let count = match count {
+                                Ok(count) => count,
+                                Err(e) => {
+                                    worker.emit(Event::Error(Box::new(RedisPollError::PollNextError(e))));
+                                    continue;
+                                }
+                            };
+                            if count == 0 {
+                                continue;
+                            }
+                            let res = self.fetch_next(worker.id(), count).await;
+                            reconnect.wait_after(&res).await;
+                            match res {
+                                Err(e) => {
+                                    worker.emit(Event::Error(Box::new(RedisPollError::PollNextError(e))));
+                                }
+                                Ok(res) => {
+                                    if let Some(empty_for) = fetch_interval.record(res.len()) {
+                                        self.listener.on_empty_poll(worker.id(), empty_for);
+                                    }
+                                    if let Some(bucket) = rate_limit.as_mut() {
+                                        bucket.take(res.len());
+                                    }
+                                    for job in res {
+                                        buffered.fetch_add(1, Ordering::Relaxed);
+                                        if let Err(e) = tx.send(Ok(Some(job))).await {
+                                            buffered.fetch_sub(1, Ordering::Relaxed);
+                                            worker.emit(Event::Error(Box::new(RedisPollError::EnqueueError(e))));
+                                        }
+                                    }
+                                }
+                            }
+                        } else if worker.is_shutting_down() {
+                            if let Err(e) = self.flush_acks().await {
+                                worker.emit(Event::Error(Box::new(RedisPollError::AckError(e))));
+                            }
+                        }
+
+                    }
+                    id_to_ack = ack_stream.next() => {
+                        if let Some((ctx, res)) = id_to_ack {
+                            let acked = match &res.inner {
+                                Ok(result)
+                                    if config.ack_batching
+                                        && ctx.cron.is_none()
+                                        && !worker.is_shutting_down() =>
+                                {
+                                    self.buffer_ack(&ctx, &res.task_id, &res.attempt, result).await
+                                }
+                                _ => self.ack(&ctx, &res).await,
+                            };
+                            reconnect.wait_after(&acked).await;
+                            match acked {
+                                // The job was reenqueued while it ran and runs again
+                                Err(e) if e.is_not_inflight() => {
+                                    debug!("Job {} is no longer inflight, skipping its ack", res.task_id);
+                                }
+                                Err(e) => {
+                                    worker.emit(Event::Error(Box::new(RedisPollError::AckError(e))));
+                                }
+                                Ok(()) => {}
+                            }
+                        }
+                    }
+                    _ = flush_acks_stm.next() => {
+                        let res = self.flush_acks().await;
+                        reconnect.wait_after(&res).await;
+                        if let Err(e) = res {
+                            worker.emit(Event::Error(Box::new(RedisPollError::AckError(e))));
+                        }
+                    }
+                    _ = reenqueue_orphaned_stm.next() => {
+                        let dead_since = config.clock.now()
+                            - chrono::Duration::from_std(config.reenqueue_orphaned_after).unwrap();
+                        let res = self.reenqueue_orphaned_jobs((config.buffer_size * 10) as i32, dead_since).await;
+                        reconnect.wait_after(&res).await;
+                        match res {
+                            Ok(orphaned) => config.notify_orphaned(orphaned),
+                            Err(e) => {
+                                worker.emit(Event::Error(Box::new(RedisPollError::ReenqueueOrphanedError(e))));
+                            }
+                        }
+                    }
+                };
+            }
+        };
+        (
+            BackendStream::new(stream, controller),
+            heartbeat.boxed(),
+            ack,
+        )
+    }
+}
+
+impl<T, Conn, C, Res> Ack<T, Res, C> for RedisStorage<T, Conn, C>
+where
+    T: Sync + Send + Serialize + DeserializeOwned + Unpin + 'static,
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + 'static,
+    Res: Serialize + Sync + Send + 'static,
+{
+    type Context = RedisContext;
+    type AckError = RedisStorageError;
+    async fn ack(
+        &mut self,
+        ctx: &Self::Context,
+        res: &Response<Res>,
+    ) -> Result<(), RedisStorageError> {
+        // Lets update the number of attempts
+        // TODO: move attempts to its own key
+        let mut task = self
+            .fetch_by_id(&res.task_id)
+            .await?
+            .expect("must be a valid task");
+        task.parts.attempt = res.attempt.clone();
+        self.update(task).await?;
+        // End of expensive update
+
+        let max_attempts = self.config.max_attempts_of(ctx);
+        // Whether this ack moved the job to a terminal state, retries kill jobs on their own
+        let finished = match &res.inner {
+            Ok(success_res) => {
+                let worker_id = ctx.lock_by.as_ref().unwrap();
+                let result = C::encode(success_res).map_err(Into::into).unwrap();
+                self.ack_job(
+                    worker_id,
+                    &res.task_id,
+                    AckStatus::Done(result),
+                    ctx.unique_key(),
+                    Some(res.attempt.current()),
+                )
+                .await
+                .map(|_| true)
+            }
+            Err(e) => match e {
+                Error::Abort(e) => {
+                    let worker_id = ctx.lock_by.as_ref().unwrap();
+                    self.kill_job(
+                        worker_id,
+                        &res.task_id,
+                        DeadReason::Killed,
+                        e,
+                        None,
+                        ctx.unique_key(),
+                    )
+                    .await
+                }
+                _ => {
+                    let last_error = e.to_string();
+                    if max_attempts > res.attempt.current() {
+                        let worker_id = ctx.lock_by.as_ref().unwrap();
+                        self.retry_job(worker_id, &res.task_id, Some(&last_error))
+                            .await
+                            .map(|_| false)
+                    } else {
+                        let worker_id = ctx.lock_by.as_ref().unwrap();
+                        let reason = if is_timeout(e) {
+                            DeadReason::Timeout
+                        } else {
+                            DeadReason::MaxRetriesExceeded
+                        };
+
+                        self.kill_job(
+                            worker_id,
+                            &res.task_id,
+                            reason,
+                            &(Box::new(io::Error::new(
+                                io::ErrorKind::Interrupted,
+                                format!("Max retries of {} exceeded", max_attempts),
+                            )) as BoxDynError),
+                            Some(&last_error),
+                            ctx.unique_key(),
+                        )
+                        .await
+                    }
+                }
+            },
+        };
+        if finished? {
+            self.finish_job(&res.task_id, Some(ctx)).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, Conn, C> RedisStorage<T, Conn, C>
+where
+    T: Serialize + DeserializeOwned + Send + Unpin + Send + Sync + 'static,
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + 'static,
+{
+    /// Consume the storage as a plain stream of the jobs fetched for `worker_id`.
+    ///
+    /// This is the fetch stream a worker polls, without its heartbeats. The caller is responsible
+    /// for calling [RedisStorage::keep_alive] before the stream is polled and at least every
+    /// `keep_alive` interval, for enqueuing scheduled jobs and for acknowledging fetched jobs.
+    /// The storage is polled every `poll_interval`, or the adaptive wait set with
+    /// [Config::set_adaptive_fetch], plus up to `fetch_jitter`, while the queue is empty or
+    /// fetching fails.
+    pub fn into_stream(self, worker_id: WorkerId) -> RequestStream<Request<T, RedisContext>>
+    where
+        C: Send + 'static,
+    {
+        let fetch_interval = self.config.fetch_interval();
+        let buffer_size = self.config.buffer_size;
+        let jitter = Jitter::new(self.config.fetch_jitter, &worker_id);
+        futures::stream::unfold(
+            (self, jitter, false),
+            move |(mut storage, mut jitter, idle)| {
+                let worker_id = worker_id.clone();
+                let fetch_interval = fetch_interval.clone();
+                async move {
+                    if idle {
+                        apalis_core::sleep(fetch_interval.current() + jitter.next_offset()).await;
+                    }
+                    let res = storage.fetch_next(&worker_id, buffer_size).await;
+                    if let Ok(jobs) = &res {
+                        if let Some(empty_for) = fetch_interval.record(jobs.len()) {
+                            storage.listener.on_empty_poll(&worker_id, empty_for);
+                        }
+                    }
+                    let idle = !matches!(&res, Ok(jobs) if !jobs.is_empty());
+                    Some((res, (storage, jitter, idle)))
+                }
+            },
+        )
+        .flat_map(|res| {
+            futures::stream::iter(match res {
+                Ok(jobs) => jobs.into_iter().map(|job| Ok(Some(job))).collect(),
+                Err(e) => vec![Err(Error::SourceError(Arc::new(Box::new(e))))],
+            })
+        })
+        .boxed()
+    }
+
+    /// Process the jobs in the active set with `handler` and return once it is empty.
+    ///
+    /// Jobs are fetched as `worker_id`, which is registered first, and acknowledged like a worker
+    /// would. Scheduled jobs, including failed jobs waiting to be retried, are not waited for.
+    /// Returns the number of processed jobs.
+    pub async fn drain<F, Fut, Res>(
+        mut self,
+        worker_id: WorkerId,
+        mut handler: F,
+    ) -> Result<usize, RedisStorageError>
+    where
+        T: Serialize,
+        C: Send + 'static,
+        F: FnMut(Request<T, RedisContext>) -> Fut,
+        Fut: Future<Output = Result<Res, Error>>,
+        Res: Serialize + Send + Sync + 'static,
+    {
+        self.keep_alive(&worker_id).await?;
+        let mut processed = 0;
+        while !self.is_empty().await? {
+            let jobs = self.fetch_next(&worker_id, self.config.buffer_size).await?;
+            for job in jobs {
+                job.parts.attempt.increment();
+                let ctx = job.parts.context.clone();
+                let task_id = job.parts.task_id.clone();
+                let attempt = job.parts.attempt.clone();
+                let res = handler(job).await;
+                self.ack(&ctx, &Response::new(res, task_id, attempt))
+                    .await?;
+                processed += 1;
+            }
+        }
+        Ok(processed)
+    }
+
+    /// Cap `count` by the slots `worker_id` has left under [Config::set_max_concurrent]
+    ///
+    /// Jobs whose acks are buffered until the next flush are still in the inflight set, they do
+    /// not take a slot.
+    async fn free_slots(
+        &mut self,
+        worker_id: &WorkerId,
+        count: usize,
+    ) -> Result<usize, RedisStorageError> {
+        let Some(max_concurrent) = self.config.max_concurrent else {
+            return Ok(count);
+        };
+        let inflight: usize = redis::cmd("SCARD")
+            .arg(format!("{}:{}", self.config.inflight_jobs_set(), worker_id))
+            .query_async(&mut self.conn)
+            .await?;
+        let acked = self
+            .pending_acks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|ack| &ack.worker_id == worker_id)
+            .count();
+        Ok(count.min(max_concurrent.saturating_sub(inflight.saturating_sub(acked))))
+    }
+
+    /// Fetch up to `count` jobs for `worker_id` once, moving them to its inflight set.
+    ///
+    /// This is the one-shot counterpart of the stream a worker polls. `worker_id` must have been
+    /// registered with [RedisStorage::keep_alive], the jobs are not fetched otherwise, and the
+    /// caller is responsible for acknowledging each returned job with [Ack::ack] or
+    /// [RedisStorage::ack_failed]. Unacknowledged jobs are reenqueued once the worker stops
+    /// sending keep-alives. Returns fewer jobs than `count` when the queue holds fewer, and none
+    /// when `count` is 0.
+    pub async fn fetch_next(
+        &mut self,
+        worker_id: &WorkerId,
+        count: usize,
+    ) -> Result<Vec<Request<T, RedisContext>>, RedisStorageError> {
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let fetch_jobs = self.scripts.get_jobs.clone();
+        let consumers_set = self.config.consumers_set();
+        let active_jobs_list = self.config.active_jobs_list();
+        let job_data_hash = self.config.job_data_hash();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let signal_list = self.config.signal_list();
+        let job_expiry_hash = self.config.job_expiry_hash();
+        let dead_jobs_set = self.config.dead_jobs_set();
+        let job_fetched_hash = self.config.job_fetched_hash();
+        let namespace = self.config.namespace.clone();
+        let now = self.config.clock.now();
+
+        let result = fetch_jobs
+            .key(&consumers_set)
+            .key(&active_jobs_list)
+            .key(&inflight_set)
+            .key(&job_data_hash)
+            .key(&signal_list)
+            .key(&job_expiry_hash)
+            .key(&dead_jobs_set)
+            .key(&job_fetched_hash)
+            .key(self.config.dead_letter_list())
+            .key(self.config.dead_reason_hash())
+            .key(self.config.job_context_hash())
+            .key(self.config.job_group_hash())
+            .key(self.config.group_stats_hash())
+            .key(self.config.job_priority_hash())
+            .arg(count) // No of jobs to fetch
+            .arg(&inflight_set)
+            .arg(now.timestamp())
+            .arg(now.timestamp_millis())
+            .arg(match self.config.fetch_order {
+                FetchOrder::Fifo => "fifo",
+                FetchOrder::Lifo => "lifo",
+            })
+            .arg(self.config.stream_backlog)
+            .invoke_async::<(Vec<Value>, Vec<String>)>(&mut self.conn)
+            .await;
+
+        match result {
+            Ok((jobs, expired)) => {
+                // Jobs that expired while waiting are dead, follow up on them like other dead jobs
+                for id in expired {
+                    let finished = match self.config.task_id_encoding.decode(&id) {
+                        Ok(task_id) => self.finish_job(&task_id, None).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = finished {
+                        warn!("Failed to finish expired job {id}: {e}");
+                    }
+                }
+                let mut processed = vec![];
+                for triple in jobs.chunks(3) {
+                    let [task_id, job, context] = triple else {
+                        return Err(build_error("get_jobs returned a job id without data"));
+                    };
+                    let bytes = deserialize_job(job)?;
+                    let context: Option<Vec<u8>> = redis::from_redis_value(context)?;
+                    let decoded = decode_job::<T, C>(self.multiplexed, bytes.clone())
+                        .and_then(|request| with_stored_context::<T, C>(request, context));
+                    let mut request = match decoded {
+                        Ok(request) => request,
+                        Err(RedisStorageError::UnexpectedJobType(job_type)) => {
+                            self.reject_job(worker_id, bytes.clone(), job_type).await?;
+                            continue;
+                        }
+                        Err(e) if self.config.on_decode_error == DecodeErrorPolicy::Quarantine => {
+                            let task_id: String = redis::from_redis_value(task_id)?;
+                            self.quarantine_job(worker_id, &task_id, bytes, e).await?;
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    request.parts.context.lock_by = Some(worker_id.clone());
+                    request
+                        .parts
+                        .data
+                        .insert(Metadata(request.parts.context.metadata.clone()));
+                    request.parts.namespace = Some(Namespace(namespace.clone()));
+                    self.listener.on_fetched(&request.parts.task_id, worker_id);
+                    processed.push(request)
+                }
+                Ok(processed)
+            }
+            Err(e) => {
+                warn!("An error occurred during streaming jobs: {e}");
+                if matches!(e.kind(), ErrorKind::ResponseError)
+                    && e.to_string().contains("consumer not registered script")
+                {
+                    self.keep_alive(worker_id).await?;
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Kill a fetched job that failed to decode, its payload is left in the job data hash
+    async fn quarantine_job(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &str,
+        job: &[u8],
+        error: RedisStorageError,
+    ) -> Result<(), RedisStorageError> {
+        warn!(
+            "Quarantining job {task_id} that failed to decode: {error}, payload: {}",
+            String::from_utf8_lossy(job)
+        );
+        let task_id = self.config.task_id_encoding.decode(task_id)?;
+        self.kill_job(
+            worker_id,
+            &task_id,
+            DeadReason::DecodeFailed,
+            &(Box::new(error) as BoxDynError),
+            None,
+            None,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Kill a fetched job whose type a multiplexed storage does not decode
+    async fn reject_job(
+        &mut self,
+        worker_id: &WorkerId,
+        job: Vec<u8>,
+        job_type: Option<String>,
+    ) -> Result<(), RedisStorageError> {
+        let untyped: Request<IgnoredAny, RedisContext> =
+            C::decode(job).map_err(|e| RedisStorageError::Codec(e.into()))?;
+        warn!(
+            "Rejecting job {} of unexpected type {:?}",
+            untyped.parts.task_id, job_type
+        );
+        self.kill_job(
+            worker_id,
+            &untyped.parts.task_id,
+            DeadReason::DecodeFailed,
+            &(Box::new(RedisStorageError::UnexpectedJobType(job_type)) as BoxDynError),
+            None,
+            untyped.parts.context.unique_key(),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+/// Find the registered worker whose inflight set holds the job, checking `buffer_size` inflight
+/// sets per round trip
+async fn find_inflight_worker<Conn: ConnectionLike>(
+    conn: &mut Conn,
+    config: &Config,
+    task_id: &TaskId,
+) -> Result<Option<WorkerId>, RedisStorageError> {
+    let task_key = config.task_key(task_id);
+    let page = config.buffer_size.max(1);
+    let mut start = 0;
+    loop {
+        let inflight_sets: Vec<String> = redis::cmd("ZRANGE")
+            .arg(config.consumers_set())
+            .arg(start)
+            .arg(start + page - 1)
+            .query_async(conn)
+            .await?;
+        if inflight_sets.is_empty() {
+            return Ok(None);
+        }
+        let mut pipe = redis::pipe();
+        for inflight_set in &inflight_sets {
+            pipe.sismember(inflight_set, &task_key);
+        }
+        let held: Vec<bool> = pipe.query_async(conn).await?;
+        if let Some((inflight_set, _)) = inflight_sets.iter().zip(held).find(|(_, held)| *held) {
+            let prefix = format!("{}:", config.inflight_jobs_set());
+            return Ok(Some(WorkerId::new(
+                inflight_set.strip_prefix(&prefix).unwrap_or(inflight_set),
+            )));
+        }
+        start += page;
+    }
+}
+
+/// Get the next occurrence of a cron expression, `None` if it has no upcoming occurrence
+fn next_occurrence(expr: &str) -> Result<Option<DateTime<Utc>>, RedisStorageError> {
+    let schedule =
+        cron::Schedule::from_str(expr).map_err(|e| RedisStorageError::InvalidCron(e.into()))?;
+    Ok(schedule.upcoming(Utc).next())
+}
+
+fn build_error(message: &str) -> RedisStorageError {
+    RedisStorageError::Codec(message.into())
+}
+
+/// Replace the context of a decoded job with the one stored by [RedisStorage::update_context]
+fn with_stored_context<T, C>(
+    mut request: Request<T, RedisContext>,
+    context: Option<Vec<u8>>,
+) -> Result<Request<T, RedisContext>, RedisStorageError>
+where
+    C: Codec<Compact = Vec<u8>>,
+{
+    if let Some(context) = context {
+        request.parts.context =
+            C::decode(context).map_err(|e| RedisStorageError::Codec(e.into()))?;
+    }
+    Ok(request)
+}
+
+fn deserialize_job(job: &Value) -> Result<&Vec<u8>, RedisStorageError> {
+    match job {
+        Value::BulkString(bytes) => Ok(bytes),
+        Value::Array(val) | Value::Set(val) => val
+            .first()
+            .and_then(|val| {
+                if let Value::BulkString(bytes) = val {
+                    Some(bytes)
+                } else {
+                    None
+                }
+            })
+            .ok_or(build_error("Value::Bulk: Invalid data returned by storage")),
+        _ => Err(build_error("unknown result type for next message")),
+    }
+}
+
+/// Pop up to `count` ids off the head of `list`, taking them with LRANGE and LTRIM as LPOP only
+/// takes a count from Redis 6.2
+async fn pop_ids<Conn: ConnectionLike>(
+    conn: &mut Conn,
+    list: &str,
+    count: usize,
+) -> Result<Vec<String>, RedisError> {
+    let count = count.max(1) as isize;
+    let (ids,): (Vec<String>,) = redis::pipe()
+        .atomic()
+        .lrange(list, 0, count - 1)
+        .ltrim(list, count, -1)
+        .ignore()
+        .query_async(conn)
+        .await?;
+    Ok(ids)
+}
+
+impl<T, Conn: ConnectionLike, C> RedisStorage<T, Conn, C> {
+    /// Register a worker as a consumer of the queue and refresh the time it was last seen.
+    ///
+    /// Workers that are not kept alive within `reenqueue_orphaned_after` lose their inflight jobs
+    /// to other workers.
+    pub async fn keep_alive(&mut self, worker_id: &WorkerId) -> Result<(), RedisStorageError> {
+        let register_consumer = self.scripts.register_consumer.clone();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let consumers_set = self.config.consumers_set();
+
+        let now: i64 = self.config.clock.now().timestamp();
+        let prune_before = match self.config.consumer_ttl {
+            Some(ttl) => now.saturating_sub(ttl.as_secs().try_into().unwrap_or(i64::MAX)),
+            None => 0,
+        };
+
+        register_consumer
+            .key(consumers_set)
+            .arg(now)
+            .arg(inflight_set)
+            .arg(prune_before)
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+impl<T, Conn, C> Storage for RedisStorage<T, Conn, C>
+where
+    T: Serialize + DeserializeOwned + Send + 'static + Unpin + Sync,
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + 'static,
+{
+    type Job = T;
+    type Error = RedisStorageError;
+    type Context = RedisContext;
+
+    type Compact = Vec<u8>;
+
+    async fn push(&mut self, job: T) -> Result<Parts<RedisContext>, RedisStorageError> {
+        let req = self.config.new_request(job);
+        self.push_request(req).await
+    }
+
+    async fn schedule(
+        &mut self,
+        job: T,
+        on: i64,
+    ) -> Result<Parts<RedisContext>, RedisStorageError> {
+        let req = self.config.new_request(job);
+        self.schedule_request(req, on).await
+    }
+
+    async fn push_request(
+        &mut self,
+        req: Request<T, RedisContext>,
+    ) -> Result<Parts<Self::Context>, RedisStorageError> {
+        self.push_job(req).await.map(|(parts, _)| parts)
+    }
+
+    async fn push_raw_request(
+        &mut self,
+        mut req: Request<Self::Compact, Self::Context>,
+    ) -> Result<Parts<Self::Context>, Self::Error> {
+        let push_job = self.scripts.push_job.clone();
+        let job_data_hash = self.config.job_data_hash();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+
+        let job_priority_hash = self.config.job_priority_hash();
+        let job_expiry_hash = self.config.job_expiry_hash();
+
+        self.config.apply_job_type::<T>(&mut req.parts.context);
+        self.config.apply_default_ttl(&mut req.parts.context);
+        Metadata::apply(&mut req.parts);
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = C::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        self.config.check_payload_size(&job)?;
+        push_job
+            .key(job_data_hash)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .key(job_expiry_hash)
+            .arg(self.config.task_key(&req.parts.task_id))
+            .arg(job)
+            .arg(req.parts.context.priority)
+            .arg(req.parts.context.expires_at.unwrap_or(0))
+            .invoke_async::<ScriptStatus>(&mut self.conn)
+            .await?
+            .0?;
+        Ok(req.parts)
+    }
+
+    async fn schedule_request(
+        &mut self,
+        mut req: Request<Self::Job, RedisContext>,
+        on: i64,
+    ) -> Result<Parts<Self::Context>, RedisStorageError> {
+        let schedule_job = self.scripts.schedule_job.clone();
+        let job_data_hash = self.config.job_data_hash();
+        let scheduled_jobs_set = self.config.scheduled_jobs_set();
+        let job_priority_hash = self.config.job_priority_hash();
+        let job_expiry_hash = self.config.job_expiry_hash();
+        self.config.apply_job_type::<T>(&mut req.parts.context);
+        Metadata::apply(&mut req.parts);
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = C::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        self.config.check_payload_size(&job)?;
+        schedule_job
+            .key(job_data_hash)
+            .key(scheduled_jobs_set)
+            .key(job_priority_hash)
+            .key(job_expiry_hash)
+            .arg(self.config.task_key(&req.parts.task_id))
+            .arg(job)
+            .arg(on)
+            .arg(req.parts.context.priority)
+            .arg(req.parts.context.expires_at.unwrap_or(0))
+            .invoke_async::<()>(&mut self.conn)
+            .await?;
+        Ok(req.parts)
+    }
+
+    async fn len(&mut self) -> Result<i64, RedisStorageError> {
+        let pending_jobs: i64 = redis::cmd("ZCARD")
+            .arg(self.config.active_jobs_list())
+            .query_async(&mut self.conn)
+            .await?;
+
+        Ok(pending_jobs)
+    }
+
+    async fn fetch_by_id(
+        &mut self,
+        job_id: &TaskId,
+    ) -> Result<Option<Request<Self::Job, RedisContext>>, RedisStorageError> {
+        #[allow(clippy::type_complexity)]
+        let (
+            data,
+            context,
+            progress,
+            last_error,
+            duration_ms,
+            active,
+            scheduled,
+            failed,
+            done,
+            dead,
+        ): (
+            Value,
+            Option<Vec<u8>>,
+            Option<u8>,
+            Option<String>,
+            Option<u64>,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+        ) = redis::pipe()
+            .cmd("HMGET")
+            .arg(self.config.job_data_hash())
+            .arg(self.config.task_key(job_id))
+            .cmd("HGET")
+            .arg(self.config.job_context_hash())
+            .arg(self.config.task_key(job_id))
+            .cmd("HGET")
+            .arg(self.config.job_progress_hash())
+            .arg(self.config.task_key(job_id))
+            .cmd("HGET")
+            .arg(self.config.job_error_hash())
+            .arg(self.config.task_key(job_id))
+            .cmd("HGET")
+            .arg(self.config.job_duration_hash())
+            .arg(self.config.task_key(job_id))
+            .zscore(self.config.active_jobs_list(), self.config.task_key(job_id))
+            .zscore(
+                self.config.scheduled_jobs_set(),
+                self.config.task_key(job_id),
+            )
+            .zscore(self.config.failed_jobs_set(), self.config.task_key(job_id))
+            .zscore(self.config.done_jobs_set(), self.config.task_key(job_id))
+            .zscore(self.config.dead_jobs_set(), self.config.task_key(job_id))
+            .query_async(&mut self.conn)
+            .await?;
+        let bytes = deserialize_job(&data)?;
+
+        let job = decode_job::<T, C>(self.multiplexed, bytes.to_vec())?;
+        let mut inner = with_stored_context::<T, C>(job, context)?;
+        if let Some(progress) = progress {
+            inner.parts.context.progress = progress;
+        }
+        if last_error.is_some() {
+            inner.parts.context.last_error = last_error;
+        }
+        if duration_ms.is_some() {
+            inner.parts.context.duration_ms = duration_ms;
+        }
+        // Pending states take precedence if a job is in several sets
+        let state = [
+            (active, JobState::Active),
+            (scheduled, JobState::Scheduled),
+            (failed, JobState::Failed),
+            (done, JobState::Done),
+            (dead, JobState::Dead),
+        ]
+        .into_iter()
+        .find_map(|(score, state)| score.map(|_| state));
+        let state = match state {
+            Some(state) => state,
+            None => match find_inflight_worker(&mut self.conn, &self.config, job_id).await? {
+                Some(worker_id) => {
+                    inner.parts.context.lock_by = Some(worker_id);
+                    JobState::Inflight
+                }
+                None => JobState::Unknown,
+            },
+        };
+        inner.parts.data.insert(state);
+        Ok(Some(inner))
+    }
+    async fn update(&mut self, mut job: Request<T, RedisContext>) -> Result<(), RedisStorageError> {
+        self.config.apply_context_profile(&mut job.parts.context);
+        let task_id = self.config.task_key(&job.parts.task_id);
+        let bytes = C::encode(&job).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        // The job carries its whole context, which supersedes one stored apart from it
+        let _: () = redis::pipe()
+            .atomic()
+            .hset(self.config.job_data_hash(), &task_id, bytes)
+            .ignore()
+            .hdel(self.config.job_context_hash(), &task_id)
+            .ignore()
+            .query_async(&mut self.conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn reschedule(
+        &mut self,
+        mut job: Request<T, RedisContext>,
+        wait: Duration,
+    ) -> Result<(), RedisStorageError> {
+        let schedule_job = self.scripts.schedule_job.clone();
+        let worker_id = &job.parts.context.lock_by.clone().unwrap();
+        if let Some(max) = self.config.count_total_attempt(&mut job.parts.context) {
+            return self
+                .kill_runaway_job(worker_id, &job.parts, max, None)
+                .await;
+        }
+        self.config.apply_context_profile(&mut job.parts.context);
+        let job_id = &job.parts.task_id;
+        let priority = job.parts.context.priority;
+        let expires_at = job.parts.context.expires_at.unwrap_or(0);
+        let job = C::encode(&job).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        let job_data_hash = self.config.job_data_hash();
+        let scheduled_jobs_set = self.config.scheduled_jobs_set();
+        let job_priority_hash = self.config.job_priority_hash();
+        let job_expiry_hash = self.config.job_expiry_hash();
+        let on: i64 = self.config.clock.now().timestamp();
+        let wait: i64 = wait
+            .as_secs()
+            .try_into()
+            .map_err(|e: TryFromIntError| RedisStorageError::InvalidDuration(e.into()))?;
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let failed_jobs_set = self.config.failed_jobs_set();
+        redis::cmd("SREM")
+            .arg(inflight_set)
+            .arg(self.config.task_key(job_id))
+            .query_async::<()>(&mut self.conn)
+            .await?;
+        redis::cmd("ZADD")
+            .arg(failed_jobs_set)
+            .arg(on)
+            .arg(self.config.task_key(job_id))
+            .query_async::<()>(&mut self.conn)
+            .await?;
+        schedule_job
+            .key(job_data_hash)
+            .key(scheduled_jobs_set)
+            .key(job_priority_hash)
+            .key(job_expiry_hash)
+            .arg(self.config.task_key(job_id))
+            .arg(job)
+            .arg(on + wait)
+            .arg(priority)
+            .arg(expires_at)
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+    async fn is_empty(&mut self) -> Result<bool, RedisStorageError> {
+        self.len().map_ok(|res| res == 0).await
+    }
+
+    async fn vacuum(&mut self) -> Result<usize, RedisStorageError> {
+        let report = self.vacuum_with_report(false).await?;
+        Ok(report.done + report.orphaned)
+    }
+}
+
+impl<T, Conn, C> RedisStorage<T, Conn, C>
+where
+    Conn: ConnectionLike + Send + Sync + 'static,
+    C: Codec<Compact = Vec<u8>> + Send + 'static,
+{
+    /// Delete the data of done jobs and of jobs that no set references, returning what was
+    /// deleted by kind.
+    ///
+    /// This is what [Storage::vacuum] runs. Orphaned data is found by scanning the job data hash
+    /// in batches of `buffer_size`, each checked against the active, scheduled, done, failed and
+    /// dead sets and the inflight sets of registered workers. With `dry_run` nothing is deleted
+    /// and the report holds what would have been.
+    pub async fn vacuum_with_report(
+        &mut self,
+        dry_run: bool,
+    ) -> Result<VacuumReport, RedisStorageError> {
+        let vacuum = self.scripts.vacuum.clone();
+        let vacuum_orphaned = self.scripts.vacuum_orphaned.clone();
+        let dry_run = if dry_run { "1" } else { "0" };
+        let done = vacuum
+            .key(self.config.done_jobs_set())
+            .key(self.config.job_data_hash())
+            .key(self.config.job_priority_hash())
+            .key(self.config.job_expiry_hash())
+            .key(self.config.job_fetched_hash())
+            .key(self.config.job_progress_hash())
+            .key(self.config.job_error_hash())
+            .key(self.config.job_duration_hash())
+            .key(self.config.job_result_hash())
+            .key(self.config.done_details_set())
+            .key(self.config.job_context_hash())
+            .arg(dry_run)
+            .invoke_async(&mut self.conn)
+            .await?;
+
+        let mut orphaned = 0;
+        let mut cursor = 0u64;
+        loop {
+            let (next, entries): (u64, Vec<Vec<u8>>) = redis::cmd("HSCAN")
+                .arg(self.config.job_data_hash())
+                .arg(cursor)
+                .arg("COUNT")
+                .arg(self.config.buffer_size)
+                .query_async(&mut self.conn)
+                .await?;
+            // Entries alternate between job ids and their data
+            let ids: Vec<Vec<u8>> = entries.into_iter().step_by(2).collect();
+            if !ids.is_empty() {
+                let count: usize = vacuum_orphaned
+                    .key(self.config.active_jobs_list())
+                    .key(self.config.scheduled_jobs_set())
+                    .key(self.config.done_jobs_set())
+                    .key(self.config.failed_jobs_set())
+                    .key(self.config.dead_jobs_set())
+                    .key(self.config.consumers_set())
+                    .key(self.config.job_data_hash())
+                    .key(self.config.job_priority_hash())
+                    .key(self.config.job_expiry_hash())
+                    .key(self.config.job_fetched_hash())
+                    .key(self.config.job_progress_hash())
+                    .key(self.config.job_error_hash())
+                    .key(self.config.job_duration_hash())
+                    .key(self.config.job_result_hash())
+                    .key(self.config.dead_reason_hash())
+                    .key(self.config.job_context_hash())
+                    .arg(dry_run)
+                    .arg(ids)
+                    .invoke_async(&mut self.conn)
+                    .await?;
+                orphaned += count;
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        Ok(VacuumReport { done, orphaned })
+    }
+
+    /// Attempt to retry a job
+    ///
+    /// The job is rescheduled after the delay given by the configured [BackoffStrategy]
+    pub async fn retry(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+    ) -> Result<i32, RedisStorageError>
+    where
+        T: Send + DeserializeOwned + Serialize + Unpin + Sync + 'static,
+    {
+        self.retry_job(worker_id, task_id, None).await
+    }
+
+    async fn retry_job(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+        last_error: Option<&str>,
+    ) -> Result<i32, RedisStorageError>
+    where
+        T: Send + DeserializeOwned + Serialize + Unpin + Sync + 'static,
+    {
+        let retry_job = self.scripts.retry_job.clone();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let scheduled_jobs_set = self.config.scheduled_jobs_set();
+        let job_data_hash = self.config.job_data_hash();
+        let job_fut = self.fetch_by_id(task_id);
+        let res = job_fut.await?;
+        let conn = &mut self.conn;
+        match res {
+            Some(mut job) => {
+                let attempt = &job.parts.attempt;
+                let max_attempts = self.config.max_attempts_of(&job.parts.context);
+                if attempt.current() >= max_attempts {
+                    let killed = self
+                        .kill_job(
+                            worker_id,
+                            task_id,
+                            DeadReason::MaxRetriesExceeded,
+                            &(Box::new(io::Error::new(
+                                io::ErrorKind::Interrupted,
+                                format!("Max retries of {} exceeded", max_attempts),
+                            )) as BoxDynError),
+                            last_error,
+                            job.parts.context.unique_key(),
+                        )
+                        .await?;
+                    if killed {
+                        self.finish_job(task_id, Some(&job.parts.context)).await?;
+                    }
+                    return Ok(1);
+                }
+                // The scheduled set is scored in seconds, round up so that a sub-second backoff
+                // still delays the retry
+                let delay = self.config.backoff.next_delay(attempt.current());
+                let delay: i64 = (delay.as_secs() + u64::from(delay.subsec_nanos() > 0))
+                    .try_into()
+                    .map_err(|e: TryFromIntError| RedisStorageError::InvalidDuration(e.into()))?;
+                let retry_at: i64 = self.config.clock.now().timestamp() + delay;
+                if let Some(max) = self.config.count_total_attempt(&mut job.parts.context) {
+                    self.kill_runaway_job(worker_id, &job.parts, max, last_error)
+                        .await?;
+                    return Ok(1);
+                }
+                self.config.apply_context_profile(&mut job.parts.context);
+                let job = C::encode(&job).map_err(|e| RedisStorageError::Encode(e.into()))?;
+                let (budget, window) = self.config.retry_budget.unwrap_or_default();
+
+                let res: Result<ScriptStatus, RedisError> = retry_job
+                    .key(inflight_set)
+                    .key(scheduled_jobs_set)
+                    .key(job_data_hash)
+                    .key(self.config.job_error_hash())
+                    .key(self.config.job_result_hash())
+                    .key(self.config.retry_budget_set())
+                    .key(self.config.job_context_hash())
+                    .key(self.config.job_fetched_hash())
+                    .arg(self.config.task_key(task_id))
+                    .arg(retry_at)
+                    .arg(job)
+                    .arg(last_error.unwrap_or_default())
+                    .arg(budget)
+                    .arg(window.as_millis() as u64)
+                    .arg(self.config.clock.now().timestamp_millis())
+                    .invoke_async(conn)
+                    .await;
+                match res {
+                    Ok(ScriptStatus(status)) => {
+                        status?;
+                        self.listener.on_retried(task_id);
+                        Ok(1)
+                    }
+                    Err(e) => Err(e.into()),
+                }
+            }
+            None => Err(RedisStorageError::NotFound(task_id.clone())),
+        }
+    }
+
+    /// Acknowledge a job that failed without retrying or killing it.
+    ///
+    /// The job is moved from the worker's inflight set to the failed jobs set, scored by the time
+    /// it failed, and the error reason is stored as its result. Its unique key is released.
+    pub async fn ack_failed(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+        error: Option<&str>,
+    ) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let job = self
+            .fetch_by_id(task_id)
+            .await?
+            .ok_or_else(|| RedisStorageError::NotFound(task_id.clone()))?;
+        let reason = error.unwrap_or_default().as_bytes().to_vec();
+        self.ack_job(
+            worker_id,
+            task_id,
+            AckStatus::Failed(reason),
+            job.parts.context.unique_key(),
+            Some(job.parts.attempt.current()),
+        )
+        .await?;
+        self.finish_job(task_id, Some(&job.parts.context)).await
+    }
+
+    /// Acknowledge an inflight job as done without knowing the worker holding it, such as from a
+    /// supervisor acting on behalf of a crashed worker.
+    ///
+    /// The inflight sets of the registered workers are checked `buffer_size` at a time until the
+    /// job is found, with no `KEYS` scan. The job is acknowledged with `()` as its result, and
+    /// [ScriptError::NotFound] is returned if no worker holds it. Like [Ack::ack], its unique key
+    /// is released and the next occurrence of a recurring job is scheduled.
+    pub async fn ack_by_id(&mut self, task_id: &TaskId) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let worker_id = find_inflight_worker(&mut self.conn, &self.config, task_id)
+            .await?
+            .ok_or(ScriptError::NotFound)?;
+        let job = self
+            .fetch_by_id(task_id)
+            .await?
+            .ok_or_else(|| RedisStorageError::NotFound(task_id.clone()))?;
+        let result = C::encode(()).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        self.ack_job(
+            &worker_id,
+            task_id,
+            AckStatus::Done(result),
+            job.parts.context.unique_key(),
+            Some(job.parts.attempt.current()),
+        )
+        .await?;
+        self.finish_job(task_id, Some(&job.parts.context)).await
+    }
+
+    /// Check whether a registered worker is currently processing the job.
+    ///
+    /// The inflight sets are checked like [RedisStorage::ack_by_id] does, stopping at the first
+    /// worker holding the job. This is a point in time check, the job may be fetched, finished
+    /// or reenqueued right after it returns, so it can only guard against obvious duplicates
+    /// such as pushing a job again while it runs.
+    pub async fn is_inflight(&self, task_id: &TaskId) -> Result<bool, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        Ok(find_inflight_worker(&mut conn, &self.config, task_id)
+            .await?
+            .is_some())
+    }
+
+    async fn ack_job(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+        status: AckStatus,
+        unique_key: Option<&str>,
+        attempts: Option<usize>,
+    ) -> Result<(), RedisStorageError> {
+        let ack_job = self.scripts.ack_job.clone();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let now = self.config.clock.now();
+        let (outcome, result) = match status {
+            AckStatus::Done(result) => ("done", result),
+            AckStatus::Failed(reason) => ("failed", reason),
+        };
+        let ScriptStatus(status) = ack_job
+            .key(inflight_set)
+            .key(self.config.done_jobs_set())
+            .key(self.config.job_data_hash())
+            .key(self.config.unique_jobs_hash())
+            .key(self.config.failed_jobs_set())
+            .key(self.config.completed_jobs_list())
+            .key(self.config.job_fetched_hash())
+            .key(self.config.job_duration_hash())
+            .key(self.config.job_result_hash())
+            .key(self.config.job_group_hash())
+            .key(self.config.group_stats_hash())
+            .key(self.config.done_details_set())
+            .key(self.config.job_error_hash())
+            .key(self.config.job_priority_hash())
+            .arg(self.config.task_key(task_id))
+            .arg(now.timestamp())
+            .arg(result)
+            .arg(unique_key.unwrap_or_default())
+            .arg(outcome)
+            .arg(now.timestamp_millis())
+            .arg(if self.config.done_details { "1" } else { "0" })
+            .arg(worker_id.to_string())
+            .arg(
+                attempts
+                    .map(|attempts| attempts.to_string())
+                    .unwrap_or_default(),
+            )
+            .arg(self.config.stream_backlog)
+            .invoke_async(&mut self.conn)
+            .await?;
+        status?;
+        self.listener.on_acked(task_id);
+        Ok(())
+    }
+
+    /// Buffer a successful ack until the next flush, flushing once `buffer_size` acks are
+    /// buffered
+    async fn buffer_ack<Res: Serialize>(
+        &mut self,
+        ctx: &RedisContext,
+        task_id: &TaskId,
+        attempt: &Attempt,
+        result: &Res,
+    ) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let result = C::encode(result).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        let buffered = {
+            let mut pending_acks = self
+                .pending_acks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            pending_acks.push(PendingAck {
+                worker_id: ctx.lock_by.clone().unwrap(),
+                task_id: task_id.clone(),
+                attempt: attempt.clone(),
+                result,
+                unique_key: ctx.unique_key.clone(),
+            });
+            pending_acks.len()
+        };
+        if buffered >= self.config.buffer_size {
+            self.flush_acks().await?;
+        }
+        Ok(())
+    }
+
+    /// Flush the successful acks buffered by the poller, see [Config::set_ack_batching].
+    ///
+    /// The acks of each worker are flushed in a single round trip. Acks that fail to flush are
+    /// kept for the next flush. Returns the number of acknowledged jobs.
+    pub async fn flush_acks(&mut self) -> Result<usize, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let mut pending_acks = std::mem::take(
+            &mut *self
+                .pending_acks
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        let mut count = 0;
+        while let Some(worker_id) = pending_acks.first().map(|ack| ack.worker_id.clone()) {
+            let (acks, rest): (Vec<_>, Vec<_>) = pending_acks
+                .into_iter()
+                .partition(|ack| ack.worker_id == worker_id);
+            match self.ack_jobs(&worker_id, &acks).await {
+                Ok(acked) => count += acked,
+                Err(e) => {
+                    self.pending_acks
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .extend(acks.into_iter().chain(rest));
+                    return Err(e);
+                }
+            }
+            pending_acks = rest;
+        }
+        Ok(count)
+    }
+
+    async fn ack_jobs(
+        &mut self,
+        worker_id: &WorkerId,
+        acks: &[PendingAck],
+    ) -> Result<usize, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let ids: Vec<String> = acks
+            .iter()
+            .map(|ack| self.config.task_key(&ack.task_id))
+            .collect();
+        let data: Vec<Option<Vec<u8>>> = redis::cmd("HMGET")
+            .arg(self.config.job_data_hash())
+            .arg(&ids)
+            .query_async(&mut self.conn)
+            .await?;
+
+        let ack_jobs = self.scripts.ack_jobs.clone();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let now = self.config.clock.now();
+        let mut invocation = ack_jobs.key(inflight_set);
+        invocation
+            .key(self.config.done_jobs_set())
+            .key(self.config.job_data_hash())
+            .key(self.config.unique_jobs_hash())
+            .key(self.config.completed_jobs_list())
+            .key(self.config.job_fetched_hash())
+            .key(self.config.job_duration_hash())
+            .key(self.config.job_result_hash())
+            .key(self.config.job_group_hash())
+            .key(self.config.group_stats_hash())
+            .key(self.config.done_details_set())
+            .key(self.config.job_priority_hash())
+            .arg(now.timestamp())
+            .arg(now.timestamp_millis())
+            .arg(if self.config.done_details { "1" } else { "0" })
+            .arg(worker_id.to_string())
+            .arg(self.config.stream_backlog);
+        for ((ack, id), job) in acks.iter().zip(ids).zip(data) {
+            // Keep the attempts made like `ack` does, the stored data is kept if it can't be decoded
+            let job = job
+                .and_then(|job| decode_job::<T, C>(self.multiplexed, job).ok())
+                .map(|mut job| {
+                    job.parts.attempt = ack.attempt.clone();
+                    self.config.apply_context_profile(&mut job.parts.context);
+                    C::encode(&job).map_err(|e| RedisStorageError::Encode(e.into()))
+                })
+                .transpose()?
+                .unwrap_or_default();
+            invocation
+                .arg(id)
+                .arg(job)
+                .arg(&ack.result)
+                .arg(ack.unique_key.as_deref().unwrap_or_default())
+                .arg(ack.attempt.current());
+        }
+        let acked = invocation.invoke_async(&mut self.conn).await?;
+        for ack in acks {
+            self.listener.on_acked(&ack.task_id);
+        }
+        Ok(acked)
+    }
+
+    /// Move the active jobs of a queue created before they were sorted by priority from a list to
+    /// a sorted set.
+    ///
+    /// Redis replies with `WRONGTYPE` to every fetch from such a queue until it is migrated.
+    /// Workers run this when they start, so it only needs to be called before pushing to an older
+    /// queue with no workers. Returns the number of migrated jobs, 0 if the queue is up to date.
+    pub async fn migrate_active_jobs(&mut self) -> Result<usize, RedisStorageError> {
+        let migrate_active = self.scripts.migrate_active.clone();
+        migrate_active
+            .key(self.config.active_jobs_list())
+            .key(self.config.job_priority_hash())
+            .key(self.config.signal_list())
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Move up to `count` jobs from the dead set back to the active set, oldest deaths first.
+    ///
+    /// Replayed jobs start over with no attempts, no expiry and no last error, so that they get
+    /// their full retries again. Their unique key is not claimed again. Returns the number of
+    /// replayed jobs.
+    pub async fn replay_dead(&mut self, count: usize) -> Result<usize, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        if count == 0 {
+            return Ok(0);
+        }
+        let ids: Vec<String> = redis::cmd("ZRANGE")
+            .arg(self.config.dead_jobs_set())
+            .arg(0)
+            .arg(count - 1)
+            .query_async(&mut self.conn)
+            .await?;
+        self.replay_jobs(ids).await
+    }
+
+    /// Move a dead job back to the active set, see [RedisStorage::replay_dead].
+    ///
+    /// Returns whether the job was dead and replayed.
+    pub async fn replay(&mut self, task_id: &TaskId) -> Result<bool, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        let replayed = self
+            .replay_jobs(vec![self.config.task_key(task_id)])
+            .await?;
+        Ok(replayed == 1)
+    }
+
+    async fn replay_jobs(&mut self, ids: Vec<String>) -> Result<usize, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let data: Vec<Option<Vec<u8>>> = redis::cmd("HMGET")
+            .arg(self.config.job_data_hash())
+            .arg(&ids)
+            .query_async(&mut self.conn)
+            .await?;
+        let replay_dead = self.scripts.replay_dead.clone();
+        let mut invocation = replay_dead.key(self.config.dead_jobs_set());
+        invocation
+            .key(self.config.active_jobs_list())
+            .key(self.config.signal_list())
+            .key(self.config.job_data_hash())
+            .key(self.config.job_priority_hash())
+            .key(self.config.job_expiry_hash())
+            .key(self.config.job_error_hash())
+            .key(self.config.dead_reason_hash());
+        for (id, job) in ids.iter().zip(data) {
+            // Reset the attempts and expiry, the stored data is kept if it can't be decoded
+            let job = job
+                .and_then(|job| decode_job::<T, C>(self.multiplexed, job).ok())
+                .map(|mut job| {
+                    job.parts.attempt = Attempt::default();
+                    job.parts.context.expires_at = None;
+                    job.parts.context.last_error = None;
+                    self.config.apply_context_profile(&mut job.parts.context);
+                    C::encode(&job)
+                        .map(|data| (data, job.parts.context.priority.to_string()))
+                        .map_err(|e| RedisStorageError::Encode(e.into()))
+                })
+                .transpose()?
+                .unwrap_or_default();
+            invocation.arg(id).arg(job.0).arg(job.1);
+        }
+        invocation
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Attempt to kill a job
+    ///
+    /// Its unique key is released and the next occurrence of a recurring job is scheduled.
     pub async fn kill(
         &mut self,
         worker_id: &WorkerId,
-        task_id: &TaskId,
-        error: &BoxDynError,
-    ) -> Result<(), RedisError> {
-        let kill_job = self.scripts.kill_job.clone();
-        let current_worker_id = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
-        let job_data_hash = self.config.job_data_hash();
-        let dead_jobs_set = self.config.dead_jobs_set();
-        let now: i64 = Utc::now().timestamp();
-        kill_job
-            .key(current_worker_id)
-            .key(dead_jobs_set)
-            .key(job_data_hash)
+        task_id: &TaskId,
+        error: &BoxDynError,
+    ) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let ctx = self
+            .fetch_by_id(task_id)
+            .await?
+            .map(|job| job.parts.context);
+        let unique_key = ctx.as_ref().and_then(|ctx| ctx.unique_key());
+        let killed = self
+            .kill_job(
+                worker_id,
+                task_id,
+                DeadReason::Killed,
+                error,
+                None,
+                unique_key,
+            )
+            .await?;
+        if killed {
+            self.finish_job(task_id, ctx.as_ref()).await?;
+        }
+        Ok(())
+    }
+
+    /// Kill a job for `reason`, keeping `last_error` as the error of its last attempt or `error`
+    /// if it is not given. Returns whether the job was inflight and killed.
+    async fn kill_job(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+        reason: DeadReason,
+        error: &BoxDynError,
+        last_error: Option<&str>,
+        unique_key: Option<&str>,
+    ) -> Result<bool, RedisStorageError> {
+        let kill_job = self.scripts.kill_job.clone();
+        let current_worker_id = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let dead_jobs_set = self.config.dead_jobs_set();
+        let now: i64 = self.config.clock.now().timestamp();
+        let error = error.to_string();
+        let last_error = last_error.unwrap_or(&error);
+        let killed: bool = kill_job
+            .key(current_worker_id)
+            .key(dead_jobs_set)
+            .key(self.config.unique_jobs_hash())
+            .key(self.config.dead_letter_list())
+            .key(self.config.job_error_hash())
+            .key(self.config.dead_reason_hash())
+            .key(self.config.job_group_hash())
+            .key(self.config.group_stats_hash())
+            .key(self.config.job_fetched_hash())
+            .key(self.config.job_priority_hash())
+            .key(self.config.job_progress_hash())
+            .arg(self.config.task_key(task_id))
+            .arg(now)
+            .arg(unique_key.unwrap_or_default())
+            .arg(last_error)
+            .arg(reason.as_str())
+            .arg(self.config.stream_backlog)
+            .invoke_async(&mut self.conn)
+            .await?;
+        if killed {
+            self.listener.on_killed(task_id, reason);
+        }
+        Ok(killed)
+    }
+
+    /// Kill a job that exceeded [Config::set_absolute_max_attempts]
+    async fn kill_runaway_job(
+        &mut self,
+        worker_id: &WorkerId,
+        parts: &Parts<RedisContext>,
+        max: usize,
+        last_error: Option<&str>,
+    ) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let killed = self
+            .kill_job(
+                worker_id,
+                &parts.task_id,
+                DeadReason::MaxRetriesExceeded,
+                &(Box::new(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    format!("Absolute max attempts of {max} exceeded"),
+                )) as BoxDynError),
+                last_error,
+                parts.context.unique_key(),
+            )
+            .await?;
+        if killed {
+            self.finish_job(&parts.task_id, Some(&parts.context))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// List the details recorded for done jobs, oldest first, see [Config::set_done_details].
+    ///
+    /// Pages start at 1. Jobs done while recording was disabled are not listed.
+    pub async fn list_done_details(
+        &self,
+        page: usize,
+        per_page: usize,
+    ) -> Result<Vec<DoneDetails>, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        if per_page == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn.clone();
+        let start = page.saturating_sub(1) * per_page;
+        let members: Vec<Vec<u8>> = redis::cmd("ZRANGE")
+            .arg(self.config.done_details_set())
+            .arg(start)
+            .arg(start + per_page - 1)
+            .query_async(&mut conn)
+            .await?;
+        members
+            .into_iter()
+            .map(|member| {
+                let raw: RawDoneDetails = JsonCodec::<Vec<u8>>::decode(member)
+                    .map_err(|e| RedisStorageError::Codec(e.into()))?;
+                Ok(DoneDetails {
+                    task_id: self.config.task_id_encoding.decode(&raw.task_id)?,
+                    worker_id: WorkerId::new(raw.worker_id),
+                    attempts: raw.attempts,
+                    duration_ms: raw.duration_ms,
+                    done_at: raw.done_at,
+                })
+            })
+            .collect()
+    }
+
+    /// List jobs in the given state without consuming them.
+    ///
+    /// Pages start at 1 and jobs are returned in the order they are kept in Redis. Dead jobs carry
+    /// the [DeadReason] they died for as a request extension, unless they died before reasons
+    /// were recorded. Inflight jobs are spread over the sets of their workers and are not listed.
+    pub async fn list_jobs_by_state(
+        &self,
+        state: JobState,
+        page: usize,
+        per_page: usize,
+    ) -> Result<Vec<Request<T, RedisContext>>, RedisStorageError>
+    where
+        T: DeserializeOwned,
+        Conn: Clone,
+    {
+        if per_page == 0 {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.conn.clone();
+        let key = match state {
+            JobState::Active => self.config.active_jobs_list(),
+            JobState::Scheduled => self.config.scheduled_jobs_set(),
+            JobState::Done => self.config.done_jobs_set(),
+            JobState::Failed => self.config.failed_jobs_set(),
+            JobState::Dead => self.config.dead_jobs_set(),
+            JobState::Inflight | JobState::Unknown => return Ok(Vec::new()),
+        };
+        let start = page.saturating_sub(1) * per_page;
+        let ids: Vec<String> = redis::cmd("ZRANGE")
+            .arg(key)
+            .arg(start)
+            .arg(start + per_page - 1)
+            .query_async(&mut conn)
+            .await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        #[allow(clippy::type_complexity)]
+        let (data, contexts): (Vec<Option<Vec<u8>>>, Vec<Option<Vec<u8>>>) = redis::pipe()
+            .cmd("HMGET")
+            .arg(self.config.job_data_hash())
+            .arg(&ids)
+            .cmd("HMGET")
+            .arg(self.config.job_context_hash())
+            .arg(&ids)
+            .query_async(&mut conn)
+            .await?;
+        let reasons: Vec<Option<String>> = match state {
+            JobState::Dead => {
+                redis::cmd("HMGET")
+                    .arg(self.config.dead_reason_hash())
+                    .arg(&ids)
+                    .query_async(&mut conn)
+                    .await?
+            }
+            _ => vec![None; ids.len()],
+        };
+        data.into_iter()
+            .zip(contexts)
+            .zip(reasons)
+            .filter_map(|((bytes, context), reason)| {
+                let job = decode_job::<T, C>(self.multiplexed, bytes?)
+                    .and_then(|job| with_stored_context::<T, C>(job, context))
+                    .map(|mut job| {
+                        if let Some(reason) = reason.as_deref().and_then(DeadReason::parse) {
+                            job.parts.data.insert(reason);
+                        }
+                        job
+                    });
+                Some(job)
+            })
+            .collect()
+    }
+
+    /// Look at the next `n` jobs in the active set without consuming them.
+    ///
+    /// Jobs are returned in the order they would be fetched with [FetchOrder::Fifo] and stay
+    /// deliverable.
+    pub async fn peek(&self, n: usize) -> Result<Vec<Request<T, RedisContext>>, RedisStorageError>
+    where
+        T: DeserializeOwned,
+        Conn: Clone,
+    {
+        self.list_jobs_by_state(JobState::Active, 1, n).await
+    }
+
+    /// Delete every key that belongs to this storage's namespace, including the inflight sets of
+    /// registered workers.
+    ///
+    /// Keys outside the configured namespace are left untouched.
+    pub async fn clear(&mut self) -> Result<(), RedisStorageError> {
+        let consumers: Vec<String> = redis::cmd("ZRANGE")
+            .arg(self.config.consumers_set())
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut self.conn)
+            .await?;
+        redis::cmd("DEL")
+            .arg(self.config.active_jobs_list())
+            .arg(self.config.completed_jobs_list())
+            .arg(self.config.consumers_set())
+            .arg(self.config.dead_jobs_set())
+            .arg(self.config.dead_letter_list())
+            .arg(self.config.dead_reason_hash())
+            .arg(self.config.done_details_set())
+            .arg(self.config.done_jobs_set())
+            .arg(self.config.failed_jobs_set())
+            .arg(self.config.group_stats_hash())
+            .arg(self.config.inflight_jobs_set())
+            .arg(self.config.job_context_hash())
+            .arg(self.config.job_data_hash())
+            .arg(self.config.job_duration_hash())
+            .arg(self.config.job_error_hash())
+            .arg(self.config.job_expiry_hash())
+            .arg(self.config.job_fetched_hash())
+            .arg(self.config.job_group_hash())
+            .arg(self.config.job_priority_hash())
+            .arg(self.config.job_progress_hash())
+            .arg(self.config.job_result_hash())
+            .arg(self.config.retry_budget_set())
+            .arg(self.config.scheduled_jobs_set())
+            .arg(self.config.signal_list())
+            .arg(self.config.unique_jobs_hash())
+            .arg(consumers)
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// List the keys of the queue that exist in Redis with their type, such as `hash` or `zset`.
+    ///
+    /// This is a diagnostic aid. Keys are derived from the config and from the inflight sets of
+    /// the workers in the consumers set, they are never discovered with `KEYS` or `SCAN`, so keys
+    /// left by workers that are no longer registered are not listed.
+    pub async fn debug_keys(&self) -> Result<Vec<(String, String)>, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        let consumers: Vec<String> = redis::cmd("ZRANGE")
+            .arg(self.config.consumers_set())
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut conn)
+            .await?;
+        let keys: Vec<String> = [
+            self.config.active_jobs_list(),
+            self.config.completed_jobs_list(),
+            self.config.consumers_set(),
+            self.config.dead_jobs_set(),
+            self.config.dead_letter_list(),
+            self.config.dead_reason_hash(),
+            self.config.done_details_set(),
+            self.config.done_jobs_set(),
+            self.config.failed_jobs_set(),
+            self.config.group_stats_hash(),
+            self.config.inflight_jobs_set(),
+            self.config.job_context_hash(),
+            self.config.job_data_hash(),
+            self.config.job_duration_hash(),
+            self.config.job_error_hash(),
+            self.config.job_expiry_hash(),
+            self.config.job_fetched_hash(),
+            self.config.job_group_hash(),
+            self.config.job_priority_hash(),
+            self.config.job_progress_hash(),
+            self.config.job_result_hash(),
+            self.config.retry_budget_set(),
+            self.config.scheduled_jobs_set(),
+            self.config.signal_list(),
+            self.config.unique_jobs_hash(),
+        ]
+        .into_iter()
+        .chain(consumers)
+        .collect();
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.cmd("TYPE").arg(key);
+        }
+        let types: Vec<String> = pipe.query_async(&mut conn).await?;
+        Ok(keys
+            .into_iter()
+            .zip(types)
+            .filter(|(_, kind)| kind != "none")
+            .collect())
+    }
+
+    /// A stream of jobs that were moved to the dead set, either because they exhausted their
+    /// retries, were aborted or expired.
+    ///
+    /// Dead jobs are polled every `poll_interval` and yielded with their final attempt count in
+    /// [Parts::attempt]. Each dead job is delivered to a single stream, so run one subscriber per
+    /// queue. Jobs killed while no stream is consumed are kept until the next subscriber polls, up to
+    /// [Config::set_stream_backlog].
+    pub fn dead_letter_stream(
+        &self,
+    ) -> impl Stream<Item = Result<Request<T, RedisContext>, RedisStorageError>> + Send + 'static
+    where
+        T: DeserializeOwned + Send + 'static,
+        Conn: Clone,
+    {
+        let conn = self.conn.clone();
+        let multiplexed = self.multiplexed;
+        let dead_letter_list = self.config.dead_letter_list();
+        let job_data_hash = self.config.job_data_hash();
+        let buffer_size = self.config.buffer_size;
+        apalis_core::interval::interval(self.config.poll_interval)
+            .then(move |_| {
+                let mut conn = conn.clone();
+                let dead_letter_list = dead_letter_list.clone();
+                let job_data_hash = job_data_hash.clone();
+                async move {
+                    let ids = pop_ids(&mut conn, &dead_letter_list, buffer_size).await?;
+                    if ids.is_empty() {
+                        return Ok(Vec::new());
+                    }
+                    let data: Vec<Option<Vec<u8>>> = redis::cmd("HMGET")
+                        .arg(job_data_hash)
+                        .arg(&ids)
+                        .query_async(&mut conn)
+                        .await?;
+                    data.into_iter()
+                        .flatten()
+                        .map(|bytes| decode_job::<T, C>(multiplexed, bytes))
+                        .collect::<Result<Vec<_>, _>>()
+                }
+            })
+            .flat_map(|res| {
+                futures::stream::iter(match res {
+                    Ok(jobs) => jobs.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                })
+            })
+    }
+
+    /// A stream of the ids of jobs that completed successfully, to chain follow-up jobs with
+    /// [Storage::fetch_by_id].
+    ///
+    /// Completed jobs are polled every `poll_interval`. Each id is delivered to a single stream,
+    /// so run one subscriber per queue. Ids of jobs completed while no stream is consumed are kept
+    /// up to [Config::set_stream_backlog]. The stream pauses and ends with the poller of this
+    /// storage.
+    pub fn completed_stream(&self) -> impl Stream<Item = TaskId> + Send + 'static
+    where
+        Conn: Clone,
+    {
+        let conn = self.conn.clone();
+        let completed_jobs_list = self.config.completed_jobs_list();
+        let buffer_size = self.config.buffer_size;
+        let encoding = self.config.task_id_encoding;
+        let stream = apalis_core::interval::interval(self.config.poll_interval)
+            .then(move |_| {
+                let mut conn = conn.clone();
+                let completed_jobs_list = completed_jobs_list.clone();
+                async move {
+                    let ids = match pop_ids(&mut conn, &completed_jobs_list, buffer_size).await {
+                        Ok(ids) => ids,
+                        Err(e) => {
+                            error!("Failed to poll completed jobs: {e}");
+                            Vec::new()
+                        }
+                    };
+                    ids.into_iter()
+                        .filter_map(|id| encoding.decode(&id).ok())
+                        .collect::<Vec<_>>()
+                }
+            })
+            .flat_map(futures::stream::iter)
+            .boxed();
+        BackendStream::new(stream, self.controller.clone())
+    }
+
+    /// Report the progress of a job as a percentage, values above 100 are clamped to 100.
+    ///
+    /// The progress is kept next to the job data so the job does not have to be re-encoded, it is
+    /// surfaced through [RedisContext::progress] when the job is fetched by id.
+    pub async fn set_progress(
+        &mut self,
+        task_id: &TaskId,
+        pct: u8,
+    ) -> Result<(), RedisStorageError> {
+        redis::cmd("HSET")
+            .arg(self.config.job_progress_hash())
+            .arg(self.config.task_key(task_id))
+            .arg(pct.min(100))
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Replace the context of a job without re-encoding its data.
+    ///
+    /// The context is encoded with the storage's codec and kept next to the job data, so updating
+    /// metadata such as the priority or max attempts of a large job only writes the context. It
+    /// replaces the context stored with the job whenever the job is fetched, listed or fetched by
+    /// id, until [Storage::update] writes the whole job again. Returns `false` without storing
+    /// anything if the job does not exist.
+    pub async fn update_context(
+        &mut self,
+        task_id: &TaskId,
+        mut context: RedisContext,
+    ) -> Result<bool, RedisStorageError> {
+        self.config.apply_context_profile(&mut context);
+        let context = C::encode(&context).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        let update_context = self.scripts.update_context.clone();
+        let stored: i64 = update_context
+            .key(self.config.job_data_hash())
+            .key(self.config.job_context_hash())
+            .arg(self.config.task_key(task_id))
+            .arg(context)
+            .invoke_async(&mut self.conn)
+            .await?;
+        Ok(stored == 1)
+    }
+
+    /// Set the result of a running job, encoded with the storage's codec.
+    ///
+    /// The result is kept when the job completes instead of the value returned by its handler, and
+    /// dropped if the attempt fails. See [RedisStorage::get_result] to read it.
+    pub async fn set_result<R: Serialize>(
+        &mut self,
+        task_id: &TaskId,
+        result: &R,
+    ) -> Result<(), RedisStorageError> {
+        let result = C::encode(result).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        redis::cmd("HSET")
+            .arg(self.config.job_result_hash())
+            .arg(self.config.task_key(task_id))
+            .arg(result)
+            .query_async::<()>(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Get the encoded result of a job, decode it with the storage's codec.
+    ///
+    /// A completed job's result is the value returned by its handler, unless one was set with
+    /// [RedisStorage::set_result]. Results are kept until the job is removed by
+    /// [RedisStorage::trim_done] or a vacuum, `None` is returned for jobs without one.
+    pub async fn get_result(&self, task_id: &TaskId) -> Result<Option<Vec<u8>>, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        redis::cmd("HGET")
+            .arg(self.config.job_result_hash())
+            .arg(self.config.task_key(task_id))
+            .query_async(&mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Count the jobs in each state.
+    ///
+    /// The counts are read in a single pipeline, followed by one more pipeline counting the
+    /// inflight jobs of each registered worker.
+    pub async fn queue_stats(&self) -> Result<QueueStats, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        let (pending, scheduled, done, failed, dead, consumers): (
+            usize,
+            usize,
+            usize,
+            usize,
+            usize,
+            Vec<String>,
+        ) = redis::pipe()
+            .zcard(self.config.active_jobs_list())
+            .zcard(self.config.scheduled_jobs_set())
+            .zcard(self.config.done_jobs_set())
+            .zcard(self.config.failed_jobs_set())
+            .zcard(self.config.dead_jobs_set())
+            .zrange(self.config.consumers_set(), 0, -1)
+            .query_async(&mut conn)
+            .await?;
+        let inflight = if consumers.is_empty() {
+            0
+        } else {
+            let mut pipe = redis::pipe();
+            for inflight_set in &consumers {
+                pipe.scard(inflight_set);
+            }
+            let counts: Vec<usize> = pipe.query_async(&mut conn).await?;
+            counts.into_iter().sum()
+        };
+        Ok(QueueStats {
+            pending,
+            scheduled,
+            inflight,
+            done,
+            failed,
+            dead,
+        })
+    }
+
+    /// Check that Redis can be reached by sending a `PING`.
+    ///
+    /// This is meant for readiness probes, see [RedisStorage::load_scripts] to also check the Lua
+    /// scripts of the storage.
+    pub async fn check_health(&self) -> Result<(), RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        let reply: String = redis::cmd("PING").query_async(&mut conn).await?;
+        if reply != "PONG" {
+            return Err(RedisStorageError::Codec(
+                format!("unexpected reply to PING: `{reply}`").into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Load the Lua scripts of the storage into Redis and check that each one is accepted.
+    ///
+    /// Scripts are otherwise loaded the first time they are run, calling this when a service
+    /// starts reports scripts that Redis rejects, such as when scripting is disabled, before any
+    /// job is pushed or fetched.
+    pub async fn load_scripts(&self) -> Result<(), RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        let scripts = self.scripts.all();
+        let mut pipe = redis::pipe();
+        for script in &scripts {
+            pipe.cmd("SCRIPT").arg("LOAD").arg(script.code());
+        }
+        let hashes: Vec<String> = pipe.query_async(&mut conn).await?;
+        for (script, hash) in scripts.iter().zip(hashes) {
+            if hash != script.hash() {
+                return Err(RedisStorageError::Codec(
+                    format!("script `{}` was loaded as `{hash}`", script.name()).into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// List the registered workers with their last keep-alive and inflight job count.
+    ///
+    /// The consumers set is only read, workers that stopped sending keep-alives are listed until
+    /// their jobs are reenqueued as orphaned.
+    pub async fn list_workers(&self) -> Result<Vec<WorkerInfo>, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        let consumers: Vec<(String, i64)> = redis::cmd("ZRANGE")
+            .arg(self.config.consumers_set())
+            .arg(0)
+            .arg(-1)
+            .arg("WITHSCORES")
+            .query_async(&mut conn)
+            .await?;
+        if consumers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for (inflight_set, _) in &consumers {
+            pipe.scard(inflight_set);
+        }
+        let counts: Vec<usize> = pipe.query_async(&mut conn).await?;
+        let prefix = format!("{}:", self.config.inflight_jobs_set());
+        Ok(consumers
+            .into_iter()
+            .zip(counts)
+            .map(|((inflight_set, last_seen), inflight_count)| WorkerInfo {
+                id: WorkerId::new(inflight_set.strip_prefix(&prefix).unwrap_or(&inflight_set)),
+                last_seen,
+                inflight_count,
+            })
+            .collect())
+    }
+
+    /// Push a job with the given priority.
+    ///
+    /// Jobs with a higher priority are fetched first, jobs with equal priority are fetched in the
+    /// order they were created.
+    pub async fn push_with_priority(
+        &mut self,
+        job: T,
+        priority: i64,
+    ) -> Result<Parts<RedisContext>, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.priority = priority;
+        self.push_request(req).await
+    }
+
+    /// Push a job whose handler fails an attempt once it runs longer than `timeout`, see
+    /// [RedisContext::set_timeout]
+    pub async fn push_with_timeout(
+        &mut self,
+        job: T,
+        timeout: Duration,
+    ) -> Result<Parts<RedisContext>, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.timeout = Some(timeout);
+        self.push_request(req).await
+    }
+
+    /// Push a job that is retried until it has been attempted `max_retries` times, instead of
+    /// [Config::set_max_attempts].
+    pub async fn push_with_retries(
+        &mut self,
+        job: T,
+        max_retries: usize,
+    ) -> Result<Parts<RedisContext>, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.max_attempts = Some(max_retries);
+        self.push_request(req).await
+    }
+
+    /// Push a job with the given context, as if it had already been attempted `attempts` times.
+    ///
+    /// This keeps the attempts of jobs migrated from another system or replayed from the dead
+    /// jobs set, a job pushed with one attempt less than its maximum is tried exactly once more.
+    /// Returns [RedisStorageError::TooManyAttempts] if `attempts` exceeds the maximum attempts of
+    /// the job.
+    pub async fn push_with_context(
+        &mut self,
+        job: T,
+        ctx: RedisContext,
+        attempts: usize,
+    ) -> Result<Parts<RedisContext>, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let max_attempts = self.config.max_attempts_of(&ctx);
+        if attempts > max_attempts {
+            return Err(RedisStorageError::TooManyAttempts {
+                attempts,
+                max_attempts,
+            });
+        }
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context = ctx;
+        req.parts.attempt = Attempt::new_with_value(attempts);
+        self.push_request(req).await
+    }
+
+    /// Encode a job the way it is pushed, returning its id and the payload to store under that id
+    /// in [Config::job_data_hash].
+    ///
+    /// This is meant for producers in other languages, for example to generate fixtures they
+    /// can be checked against, see [PUSH_JOB_SCRIPT] for the keys and arguments to push it with.
+    pub fn encode_external(&self, job: T) -> Result<(TaskId, Vec<u8>), RedisStorageError>
+    where
+        T: Serialize,
+    {
+        let mut req = self.config.new_request(job);
+        self.config.apply_job_type::<T>(&mut req.parts.context);
+        self.config.apply_default_ttl(&mut req.parts.context);
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = C::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        self.config.check_payload_size(&job)?;
+        Ok((req.parts.task_id, job))
+    }
+
+    /// Schedule a job with the given priority.
+    ///
+    /// The priority applies once the job is moved to the active set.
+    pub async fn schedule_with_priority(
+        &mut self,
+        job: T,
+        on: i64,
+        priority: i64,
+    ) -> Result<Parts<RedisContext>, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.priority = priority;
+        self.schedule_request(req, on).await
+    }
+
+    /// Schedule a job to run after `delay` from now.
+    ///
+    /// Use [Storage::schedule] instead when the job has a wall-clock target.
+    pub async fn schedule_in(
+        &mut self,
+        job: T,
+        delay: Duration,
+    ) -> Result<TaskId, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let delay = chrono::Duration::from_std(delay)
+            .map_err(|e| RedisStorageError::InvalidDuration(e.into()))?;
+        let on = self
+            .config
+            .clock
+            .now()
+            .checked_add_signed(delay)
+            .ok_or_else(|| RedisStorageError::InvalidDuration("delay is out of range".into()))?;
+        let parts = self.schedule(job, on.timestamp()).await?;
+        Ok(parts.task_id)
+    }
+
+    /// Schedule a recurring job from a cron expression.
+    ///
+    /// The expression is parsed with the [cron] crate, it starts with a seconds field such as
+    /// `0 */5 * * * *`. The job is scheduled for the next occurrence, and once it is done,
+    /// failed, killed or expired a new job is scheduled for the occurrence after. Invalid
+    /// expressions are rejected before anything is written to Redis.
+    pub async fn schedule_cron(&mut self, job: T, expr: &str) -> Result<TaskId, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let next = next_occurrence(expr)?.ok_or_else(|| {
+            RedisStorageError::InvalidCron(format!("`{expr}` has no upcoming occurrence").into())
+        })?;
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.cron = Some(expr.to_owned());
+        let parts = self.schedule_request(req, next.timestamp()).await?;
+        Ok(parts.task_id)
+    }
+
+    /// Schedule the occurrence after a finished recurring job as a new job
+    async fn schedule_next_occurrence(
+        &mut self,
+        job: Request<T, RedisContext>,
+    ) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let ctx = job.parts.context;
+        let Some(next) = ctx
+            .cron
+            .as_deref()
+            .map(next_occurrence)
+            .transpose()?
+            .flatten()
+        else {
+            return Ok(());
+        };
+        let mut req: Request<T, RedisContext> = self.config.new_request(job.args);
+        req.parts.context = RedisContext {
+            max_attempts: ctx.max_attempts,
+            priority: ctx.priority,
+            job_type: ctx.job_type,
+            cron: ctx.cron,
+            ..Default::default()
+        };
+        self.schedule_request(req, next.timestamp()).await?;
+        Ok(())
+    }
+
+    /// Follow up on a job that reached a terminal state, done, failed, killed or expired, by
+    /// scheduling the next occurrence of a recurring job.
+    ///
+    /// Every terminal transition ends here once its script moved the job, so that no path drops
+    /// a recurring job. `ctx` is the context the job was fetched with, the job is only read back
+    /// when it is recurring or its context is unknown.
+    async fn finish_job(
+        &mut self,
+        task_id: &TaskId,
+        ctx: Option<&RedisContext>,
+    ) -> Result<(), RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        if ctx.is_some_and(|ctx| ctx.cron.is_none()) {
+            return Ok(());
+        }
+        match self.fetch_by_id(task_id).await? {
+            Some(job) => self.schedule_next_occurrence(job).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Push a job that is dropped into the dead set if it is not fetched within `ttl`.
+    pub async fn push_with_ttl(
+        &mut self,
+        job: T,
+        ttl: Duration,
+    ) -> Result<Parts<RedisContext>, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned + Send + Unpin + Sync + 'static,
+    {
+        let ttl: i64 = ttl
+            .as_secs()
+            .try_into()
+            .map_err(|e: TryFromIntError| RedisStorageError::InvalidDuration(e.into()))?;
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.expires_at = Some(self.config.clock.now().timestamp() + ttl);
+        self.push_request(req).await
+    }
+
+    /// Push a job, reporting whether it was newly enqueued.
+    ///
+    /// A job is not enqueued twice if a [TaskIdFactory] returns the id of a job that is still
+    /// stored, in which case [PushOutcome::was_new] is `false`.
+    pub async fn push_with_outcome(&mut self, job: T) -> Result<PushOutcome, RedisStorageError>
+    where
+        T: Serialize,
+    {
+        let req = self.config.new_request(job);
+        let (parts, was_new) = self.push_job(req).await?;
+        Ok(PushOutcome {
+            task_id: parts.task_id,
+            was_new,
+        })
+    }
+
+    /// Push a job and wait until it is done, returning its encoded result.
+    ///
+    /// The done, failed and dead jobs sets are checked every `poll_interval`. The result is the
+    /// value returned by the handler or set with [RedisStorage::set_result], decode it with the
+    /// storage's codec, and `None` for a job done without one. A job that is acknowledged as
+    /// failed or killed returns [RedisStorageError::JobFailed] with the error of its last attempt,
+    /// while a job that is retried is waited on. If the job is not done within `timeout`,
+    /// [RedisStorageError::Timeout] is returned with its id and the job stays queued.
+    pub async fn push_and_wait(
+        &mut self,
+        job: T,
+        timeout: Duration,
+    ) -> Result<Option<Vec<u8>>, RedisStorageError>
+    where
+        T: Serialize,
+        Conn: Clone,
+    {
+        let deadline = Instant::now() + timeout;
+        let req = self.config.new_request(job);
+        let (parts, _) = self.push_job(req).await?;
+        let task_key = self.config.task_key(&parts.task_id);
+        loop {
+            let (done, failed, dead): (Option<f64>, Option<f64>, Option<f64>) = redis::pipe()
+                .zscore(self.config.done_jobs_set(), &task_key)
+                .zscore(self.config.failed_jobs_set(), &task_key)
+                .zscore(self.config.dead_jobs_set(), &task_key)
+                .query_async(&mut self.conn)
+                .await?;
+            if done.is_some() {
+                return self.get_result(&parts.task_id).await;
+            }
+            if failed.is_some() || dead.is_some() {
+                let (dead_reason, error): (Option<String>, Option<String>) = redis::pipe()
+                    .hget(self.config.dead_reason_hash(), &task_key)
+                    .hget(self.config.job_error_hash(), &task_key)
+                    .query_async(&mut self.conn)
+                    .await?;
+                return Err(RedisStorageError::JobFailed {
+                    task_id: parts.task_id,
+                    dead_reason: dead_reason
+                        .filter(|_| dead.is_some())
+                        .and_then(|reason| DeadReason::parse(&reason)),
+                    error,
+                });
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(RedisStorageError::Timeout(parts.task_id));
+            }
+            apalis_core::sleep(self.config.poll_interval.min(left)).await;
+        }
+    }
+
+    async fn push_job(
+        &mut self,
+        mut req: Request<T, RedisContext>,
+    ) -> Result<(Parts<RedisContext>, bool), RedisStorageError>
+    where
+        T: Serialize,
+    {
+        let push_job = self.scripts.push_job.clone();
+        let job_data_hash = self.config.job_data_hash();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+
+        let job_priority_hash = self.config.job_priority_hash();
+        let job_expiry_hash = self.config.job_expiry_hash();
+
+        self.config.apply_job_type::<T>(&mut req.parts.context);
+        self.config.apply_default_ttl(&mut req.parts.context);
+        Metadata::apply(&mut req.parts);
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = C::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        self.config.check_payload_size(&job)?;
+        let ScriptStatus(status) = push_job
+            .key(job_data_hash)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .key(job_expiry_hash)
+            .arg(self.config.task_key(&req.parts.task_id))
+            .arg(job)
+            .arg(req.parts.context.priority)
+            .arg(req.parts.context.expires_at.unwrap_or(0))
+            .invoke_async(&mut self.conn)
+            .await?;
+        let was_new = match status {
+            Ok(()) => true,
+            Err(ScriptError::Conflict) => false,
+            Err(e) => return Err(e.into()),
+        };
+        Ok((req.parts, was_new))
+    }
+
+    /// Push a job unless another job with the same unique key is still pending.
+    ///
+    /// If the push was skipped, the outcome holds the [TaskId] of the existing job and
+    /// [PushOutcome::was_new] is `false`. The key is released once that job is done, failed or
+    /// killed.
+    pub async fn push_unique(
+        &mut self,
+        job: T,
+        unique_key: &str,
+    ) -> Result<PushOutcome, RedisStorageError>
+    where
+        T: Serialize,
+    {
+        let push_unique_job = self.scripts.push_unique_job.clone();
+        let mut req: Request<T, RedisContext> = self.config.new_request(job);
+        req.parts.context.unique_key = Some(unique_key.to_owned());
+        self.config.apply_job_type::<T>(&mut req.parts.context);
+        self.config.apply_default_ttl(&mut req.parts.context);
+        self.config.apply_context_profile(&mut req.parts.context);
+        let job = C::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+        self.config.check_payload_size(&job)?;
+        let task_id: String = push_unique_job
+            .key(self.config.job_data_hash())
+            .key(self.config.active_jobs_list())
+            .key(self.config.signal_list())
+            .key(self.config.job_priority_hash())
+            .key(self.config.job_expiry_hash())
+            .key(self.config.unique_jobs_hash())
+            .key(self.config.done_jobs_set())
+            .key(self.config.dead_jobs_set())
+            .key(self.config.failed_jobs_set())
+            .arg(self.config.task_key(&req.parts.task_id))
+            .arg(job)
+            .arg(req.parts.context.priority)
+            .arg(req.parts.context.expires_at.unwrap_or(0))
+            .arg(unique_key)
+            .invoke_async(&mut self.conn)
+            .await?;
+        let task_id = self.config.task_id_encoding.decode(&task_id)?;
+        Ok(PushOutcome {
+            was_new: task_id == req.parts.task_id,
+            task_id,
+        })
+    }
+
+    /// Push multiple jobs to the active set in a single round trip.
+    ///
+    /// The returned [TaskId]s are in the same order as the provided jobs.
+    pub async fn push_batch(&mut self, jobs: Vec<T>) -> Result<Vec<TaskId>, RedisStorageError>
+    where
+        T: Serialize,
+    {
+        self.push_jobs(jobs, None).await
+    }
+
+    /// Push multiple jobs as members of `group_id` in a single round trip.
+    ///
+    /// Each job finishing is counted in the group, see [RedisStorage::group_status], so a
+    /// finalizer can run once all of them completed. Pushing to a group again adds to its jobs.
+    /// The returned [TaskId]s are in the same order as the provided jobs.
+    pub async fn push_group(
+        &mut self,
+        jobs: Vec<T>,
+        group_id: &str,
+    ) -> Result<Vec<TaskId>, RedisStorageError>
+    where
+        T: Serialize,
+    {
+        self.push_jobs(jobs, Some(group_id)).await
+    }
+
+    /// Get how many jobs of `group_id` were pushed and how many of them finished.
+    ///
+    /// Jobs count as finished once they are done, killed, cancelled, expired or acknowledged as
+    /// failed, retries are not counted. A job replayed from the dead set stays counted as failed.
+    pub async fn group_status(&self, group_id: &str) -> Result<GroupStatus, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        let (total, done, failed): (Option<usize>, Option<usize>, Option<usize>) =
+            redis::cmd("HMGET")
+                .arg(self.config.group_stats_hash())
+                .arg(format!("{group_id}:total"))
+                .arg(format!("{group_id}:done"))
+                .arg(format!("{group_id}:failed"))
+                .query_async(&mut conn)
+                .await?;
+        Ok(GroupStatus {
+            total: total.unwrap_or_default(),
+            done: done.unwrap_or_default(),
+            failed: failed.unwrap_or_default(),
+        })
+    }
+
+    /// Delete the counters of `group_id`, returning whether the group had any.
+    ///
+    /// Call this once the group is complete and its status is no longer needed, the counters are
+    /// otherwise kept. Jobs of the group that are still pending are counted from zero again.
+    pub async fn clear_group(&mut self, group_id: &str) -> Result<bool, RedisStorageError> {
+        let removed: usize = redis::cmd("HDEL")
+            .arg(self.config.group_stats_hash())
+            .arg(format!("{group_id}:total"))
+            .arg(format!("{group_id}:done"))
+            .arg(format!("{group_id}:failed"))
+            .query_async(&mut self.conn)
+            .await?;
+        Ok(removed > 0)
+    }
+
+    async fn push_jobs(
+        &mut self,
+        jobs: Vec<T>,
+        group_id: Option<&str>,
+    ) -> Result<Vec<TaskId>, RedisStorageError>
+    where
+        T: Serialize,
+    {
+        if jobs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let push_jobs = self.scripts.push_jobs.clone();
+        let job_data_hash = self.config.job_data_hash();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+        let job_priority_hash = self.config.job_priority_hash();
+        let job_expiry_hash = self.config.job_expiry_hash();
+
+        let mut invocation = push_jobs.key(job_data_hash);
+        invocation
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .key(job_expiry_hash)
+            .key(self.config.job_group_hash())
+            .key(self.config.group_stats_hash());
+        let mut task_ids = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            let mut req: Request<T, RedisContext> = self.config.new_request(job);
+            req.parts.context.group_id = group_id.map(str::to_owned);
+            self.config.apply_job_type::<T>(&mut req.parts.context);
+            self.config.apply_default_ttl(&mut req.parts.context);
+            self.config.apply_context_profile(&mut req.parts.context);
+            let job = C::encode(&req).map_err(|e| RedisStorageError::Encode(e.into()))?;
+            self.config.check_payload_size(&job)?;
+            invocation
+                .arg(self.config.task_key(&req.parts.task_id))
+                .arg(job)
+                .arg(req.parts.context.priority)
+                .arg(req.parts.context.expires_at.unwrap_or(0))
+                .arg(group_id.unwrap_or_default());
+            task_ids.push(req.parts.task_id);
+        }
+        invocation.invoke_async::<usize>(&mut self.conn).await?;
+        Ok(task_ids)
+    }
+
+    /// Required to add scheduled jobs to the active set
+    pub async fn enqueue_scheduled(&mut self, count: usize) -> Result<usize, RedisStorageError> {
+        let enqueue_jobs = self.scripts.enqueue_scheduled.clone();
+        let scheduled_jobs_set = self.config.scheduled_jobs_set();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+        let job_priority_hash = self.config.job_priority_hash();
+        let now: i64 = self.config.clock.now().timestamp();
+        let res: Result<usize, _> = enqueue_jobs
+            .key(scheduled_jobs_set)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .arg(now)
+            .arg(count)
+            .invoke_async(&mut self.conn)
+            .await;
+        match res {
+            Ok(count) => Ok(count),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Move every scheduled job that is due to the active set now, returning how many were moved
+    ///
+    /// Jobs are moved `buffer_size` at a time like [RedisStorage::enqueue_scheduled], in as many
+    /// rounds as it takes, so tests and cold starts do not wait for the heartbeat to catch up. The
+    /// rounds are bounded by the jobs scheduled when the drain starts, so it returns even if the
+    /// clock or other producers keep jobs coming due.
+    pub async fn drain_scheduled(&mut self) -> Result<usize, RedisStorageError> {
+        let batch = self.config.buffer_size.max(1);
+        let scheduled: usize = redis::cmd("ZCARD")
+            .arg(self.config.scheduled_jobs_set())
+            .query_async(&mut self.conn)
+            .await?;
+        let mut total = 0;
+        for _ in 0..=scheduled / batch {
+            let moved = self.enqueue_scheduled(batch).await?;
+            total += moved;
+            if moved < batch {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Count the jobs waiting in the active set, the same as [Storage::len]
+    ///
+    /// Like the other counts this is a single `ZCARD` of the sorted set holding the jobs of the
+    /// state, which Redis keeps in constant time, so no separate counter can drift from the sets.
+    pub async fn pending_count(&self) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        self.count_set(self.config.active_jobs_list()).await
+    }
+
+    /// Count the jobs waiting in the scheduled set, whether they are due or not
+    pub async fn scheduled_count(&self) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        self.count_set(self.config.scheduled_jobs_set()).await
+    }
+
+    /// Count the jobs in the done set
+    pub async fn done_count(&self) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        self.count_set(self.config.done_jobs_set()).await
+    }
+
+    /// Count the jobs in the failed set
+    pub async fn failed_count(&self) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        self.count_set(self.config.failed_jobs_set()).await
+    }
+
+    /// Count the jobs in the dead set
+    pub async fn dead_count(&self) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        self.count_set(self.config.dead_jobs_set()).await
+    }
+
+    async fn count_set(&self, key: String) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        redis::cmd("ZCARD")
+            .arg(key)
+            .query_async(&mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Count the scheduled jobs that are due, without moving them
+    ///
+    /// This is the number of jobs the next [RedisStorage::enqueue_scheduled] would move if its
+    /// `count` is large enough, and a measure of the scheduling backlog.
+    pub async fn count_scheduled_due(&self) -> Result<usize, RedisStorageError>
+    where
+        Conn: Clone,
+    {
+        let mut conn = self.conn.clone();
+        redis::cmd("ZCOUNT")
+            .arg(self.config.scheduled_jobs_set())
+            .arg("-inf")
+            .arg(self.config.clock.now().timestamp())
+            .query_async(&mut conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Re-enqueue some jobs that might be abandoned.
+    pub async fn reenqueue_active(
+        &mut self,
+        job_ids: Vec<&TaskId>,
+    ) -> Result<(), RedisStorageError> {
+        let reenqueue_active = self.scripts.reenqueue_active.clone();
+        let inflight_set: String = self.config.inflight_jobs_set().to_string();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+        let job_priority_hash = self.config.job_priority_hash();
+
+        reenqueue_active
+            .key(inflight_set)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .arg(
+                job_ids
+                    .iter()
+                    .map(|j| self.config.task_key(j))
+                    .collect::<Vec<String>>(),
+            )
+            .invoke_async::<()>(&mut self.conn)
+            .await?;
+        for task_id in job_ids {
+            self.listener.on_reenqueued(task_id);
+        }
+        Ok(())
+    }
+
+    /// Move a job held by `worker_id` back to the active set, without counting an attempt.
+    ///
+    /// Use this when a job hits a transient condition, such as a dependency that is down, and
+    /// should be fetched again right away. Unlike [RedisStorage::retry] the job is not delayed and
+    /// its attempts are left as they were stored when it was fetched. Returns whether the job was
+    /// inflight with `worker_id` and requeued.
+    pub async fn requeue(
+        &mut self,
+        worker_id: &WorkerId,
+        task_id: &TaskId,
+    ) -> Result<bool, RedisStorageError> {
+        let reenqueue_active = self.scripts.reenqueue_active.clone();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let requeued: usize = reenqueue_active
+            .key(inflight_set)
+            .key(self.config.active_jobs_list())
+            .key(self.config.signal_list())
+            .key(self.config.job_priority_hash())
+            .arg(self.config.task_key(task_id))
+            .invoke_async(&mut self.conn)
+            .await?;
+        if requeued > 0 {
+            self.listener.on_reenqueued(task_id);
+        }
+        Ok(requeued > 0)
+    }
+
+    /// Return all jobs held by a worker to the active set so other workers can pick them up.
+    ///
+    /// Call this when a worker is stopping instead of waiting for its jobs to be re-enqueued as
+    /// orphaned. Acks buffered by the poller are flushed first so that completed jobs are not
+    /// re-enqueued. Returns the number of jobs that were re-enqueued.
+    pub async fn shutdown(&mut self, worker_id: &WorkerId) -> Result<usize, RedisStorageError>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        self.flush_acks().await?;
+        let reenqueue_active = self.scripts.reenqueue_active.clone();
+        let inflight_set = format!("{}:{}", self.config.inflight_jobs_set(), worker_id);
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+        let job_priority_hash = self.config.job_priority_hash();
+
+        let job_ids: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(&inflight_set)
+            .query_async(&mut self.conn)
+            .await?;
+        if job_ids.is_empty() {
+            return Ok(0);
+        }
+        let reenqueued = reenqueue_active
+            .key(inflight_set)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .arg(&job_ids)
+            .invoke_async(&mut self.conn)
+            .await?;
+        for task_id in &job_ids {
+            if let Ok(task_id) = self.config.task_id_encoding.decode(task_id) {
+                self.listener.on_reenqueued(&task_id);
+            }
+        }
+        Ok(reenqueued)
+    }
+
+    /// Re-enqueue some jobs that might be orphaned after a number of seconds
+    ///
+    /// Returns the number of reenqueued jobs, at most `count`. See
+    /// [RedisStorage::reenqueue_orphaned_jobs] for the jobs themselves.
+    pub async fn reenqueue_orphaned(
+        &mut self,
+        count: i32,
+        dead_since: DateTime<Utc>,
+    ) -> Result<usize, RedisStorageError> {
+        self.reenqueue_orphaned_jobs(count, dead_since)
+            .await
+            .map(|orphaned| orphaned.len())
+    }
+
+    /// Re-enqueue the inflight jobs of workers that have not sent a keep-alive since `dead_since`.
+    ///
+    /// Returns the reenqueued jobs along with the worker that held each of them, at most `count`.
+    pub async fn reenqueue_orphaned_jobs(
+        &mut self,
+        count: i32,
+        dead_since: DateTime<Utc>,
+    ) -> Result<Vec<OrphanedJob>, RedisStorageError> {
+        let reenqueue_orphaned = self.scripts.reenqueue_orphaned.clone();
+        let consumers_set = self.config.consumers_set();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+        let job_priority_hash = self.config.job_priority_hash();
+
+        let dead_since = dead_since.timestamp();
+
+        let orphaned: Vec<(String, String)> = reenqueue_orphaned
+            .key(consumers_set)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .arg(dead_since)
+            .arg(count)
+            .invoke_async(&mut self.conn)
+            .await?;
+        let prefix = format!("{}:", self.config.inflight_jobs_set());
+        let orphaned = orphaned
+            .into_iter()
+            .map(|(inflight_set, task_id)| {
+                Ok(OrphanedJob {
+                    task_id: self.config.task_id_encoding.decode(&task_id)?,
+                    worker_id: WorkerId::new(
+                        inflight_set.strip_prefix(&prefix).unwrap_or(&inflight_set),
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>, RedisStorageError>>()?;
+        for job in &orphaned {
+            self.listener.on_reenqueued(&job.task_id);
+        }
+        Ok(orphaned)
+    }
+
+    /// Run one pass of the scheduling done by a polling worker, without a worker
+    ///
+    /// This enqueues up to `buffer_size` due scheduled jobs and reenqueues up to ten times as many
+    /// jobs orphaned for longer than [Config::set_reenqueue_orphaned_after], for deployments such
+    /// as cron triggered serverless functions that cannot keep a worker's heartbeat running.
+    pub async fn tick(&mut self) -> Result<TickReport, RedisStorageError> {
+        let scheduled = self.enqueue_scheduled(self.config.buffer_size).await?;
+        let dead_since = self.config.clock.now()
+            - chrono::Duration::from_std(self.config.reenqueue_orphaned_after)
+                .map_err(|e| RedisStorageError::InvalidDuration(e.into()))?;
+        let orphaned = self
+            .reenqueue_orphaned_jobs((self.config.buffer_size * 10) as i32, dead_since)
+            .await?;
+        let report = TickReport {
+            scheduled,
+            orphaned: orphaned.len(),
+        };
+        self.config.notify_orphaned(orphaned);
+        Ok(report)
+    }
+
+    /// Remove jobs that have been done for longer than `retention` from the done set, along with
+    /// their data and results
+    pub async fn trim_done(&mut self, retention: Duration) -> Result<usize, RedisStorageError> {
+        let trim_done = self.scripts.trim_done.clone();
+        let done_before = self.config.clock.now()
+            - chrono::Duration::from_std(retention)
+                .map_err(|e| RedisStorageError::InvalidDuration(e.into()))?;
+        trim_done
+            .key(self.config.done_jobs_set())
+            .key(self.config.job_data_hash())
+            .key(self.config.job_priority_hash())
+            .key(self.config.job_expiry_hash())
+            .key(self.config.job_fetched_hash())
+            .key(self.config.job_progress_hash())
+            .key(self.config.job_error_hash())
+            .key(self.config.job_duration_hash())
+            .key(self.config.job_result_hash())
+            .key(self.config.done_details_set())
+            .key(self.config.job_context_hash())
+            .arg(done_before.timestamp())
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Cancel a pending job, removing it from the active or scheduled set along with its data.
+    ///
+    /// Returns whether the job was removed. Jobs that are already inflight are not affected.
+    pub async fn cancel(&mut self, task_id: &TaskId) -> Result<bool, RedisStorageError> {
+        let cancel_job = self.scripts.cancel_job.clone();
+        cancel_job
+            .key(self.config.active_jobs_list())
+            .key(self.config.scheduled_jobs_set())
+            .key(self.config.job_data_hash())
+            .key(self.config.job_priority_hash())
+            .key(self.config.job_expiry_hash())
+            .key(self.config.job_fetched_hash())
+            .key(self.config.job_progress_hash())
+            .key(self.config.job_error_hash())
+            .key(self.config.job_duration_hash())
+            .key(self.config.job_result_hash())
+            .key(self.config.job_group_hash())
+            .key(self.config.group_stats_hash())
+            .key(self.config.job_context_hash())
+            .arg(self.config.task_key(task_id))
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Reenqueue jobs that have been inflight since before `stale_since`, even if their worker is alive
+    pub async fn reenqueue_stale(
+        &mut self,
+        count: i32,
+        stale_since: DateTime<Utc>,
+    ) -> Result<usize, RedisStorageError> {
+        let reenqueue_stale = self.scripts.reenqueue_stale.clone();
+        let consumers_set = self.config.consumers_set();
+        let active_jobs_list = self.config.active_jobs_list();
+        let signal_list = self.config.signal_list();
+        let job_priority_hash = self.config.job_priority_hash();
+        let job_fetched_hash = self.config.job_fetched_hash();
+
+        reenqueue_stale
+            .key(consumers_set)
+            .key(active_jobs_list)
+            .key(signal_list)
+            .key(job_priority_hash)
+            .key(job_fetched_hash)
+            .arg(stale_since.timestamp_millis())
+            .arg(count)
+            .invoke_async(&mut self.conn)
+            .await
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apalis_core::layers::{Layer, Service};
+    use apalis_core::worker::Context;
+    use apalis_core::{generic_storage_test, sleep};
+    use email_service::Email;
+
+    use apalis_core::test_utils::apalis_test_service_fn;
+    use apalis_core::test_utils::TestWrapper;
+
+    generic_storage_test!(setup);
+
+    use super::*;
+    use crate::MockClock;
+
+    /// migrate DB and return a storage instance.
+    async fn setup<T: Serialize + DeserializeOwned>() -> RedisStorage<T> {
+        let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
+        // Because connections cannot be shared across async runtime
+        // (different runtimes are created for each test),
+        // we don't share the storage and tests must be run sequentially.
+        let conn = connect(redis_url).await.unwrap();
+        let config = Config::default()
+            .set_namespace("apalis::test")
+            .set_enqueue_scheduled(Duration::from_millis(500)); // Instantly return jobs to the queue
+        let mut storage = RedisStorage::new_with_config(conn, config);
+        cleanup(&mut storage, &WorkerId::new("test-worker")).await;
+        storage
+    }
+
+    /// rollback DB changes made by tests.
+    ///
+    /// You should execute this function in the end of a test
+    async fn cleanup<T>(storage: &mut RedisStorage<T>, _worker_id: &WorkerId) {
+        let _resp: String = redis::cmd("FLUSHDB")
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to Flushdb");
+    }
+
+    fn example_email() -> Email {
+        Email {
+            subject: "Test Subject".to_string(),
+            to: "example@postgres".to_string(),
+            text: "Some Text".to_string(),
+        }
+    }
+
+    async fn consume_one(
+        storage: &mut RedisStorage<Email>,
+        worker_id: &WorkerId,
+    ) -> Request<Email, RedisContext> {
+        let stream = storage.fetch_next(worker_id, storage.config.buffer_size);
+        stream
+            .await
+            .expect("failed to poll job")
+            .first()
+            .expect("stream is empty")
+            .clone()
+    }
+
+    async fn register_worker_at(storage: &mut RedisStorage<Email>) -> Worker<Context> {
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        worker.start();
+        storage
+            .keep_alive(worker.id())
+            .await
+            .expect("failed to register worker");
+        worker
+    }
+
+    async fn register_worker(storage: &mut RedisStorage<Email>) -> Worker<Context> {
+        register_worker_at(storage).await
+    }
+
+    async fn push_email(storage: &mut RedisStorage<Email>, email: Email) {
+        storage.push(email).await.expect("failed to push a job");
+    }
+
+    async fn get_job(
+        storage: &mut RedisStorage<Email>,
+        job_id: &TaskId,
+    ) -> Request<Email, RedisContext> {
+        storage
+            .fetch_by_id(job_id)
+            .await
+            .expect("failed to fetch job by id")
+            .expect("no job found by id")
+    }
+
+    #[tokio::test]
+    async fn test_consume_last_pushed_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+
+        let _job = consume_one(&mut storage, worker.id()).await;
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        let ctx = &job.parts.context;
+        let res = 42usize;
+        storage
+            .ack(
+                ctx,
+                &Response::success(res, job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let _job = get_job(&mut storage, &job.parts.task_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_ack_failed_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_failed(worker.id(), &job.parts.task_id, Some("Mailbox unavailable"))
+            .await
+            .expect("failed to acknowledge the failed job");
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.failed, 1);
+        assert_eq!(stats.done, 0);
+        assert_eq!(stats.inflight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_trim_done() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+        assert!(storage
+            .update_context(&job.parts.task_id, job.parts.context.clone())
+            .await
+            .expect("failed to update the context"));
+
+        let trimmed = storage
+            .trim_done(Duration::from_secs(0))
+            .await
+            .expect("failed to trim done jobs");
+        assert_eq!(trimmed, 1);
+        for hash in [
+            storage.config.job_data_hash(),
+            storage.config.job_context_hash(),
+        ] {
+            let exists: bool = redis::cmd("HEXISTS")
+                .arg(hash)
+                .arg(job.parts.task_id.to_string())
+                .query_async(&mut storage.conn)
+                .await
+                .expect("failed to check the job data");
+            assert!(!exists);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_removes_orphaned_data() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let _inflight = consume_one(&mut storage, worker.id()).await;
+        // Data left behind by a crash, referenced by no set
+        let _: () = redis::cmd("HSET")
+            .arg(storage.config.job_data_hash())
+            .arg(TaskId::new().to_string())
+            .arg("{}")
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to write orphaned data");
+
+        let report = storage
+            .vacuum_with_report(true)
+            .await
+            .expect("failed to vacuum");
+        assert_eq!(
+            report,
+            VacuumReport {
+                done: 0,
+                orphaned: 1
+            }
+        );
+        let report = storage
+            .vacuum_with_report(false)
+            .await
+            .expect("failed to vacuum");
+        assert_eq!(report.orphaned, 1);
+
+        let remaining: usize = redis::cmd("HLEN")
+            .arg(storage.config.job_data_hash())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to count the job data");
+        assert_eq!(remaining, 2);
+    }
+
+    #[tokio::test]
+    async fn test_requeue_keeps_attempts() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        let attempts = job.parts.attempt.current();
+
+        assert!(storage
+            .requeue(worker.id(), &job.parts.task_id)
+            .await
+            .expect("failed to requeue the job"));
+        assert!(!storage
+            .requeue(worker.id(), &job.parts.task_id)
+            .await
+            .expect("failed to requeue the job"));
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.inflight, 0);
+        assert_eq!(stats.failed, 0);
+
+        let requeued = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(requeued.parts.task_id, job.parts.task_id);
+        assert_eq!(requeued.parts.attempt.current(), attempts);
+    }
+
+    #[tokio::test]
+    async fn test_ack_of_a_job_not_inflight_is_not_found() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_failed(worker.id(), &job.parts.task_id, None)
+            .await
+            .expect("failed to ack the job");
+
+        let res = storage
+            .ack_failed(worker.id(), &job.parts.task_id, None)
+            .await;
+        assert!(matches!(
+            res,
+            Err(RedisStorageError::Script(ScriptError::NotFound))
+        ));
+        let res = storage.retry(worker.id(), &job.parts.task_id).await;
+        assert!(matches!(
+            res,
+            Err(RedisStorageError::Script(ScriptError::NotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replaced_script_runs() {
+        let push_job = ScriptName::PushJob.bundled().replace(
+            "  return {\"ok\"}",
+            "  redis.call(\"incr\", KEYS[1] .. \":pushed\")\n  return {\"ok\"}",
+        );
+        let mut storage = setup()
+            .await
+            .with_scripts(RedisScripts::new().set(ScriptName::PushJob, push_job));
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+
+        let pushed: i64 = redis::cmd("GET")
+            .arg(format!("{}:pushed", storage.config.job_data_hash()))
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the counter");
+        assert_eq!(pushed, 2);
+    }
+
+    #[tokio::test]
+    async fn test_debug_keys() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+
+        let keys: HashMap<String, String> = storage
+            .debug_keys()
+            .await
+            .expect("failed to list the keys")
+            .into_iter()
+            .collect();
+        let inflight_set = format!("{}:{}", storage.config.inflight_jobs_set(), worker.id());
+        assert_eq!(keys[&storage.config.job_data_hash()], "hash");
+        assert_eq!(keys[&storage.config.active_jobs_list()], "zset");
+        assert_eq!(keys[&inflight_set], "set");
+        assert!(!keys.contains_key(&storage.config.dead_jobs_set()));
+    }
+
+    #[tokio::test]
+    async fn test_absolute_max_attempts_kills_runaway_jobs() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_absolute_max_attempts(2);
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        for _ in 0..2 {
+            let job = consume_one(&mut storage, worker.id()).await;
+            storage
+                .reschedule(job, Duration::ZERO)
+                .await
+                .expect("failed to reschedule the job");
+            storage
+                .enqueue_scheduled(10)
+                .await
+                .expect("failed to enqueue the scheduled jobs");
+        }
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.total_attempts(), 2);
+        let task_id = job.parts.task_id.clone();
+        storage
+            .reschedule(job, Duration::ZERO)
+            .await
+            .expect("failed to kill the job");
+        let dead: Option<i64> = redis::cmd("ZSCORE")
+            .arg(storage.config.dead_jobs_set())
             .arg(task_id.to_string())
-            .arg(now)
-            .arg(error.to_string())
-            .invoke_async(&mut self.conn)
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the dead jobs set");
+        assert!(dead.is_some());
+        let scheduled: Option<i64> = redis::cmd("ZSCORE")
+            .arg(storage.config.scheduled_jobs_set())
+            .arg(task_id.to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the scheduled jobs set");
+        assert!(scheduled.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sub_second_backoff_delays_the_retry() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_backoff(FixedBackoff::new(Duration::from_millis(200)));
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        let attempt = job.parts.attempt.clone();
+        attempt.increment();
+        let failed_at = storage.config.clock.now().timestamp();
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::<()>::failure(
+                    Error::Failed(Arc::new(Box::new(io::Error::other(
+                        "SMTP server unreachable",
+                    )))),
+                    job.parts.task_id.clone(),
+                    attempt,
+                ),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let retry_at: Option<i64> = redis::cmd("ZSCORE")
+            .arg(storage.config.scheduled_jobs_set())
+            .arg(storage.config.task_key(&job.parts.task_id))
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the scheduled jobs set");
+        assert!(retry_at.expect("the job was not retried") > failed_at);
+    }
+
+    #[tokio::test]
+    async fn test_absolute_max_attempts_kills_jobs_failing_through_ack() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_max_attempts(10)
+            .set_absolute_max_attempts(2)
+            .set_backoff(FixedBackoff::new(Duration::ZERO));
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let mut task_id = None;
+        for _ in 0..3 {
+            let job = consume_one(&mut storage, worker.id()).await;
+            let attempt = job.parts.attempt.clone();
+            attempt.increment();
+            storage
+                .ack(
+                    &job.parts.context,
+                    &Response::<()>::failure(
+                        Error::Failed(Arc::new(Box::new(io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            "SMTP server unreachable",
+                        )))),
+                        job.parts.task_id.clone(),
+                        attempt,
+                    ),
+                )
+                .await
+                .expect("failed to acknowledge the job");
+            storage
+                .enqueue_scheduled(10)
+                .await
+                .expect("failed to enqueue the scheduled jobs");
+            task_id = Some(job.parts.task_id);
+        }
+
+        let task_id = task_id.expect("the job was consumed");
+        let dead: Option<i64> = redis::cmd("ZSCORE")
+            .arg(storage.config.dead_jobs_set())
+            .arg(task_id.to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the dead jobs set");
+        assert!(dead.is_some());
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.scheduled, 0);
+    }
+
+    #[tokio::test]
+    async fn test_kill_job() {
+        let mut storage = setup().await;
+
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        let job_id = &job.parts.task_id;
+
+        storage
+            .kill(
+                worker.id(),
+                job_id,
+                &(Box::new(io::Error::new(
+                    io::ErrorKind::Interrupted,
+                    "Some unforeseen error occurred",
+                )) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill job");
+
+        let _job = get_job(&mut storage, job_id).await;
+    }
+
+    #[tokio::test]
+    async fn test_last_error_of_retried_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.last_error(), None);
+        let ctx = &job.parts.context;
+        storage
+            .ack(
+                ctx,
+                &Response::<()>::failure(
+                    Error::Failed(Arc::new(Box::new(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "SMTP server unreachable",
+                    )))),
+                    job.parts.task_id.clone(),
+                    job.parts.attempt.clone(),
+                ),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        assert!(job
+            .parts
+            .context
+            .last_error()
+            .is_some_and(|e| e.contains("SMTP server unreachable")));
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_defers_retries() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_retry_budget(1, Duration::from_secs(60));
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let mut retried_at = Vec::new();
+        for _ in 0..2 {
+            let job = consume_one(&mut storage, worker.id()).await;
+            storage
+                .ack(
+                    &job.parts.context,
+                    &Response::<()>::failure(
+                        Error::Failed(Arc::new(Box::new(io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            "SMTP server unreachable",
+                        )))),
+                        job.parts.task_id.clone(),
+                        job.parts.attempt.clone(),
+                    ),
+                )
+                .await
+                .expect("failed to acknowledge the job");
+            let scheduled: Option<i64> = redis::cmd("ZSCORE")
+                .arg(storage.config.scheduled_jobs_set())
+                .arg(job.parts.task_id.to_string())
+                .query_async(&mut storage.conn)
+                .await
+                .expect("failed to read the scheduled jobs set");
+            retried_at.push(scheduled.expect("the job should be retried"));
+        }
+        // The second retry waits for the first to leave the window
+        assert!(retried_at[1] - retried_at[0] >= 60);
+    }
+
+    #[tokio::test]
+    async fn test_consume_job_pushed_by_external_producer() {
+        let mut storage = setup().await;
+        let task_id = TaskId::new();
+        let job = format!(
+            r#"{{"args":{{"subject":"Test Subject","to":"example@postgres","text":"Some Text"}},"parts":{{"task_id":"{task_id}","context":{{}}}}}}"#
+        );
+        let _: Vec<String> = redis::Script::new(PUSH_JOB_SCRIPT)
+            .key(storage.config.job_data_hash())
+            .key(storage.config.active_jobs_list())
+            .key(storage.config.signal_list())
+            .key(storage.config.job_priority_hash())
+            .key(storage.config.job_expiry_hash())
+            .arg(task_id.to_string())
+            .arg(job)
+            .arg(0)
+            .arg(0)
+            .invoke_async(&mut storage.conn)
+            .await
+            .expect("failed to push an external job");
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, task_id);
+        assert_eq!(job.parts.attempt.current(), 0);
+        assert_eq!(job.args.subject, "Test Subject");
+    }
+
+    #[tokio::test]
+    async fn test_encode_external() {
+        let storage = setup::<Email>().await;
+        let (task_id, job) = storage
+            .encode_external(example_email())
+            .expect("failed to encode a job");
+        let req: Request<Email, RedisContext> =
+            JsonCodec::<Vec<u8>>::decode(job).expect("failed to decode the job");
+        assert_eq!(req.parts.task_id, task_id);
+        assert_eq!(req.parts.context.job_type(), Some(type_name::<Email>()));
+    }
+
+    async fn push_undecodable(storage: &mut RedisStorage<Email>) -> TaskId {
+        let task_id = TaskId::new();
+        let _: Vec<String> = redis::Script::new(PUSH_JOB_SCRIPT)
+            .key(storage.config.job_data_hash())
+            .key(storage.config.active_jobs_list())
+            .key(storage.config.signal_list())
+            .key(storage.config.job_priority_hash())
+            .key(storage.config.job_expiry_hash())
+            .arg(task_id.to_string())
+            .arg("not a job")
+            .arg(1)
+            .arg(0)
+            .invoke_async(&mut storage.conn)
+            .await
+            .expect("failed to push an undecodable job");
+        task_id
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_job_stops_the_stream() {
+        let mut storage = setup().await;
+        push_undecodable(&mut storage).await;
+
+        let worker = register_worker(&mut storage).await;
+        let res = storage
+            .fetch_next(worker.id(), storage.config.buffer_size)
+            .await;
+        assert!(matches!(res, Err(RedisStorageError::Codec(_))));
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_job_is_quarantined() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_on_decode_error(DecodeErrorPolicy::Quarantine);
+        push_email(&mut storage, example_email()).await;
+        let task_id = push_undecodable(&mut storage).await;
+
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), storage.config.buffer_size)
+            .await
+            .expect("failed to fetch jobs");
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].args.subject, example_email().subject);
+
+        let dead: Option<i64> = redis::cmd("ZSCORE")
+            .arg(storage.config.dead_jobs_set())
+            .arg(task_id.to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the dead jobs set");
+        assert!(dead.is_some());
+        let payload: Vec<u8> = redis::cmd("HGET")
+            .arg(storage.config.job_data_hash())
+            .arg(task_id.to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the job data hash");
+        assert_eq!(payload, b"not a job");
+    }
+
+    #[tokio::test]
+    async fn test_minimal_context_profile_skips_optional_fields() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_context_profile(ContextProfile::minimal());
+        let mut ctx = RedisContext::default();
+        ctx.set_metadata("tenant", "acme");
+        let parts = storage
+            .push_with_context(example_email(), ctx, 0)
+            .await
+            .expect("failed to push a job");
+
+        let payload: String = redis::cmd("HGET")
+            .arg(storage.config.job_data_hash())
+            .arg(parts.task_id.to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the job data hash");
+        assert!(!payload.contains("metadata"));
+        assert!(!payload.contains("last_error"));
+        assert!(!payload.contains("duration_ms"));
+        let job = get_job(&mut storage, &parts.task_id).await;
+        assert!(job.parts.context.metadata().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_push_with_context_keeps_attempts() {
+        let mut storage = setup().await;
+        let mut ctx = RedisContext::default();
+        ctx.set_max_attempts(Some(3));
+        let err = storage
+            .push_with_context(example_email(), ctx.clone(), 4)
+            .await
+            .expect_err("pushed a job with too many attempts");
+        assert!(matches!(
+            err,
+            RedisStorageError::TooManyAttempts {
+                attempts: 4,
+                max_attempts: 3
+            }
+        ));
+
+        storage
+            .push_with_context(example_email(), ctx, 2)
+            .await
+            .expect("failed to push a job");
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.attempt.current(), 2);
+        assert_eq!(job.parts.context.max_attempts(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_survives_the_round_trip() {
+        let mut storage = setup().await;
+        let mut req = Request::new(example_email());
+        req.parts
+            .data
+            .insert(Metadata::from([("trace_id".to_owned(), "abc".to_owned())]));
+        req.parts.data.insert(String::from("ephemeral"));
+        storage
+            .push_request(req)
+            .await
+            .expect("failed to push a job");
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        let metadata = job
+            .parts
+            .data
+            .get::<Metadata>()
+            .expect("the metadata extension is missing");
+        assert_eq!(metadata.0.get("trace_id").map(String::as_str), Some("abc"));
+        assert_eq!(job.parts.context.metadata(), &metadata.0);
+        assert!(job.parts.data.get::<String>().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_push_and_wait() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_ack_batching(false);
+        let err = storage
+            .push_and_wait(example_email(), Duration::from_millis(200))
+            .await
+            .expect_err("a job nobody processes was done");
+        let RedisStorageError::Timeout(timed_out) = err else {
+            panic!("unexpected error: {err}");
+        };
+
+        let mut worker_storage = storage.clone();
+        let worker = register_worker(&mut worker_storage).await;
+        let processing = tokio::spawn(async move {
+            let mut acked = 0;
+            while acked < 2 {
+                let jobs = worker_storage
+                    .fetch_next(worker.id(), 10)
+                    .await
+                    .expect("failed to fetch jobs");
+                for job in jobs {
+                    worker_storage
+                        .ack(
+                            &job.parts.context,
+                            &Response::success(
+                                job.args.subject.clone(),
+                                job.parts.task_id.clone(),
+                                job.parts.attempt.clone(),
+                            ),
+                        )
+                        .await
+                        .expect("failed to acknowledge the job");
+                    acked += 1;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let result = storage
+            .push_and_wait(example_email(), Duration::from_secs(5))
+            .await
+            .expect("failed to wait for the job")
+            .expect("the job has no result");
+        let subject: String = JsonCodec::<Vec<u8>>::decode(result).expect("failed to decode");
+        assert_eq!(subject, example_email().subject);
+        processing.await.expect("failed to process the jobs");
+        assert!(get_job(&mut storage, &timed_out)
+            .await
+            .parts
+            .data
+            .get::<JobState>()
+            .is_some_and(|state| *state == JobState::Done));
+    }
+
+    #[tokio::test]
+    async fn test_push_and_wait_returns_killed_jobs() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_ack_batching(false);
+        let mut worker_storage = storage.clone();
+        let worker = register_worker(&mut worker_storage).await;
+        let processing = tokio::spawn(async move {
+            loop {
+                let jobs = worker_storage
+                    .fetch_next(worker.id(), 10)
+                    .await
+                    .expect("failed to fetch jobs");
+                if let Some(job) = jobs.into_iter().next() {
+                    worker_storage
+                        .ack(
+                            &job.parts.context,
+                            &Response::<()>::new(
+                                Err(Error::Abort(Arc::new("oh no".into()))),
+                                job.parts.task_id.clone(),
+                                job.parts.attempt.clone(),
+                            ),
+                        )
+                        .await
+                        .expect("failed to acknowledge the job");
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let err = storage
+            .push_and_wait(example_email(), Duration::from_secs(5))
+            .await
+            .expect_err("a killed job was done");
+        processing.await.expect("failed to process the job");
+        let RedisStorageError::JobFailed { dead_reason, .. } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(dead_reason, Some(DeadReason::Killed));
+    }
+
+    #[tokio::test]
+    async fn test_get_result_of_acknowledged_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let returned = consume_one(&mut storage, worker.id()).await;
+        let explicit = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(
+            storage
+                .get_result(&returned.parts.task_id)
+                .await
+                .expect("failed to get the result"),
+            None
+        );
+
+        storage
+            .set_result(&explicit.parts.task_id, &"https://example.com/report")
+            .await
+            .expect("failed to set the result");
+        for job in [&returned, &explicit] {
+            storage
+                .ack(
+                    &job.parts.context,
+                    &Response::success(
+                        "returned".to_owned(),
+                        job.parts.task_id.clone(),
+                        job.parts.attempt.clone(),
+                    ),
+                )
+                .await
+                .expect("failed to acknowledge the job");
+        }
+
+        let result = |job: &Request<Email, RedisContext>| {
+            let storage = storage.clone();
+            let task_id = job.parts.task_id.clone();
+            async move {
+                let result = storage
+                    .get_result(&task_id)
+                    .await
+                    .expect("failed to get the result")
+                    .expect("the job has no result");
+                JsonCodec::<Vec<u8>>::decode::<String>(result).expect("failed to decode the result")
+            }
+        };
+        assert_eq!(result(&returned).await, "returned");
+        assert_eq!(result(&explicit).await, "https://example.com/report");
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_prunes_expired_consumers() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_consumer_ttl(Duration::from_secs(60));
+        let long_ago = Utc::now().timestamp() - 3600;
+        let idle = format!("{}:idle-worker", storage.config.inflight_jobs_set());
+        let busy = format!("{}:busy-worker", storage.config.inflight_jobs_set());
+        let _: () = redis::pipe()
+            .zadd(storage.config.consumers_set(), &idle, long_ago)
+            .zadd(storage.config.consumers_set(), &busy, long_ago)
+            .sadd(&busy, TaskId::new().to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to register the expired consumers");
+
+        let worker = register_worker(&mut storage).await;
+        let workers: Vec<WorkerId> = storage
+            .list_workers()
+            .await
+            .expect("failed to list workers")
+            .into_iter()
+            .map(|worker| worker.id)
+            .collect();
+        assert!(workers.contains(worker.id()));
+        assert!(workers.contains(&WorkerId::new("busy-worker")));
+        assert!(!workers.contains(&WorkerId::new("idle-worker")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_by_id_attaches_job_state() {
+        let mut storage = setup().await;
+        let parts = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let job = get_job(&mut storage, &parts.task_id).await;
+        assert_eq!(job.parts.data.get::<JobState>(), Some(&JobState::Active));
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        let fetched = get_job(&mut storage, &job.parts.task_id).await;
+        assert_eq!(
+            fetched.parts.data.get::<JobState>(),
+            Some(&JobState::Inflight)
+        );
+        assert_eq!(fetched.parts.context.lock_by(), Some(worker.id()));
+
+        storage
+            .kill(
+                worker.id(),
+                &job.parts.task_id,
+                &(Box::new(io::Error::other("killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill the job");
+        let killed = get_job(&mut storage, &job.parts.task_id).await;
+        assert_eq!(killed.parts.data.get::<JobState>(), Some(&JobState::Dead));
+    }
+
+    #[tokio::test]
+    async fn test_base62_task_ids_round_trip() {
+        let mut storage = setup::<Email>().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_task_id_encoding(TaskIdEncoding::Base62);
+        let parts = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let job = get_job(&mut storage, &parts.task_id).await;
+        assert_eq!(job.parts.task_id, parts.task_id);
+
+        let keys: Vec<String> = redis::cmd("HKEYS")
+            .arg(storage.config.job_data_hash())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to list the job keys");
+        assert_eq!(keys, vec![TaskIdEncoding::Base62.encode(&parts.task_id)]);
+        assert_eq!(keys[0].len(), 22);
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, parts.task_id);
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success(1usize, job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.done, 1);
+        assert_eq!(stats.inflight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_next_on_demand() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let none = storage
+            .fetch_next(worker.id(), 0)
+            .await
+            .expect("failed to fetch jobs");
+        assert!(none.is_empty());
+        let jobs = storage
+            .fetch_next(worker.id(), 2)
+            .await
+            .expect("failed to fetch jobs");
+        assert_eq!(jobs.len(), 2);
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.inflight, 2);
+        assert_eq!(stats.pending, 1);
+
+        for job in &jobs {
+            storage
+                .ack(
+                    &job.parts.context,
+                    &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+                )
+                .await
+                .expect("failed to acknowledge the job");
+        }
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.inflight, 0);
+        assert_eq!(stats.done, 2);
+    }
+
+    #[tokio::test]
+    async fn test_dead_jobs_carry_their_reason() {
+        let mut storage = setup().await;
+        storage
+            .push_with_ttl(example_email(), Duration::from_secs(1))
+            .await
+            .expect("failed to push a job");
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 3)
+            .await
+            .expect("failed to fetch jobs");
+        let [killed, exhausted] = jobs.as_slice() else {
+            panic!("expected the two live jobs, got {}", jobs.len());
+        };
+        storage
+            .kill(
+                worker.id(),
+                &killed.parts.task_id,
+                &(Box::new(io::Error::other("killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill the job");
+        storage
+            .ack(
+                &exhausted.parts.context,
+                &Response::<()>::failure(
+                    Error::Failed(Arc::new(Box::new(io::Error::other("bounced")))),
+                    exhausted.parts.task_id.clone(),
+                    Attempt::new_with_value(storage.config.max_attempts),
+                ),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let dead = storage
+            .list_jobs_by_state(JobState::Dead, 1, 10)
+            .await
+            .expect("failed to list dead jobs");
+        assert_eq!(dead.len(), 3);
+        let reason_of = |task_id: &TaskId| {
+            dead.iter()
+                .find(|job| &job.parts.task_id == task_id)
+                .and_then(|job| job.parts.data.get::<DeadReason>().copied())
+        };
+        assert_eq!(reason_of(&killed.parts.task_id), Some(DeadReason::Killed));
+        assert_eq!(
+            reason_of(&exhausted.parts.task_id),
+            Some(DeadReason::MaxRetriesExceeded)
+        );
+        let expired = dead
+            .iter()
+            .find(|job| {
+                job.parts.task_id != killed.parts.task_id
+                    && job.parts.task_id != exhausted.parts.task_id
+            })
+            .expect("the expired job is dead");
+        assert_eq!(
+            expired.parts.data.get::<DeadReason>(),
+            Some(&DeadReason::Expired)
+        );
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct RecordingListener(Arc<Mutex<Vec<String>>>);
+
+    impl JobEventListener for RecordingListener {
+        fn on_fetched(&self, task_id: &TaskId, _worker_id: &WorkerId) {
+            self.0.lock().unwrap().push(format!("fetched {task_id}"));
+        }
+
+        fn on_acked(&self, task_id: &TaskId) {
+            self.0.lock().unwrap().push(format!("acked {task_id}"));
+        }
+
+        fn on_killed(&self, task_id: &TaskId, reason: DeadReason) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("killed {task_id} {reason}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listener_observes_the_job_lifecycle() {
+        let listener = RecordingListener::default();
+        let mut storage = setup().await.with_listener(listener.clone());
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 2)
+            .await
+            .expect("failed to fetch jobs");
+        let [done, killed] = [&jobs[0].parts.task_id, &jobs[1].parts.task_id];
+        storage
+            .ack_failed(worker.id(), done, None)
+            .await
+            .expect("failed to acknowledge the job");
+        storage
+            .kill(
+                worker.id(),
+                killed,
+                &(Box::new(io::Error::other("killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill the job");
+        // Only the first kill moves the job
+        storage
+            .kill(
+                worker.id(),
+                killed,
+                &(Box::new(io::Error::other("killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill the job");
+
+        assert_eq!(
+            *listener.0.lock().unwrap(),
+            vec![
+                format!("fetched {done}"),
+                format!("fetched {killed}"),
+                format!("acked {done}"),
+                format!("killed {killed} killed"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_dead_jobs() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let mut dead = Vec::new();
+        for _ in 0..3 {
+            let job = consume_one(&mut storage, worker.id()).await;
+            job.parts.attempt.increment();
+            storage
+                .update(job.clone())
+                .await
+                .expect("failed to update the job");
+            storage
+                .kill(
+                    worker.id(),
+                    &job.parts.task_id,
+                    &(Box::new(io::Error::other("killed")) as BoxDynError),
+                )
+                .await
+                .expect("failed to kill the job");
+            dead.push(job.parts.task_id);
+        }
+
+        assert!(storage
+            .replay(&dead[0])
+            .await
+            .expect("failed to replay the job"));
+        assert!(!storage
+            .replay(&dead[0])
+            .await
+            .expect("failed to replay the job"));
+        assert_eq!(
+            storage
+                .replay_dead(10)
+                .await
+                .expect("failed to replay the dead jobs"),
+            2
+        );
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 3);
+        assert_eq!(stats.dead, 0);
+        for task_id in &dead {
+            let job = get_job(&mut storage, task_id).await;
+            assert_eq!(job.parts.attempt.current(), 0);
+            assert_eq!(job.parts.data.get::<JobState>(), Some(&JobState::Active));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_with_retries_overrides_max_attempts() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_max_attempts(5);
+        let parts = storage
+            .push_with_retries(example_email(), 1)
+            .await
+            .expect("failed to push a job");
+        assert_eq!(parts.context.max_attempts(), Some(1));
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        let attempt = job.parts.attempt.clone();
+        attempt.increment();
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::<()>::failure(
+                    Error::Failed(Arc::new(Box::new(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "SMTP server unreachable",
+                    )))),
+                    job.parts.task_id.clone(),
+                    attempt,
+                ),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.dead, 1);
+        assert_eq!(stats.scheduled, 0);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_job_is_killed_with_timeout_reason() {
+        let mut storage = setup().await;
+        let timeout = Duration::from_millis(50);
+        let parts = storage
+            .push_with_timeout(example_email(), timeout)
+            .await
+            .expect("failed to push a job");
+        assert_eq!(parts.context.timeout(), Some(timeout));
+        storage.config = storage.config.clone().set_max_attempts(1);
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.timeout(), Some(timeout));
+        let ctx = job.parts.context.clone();
+        let task_id = job.parts.task_id.clone();
+        let attempt = job.parts.attempt.clone();
+        attempt.increment();
+        let mut service =
+            JobTimeoutLayer.layer(apalis_core::service_fn::service_fn(|_: Email| async {
+                sleep(Duration::from_secs(5)).await;
+                Ok::<_, Error>(())
+            }));
+        let err = service
+            .call(job)
+            .await
+            .expect_err("the handler must time out");
+        storage
+            .ack(
+                &ctx,
+                &Response::<()>::failure(err, task_id.clone(), attempt),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let dead = storage
+            .list_jobs_by_state(JobState::Dead, 1, 10)
+            .await
+            .expect("failed to list dead jobs");
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].parts.task_id, task_id);
+        assert_eq!(
+            dead[0].parts.data.get::<DeadReason>(),
+            Some(&DeadReason::Timeout)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duration_of_acknowledged_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.duration_ms(), None);
+        sleep(Duration::from_millis(100)).await;
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        assert!(job
+            .parts
+            .context
+            .duration_ms()
+            .is_some_and(|duration| duration >= 100));
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_renqueueorphaned_pulse_last_seen_1sec() {
+        let mut storage = setup().await;
+
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker_at(&mut storage).await;
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        sleep(Duration::from_millis(1000)).await;
+        let dead_since = Utc::now() - chrono::Duration::from_std(Duration::from_secs(1)).unwrap();
+        let res = storage
+            .reenqueue_orphaned(1, dead_since)
+            .await
+            .expect("failed to reenqueue_orphaned");
+        // We expect 1 job to be re-enqueued
+        assert_eq!(res, 1);
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        let ctx = &job.parts.context;
+        // assert_eq!(*ctx.status(), State::Pending);
+        // assert!(ctx.done_at().is_none());
+        assert!(ctx.lock_by.is_none());
+        // assert!(ctx.lock_at().is_none());
+        // assert_eq!(*ctx.last_error(), Some("Job was abandoned".to_owned()));
+        // TODO: Redis should store context aside
+        // assert_eq!(job.parts.attempt.current(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reenqueue_orphaned_jobs_reports_the_jobs() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker_at(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        sleep(Duration::from_millis(1000)).await;
+        let dead_since = Utc::now() - chrono::Duration::from_std(Duration::from_secs(1)).unwrap();
+        let orphaned = storage
+            .reenqueue_orphaned_jobs(1, dead_since)
+            .await
+            .expect("failed to reenqueue_orphaned_jobs");
+        assert_eq!(orphaned.len(), 1);
+        assert_eq!(orphaned[0].task_id, job.parts.task_id);
+        assert_eq!(&orphaned[0].worker_id, worker.id());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_jobs_are_due_by_the_clock() {
+        let mut storage = setup().await;
+        let clock = MockClock::default();
+        storage.config = storage.config.clone().set_clock(clock.clone());
+        storage
+            .schedule_in(example_email(), Duration::from_secs(60))
+            .await
+            .expect("failed to schedule a job");
+
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(storage.enqueue_scheduled(10).await.unwrap(), 0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(storage.enqueue_scheduled(10).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_drain_scheduled_moves_every_due_job() {
+        let mut storage = setup().await;
+        let now = Utc::now().timestamp();
+        for _ in 0..25 {
+            storage
+                .schedule(example_email(), now - 1)
+                .await
+                .expect("failed to schedule a job");
+        }
+        storage
+            .schedule(example_email(), now + 3600)
+            .await
+            .expect("failed to schedule a job");
+
+        assert_eq!(storage.drain_scheduled().await.unwrap(), 25);
+        assert_eq!(storage.pending_count().await.unwrap(), 25);
+        assert_eq!(storage.scheduled_count().await.unwrap(), 1);
+        assert_eq!(storage.drain_scheduled().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tick_moves_due_and_orphaned_jobs() {
+        let mut storage = setup().await;
+        storage.config = storage
+            .config
+            .clone()
+            .set_reenqueue_orphaned_after(Duration::from_secs(1));
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker_at(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+        storage
+            .schedule(example_email(), Utc::now().timestamp() - 1)
+            .await
+            .expect("failed to schedule a job");
+        sleep(Duration::from_millis(2000)).await;
+
+        let report = storage.tick().await.expect("failed to tick");
+        assert_eq!(
+            report,
+            TickReport {
+                scheduled: 1,
+                orphaned: 1
+            }
+        );
+        assert_eq!(storage.len().await.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_orphaned_jobs_are_sent_to_the_channel() {
+        // The sender's own slot is the only room in the channel
+        let (tx, mut rx) = mpsc::channel(0);
+        let mut config = Config::default().set_orphaned_jobs_sender(tx);
+        let task_id = TaskId::new();
+        config.notify_orphaned(vec![
+            OrphanedJob {
+                task_id: task_id.clone(),
+                worker_id: WorkerId::new("crashed-worker"),
+            },
+            OrphanedJob {
+                task_id: TaskId::new(),
+                worker_id: WorkerId::new("crashed-worker"),
+            },
+        ]);
+        let job = rx.try_recv().expect("the first job should be sent");
+        assert_eq!(job.task_id, task_id);
+        assert_eq!(job.worker_id, WorkerId::new("crashed-worker"));
+        // The second notification is dropped instead of waiting for the full channel
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_renqueueorphaned_pulse_last_seen_5sec() {
+        let mut storage = setup().await;
+
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker_at(&mut storage).await;
+        sleep(Duration::from_millis(1100)).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        let dead_since = Utc::now() - chrono::Duration::from_std(Duration::from_secs(5)).unwrap();
+        let res = storage
+            .reenqueue_orphaned(1, dead_since)
+            .await
+            .expect("failed to reenqueue_orphaned");
+        // We expect 0 job to be re-enqueued
+        assert_eq!(res, 0);
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        let _ctx = &job.parts.context;
+        // assert_eq!(*ctx.status(), State::Running);
+        // TODO: update redis context
+        // assert_eq!(ctx.lock_by, Some(worker_id));
+        // assert!(ctx.lock_at().is_some());
+        // assert_eq!(*ctx.last_error(), None);
+        assert_eq!(job.parts.attempt.current(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_counts_by_state() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+        storage
+            .schedule(example_email(), Utc::now().timestamp() + 3600)
+            .await
+            .expect("failed to schedule a job");
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        storage
+            .kill(
+                worker.id(),
+                &job.parts.task_id,
+                &(Box::new(io::Error::other("oh no")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill a job");
+
+        assert_eq!(storage.pending_count().await.unwrap(), 1);
+        assert_eq!(storage.scheduled_count().await.unwrap(), 1);
+        assert_eq!(storage.done_count().await.unwrap(), 0);
+        assert_eq!(storage.failed_count().await.unwrap(), 0);
+        assert_eq!(storage.dead_count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_update_context_keeps_the_job_data() {
+        let mut storage = setup().await;
+        let parts = storage.push(example_email()).await.unwrap();
+        let task_key = storage.config.task_key(&parts.task_id);
+        let data: Vec<u8> = redis::cmd("HGET")
+            .arg(storage.config.job_data_hash())
+            .arg(&task_key)
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+
+        let mut context = parts.context.clone();
+        context.set_priority(7);
+        context.set_metadata("tenant", "acme");
+        assert!(storage
+            .update_context(&parts.task_id, context)
+            .await
+            .expect("failed to update the context"));
+        let stored: Vec<u8> = redis::cmd("HGET")
+            .arg(storage.config.job_data_hash())
+            .arg(&task_key)
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+        assert_eq!(stored, data);
+
+        let job = get_job(&mut storage, &parts.task_id).await;
+        assert_eq!(job.parts.context.priority(), 7);
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.priority(), 7);
+
+        // Updating the whole job drops the separately stored context
+        let mut job = get_job(&mut storage, &parts.task_id).await;
+        job.parts.context.set_priority(3);
+        storage.update(job).await.unwrap();
+        let job = get_job(&mut storage, &parts.task_id).await;
+        assert_eq!(job.parts.context.priority(), 3);
+
+        assert!(!storage
+            .update_context(&TaskId::new(), RedisContext::default())
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_namespaced_storage_uses_its_own_keys() {
+        let storage = setup().await;
+        let mut other = storage.clone().namespaced("apalis::test::namespaced");
+        assert_eq!(
+            other.get_config().get_namespace(),
+            "apalis::test::namespaced"
+        );
+        push_email(&mut other, example_email()).await;
+
+        assert_eq!(storage.pending_count().await.unwrap(), 0);
+        assert_eq!(other.pending_count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_push_batch() {
+        let mut storage = setup().await;
+        let ids = storage
+            .push_batch(vec![example_email(), example_email(), example_email()])
+            .await
+            .expect("failed to push batch");
+        assert_eq!(ids.len(), 3);
+        assert_eq!(storage.len().await.unwrap(), 3);
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, ids[0]);
+
+        let ids = storage
+            .push_batch(Vec::new())
+            .await
+            .expect("failed to push empty batch");
+        assert!(ids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_ack_by_id_finds_the_worker_holding_the_job() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+
+        storage
+            .ack_by_id(&job.parts.task_id)
+            .await
+            .expect("failed to ack by id");
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        assert_eq!(job.parts.data.get::<JobState>(), Some(&JobState::Done));
+
+        let err = storage.ack_by_id(&job.parts.task_id).await.unwrap_err();
+        assert!(matches!(
+            err,
+            RedisStorageError::Script(ScriptError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_ack_by_id_releases_the_unique_key() {
+        let mut storage = setup().await;
+        let first = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_by_id(&first.task_id)
+            .await
+            .expect("failed to ack by id");
+
+        let second = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        assert!(second.was_new);
+    }
+
+    #[tokio::test]
+    async fn test_list_done_details() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_done_details(true);
+        let parts = storage.push(example_email()).await.unwrap();
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_job(
+                worker.id(),
+                &parts.task_id,
+                AckStatus::Done(Vec::new()),
+                None,
+                Some(1),
+            )
+            .await
+            .expect("failed to ack a job");
+
+        let details = storage.list_done_details(1, 10).await.unwrap();
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].task_id, parts.task_id);
+        assert_eq!(&details[0].worker_id, worker.id());
+        assert_eq!(details[0].attempts, Some(1));
+        assert!(details[0].duration_ms.is_some());
+
+        storage.vacuum().await.unwrap();
+        assert!(storage.list_done_details(1, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_is_inflight() {
+        let mut storage = setup().await;
+        let parts = storage.push(example_email()).await.unwrap();
+        assert!(!storage.is_inflight(&parts.task_id).await.unwrap());
+
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+        assert!(storage.is_inflight(&parts.task_id).await.unwrap());
+
+        storage.ack_by_id(&parts.task_id).await.unwrap();
+        assert!(!storage.is_inflight(&parts.task_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_group_status_counts_finished_jobs() {
+        let mut storage = setup().await;
+        let ids = storage
+            .push_group(
+                vec![example_email(), example_email(), example_email()],
+                "batch-1",
+            )
+            .await
+            .expect("failed to push group");
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 2)
+            .await
+            .expect("failed to fetch jobs");
+        assert_eq!(jobs[0].parts.context.group_id(), Some("batch-1"));
+        storage
+            .ack_job(
+                worker.id(),
+                &ids[0],
+                AckStatus::Done(Vec::new()),
+                None,
+                None,
+            )
+            .await
+            .expect("failed to ack a job");
+        storage
+            .kill(
+                worker.id(),
+                &ids[1],
+                &(Box::new(io::Error::other("oh no")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill a job");
+
+        let status = storage.group_status("batch-1").await.unwrap();
+        assert_eq!(
+            status,
+            GroupStatus {
+                total: 3,
+                done: 1,
+                failed: 1
+            }
+        );
+        assert!(!status.is_complete());
+
+        assert!(storage.cancel(&ids[2]).await.unwrap());
+        assert!(storage.group_status("batch-1").await.unwrap().is_complete());
+        assert!(storage.clear_group("batch-1").await.unwrap());
+        assert_eq!(
+            storage.group_status("batch-1").await.unwrap(),
+            GroupStatus::default()
+        );
+        assert!(!storage.clear_group("batch-1").await.unwrap());
+        assert_eq!(
+            storage.group_status("unknown").await.unwrap(),
+            GroupStatus::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_consume_jobs_with_priority() {
+        let mut storage = setup().await;
+        let low = storage
+            .push_with_priority(example_email(), -1)
+            .await
+            .expect("failed to push a job");
+        let normal = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let high = storage
+            .push_with_priority(example_email(), 10)
+            .await
+            .expect("failed to push a job");
+
+        let worker = register_worker(&mut storage).await;
+
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, high.task_id);
+        assert_eq!(job.parts.context.priority(), 10);
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, normal.task_id);
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, low.task_id);
+    }
+
+    #[tokio::test]
+    async fn test_finished_jobs_release_their_priority() {
+        let mut storage = setup().await;
+        let done = storage
+            .push_with_priority(example_email(), 10)
+            .await
+            .expect("failed to push a job");
+        let killed = storage
+            .push_with_priority(example_email(), 5)
+            .await
+            .expect("failed to push a job");
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+        consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_by_id(&done.task_id)
+            .await
+            .expect("failed to acknowledge the job");
+        storage
+            .kill(
+                worker.id(),
+                &killed.task_id,
+                &(Box::new(io::Error::other("killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill the job");
+
+        let priority_hash = storage.config.job_priority_hash();
+        let priorities: usize = redis::cmd("HLEN")
+            .arg(&priority_hash)
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+        assert_eq!(priorities, 0);
+
+        // Replaying restores the priority from the job data
+        assert!(storage
+            .replay(&killed.task_id)
+            .await
+            .expect("failed to replay the job"));
+        let priority: Option<i32> = redis::cmd("HGET")
+            .arg(&priority_hash)
+            .arg(storage.config.task_key(&killed.task_id))
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+        assert_eq!(priority, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_active_jobs_from_a_list() {
+        let mut storage = setup().await;
+        let low = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let high = storage
+            .push_with_priority(example_email(), 10)
+            .await
+            .expect("failed to push a job");
+
+        // Hold the active jobs in a list, like queues created before they were sorted
+        let active_jobs_list = storage.config.active_jobs_list();
+        let _: () = redis::pipe()
+            .del(&active_jobs_list)
+            .rpush(&active_jobs_list, storage.config.task_key(&low.task_id))
+            .rpush(&active_jobs_list, storage.config.task_key(&high.task_id))
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage
+                .migrate_active_jobs()
+                .await
+                .expect("failed to migrate the active jobs"),
+            2
+        );
+        assert_eq!(
+            storage
+                .migrate_active_jobs()
+                .await
+                .expect("failed to migrate the active jobs"),
+            0
+        );
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, high.task_id);
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, low.task_id);
+    }
+
+    #[tokio::test]
+    async fn test_consume_jobs_lifo() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_fetch_order(FetchOrder::Lifo);
+        let mut task_ids = Vec::new();
+        for _ in 0..3 {
+            let parts = storage
+                .push(example_email())
+                .await
+                .expect("failed to push a job");
+            task_ids.push(parts.task_id);
+        }
+        let high = storage
+            .push_with_priority(example_email(), 10)
+            .await
+            .expect("failed to push a job");
+
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 4)
+            .await
+            .expect("failed to fetch jobs");
+        let fetched: Vec<_> = jobs.into_iter().map(|job| job.parts.task_id).collect();
+        assert_eq!(
+            fetched,
+            vec![
+                high.task_id,
+                task_ids[2].clone(),
+                task_ids[1].clone(),
+                task_ids[0].clone()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drain() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        storage
+            .schedule(example_email(), Utc::now().timestamp() + 60)
+            .await
+            .expect("failed to schedule a job");
+
+        let processed = storage
+            .clone()
+            .drain(WorkerId::new("test-worker"), |_job| async {
+                Ok::<_, Error>(())
+            })
+            .await
+            .expect("failed to drain the queue");
+        assert_eq!(processed, 3);
+        assert_eq!(storage.len().await.expect("failed to get len"), 0);
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.done, 3);
+        assert_eq!(stats.scheduled, 1);
+    }
+
+    #[tokio::test]
+    async fn test_multiplexed_job_types() {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Sms {
+            to: String,
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        enum Notification {
+            Email(Email),
+            Sms(Sms),
+        }
+
+        impl MultiplexedJob for Notification {
+            fn decode_job<C: Codec<Compact = Vec<u8>>>(
+                job_type: &str,
+                job: Vec<u8>,
+            ) -> Option<Result<Request<Self, RedisContext>, C::Error>> {
+                match job_type {
+                    "email" => Some(C::decode(job).map(|req: Request<Email, RedisContext>| {
+                        Request::new_with_parts(Notification::Email(req.args), req.parts)
+                    })),
+                    "sms" => Some(C::decode(job).map(|req: Request<Sms, RedisContext>| {
+                        Request::new_with_parts(Notification::Sms(req.args), req.parts)
+                    })),
+                    _ => None,
+                }
+            }
+        }
+
+        let storage: RedisStorage<Email> = setup().await;
+        let conn = storage.get_connection().clone();
+        let config = storage.get_config().clone();
+
+        let mut emails: RedisStorage<Email> =
+            RedisStorage::new_with_config(conn.clone(), config.clone().set_job_type("email"));
+        let mut sms: RedisStorage<Sms> =
+            RedisStorage::new_with_config(conn.clone(), config.clone().set_job_type("sms"));
+        let mut others: RedisStorage<String> =
+            RedisStorage::new_with_config(conn.clone(), config.clone());
+        push_email(&mut emails, example_email()).await;
+        sms.push(Sms {
+            to: "+254700000000".to_owned(),
+        })
+        .await
+        .expect("failed to push an sms");
+        let other = others
+            .push("unexpected".to_owned())
+            .await
+            .expect("failed to push a string");
+        assert_eq!(other.context.job_type(), Some(type_name::<String>()));
+
+        let mut storage: RedisStorage<Notification> = RedisStorage::new_multiplexed(conn, config);
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        worker.start();
+        storage
+            .keep_alive(worker.id())
+            .await
+            .expect("failed to register worker");
+
+        let jobs = storage
+            .fetch_next(worker.id(), 10)
+            .await
+            .expect("failed to fetch jobs");
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs
+            .iter()
+            .any(|job| matches!(job.args, Notification::Email(_))));
+        assert!(jobs
+            .iter()
+            .any(|job| matches!(job.args, Notification::Sms(_))));
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.inflight, 2);
+        assert_eq!(stats.dead, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let inflight = consume_one(&mut storage, worker.id()).await.parts.task_id;
+
+        let pending = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job")
+            .task_id;
+        let scheduled = storage
+            .schedule(example_email(), Utc::now().timestamp() + 60)
+            .await
+            .expect("failed to schedule a job")
+            .task_id;
+        assert!(!storage
+            .cancel(&inflight)
+            .await
+            .expect("failed to cancel the inflight job"));
+
+        for task_id in [&pending, &scheduled] {
+            assert!(storage
+                .update_context(task_id, RedisContext::default())
+                .await
+                .expect("failed to update the context"));
+            assert!(storage.cancel(task_id).await.expect("failed to cancel"));
+            let context: bool = redis::cmd("HEXISTS")
+                .arg(storage.config.job_context_hash())
+                .arg(task_id.to_string())
+                .query_async(&mut storage.conn)
+                .await
+                .expect("failed to check the job context");
+            assert!(!context);
+            assert!(storage
+                .fetch_by_id(task_id)
+                .await
+                .expect("failed to fetch the job")
+                .is_none());
+            assert!(!storage.cancel(task_id).await.expect("failed to cancel"));
+        }
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.scheduled, 0);
+        assert_eq!(stats.inflight, 1);
+        get_job(&mut storage, &inflight).await;
+    }
+
+    #[tokio::test]
+    async fn test_ack_batching() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 3)
+            .await
+            .expect("failed to fetch jobs");
+        assert_eq!(jobs.len(), 3);
+
+        for job in &jobs[..2] {
+            job.parts.attempt.increment();
+            storage
+                .buffer_ack(
+                    &job.parts.context,
+                    &job.parts.task_id,
+                    &job.parts.attempt,
+                    &(),
+                )
+                .await
+                .expect("failed to buffer the ack");
+        }
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.inflight, 3);
+        assert_eq!(stats.done, 0);
+
+        assert_eq!(storage.flush_acks().await.expect("failed to flush"), 2);
+        assert_eq!(storage.flush_acks().await.expect("failed to flush"), 0);
+        let job = get_job(&mut storage, &jobs[0].parts.task_id).await;
+        assert_eq!(job.parts.attempt.current(), 1);
+
+        // The last ack is flushed before the shutdown re-enqueues inflight jobs
+        let mut clone = storage.clone();
+        clone
+            .buffer_ack(
+                &jobs[2].parts.context,
+                &jobs[2].parts.task_id,
+                &jobs[2].parts.attempt,
+                &(),
+            )
+            .await
+            .expect("failed to buffer the ack");
+        assert_eq!(
+            storage
+                .shutdown(worker.id())
+                .await
+                .expect("failed to shutdown"),
+            0
+        );
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.done, 3);
+        assert_eq!(stats.pending, 0);
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_failures_stop_the_worker() {
+        use futures::StreamExt;
+        use redis::{Cmd, Pipeline, RedisFuture, Value};
+
+        /// A connection that fails every command, as if Redis was unreachable
+        #[derive(Clone)]
+        struct FailingConn;
+
+        impl ConnectionLike for FailingConn {
+            fn req_packed_command<'a>(&'a mut self, _cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+                Box::pin(async { Err(std::io::Error::other("unreachable").into()) })
+            }
+
+            fn req_packed_commands<'a>(
+                &'a mut self,
+                _cmd: &'a Pipeline,
+                _offset: usize,
+                _count: usize,
+            ) -> RedisFuture<'a, Vec<Value>> {
+                Box::pin(async { Err(std::io::Error::other("unreachable").into()) })
+            }
+
+            fn get_db(&self) -> i64 {
+                0
+            }
+        }
+
+        let config = Config::default()
+            .set_keep_alive(Duration::from_millis(10))
+            .set_max_keepalive_failures(3);
+        let storage: RedisStorage<Email, FailingConn> =
+            RedisStorage::new_with_config(FailingConn, config);
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        worker.start();
+
+        let mut poller = storage.poll(&worker);
+        tokio::time::timeout(Duration::from_secs(5), poller.heartbeat)
+            .await
+            .expect("heartbeat should stop after repeated keep-alive failures");
+
+        assert!(!worker.is_running());
+        assert!(poller.stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_paused_controller_skips_fetching() {
+        use futures::StreamExt;
+
+        let mut storage = setup().await;
+        let controller = storage.controller();
+        controller.unplug();
+        push_email(&mut storage, example_email()).await;
+
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        worker.start();
+        let mut poller = storage.clone().poll(&worker);
+        let heartbeat = tokio::spawn(poller.heartbeat);
+
+        sleep(Duration::from_millis(500)).await;
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.inflight, 0);
+
+        controller.plug();
+        let job = tokio::time::timeout(Duration::from_secs(5), poller.stream.next())
+            .await
+            .expect("the job should be fetched once resumed");
+        assert!(matches!(job, Some(Ok(Some(_)))));
+        heartbeat.abort();
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_skips_fetching_but_acks() {
+        use futures::StreamExt;
+
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        worker.start();
+        let mut poller = storage.clone().poll(&worker);
+        let heartbeat = tokio::spawn(poller.heartbeat);
+
+        let job = tokio::time::timeout(Duration::from_secs(5), poller.stream.next())
+            .await
+            .expect("the job should be fetched")
+            .expect("the stream should not end")
+            .expect("the job should be fetched")
+            .expect("the job should be fetched");
+        storage.enter_maintenance();
+        assert!(storage.is_in_maintenance());
+        push_email(&mut storage, example_email()).await;
+
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
             .await
+            .expect("failed to acknowledge the job");
+        sleep(Duration::from_millis(500)).await;
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.done, 1);
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.inflight, 0);
+
+        storage.exit_maintenance();
+        let job = tokio::time::timeout(Duration::from_secs(5), poller.stream.next())
+            .await
+            .expect("the job should be fetched once maintenance ends");
+        assert!(matches!(job, Some(Ok(Some(_)))));
+        heartbeat.abort();
     }
 
-    /// Required to add scheduled jobs to the active set
-    pub async fn enqueue_scheduled(&mut self, count: usize) -> Result<usize, RedisError> {
-        let enqueue_jobs = self.scripts.enqueue_scheduled.clone();
-        let scheduled_jobs_set = self.config.scheduled_jobs_set();
-        let active_jobs_list = self.config.active_jobs_list();
-        let signal_list = self.config.signal_list();
-        let now: i64 = Utc::now().timestamp();
-        let res: Result<usize, _> = enqueue_jobs
-            .key(scheduled_jobs_set)
-            .key(active_jobs_list)
-            .key(signal_list)
-            .arg(now)
-            .arg(count)
-            .invoke_async(&mut self.conn)
-            .await;
-        match res {
-            Ok(count) => Ok(count),
-            Err(e) => Err(e),
+    #[tokio::test]
+    async fn test_fetch_waits_for_the_previous_batch_to_be_taken() {
+        use futures::StreamExt;
+
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_buffer_size(1);
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
         }
+
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        worker.start();
+        let mut poller = storage.clone().poll(&worker);
+        let heartbeat = tokio::spawn(poller.heartbeat);
+
+        sleep(Duration::from_millis(500)).await;
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 2);
+        assert_eq!(stats.inflight, 1);
+
+        let job = tokio::time::timeout(Duration::from_secs(5), poller.stream.next())
+            .await
+            .expect("the fetched job should be in the stream");
+        assert!(matches!(job, Some(Ok(Some(_)))));
+        sleep(Duration::from_millis(500)).await;
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 1);
+        assert_eq!(stats.inflight, 2);
+        heartbeat.abort();
     }
 
-    /// Re-enqueue some jobs that might be abandoned.
-    pub async fn reenqueue_active(&mut self, job_ids: Vec<&TaskId>) -> Result<(), RedisError> {
-        let reenqueue_active = self.scripts.reenqueue_active.clone();
-        let inflight_set: String = self.config.inflight_jobs_set().to_string();
-        let active_jobs_list = self.config.active_jobs_list();
-        let signal_list = self.config.signal_list();
+    #[tokio::test]
+    async fn test_check_health() {
+        let storage = setup::<Email>().await;
+        storage
+            .check_health()
+            .await
+            .expect("redis should be reachable");
+        storage
+            .load_scripts()
+            .await
+            .expect("the scripts should be loadable");
+    }
 
-        reenqueue_active
-            .key(inflight_set)
-            .key(active_jobs_list)
-            .key(signal_list)
-            .arg(
-                job_ids
-                    .into_iter()
-                    .map(|j| j.to_string())
-                    .collect::<Vec<String>>(),
+    #[tokio::test]
+    async fn test_max_concurrent() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_max_concurrent(2);
+        for _ in 0..5 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let buffer_size = storage.config.buffer_size;
+
+        let count = storage
+            .free_slots(worker.id(), buffer_size)
+            .await
+            .expect("failed to count free slots");
+        assert_eq!(count, 2);
+        let jobs = storage
+            .fetch_next(worker.id(), count)
+            .await
+            .expect("failed to poll jobs");
+        assert_eq!(jobs.len(), 2);
+
+        // Slow jobs have not been acknowledged yet
+        let count = storage
+            .free_slots(worker.id(), buffer_size)
+            .await
+            .expect("failed to count free slots");
+        assert_eq!(count, 0);
+
+        let job = &jobs[0];
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
             )
-            .invoke_async(&mut self.conn)
             .await
+            .expect("failed to acknowledge the job");
+        let count = storage
+            .free_slots(worker.id(), buffer_size)
+            .await
+            .expect("failed to count free slots");
+        assert_eq!(count, 1);
     }
-    /// Re-enqueue some jobs that might be orphaned after a number of seconds
-    pub async fn reenqueue_orphaned(
-        &mut self,
-        count: i32,
-        dead_since: DateTime<Utc>,
-    ) -> Result<usize, RedisError> {
-        let reenqueue_orphaned = self.scripts.reenqueue_orphaned.clone();
-        let consumers_set = self.config.consumers_set();
-        let active_jobs_list = self.config.active_jobs_list();
-        let signal_list = self.config.signal_list();
 
-        let dead_since = dead_since.timestamp();
+    #[tokio::test]
+    async fn test_buffered_acks_free_their_slots() {
+        let mut storage = setup().await;
+        storage.config = storage.config.clone().set_max_concurrent(2);
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 2)
+            .await
+            .expect("failed to poll jobs");
+        let job = &jobs[0];
+        storage
+            .buffer_ack(
+                &job.parts.context,
+                &job.parts.task_id,
+                &job.parts.attempt,
+                &(),
+            )
+            .await
+            .expect("failed to buffer the ack");
 
-        let res: Result<usize, RedisError> = reenqueue_orphaned
-            .key(consumers_set)
-            .key(active_jobs_list)
-            .key(signal_list)
-            .arg(dead_since)
-            .arg(count)
-            .invoke_async(&mut self.conn)
+        // The job is inflight until the acks are flushed, but no longer holds a slot
+        assert!(storage
+            .is_inflight(&job.parts.task_id)
+            .await
+            .expect("failed to check the job"));
+        let count = storage
+            .free_slots(worker.id(), storage.config.buffer_size)
+            .await
+            .expect("failed to count free slots");
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_into_stream() {
+        let mut storage = setup().await;
+        for _ in 0..2 {
+            push_email(&mut storage, example_email()).await;
+        }
+
+        let worker_id = WorkerId::new("test-worker");
+        storage
+            .keep_alive(&worker_id)
+            .await
+            .expect("failed to register the worker");
+        let jobs: Vec<_> = storage
+            .clone()
+            .into_stream(worker_id)
+            .take(2)
+            .collect()
             .await;
-        match res {
-            Ok(count) => Ok(count),
-            Err(e) => Err(e),
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs.iter().all(|job| matches!(job, Ok(Some(_)))));
+        assert_eq!(storage.len().await.expect("failed to get len"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_peek_does_not_consume() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+
+        let peeked = storage.peek(2).await.expect("failed to peek");
+        assert_eq!(peeked.len(), 2);
+        assert_eq!(storage.len().await.expect("failed to get len"), 3);
+
+        let worker = register_worker(&mut storage).await;
+        let fetched = storage
+            .fetch_next(worker.id(), storage.config.buffer_size)
+            .await
+            .expect("failed to poll jobs");
+        assert_eq!(fetched.len(), 3);
+        for (peeked, fetched) in peeked.iter().zip(&fetched) {
+            assert_eq!(peeked.parts.task_id, fetched.parts.task_id);
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use apalis_core::worker::Context;
-    use apalis_core::{generic_storage_test, sleep};
-    use email_service::Email;
+    #[tokio::test]
+    async fn test_list_jobs() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        storage
+            .schedule(example_email(), Utc::now().timestamp() + 60)
+            .await
+            .expect("failed to schedule a job");
+
+        let active = storage
+            .list_jobs_by_state(JobState::Active, 1, 2)
+            .await
+            .expect("failed to list jobs");
+        assert_eq!(active.len(), 2);
+        let active = storage
+            .list_jobs_by_state(JobState::Active, 2, 2)
+            .await
+            .expect("failed to list jobs");
+        assert_eq!(active.len(), 1);
+        let scheduled = storage
+            .list_jobs_by_state(JobState::Scheduled, 1, 10)
+            .await
+            .expect("failed to list jobs");
+        assert_eq!(scheduled.len(), 1);
+        let dead = storage
+            .list_jobs_by_state(JobState::Dead, 1, 10)
+            .await
+            .expect("failed to list jobs");
+        assert!(dead.is_empty());
+        // Listing does not consume the jobs
+        assert_eq!(storage.len().await.unwrap(), 3);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[tokio::test]
+    async fn test_msgpack_codec_round_trip() {
+        use apalis_core::codec::msgpack::MessagePackCodec;
+
+        let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
+        let conn = connect(redis_url).await.unwrap();
+        let config = Config::default().set_namespace("apalis::test::msgpack");
+        let mut storage: RedisStorage<Email, _, MessagePackCodec> =
+            RedisStorage::new_with_codec::<MessagePackCodec>(conn, config);
+        let _resp: String = redis::cmd("FLUSHDB")
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to Flushdb");
+
+        let parts = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let job = storage
+            .fetch_by_id(&parts.task_id)
+            .await
+            .expect("failed to fetch job by id")
+            .expect("no job found by id");
+        assert_eq!(job.args.to, example_email().to);
+        assert_eq!(job.args.subject, example_email().subject);
+        assert_eq!(job.args.text, example_email().text);
+    }
+
+    #[test]
+    fn test_zero_retry_budget_allows_one_retry_per_window() {
+        let window = Duration::from_secs(60);
+        let config = Config::default().set_retry_budget(0, window);
+        assert_eq!(config.get_retry_budget(), Some((1, window)));
+    }
+
+    #[test]
+    fn test_unset_context_fields_are_not_encoded() {
+        let mut ctx = RedisContext::default();
+        ctx.set_priority(2);
+        let json = JsonCodec::<Vec<u8>>::encode(&ctx).expect("failed to encode the context");
+        assert_eq!(json, br#"{"priority":2}"#);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn test_bincode_context_round_trip() {
+        use apalis_core::codec::bincode::BincodeCodec;
+
+        let mut ctx = RedisContext::default();
+        ctx.set_priority(2);
+        ctx.set_metadata("tenant", "acme");
+        let ctx: RedisContext = BincodeCodec::decode(BincodeCodec::encode(&ctx).unwrap())
+            .expect("failed to decode the context");
+        assert_eq!(ctx.priority(), 2);
+        assert_eq!(ctx.metadata()["tenant"], "acme");
+        assert_eq!(ctx.expires_at(), None);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[tokio::test]
+    async fn test_bincode_codec_round_trip() {
+        use apalis_core::codec::bincode::BincodeCodec;
+
+        let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
+        let conn = connect(redis_url).await.unwrap();
+        let config = Config::default().set_namespace("apalis::test::bincode");
+        let mut storage: RedisStorage<Email, _, BincodeCodec> =
+            RedisStorage::new_with_codec::<BincodeCodec>(conn, config);
+        let _resp: String = redis::cmd("FLUSHDB")
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to Flushdb");
+
+        let parts = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let job = storage
+            .fetch_by_id(&parts.task_id)
+            .await
+            .expect("failed to fetch job by id")
+            .expect("no job found by id");
+        assert_eq!(job.args.to, example_email().to);
+        assert_eq!(job.args.subject, example_email().subject);
+        assert_eq!(job.args.text, example_email().text);
+    }
+
+    #[cfg(feature = "deadpool")]
+    #[tokio::test]
+    async fn test_pooled_connection_round_trip() {
+        let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
+        let pool = deadpool_redis::Config::from_url(redis_url)
+            .create_pool(None)
+            .unwrap();
+        let config = Config::default().set_namespace("apalis::test::pool");
+        let mut storage: RedisStorage<Email, RedisConn> =
+            RedisStorage::new_with_config(RedisConn::Pooled(pool), config);
+        let _resp: String = redis::cmd("FLUSHDB")
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to Flushdb");
+
+        let task_ids = storage
+            .push_batch(vec![example_email(), example_email()])
+            .await
+            .expect("failed to push jobs");
+        let worker_id = WorkerId::new("test-worker");
+        storage
+            .keep_alive(&worker_id)
+            .await
+            .expect("failed to register worker");
+        let jobs = storage
+            .fetch_next(&worker_id, storage.config.buffer_size)
+            .await
+            .expect("failed to fetch jobs");
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].parts.task_id, task_ids[0]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_reenqueues_inflight_jobs() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let _job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(storage.len().await.unwrap(), 0);
+
+        let count = storage
+            .shutdown(worker.id())
+            .await
+            .expect("failed to shutdown");
+        assert_eq!(count, 2);
+        assert_eq!(storage.len().await.unwrap(), 2);
+
+        let count = storage
+            .shutdown(worker.id())
+            .await
+            .expect("failed to shutdown");
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reenqueue_stale_jobs_of_live_worker() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+
+        let worker = register_worker(&mut storage).await;
+        let _job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(storage.len().await.unwrap(), 0);
+
+        let count = storage
+            .reenqueue_stale(100, Utc::now() - chrono::Duration::seconds(60))
+            .await
+            .expect("failed to reenqueue stale jobs");
+        assert_eq!(count, 0);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        let count = storage
+            .reenqueue_stale(100, Utc::now())
+            .await
+            .expect("failed to reenqueue stale jobs");
+        assert_eq!(count, 1);
+        assert_eq!(storage.len().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_push_unique_skips_pending_duplicates() {
+        let mut storage = setup().await;
+        let first = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        assert!(first.was_new);
+        let second = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        assert_eq!(first.task_id, second.task_id);
+        assert!(!second.was_new);
+        assert_eq!(storage.len().await.unwrap(), 1);
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.unique_key(), Some("user-1"));
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success(
+                    42usize,
+                    job.parts.task_id.clone(),
+                    job.parts.attempt.clone(),
+                ),
+            )
+            .await
+            .expect("failed to acknowledge the job");
+
+        let third = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        assert_ne!(first.task_id, third.task_id);
+        assert!(third.was_new);
+        assert_eq!(storage.len().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ack_failed_releases_the_unique_key() {
+        let mut storage = setup().await;
+        let first = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_failed(worker.id(), &job.parts.task_id, Some("Mailbox unavailable"))
+            .await
+            .expect("failed to acknowledge the failed job");
+        let failed = get_job(&mut storage, &job.parts.task_id).await;
+        assert_eq!(
+            failed.parts.context.last_error(),
+            Some("Mailbox unavailable")
+        );
+        assert_eq!(storage.get_result(&job.parts.task_id).await.unwrap(), None);
+
+        let second = storage
+            .push_unique(example_email(), "user-1")
+            .await
+            .expect("failed to push a unique job");
+        assert_ne!(first.task_id, second.task_id);
+        assert!(second.was_new);
+    }
+
+    #[tokio::test]
+    async fn test_push_with_outcome_reports_duplicates() {
+        #[derive(Debug)]
+        struct FixedFactory(TaskId);
+
+        impl TaskIdFactory for FixedFactory {
+            fn next_id(&self) -> TaskId {
+                self.0.clone()
+            }
+        }
+
+        let mut storage: RedisStorage<Email> = setup().await;
+        let task_id = TaskId::new();
+        storage.config = storage
+            .config
+            .clone()
+            .set_task_id_factory(FixedFactory(task_id.clone()));
+
+        let first = storage
+            .push_with_outcome(example_email())
+            .await
+            .expect("failed to push a job");
+        assert_eq!(
+            first,
+            PushOutcome {
+                task_id: task_id.clone(),
+                was_new: true
+            }
+        );
+        let second = storage
+            .push_with_outcome(example_email())
+            .await
+            .expect("failed to push a job");
+        assert_eq!(second.task_id, task_id);
+        assert!(!second.was_new);
+        assert_eq!(storage.len().await.expect("failed to get len"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_in() {
+        let mut storage = setup().await;
+        let before = Utc::now().timestamp();
+        let task_id = storage
+            .schedule_in(example_email(), Duration::from_secs(60))
+            .await
+            .expect("failed to schedule a job");
+        let on: i64 = redis::cmd("ZSCORE")
+            .arg(storage.config.scheduled_jobs_set())
+            .arg(task_id.to_string())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to get the schedule");
+        assert!(on >= before + 60 && on <= Utc::now().timestamp() + 60);
+
+        let err = storage
+            .schedule_in(example_email(), Duration::MAX)
+            .await
+            .expect_err("an out of range delay must be rejected");
+        assert!(matches!(err, RedisStorageError::InvalidDuration(_)));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_cron_rejects_invalid_expressions() {
+        let mut storage = setup().await;
+        let err = storage
+            .schedule_cron(example_email(), "every monday")
+            .await
+            .expect_err("an invalid expression must be rejected");
+        assert!(matches!(err, RedisStorageError::InvalidCron(_)));
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.scheduled, 0);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_cron_reschedules_on_ack() {
+        let mut storage = setup().await;
+        let task_id = storage
+            .schedule_cron(example_email(), "* * * * * *")
+            .await
+            .expect("failed to schedule a cron job");
+        let job = get_job(&mut storage, &task_id).await;
+        assert_eq!(job.parts.context.cron(), Some("* * * * * *"));
+
+        sleep(Duration::from_secs(2)).await;
+        storage
+            .enqueue_scheduled(10)
+            .await
+            .expect("failed to enqueue scheduled jobs");
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, task_id);
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
+            .await
+            .expect("failed to acknowledge the job");
 
-    use apalis_core::test_utils::apalis_test_service_fn;
-    use apalis_core::test_utils::TestWrapper;
+        let scheduled = storage
+            .list_jobs_by_state(JobState::Scheduled, 1, 10)
+            .await
+            .expect("failed to list scheduled jobs");
+        assert_eq!(scheduled.len(), 1);
+        assert_ne!(scheduled[0].parts.task_id, task_id);
+        assert_eq!(scheduled[0].parts.context.cron(), Some("* * * * * *"));
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.done, 1);
+    }
 
-    generic_storage_test!(setup);
+    #[tokio::test]
+    async fn test_schedule_cron_reschedules_on_ack_by_id() {
+        let mut storage = setup().await;
+        let task_id = storage
+            .schedule_cron(example_email(), "* * * * * *")
+            .await
+            .expect("failed to schedule a cron job");
+        sleep(Duration::from_secs(2)).await;
+        storage
+            .enqueue_scheduled(10)
+            .await
+            .expect("failed to enqueue scheduled jobs");
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack_by_id(&task_id)
+            .await
+            .expect("failed to ack by id");
 
-    use super::*;
+        let scheduled = storage
+            .list_jobs_by_state(JobState::Scheduled, 1, 10)
+            .await
+            .expect("failed to list scheduled jobs");
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].parts.context.cron(), Some("* * * * * *"));
+    }
 
-    /// migrate DB and return a storage instance.
-    async fn setup<T: Serialize + DeserializeOwned>() -> RedisStorage<T> {
-        let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
-        // Because connections cannot be shared across async runtime
-        // (different runtimes are created for each test),
-        // we don't share the storage and tests must be run sequentially.
-        let conn = connect(redis_url).await.unwrap();
-        let config = Config::default()
-            .set_namespace("apalis::test")
-            .set_enqueue_scheduled(Duration::from_millis(500)); // Instantly return jobs to the queue
-        let mut storage = RedisStorage::new_with_config(conn, config);
-        cleanup(&mut storage, &WorkerId::new("test-worker")).await;
+    #[tokio::test]
+    async fn test_schedule_cron_reschedules_on_kill() {
+        let mut storage = setup().await;
+        let task_id = storage
+            .schedule_cron(example_email(), "* * * * * *")
+            .await
+            .expect("failed to schedule a cron job");
+        sleep(Duration::from_secs(2)).await;
+        storage
+            .enqueue_scheduled(10)
+            .await
+            .expect("failed to enqueue scheduled jobs");
+        let worker = register_worker(&mut storage).await;
+        consume_one(&mut storage, worker.id()).await;
         storage
+            .kill(
+                worker.id(),
+                &task_id,
+                &(Box::new(io::Error::new(io::ErrorKind::Interrupted, "killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill job");
+
+        let scheduled = storage
+            .list_jobs_by_state(JobState::Scheduled, 1, 10)
+            .await
+            .expect("failed to list scheduled jobs");
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].parts.context.cron(), Some("* * * * * *"));
     }
 
-    /// rollback DB changes made by tests.
-    ///
-    /// You should execute this function in the end of a test
-    async fn cleanup<T>(storage: &mut RedisStorage<T>, _worker_id: &WorkerId) {
-        let _resp: String = redis::cmd("FLUSHDB")
+    #[tokio::test]
+    async fn test_schedule_cron_reschedules_on_expiry() {
+        let mut storage = setup().await;
+        let task_id = storage
+            .schedule_cron(example_email(), "* * * * * *")
+            .await
+            .expect("failed to schedule a cron job");
+        let _: () = redis::cmd("HSET")
+            .arg(storage.config.job_expiry_hash())
+            .arg(storage.config.task_key(&task_id))
+            .arg(1)
             .query_async(&mut storage.conn)
             .await
-            .expect("failed to Flushdb");
-    }
+            .expect("failed to expire the job");
+        sleep(Duration::from_secs(2)).await;
+        storage
+            .enqueue_scheduled(10)
+            .await
+            .expect("failed to enqueue scheduled jobs");
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 10)
+            .await
+            .expect("failed to fetch jobs");
+        assert!(jobs.is_empty());
 
-    fn example_email() -> Email {
-        Email {
-            subject: "Test Subject".to_string(),
-            to: "example@postgres".to_string(),
-            text: "Some Text".to_string(),
-        }
+        let scheduled = storage
+            .list_jobs_by_state(JobState::Scheduled, 1, 10)
+            .await
+            .expect("failed to list scheduled jobs");
+        assert_eq!(scheduled.len(), 1);
+        assert_ne!(scheduled[0].parts.task_id, task_id);
     }
 
-    async fn consume_one(
-        storage: &mut RedisStorage<Email>,
-        worker_id: &WorkerId,
-    ) -> Request<Email, RedisContext> {
-        let stream = storage.fetch_next(worker_id);
-        stream
+    #[tokio::test]
+    async fn test_expired_group_jobs_count_as_failed() {
+        let mut storage = setup().await;
+        let ids = storage
+            .push_group(vec![example_email()], "batch-expired")
             .await
-            .expect("failed to poll job")
-            .first()
-            .expect("stream is empty")
-            .clone()
+            .expect("failed to push group");
+        let _: () = redis::cmd("HSET")
+            .arg(storage.config.job_expiry_hash())
+            .arg(storage.config.task_key(&ids[0]))
+            .arg(1)
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to expire the job");
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 10)
+            .await
+            .expect("failed to fetch jobs");
+        assert!(jobs.is_empty());
+
+        let status = storage.group_status("batch-expired").await.unwrap();
+        assert_eq!(status.failed, 1);
+        assert!(status.is_complete());
+        let group: Option<String> = redis::cmd("HGET")
+            .arg(storage.config.job_group_hash())
+            .arg(storage.config.task_key(&ids[0]))
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+        assert!(group.is_none());
     }
 
-    async fn register_worker_at(storage: &mut RedisStorage<Email>) -> Worker<Context> {
-        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
-        worker.start();
+    #[tokio::test]
+    async fn test_expired_job_is_never_delivered() {
+        let mut storage = setup().await;
         storage
-            .keep_alive(&worker.id())
+            .push_with_ttl(example_email(), Duration::from_secs(1))
             .await
-            .expect("failed to register worker");
-        worker
-    }
+            .expect("failed to push a job");
 
-    async fn register_worker(storage: &mut RedisStorage<Email>) -> Worker<Context> {
-        register_worker_at(storage).await
-    }
+        tokio::time::sleep(Duration::from_secs(2)).await;
 
-    async fn push_email(storage: &mut RedisStorage<Email>, email: Email) {
-        storage.push(email).await.expect("failed to push a job");
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), storage.config.buffer_size)
+            .await
+            .expect("failed to fetch jobs");
+        assert!(jobs.is_empty());
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.inflight, 0);
+        assert_eq!(stats.dead, 1);
     }
 
-    async fn get_job(
-        storage: &mut RedisStorage<Email>,
-        job_id: &TaskId,
-    ) -> Request<Email, RedisContext> {
+    #[tokio::test]
+    async fn test_queue_stats() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
         storage
-            .fetch_by_id(job_id)
+            .schedule(example_email(), Utc::now().timestamp() + 60)
             .await
-            .expect("failed to fetch job by id")
-            .expect("no job found by id")
+            .expect("failed to schedule a job");
+
+        let worker = register_worker(&mut storage).await;
+        let _job = consume_one(&mut storage, worker.id()).await;
+
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.pending, 0);
+        assert_eq!(stats.scheduled, 1);
+        assert_eq!(stats.inflight, 2);
+        assert_eq!(stats.done, 0);
+        assert_eq!(stats.failed, 0);
+        assert_eq!(stats.dead, 0);
     }
 
     #[tokio::test]
-    async fn test_consume_last_pushed_job() {
+    async fn test_list_workers() {
         let mut storage = setup().await;
+        assert!(storage
+            .list_workers()
+            .await
+            .expect("failed to list workers")
+            .is_empty());
+
         push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+        let worker = register_worker(&mut storage).await;
+        let _job = consume_one(&mut storage, worker.id()).await;
+
+        let workers = storage
+            .list_workers()
+            .await
+            .expect("failed to list workers");
+        assert_eq!(workers.len(), 1);
+        assert_eq!(&workers[0].id, worker.id());
+        assert_eq!(workers[0].inflight_count, 2);
+        assert!(workers[0].last_seen <= Utc::now().timestamp());
+        assert_eq!(storage.list_workers().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_clear_only_removes_own_namespace() {
+        let mut storage = setup().await;
+        let redis_url = std::env::var("REDIS_URL").expect("No REDIS_URL is specified");
+        let conn = connect(redis_url).await.unwrap();
+        let mut other: RedisStorage<Email> = RedisStorage::new_with_config(
+            conn,
+            Config::default().set_namespace("apalis::test::other"),
+        );
+        other.clear().await.expect("failed to clear the queue");
 
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut storage, example_email()).await;
+        push_email(&mut other, example_email()).await;
         let worker = register_worker(&mut storage).await;
+        let _job = consume_one(&mut storage, worker.id()).await;
 
-        let _job = consume_one(&mut storage, &worker.id()).await;
+        storage.clear().await.expect("failed to clear the queue");
+
+        assert_eq!(storage.len().await.unwrap(), 0);
+        let stats = storage.queue_stats().await.expect("failed to get stats");
+        assert_eq!(stats.inflight, 0);
+        assert_eq!(other.len().await.unwrap(), 1);
     }
 
     #[tokio::test]
-    async fn test_acknowledge_job() {
+    async fn test_set_progress() {
         let mut storage = setup().await;
         push_email(&mut storage, example_email()).await;
 
         let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.context.progress(), 0);
 
-        let job = consume_one(&mut storage, &worker.id()).await;
-        let ctx = &job.parts.context;
-        let res = 42usize;
         storage
-            .ack(
-                ctx,
-                &Response::success(res, job.parts.task_id.clone(), job.parts.attempt.clone()),
-            )
+            .set_progress(&job.parts.task_id, 42)
             .await
-            .expect("failed to acknowledge the job");
+            .expect("failed to set progress");
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        assert_eq!(job.parts.context.progress(), 42);
 
-        let _job = get_job(&mut storage, &job.parts.task_id).await;
+        storage
+            .set_progress(&job.parts.task_id, 250)
+            .await
+            .expect("failed to set progress");
+        let job = get_job(&mut storage, &job.parts.task_id).await;
+        assert_eq!(job.parts.context.progress(), 100);
+
+        // Killing the job drops its progress
+        storage
+            .kill(
+                worker.id(),
+                &job.parts.task_id,
+                &(Box::new(io::Error::other("killed")) as BoxDynError),
+            )
+            .await
+            .expect("failed to kill job");
+        let progress: bool = redis::cmd("HEXISTS")
+            .arg(storage.config.job_progress_hash())
+            .arg(storage.config.task_key(&job.parts.task_id))
+            .query_async(&mut storage.conn)
+            .await
+            .unwrap();
+        assert!(!progress);
     }
 
     #[tokio::test]
-    async fn test_kill_job() {
+    async fn test_dead_letter_stream() {
         let mut storage = setup().await;
-
         push_email(&mut storage, example_email()).await;
 
         let worker = register_worker(&mut storage).await;
-
-        let job = consume_one(&mut storage, &worker.id()).await;
-        let job_id = &job.parts.task_id;
-
+        let job = consume_one(&mut storage, worker.id()).await;
         storage
             .kill(
-                &worker.id(),
-                &job_id,
+                worker.id(),
+                &job.parts.task_id,
                 &(Box::new(io::Error::new(
                     io::ErrorKind::Interrupted,
-                    "Some unforeseen error occurred",
+                    "Max retries exceeded",
                 )) as BoxDynError),
             )
             .await
             .expect("failed to kill job");
 
-        let _job = get_job(&mut storage, &job_id).await;
+        let mut dead_letters = storage.dead_letter_stream().boxed();
+        let dead = dead_letters
+            .next()
+            .await
+            .expect("stream is empty")
+            .expect("failed to read dead letter");
+        assert_eq!(dead.parts.task_id, job.parts.task_id);
+        assert_eq!(dead.parts.attempt.current(), job.parts.attempt.current());
     }
 
     #[tokio::test]
-    async fn test_heartbeat_renqueueorphaned_pulse_last_seen_1sec() {
+    async fn test_dead_letter_list_is_capped_by_the_stream_backlog() {
         let mut storage = setup().await;
+        storage.config = storage.config.clone().set_stream_backlog(1);
+        for _ in 0..2 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 2)
+            .await
+            .expect("failed to fetch jobs");
+        for job in &jobs {
+            storage
+                .kill(
+                    worker.id(),
+                    &job.parts.task_id,
+                    &(Box::new(io::Error::new(io::ErrorKind::Interrupted, "killed"))
+                        as BoxDynError),
+                )
+                .await
+                .expect("failed to kill job");
+        }
 
-        push_email(&mut storage, example_email()).await;
+        let mut dead_letters = storage.dead_letter_stream().boxed();
+        let dead = dead_letters
+            .next()
+            .await
+            .expect("stream is empty")
+            .expect("failed to read dead letter");
+        assert_eq!(dead.parts.task_id, jobs[1].parts.task_id);
+        let len: usize = redis::cmd("LLEN")
+            .arg(storage.config.dead_letter_list())
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the dead letter list");
+        assert_eq!(len, 0);
+    }
 
-        let worker = register_worker_at(&mut storage).await;
+    #[tokio::test]
+    async fn test_completed_stream() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
 
-        let job = consume_one(&mut storage, &worker.id()).await;
-        sleep(Duration::from_millis(1000)).await;
-        let dead_since = Utc::now() - chrono::Duration::from_std(Duration::from_secs(1)).unwrap();
-        let res = storage
-            .reenqueue_orphaned(1, dead_since)
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        storage
+            .ack(
+                &job.parts.context,
+                &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+            )
             .await
-            .expect("failed to reenqueue_orphaned");
-        // We expect 1 job to be re-enqueued
-        assert_eq!(res, 1);
-        let job = get_job(&mut storage, &job.parts.task_id).await;
-        let ctx = &job.parts.context;
-        // assert_eq!(*ctx.status(), State::Pending);
-        // assert!(ctx.done_at().is_none());
-        assert!(ctx.lock_by.is_none());
-        // assert!(ctx.lock_at().is_none());
-        // assert_eq!(*ctx.last_error(), Some("Job was abandoned".to_owned()));
-        // TODO: Redis should store context aside
-        // assert_eq!(job.parts.attempt.current(), 1);
+            .expect("failed to acknowledge the job");
+
+        let mut completed = storage.completed_stream();
+        let task_id = completed.next().await.expect("stream is empty");
+        assert_eq!(task_id, job.parts.task_id);
+
+        storage.controller.stop();
+        assert!(completed.next().await.is_none());
     }
 
     #[tokio::test]
-    async fn test_heartbeat_renqueueorphaned_pulse_last_seen_5sec() {
+    async fn test_completed_list_is_capped_by_the_stream_backlog() {
         let mut storage = setup().await;
+        storage.config = storage.config.clone().set_stream_backlog(2);
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+        let worker = register_worker(&mut storage).await;
+        let jobs = storage
+            .fetch_next(worker.id(), 3)
+            .await
+            .expect("failed to fetch jobs");
+        for job in &jobs {
+            storage
+                .ack(
+                    &job.parts.context,
+                    &Response::success((), job.parts.task_id.clone(), job.parts.attempt.clone()),
+                )
+                .await
+                .expect("failed to acknowledge the job");
+        }
+        let completed: Vec<String> = redis::cmd("LRANGE")
+            .arg(storage.config.completed_jobs_list())
+            .arg(0)
+            .arg(-1)
+            .query_async(&mut storage.conn)
+            .await
+            .expect("failed to read the completed jobs list");
+        let newest: Vec<String> = jobs[1..]
+            .iter()
+            .map(|job| storage.config.task_key(&job.parts.task_id))
+            .collect();
+        assert_eq!(completed, newest);
+    }
+
+    #[tokio::test]
+    async fn test_custom_task_id_factory() {
+        #[derive(Debug, Default)]
+        struct RecordingFactory(std::sync::Mutex<Vec<TaskId>>);
+
+        impl TaskIdFactory for Arc<RecordingFactory> {
+            fn next_id(&self) -> TaskId {
+                let id = TaskId::new();
+                self.0.lock().unwrap().push(id.clone());
+                id
+            }
+        }
 
+        let factory = Arc::new(RecordingFactory::default());
+        let mut storage: RedisStorage<Email> = setup().await;
+        storage.config = storage.config.clone().set_task_id_factory(factory.clone());
+
+        let parts = storage
+            .push(example_email())
+            .await
+            .expect("failed to push a job");
+        let scheduled = storage
+            .schedule(example_email(), Utc::now().timestamp() + 60)
+            .await
+            .expect("failed to schedule a job");
+        assert_eq!(
+            *factory.0.lock().unwrap(),
+            vec![parts.task_id.clone(), scheduled.task_id]
+        );
+
+        let job = get_job(&mut storage, &parts.task_id).await;
+        assert_eq!(job.parts.task_id, parts.task_id);
+    }
+
+    #[tokio::test]
+    async fn test_max_payload_bytes() {
+        let mut storage: RedisStorage<Email> = setup().await;
+        storage.config = storage.config.clone().set_max_payload_bytes(16);
+
+        let err = storage
+            .push(example_email())
+            .await
+            .expect_err("an oversized job must be rejected");
+        assert!(matches!(
+            err,
+            RedisStorageError::PayloadTooLarge { limit: 16, .. }
+        ));
+        assert_eq!(storage.len().await.expect("failed to get len"), 0);
+
+        storage.config = storage.config.clone().set_max_payload_bytes(0);
         push_email(&mut storage, example_email()).await;
+        assert_eq!(storage.len().await.expect("failed to get len"), 1);
+    }
 
-        let worker = register_worker_at(&mut storage).await;
-        sleep(Duration::from_millis(1100)).await;
-        let job = consume_one(&mut storage, &worker.id()).await;
-        let dead_since = Utc::now() - chrono::Duration::from_std(Duration::from_secs(5)).unwrap();
-        let res = storage
-            .reenqueue_orphaned(1, dead_since)
+    #[tokio::test]
+    async fn test_count_scheduled_due() {
+        let mut storage = setup().await;
+        let now = Utc::now().timestamp();
+        for on in [now - 60, now - 30, now - 1, now + 60, now + 120] {
+            storage
+                .schedule(example_email(), on)
+                .await
+                .expect("failed to schedule a job");
+        }
+
+        let due = storage
+            .count_scheduled_due()
             .await
-            .expect("failed to reenqueue_orphaned");
-        // We expect 0 job to be re-enqueued
-        assert_eq!(res, 0);
-        let job = get_job(&mut storage, &job.parts.task_id).await;
-        let _ctx = &job.parts.context;
-        // assert_eq!(*ctx.status(), State::Running);
-        // TODO: update redis context
-        // assert_eq!(ctx.lock_by, Some(worker_id));
-        // assert!(ctx.lock_at().is_some());
-        // assert_eq!(*ctx.last_error(), None);
-        assert_eq!(job.parts.attempt.current(), 0);
+            .expect("failed to count the due jobs");
+        assert_eq!(due, 3);
     }
 
     #[tokio::test]
@@ -1229,7 +8185,7 @@ mod tests {
 
         let worker = register_worker(&mut storage).await;
 
-        let _job = consume_one(&mut storage, &worker.id()).await;
+        let _job = consume_one(&mut storage, worker.id()).await;
 
         let stats = storage.stats().await.expect("failed to get stats");
         assert_eq!(stats.pending, 0);
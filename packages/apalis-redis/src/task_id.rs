@@ -0,0 +1,148 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+use std::sync::{Mutex, PoisonError};
+
+use apalis_core::task::task_id::TaskId;
+use ulid::{Generator, Ulid};
+
+use crate::RedisStorageError;
+
+/// Generates the [TaskId] of jobs pushed or scheduled by a [RedisStorage](crate::RedisStorage)
+///
+/// Task ids are ULIDs, a factory can choose their timestamp and randomness, for example with
+/// `TaskId::from(Ulid::from_parts(timestamp, random))`. Jobs of equal priority are fetched in the
+/// order of their ids, so a factory should generate increasing ids to keep them first in, first
+/// out.
+pub trait TaskIdFactory: Debug + Send + Sync {
+    /// Generate the id of the next job
+    fn next_id(&self) -> TaskId;
+}
+
+/// Generates a new ULID for every job, in increasing order within the process
+///
+/// ULIDs created in the same millisecond differ only by their random part, so jobs of equal
+/// priority pushed in a burst would be fetched in random order. Instead the random part is
+/// incremented within a millisecond, so that ids sort in the order the jobs were pushed.
+#[derive(Debug, Clone, Default)]
+pub struct UlidFactory;
+
+static ULID_GENERATOR: Mutex<Generator> = Mutex::new(Generator::new());
+
+impl TaskIdFactory for UlidFactory {
+    fn next_id(&self) -> TaskId {
+        let next = ULID_GENERATOR
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .generate();
+        // The random part only overflows after 2^80 ids in one millisecond
+        TaskId::from(next.unwrap_or_else(|_| Ulid::new()))
+    }
+}
+
+/// How task ids are written in the keys and sets of a [RedisStorage](crate::RedisStorage)
+///
+/// Every job id is stored several times, as a field of the job hashes and as a member of the set
+/// of its state, so shorter ids save memory on large queues. With 1M jobs [TaskIdEncoding::Base62]
+/// saves 4 bytes per stored id, about 4 MB for each hash or set holding all of them. Both
+/// encodings sort like the ids they encode, which [FetchOrder](crate::FetchOrder) relies on.
+///
+/// The encoding only applies to keys, the payload of a job keeps its id as a ULID. Changing the
+/// encoding of a namespace that holds jobs orphans them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TaskIdEncoding {
+    /// The 26 characters of the ULID in Crockford's base32
+    #[default]
+    Ulid,
+    /// 22 characters in base62
+    Base62,
+}
+
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE62_LEN: usize = 22;
+
+impl TaskIdEncoding {
+    /// Encode a task id as it is stored in Redis
+    pub(crate) fn encode(self, task_id: &TaskId) -> String {
+        match self {
+            TaskIdEncoding::Ulid => task_id.to_string(),
+            TaskIdEncoding::Base62 => {
+                let mut value = u128::from(task_id.inner());
+                let mut encoded = [b'0'; BASE62_LEN];
+                for digit in encoded.iter_mut().rev() {
+                    *digit = BASE62[(value % 62) as usize];
+                    value /= 62;
+                }
+                encoded.iter().map(|&digit| digit as char).collect()
+            }
+        }
+    }
+
+    /// Decode a task id read from Redis
+    pub(crate) fn decode(self, encoded: &str) -> Result<TaskId, RedisStorageError> {
+        match self {
+            TaskIdEncoding::Ulid => {
+                TaskId::from_str(encoded).map_err(|e| RedisStorageError::Codec(e.into()))
+            }
+            TaskIdEncoding::Base62 => {
+                let invalid =
+                    || RedisStorageError::Codec(format!("invalid task id `{encoded}`").into());
+                if encoded.len() != BASE62_LEN {
+                    return Err(invalid());
+                }
+                let mut value: u128 = 0;
+                for byte in encoded.bytes() {
+                    let digit = BASE62.iter().position(|&c| c == byte).ok_or_else(invalid)?;
+                    value = value
+                        .checked_mul(62)
+                        .and_then(|value| value.checked_add(digit as u128))
+                        .ok_or_else(invalid)?;
+                }
+                Ok(TaskId::from(Ulid::from(value)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base62_round_trip() {
+        let encoding = TaskIdEncoding::Base62;
+        for task_id in [
+            TaskId::from(Ulid::nil()),
+            TaskId::from(Ulid::from(u128::MAX)),
+            TaskId::new(),
+        ] {
+            let encoded = encoding.encode(&task_id);
+            assert_eq!(encoded.len(), 22);
+            assert_eq!(encoding.decode(&encoded).unwrap(), task_id);
+        }
+        assert!(encoding.decode("not a task id").is_err());
+        assert!(encoding.decode("zzzzzzzzzzzzzzzzzzzzzz").is_err());
+    }
+
+    #[test]
+    fn test_ulid_factory_ids_increase() {
+        let ids: Vec<TaskId> = (0..1000).map(|_| UlidFactory.next_id()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0].inner() < pair[1].inner()));
+    }
+
+    #[test]
+    fn test_base62_keeps_order() {
+        let encoding = TaskIdEncoding::Base62;
+        let mut ids: Vec<TaskId> = (0..1000)
+            .map(|i| TaskId::from(Ulid::from(i * 7919)))
+            .collect();
+        ids.push(TaskId::new());
+        let mut encoded: Vec<String> = ids.iter().map(|id| encoding.encode(id)).collect();
+        encoded.sort();
+        let decoded: Vec<TaskId> = encoded
+            .iter()
+            .map(|id| encoding.decode(id).unwrap())
+            .collect();
+        ids.sort_by_key(|id| id.inner());
+        assert_eq!(decoded, ids);
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use apalis_core::error::{BoxDynError, Error};
+use apalis_core::layers::{Layer, Service};
+use apalis_core::request::Request;
+use futures::future::{self, BoxFuture, Either};
+use futures::FutureExt;
+
+use crate::{JobTimedOut, RedisContext};
+
+/// Fails a job with [JobTimedOut] when its handler runs longer than the timeout of the job
+///
+/// The handler future is dropped once the timeout elapses, so the job goes through the same
+/// retry path as any other failure. Jobs without a timeout run to completion.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JobTimeoutLayer;
+
+impl<S> Layer<S> for JobTimeoutLayer {
+    type Service = JobTimeoutService<S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        JobTimeoutService { service }
+    }
+}
+
+/// The service built by [JobTimeoutLayer]
+#[derive(Debug, Clone)]
+pub struct JobTimeoutService<S> {
+    service: S,
+}
+
+impl<S, T> Service<Request<T, RedisContext>> for JobTimeoutService<S>
+where
+    S: Service<Request<T, RedisContext>>,
+    S::Error: Into<BoxDynError> + Send + 'static,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx).map_err(into_error)
+    }
+
+    fn call(&mut self, request: Request<T, RedisContext>) -> Self::Future {
+        let timeout = request.parts.context.timeout();
+        let fut = self
+            .service
+            .call(request)
+            .map(|res| res.map_err(into_error));
+        match timeout {
+            None => fut.boxed(),
+            Some(timeout) => {
+                let sleep = apalis_core::sleep(timeout).boxed();
+                future::select(fut.boxed(), sleep)
+                    .map(move |either| match either {
+                        Either::Left((res, _)) => res,
+                        Either::Right(((), _)) => {
+                            Err(Error::Failed(Arc::new(Box::new(JobTimedOut(timeout)))))
+                        }
+                    })
+                    .boxed()
+            }
+        }
+    }
+}
+
+/// Keep errors that already are an [Error], such as an abort, as they are
+fn into_error<E: Into<BoxDynError>>(err: E) -> Error {
+    Error::from(err.into())
+}
+
+/// Whether the job failed because it ran longer than its timeout
+pub(crate) fn is_timeout(err: &Error) -> bool {
+    matches!(err, Error::Failed(e) if e.downcast_ref::<JobTimedOut>().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use apalis_core::service_fn::service_fn;
+
+    async fn sleepy(_job: u32) -> Result<u32, Error> {
+        apalis_core::sleep(Duration::from_secs(5)).await;
+        Ok(1)
+    }
+
+    #[tokio::test]
+    async fn sleepy_handler_times_out() {
+        let mut service = JobTimeoutLayer.layer(service_fn(sleepy));
+        let mut req: Request<u32, RedisContext> = Request::new(1);
+        req.parts
+            .context
+            .set_timeout(Some(Duration::from_millis(50)));
+
+        let started = std::time::Instant::now();
+        let err = service.call(req).await.unwrap_err();
+        assert!(is_timeout(&err));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn handler_without_timeout_runs_to_completion() {
+        let mut service = JobTimeoutLayer.layer(service_fn(|job: u32| async move {
+            apalis_core::sleep(Duration::from_millis(20)).await;
+            Ok::<_, Error>(job + 1)
+        }));
+        let req: Request<u32, RedisContext> = Request::new(1);
+        assert_eq!(service.call(req).await.unwrap(), 2);
+    }
+}
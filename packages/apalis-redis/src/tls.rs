@@ -0,0 +1,100 @@
+use redis::aio::ConnectionManager;
+use redis::{
+    Client, ClientTlsConfig, ConnectionAddr, ErrorKind, IntoConnectionInfo, RedisError,
+    TlsCertificates,
+};
+
+/// Certificates used by [connect_tls] to connect to Redis over TLS.
+///
+/// By default the server certificate is verified against the native trust store and no client
+/// certificate is sent. Certificates are given as PEM bytes.
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfig {
+    root_cert: Option<Vec<u8>>,
+    client_cert: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl TlsConfig {
+    /// Create a config that trusts the native trust store and sends no client certificate
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify the server certificate against this CA certificate instead of the native trust
+    /// store, such as the CA of a managed Redis
+    pub fn with_root_cert(mut self, root_cert: impl Into<Vec<u8>>) -> Self {
+        self.root_cert = Some(root_cert.into());
+        self
+    }
+
+    /// Authenticate with a client certificate chain and its private key, for mutual TLS
+    pub fn with_client_cert(
+        mut self,
+        client_cert: impl Into<Vec<u8>>,
+        client_key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.client_cert = Some((client_cert.into(), client_key.into()));
+        self
+    }
+}
+
+/// Shorthand to create a client and connect over TLS
+///
+/// The url must use the `rediss://` scheme, a `redis://` url is rejected instead of silently
+/// connecting without TLS. Invalid certificates are returned as
+/// [ErrorKind::InvalidClientConfig] errors.
+pub async fn connect_tls<S: IntoConnectionInfo>(
+    redis: S,
+    tls: TlsConfig,
+) -> Result<ConnectionManager, RedisError> {
+    let info = redis.into_connection_info()?;
+    if !matches!(info.addr, ConnectionAddr::TcpTls { .. }) {
+        return Err(RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "TLS connections require a rediss:// url",
+        )));
+    }
+    let certificates = TlsCertificates {
+        client_tls: tls
+            .client_cert
+            .map(|(client_cert, client_key)| ClientTlsConfig {
+                client_cert,
+                client_key,
+            }),
+        root_cert: tls.root_cert,
+    };
+    let client = Client::build_with_tls(info, certificates).map_err(|e| {
+        RedisError::from((
+            ErrorKind::InvalidClientConfig,
+            "invalid TLS certificate",
+            e.to_string(),
+        ))
+    })?;
+    client.get_connection_manager().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_tls_requires_rediss_scheme() {
+        let Err(err) = connect_tls("redis://127.0.0.1/", TlsConfig::new()).await else {
+            panic!("connected without TLS");
+        };
+        assert_eq!(err.kind(), ErrorKind::InvalidClientConfig);
+    }
+
+    #[tokio::test]
+    async fn test_connect_tls_rejects_invalid_certificate() {
+        let Err(err) = connect_tls(
+            "rediss://127.0.0.1/",
+            TlsConfig::new().with_client_cert("not a certificate", "not a key"),
+        )
+        .await
+        else {
+            panic!("connected with an invalid certificate");
+        };
+        assert_eq!(err.kind(), ErrorKind::InvalidClientConfig);
+    }
+}
@@ -0,0 +1,18 @@
+CREATE OR REPLACE FUNCTION apalis.notify_new_job_types() RETURNS trigger AS $$
+    DECLARE
+        v_channel text := coalesce(TG_ARGV[0], 'apalis::job');
+        v_job_type text;
+    BEGIN
+        FOR v_job_type IN SELECT DISTINCT job_type FROM new_jobs LOOP
+            PERFORM pg_notify(v_channel, v_job_type);
+        END LOOP;
+        RETURN NULL;
+    END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS notify_workers ON apalis.jobs;
+
+CREATE TRIGGER notify_workers
+    AFTER INSERT ON apalis.jobs
+    REFERENCING NEW TABLE AS new_jobs
+    FOR EACH STATEMENT EXECUTE PROCEDURE apalis.notify_new_job_types();
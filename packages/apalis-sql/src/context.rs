@@ -126,3 +126,17 @@ impl<Req> FromRequest<Request<Req, SqlContext>> for SqlContext {
         Ok(req.parts.context.clone())
     }
 }
+
+/// The lifecycle state of a job as read from the `status` column
+///
+/// Requests returned by `fetch_by_id` carry it in their extensions so callers can inspect it
+/// with `req.parts.data.get::<Status>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status(pub State);
+
+impl Status {
+    /// Get the state of the job
+    pub fn state(&self) -> &State {
+        &self.0
+    }
+}
@@ -1,14 +1,33 @@
+use apalis_core::codec::Codec;
 use apalis_core::request::Parts;
 use apalis_core::task::attempt::Attempt;
 use apalis_core::task::task_id::TaskId;
 use apalis_core::{request::Request, worker::WorkerId};
 
-use serde::{Deserialize, Serialize};
-use sqlx::{Decode, Type};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sqlx::{Database, Decode, Encode, Type};
 
 use crate::context::SqlContext;
+
+/// A [Codec::Compact] that can be stored in the `job` column of a jobs table
+///
+/// Implemented for every type sqlx can encode and decode, such as `serde_json::Value` for the
+/// default JSON columns or `Vec<u8>` for `bytea` and `BLOB` columns.
+pub trait SqlCompact<DB: Database>:
+    for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB> + Type<DB> + Send + Sync + Unpin + 'static
+{
+}
+
+impl<DB, T> SqlCompact<DB> for T
+where
+    DB: Database,
+    T: for<'q> Encode<'q, DB> + for<'r> Decode<'r, DB> + Type<DB> + Send + Sync + Unpin + 'static,
+{
+}
+
 /// Wrapper for [Request]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::manual_non_exhaustive)]
 pub struct SqlRequest<T> {
     /// The inner request
     pub req: Request<T, SqlContext>,
@@ -20,6 +39,20 @@ impl<T> SqlRequest<T> {
     pub fn new(req: Request<T, SqlContext>) -> Self {
         SqlRequest { req, _priv: () }
     }
+
+    /// Decodes the job with the codec of the storage it was fetched from
+    pub fn decode<J, C>(self) -> Result<Request<J, SqlContext>, sqlx::Error>
+    where
+        J: DeserializeOwned,
+        C: Codec<Compact = T>,
+    {
+        let (job, parts) = self.req.take_parts();
+        let job = C::decode(job).map_err(|e| sqlx::Error::ColumnDecode {
+            index: "job".to_string(),
+            source: e.into(),
+        })?;
+        Ok(Request::new_with_parts(job, parts))
+    }
 }
 
 #[cfg(feature = "sqlite")]
@@ -27,6 +27,7 @@
 use std::{num::TryFromIntError, time::Duration};
 
 use apalis_core::{error::Error, request::State, response::Response};
+use serde::Serialize;
 
 /// The context of the sql job
 pub mod context;
@@ -57,10 +58,52 @@ pub use sqlx;
 #[derive(Debug, Clone)]
 pub struct Config {
     keep_alive: Duration,
+    enqueue_scheduled: Duration,
     buffer_size: usize,
     poll_interval: Duration,
     reenqueue_orphaned_after: Duration,
     namespace: String,
+    schema_name: String,
+    table_name: String,
+    busy_timeout: Duration,
+    notify_channel: String,
+    done_retention: Duration,
+    scheduler_leader: bool,
+}
+
+/// Counts of the jobs of a namespace in each status, see `queue_stats` on the sql storages
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct QueueStats {
+    /// Jobs waiting to be fetched, including scheduled jobs
+    pub pending: usize,
+    /// Jobs currently locked by a worker
+    pub running: usize,
+    /// Jobs completed successfully
+    pub done: usize,
+    /// Jobs that failed and may be retried
+    pub failed: usize,
+    /// Jobs that are no longer retryable
+    pub killed: usize,
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+impl QueueStats {
+    /// Build the stats from the rows of a `GROUP BY status` count
+    pub(crate) fn from_counts(counts: Vec<(String, i64)>) -> Self {
+        let mut stats = QueueStats::default();
+        for (status, count) in counts {
+            let count: usize = count.try_into().unwrap_or_default();
+            match status.parse() {
+                Ok(State::Pending) => stats.pending += count,
+                Ok(State::Running) => stats.running += count,
+                Ok(State::Done) => stats.done += count,
+                Ok(State::Failed) => stats.failed += count,
+                Ok(State::Killed) => stats.killed += count,
+                _ => {}
+            }
+        }
+        stats
+    }
 }
 
 /// A general sql error
@@ -72,16 +115,26 @@ pub enum SqlError {
     /// Handles int conversion errors
     #[error("TryFromIntError: {0}")]
     TryFromInt(#[from] TryFromIntError),
+    /// Handles identifiers that are not safe to interpolate into queries
+    #[error("Invalid identifier: `{0}`")]
+    InvalidIdentifier(String),
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             keep_alive: Duration::from_secs(30),
+            enqueue_scheduled: Duration::from_secs(30),
             buffer_size: 10,
             poll_interval: Duration::from_millis(100),
             reenqueue_orphaned_after: Duration::from_secs(300), // 5 minutes
             namespace: String::from("apalis::sql"),
+            schema_name: String::from("apalis"),
+            table_name: String::from("jobs"),
+            busy_timeout: Duration::from_secs(5),
+            notify_channel: String::from("apalis::job"),
+            done_retention: Duration::ZERO,
+            scheduler_leader: false,
         }
     }
 }
@@ -108,6 +161,15 @@ impl Config {
         self
     }
 
+    /// Interval between checks for scheduled jobs that became due
+    ///
+    /// Only the Postgres storage acts on the check, by announcing the due jobs on its notify
+    /// channel. Defaults to 30s
+    pub fn set_enqueue_scheduled(mut self, enqueue_scheduled: Duration) -> Self {
+        self.enqueue_scheduled = enqueue_scheduled;
+        self
+    }
+
     /// Buffer size to use when querying for jobs
     ///
     /// Defaults to 10
@@ -134,6 +196,16 @@ impl Config {
         &mut self.keep_alive
     }
 
+    /// Gets a reference to the enqueue_scheduled duration.
+    pub fn enqueue_scheduled(&self) -> &Duration {
+        &self.enqueue_scheduled
+    }
+
+    /// Gets a mutable reference to the enqueue_scheduled duration.
+    pub fn enqueue_scheduled_mut(&mut self) -> &mut Duration {
+        &mut self.enqueue_scheduled
+    }
+
     /// Gets the buffer size.
     pub fn buffer_size(&self) -> usize {
         self.buffer_size
@@ -177,6 +249,239 @@ impl Config {
         self.reenqueue_orphaned_after = after;
         self
     }
+
+    /// How long a Sqlite connection waits for a lock before failing with `database is locked`
+    ///
+    /// Only used by `SqliteStorage::connect_options` and `SqliteStorage::setup_with_pragmas`.
+    /// Defaults to 5s
+    pub fn set_busy_timeout(mut self, busy_timeout: Duration) -> Self {
+        self.busy_timeout = busy_timeout;
+        self
+    }
+
+    /// Gets the busy_timeout duration.
+    pub fn busy_timeout(&self) -> &Duration {
+        &self.busy_timeout
+    }
+
+    /// Set the schema of the jobs table, only used by the Postgres storage
+    ///
+    /// The name is interpolated into queries, so it must be a lowercase identifier of at most
+    /// 63 ascii letters, digits and underscores that does not start with a digit and is not a
+    /// reserved SQL keyword.
+    ///
+    /// Defaults to "apalis"
+    pub fn set_schema_name(mut self, schema_name: &str) -> Result<Self, SqlError> {
+        self.schema_name = validate_identifier(schema_name)?;
+        Ok(self)
+    }
+
+    /// Set the name of the jobs table, only used by the Postgres storage
+    ///
+    /// `PostgresStorage::setup_with_config` creates the table like `apalis.jobs` but does not
+    /// migrate it afterwards, columns added to `apalis.jobs` by later migrations must be added to
+    /// it by hand.
+    ///
+    /// The name is interpolated into queries, so it must be a lowercase identifier of at most
+    /// 63 ascii letters, digits and underscores that does not start with a digit and is not a
+    /// reserved SQL keyword.
+    ///
+    /// Defaults to "jobs"
+    pub fn set_table_name(mut self, table_name: &str) -> Result<Self, SqlError> {
+        self.table_name = validate_identifier(table_name)?;
+        Ok(self)
+    }
+
+    /// Gets the schema of the jobs table.
+    pub fn schema_name(&self) -> &str {
+        &self.schema_name
+    }
+
+    /// Gets the name of the jobs table.
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Set the channel new jobs are announced on, only used by the Postgres storage
+    ///
+    /// The channel is attached to the jobs table by `PostgresStorage::setup_with_config`, so
+    /// storages sharing a table share its channel. The name is interpolated into queries, so it
+    /// must be at most 63 ascii letters, digits, underscores and colons.
+    ///
+    /// Defaults to "apalis::job"
+    pub fn set_notify_channel(mut self, channel: &str) -> Result<Self, SqlError> {
+        let valid = !channel.is_empty()
+            && channel.len() <= 63
+            && channel
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+        if !valid {
+            return Err(SqlError::InvalidIdentifier(channel.to_owned()));
+        }
+        self.notify_channel = channel.to_owned();
+        Ok(self)
+    }
+
+    /// Gets the channel new jobs are announced on.
+    pub fn notify_channel(&self) -> &str {
+        &self.notify_channel
+    }
+
+    /// How long `Done` and `Killed` jobs are kept before `vacuum` deletes them
+    ///
+    /// When non-zero, workers also vacuum their namespace on every keep-alive. Defaults to zero,
+    /// which keeps finished jobs until `vacuum` is called and then deletes all of them
+    pub fn set_done_retention(mut self, retention: Duration) -> Self {
+        self.done_retention = retention;
+        self
+    }
+
+    /// Gets the done_retention duration.
+    pub fn done_retention(&self) -> Duration {
+        self.done_retention
+    }
+
+    /// Elect a single worker of the namespace to run the maintenance heartbeats, only used by the
+    /// Postgres storage
+    ///
+    /// A storage and its clones elect once and hold a `pg_advisory_lock` on a single connection
+    /// while their workers lead, the others skip reenqueuing orphaned jobs and vacuuming and take
+    /// over once the leader's connection closes. Defaults to false, where every worker runs them
+    pub fn set_scheduler_leader(mut self, scheduler_leader: bool) -> Self {
+        self.scheduler_leader = scheduler_leader;
+        self
+    }
+
+    /// Gets whether a single worker of the namespace runs the maintenance heartbeats.
+    pub fn scheduler_leader(&self) -> bool {
+        self.scheduler_leader
+    }
+
+    /// The schema qualified jobs table, eg `apalis.jobs`
+    #[cfg(feature = "postgres")]
+    pub(crate) fn jobs_table(&self) -> String {
+        format!("{}.{}", self.schema_name, self.table_name)
+    }
+}
+
+/// The Postgres keywords that can't name a table or schema unless quoted, sorted
+const RESERVED_KEYWORDS: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "binary",
+    "both",
+    "case",
+    "cast",
+    "check",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "false",
+    "fetch",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "group",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "intersect",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "natural",
+    "not",
+    "notnull",
+    "null",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "outer",
+    "overlaps",
+    "placing",
+    "primary",
+    "references",
+    "returning",
+    "right",
+    "select",
+    "session_user",
+    "similar",
+    "some",
+    "symmetric",
+    "system_user",
+    "table",
+    "tablesample",
+    "then",
+    "to",
+    "trailing",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+];
+
+fn validate_identifier(name: &str) -> Result<String, SqlError> {
+    let mut chars = name.chars();
+    let valid = name.len() <= 63
+        && chars
+            .next()
+            .is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && RESERVED_KEYWORDS.binary_search(&name).is_err();
+    if valid {
+        Ok(name.to_owned())
+    } else {
+        Err(SqlError::InvalidIdentifier(name.to_owned()))
+    }
 }
 
 /// Calculates the status from a result
@@ -249,6 +554,109 @@ macro_rules! sql_storage_tests {
             );
         }
 
+        #[tokio::test]
+        async fn integration_test_fetch_by_id_status() {
+            let mut storage = setup_test_wrapper().await;
+
+            let task_id = storage
+                .push(email_service::example_good_email())
+                .await
+                .expect("failed to push a job")
+                .task_id;
+
+            let job = get_job(&mut storage, &task_id).await;
+            let status = job
+                .parts
+                .data
+                .get::<$crate::context::Status>()
+                .expect("no status in extensions");
+            assert_eq!(status.state(), job.parts.context.status());
+        }
+
+        #[tokio::test]
+        async fn integration_test_reschedule_job() {
+            let mut storage = setup_test_wrapper().await;
+
+            let task_id = storage
+                .push(email_service::example_good_email())
+                .await
+                .expect("failed to push a job")
+                .task_id;
+
+            let mut job = get_job(&mut storage, &task_id).await;
+            job.parts.context.set_status(State::Failed);
+            storage.update(job).await.expect("updating to succeed");
+
+            let job = get_job(&mut storage, &task_id).await;
+            storage
+                .reschedule(job, Duration::from_secs(60))
+                .await
+                .expect("failed to reschedule the job");
+
+            let job = get_job(&mut storage, &task_id).await;
+            let ctx = job.parts.context;
+            assert_eq!(*ctx.status(), State::Pending);
+            assert!(ctx.lock_by().is_none());
+            assert!(ctx.run_at() > &Utc::now());
+        }
+
+        #[tokio::test]
+        async fn integration_test_cancel_job() {
+            let mut storage = setup_test_wrapper().await;
+
+            let task_id = storage
+                .schedule(
+                    email_service::example_good_email(),
+                    (Utc::now() + Duration::from_secs(60)).timestamp(),
+                )
+                .await
+                .expect("failed to schedule a job")
+                .task_id;
+            assert!(storage.cancel(&task_id).await.expect("failed to cancel"));
+            assert!(storage
+                .fetch_by_id(&task_id)
+                .await
+                .expect("failed to fetch the job")
+                .is_none());
+            assert!(!storage.cancel(&task_id).await.expect("failed to cancel"));
+
+            storage
+                .push(email_service::example_good_email())
+                .await
+                .expect("failed to push a job");
+            let (task_id, _) = storage.execute_next().await.unwrap();
+            apalis_core::sleep(Duration::from_secs(1)).await;
+            assert!(!storage.cancel(&task_id).await.expect("failed to cancel"));
+            let job = get_job(&mut storage, &task_id).await;
+            assert_eq!(*job.parts.context.status(), State::Done);
+        }
+
+        #[tokio::test]
+        async fn integration_test_vacuum_and_stats() {
+            let mut storage = setup_test_wrapper().await;
+
+            storage
+                .push(email_service::example_good_email())
+                .await
+                .expect("failed to push a job");
+            let (_, res) = storage.execute_next().await.unwrap();
+            assert!(res.is_ok());
+            apalis_core::sleep(Duration::from_secs(1)).await;
+            storage
+                .push(email_service::example_good_email())
+                .await
+                .expect("failed to push a job");
+
+            let stats = storage.queue_stats().await.expect("failed to get stats");
+            assert_eq!(stats.pending, 1);
+            assert_eq!(stats.done, 1);
+
+            assert_eq!(storage.vacuum_done().await.expect("failed to vacuum"), 1);
+            let stats = storage.queue_stats().await.expect("failed to get stats");
+            assert_eq!(stats.pending, 1);
+            assert_eq!(stats.done, 0);
+        }
+
         #[tokio::test]
         async fn integration_test_update_job() {
             let mut storage = setup_test_wrapper().await;
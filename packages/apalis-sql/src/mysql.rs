@@ -16,7 +16,6 @@ use apalis_core::{backend::Backend, codec::Codec};
 use async_stream::try_stream;
 use chrono::{DateTime, Utc};
 use futures::{Stream, StreamExt, TryStreamExt};
-use log::error;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use sqlx::mysql::MySqlRow;
@@ -28,16 +27,13 @@ use std::sync::Arc;
 use std::{fmt, io};
 use std::{marker::PhantomData, ops::Add, time::Duration};
 
-use crate::context::SqlContext;
-use crate::from_row::SqlRequest;
-use crate::{calculate_status, Config, SqlError};
+use crate::context::{SqlContext, Status};
+use crate::from_row::{SqlCompact, SqlRequest};
+use crate::{calculate_status, Config, QueueStats, SqlError};
 
 pub use sqlx::mysql::MySqlPool;
 
-type MysqlCodec = JsonCodec<Value>;
-
 /// Represents a [Storage] that persists to MySQL
-
 pub struct MysqlStorage<T, C = JsonCodec<Value>>
 where
     C: Codec,
@@ -47,13 +43,12 @@ where
     controller: Controller,
     config: Config,
     codec: PhantomData<C>,
-    ack_notify: Notify<(SqlContext, Response<C::Compact>)>,
+    ack_notify: Notify<(SqlContext, Response<Value>)>,
 }
 
 impl<T, C> fmt::Debug for MysqlStorage<T, C>
 where
     C: Codec,
-    C::Compact: Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("MysqlStorage")
@@ -110,7 +105,19 @@ where
 
     /// Create a new instance from a pool and custom config
     pub fn new_with_config(pool: MySqlPool, config: Config) -> Self {
-        Self {
+        Self::new_with_codec(pool, config)
+    }
+
+    /// Create a new instance from a pool and custom config that encodes jobs with the codec `K`
+    ///
+    /// The `job` column must hold the codec's compact type. The default `JSON` column stores
+    /// `serde_json::Value`, binary codecs with a `Vec<u8>` compact need a blob column, for example
+    /// after `ALTER TABLE jobs MODIFY job LONGBLOB NOT NULL`.
+    pub fn new_with_codec<K>(pool: MySqlPool, config: Config) -> MysqlStorage<T, K>
+    where
+        K: Codec,
+    {
+        MysqlStorage {
             pool,
             job_type: PhantomData,
             controller: Controller::new(),
@@ -119,7 +126,9 @@ where
             codec: PhantomData,
         }
     }
+}
 
+impl<T, C: Codec> MysqlStorage<T, C> {
     /// Expose the pool for other functionality, eg custom migrations
     pub fn pool(&self) -> &Pool<MySql> {
         &self.pool
@@ -134,7 +143,8 @@ where
 impl<T, C> MysqlStorage<T, C>
 where
     T: DeserializeOwned + Send + Sync + 'static,
-    C: Codec<Compact = Value> + Send + 'static,
+    C: Codec + Send + 'static,
+    C::Compact: SqlCompact<MySql>,
 {
     fn stream_jobs(
         self,
@@ -169,27 +179,26 @@ where
                 } else {
                     let task_ids: Vec<String> = task_ids.iter().map(|r| r.get_unchecked("id")).collect();
                     let id_params = format!("?{}", ", ?".repeat(task_ids.len() - 1));
-                    let query = format!("UPDATE jobs SET status = 'Running', lock_by = ?, lock_at = NOW() WHERE id IN({}) AND status = 'Pending' AND lock_by IS NULL;", id_params);
+                    // The selected rows stay locked until the commit, so every one of them is
+                    // claimed here, including failed jobs that are still locked by their last worker
+                    let query = format!("UPDATE jobs SET status = 'Running', lock_by = ?, lock_at = NOW() WHERE id IN({});", id_params);
                     let mut query = sqlx::query(&query).bind(worker_id.clone());
                     for i in &task_ids {
                         query = query.bind(i);
                     }
                     query.execute(&mut *tx).await?;
-                    tx.commit().await?;
 
-                    let fetch_query = format!("SELECT * FROM jobs WHERE ID IN ({}) ORDER BY priority DESC, run_at ASC", id_params);
+                    let fetch_query = format!("SELECT * FROM jobs WHERE id IN ({}) ORDER BY priority DESC, run_at ASC", id_params);
                     let mut query = sqlx::query_as(&fetch_query);
                     for i in task_ids {
                         query = query.bind(i);
                     }
-                    let jobs: Vec<SqlRequest<Value>> = query.fetch_all(&pool).await?;
+                    let jobs: Vec<SqlRequest<C::Compact>> = query.fetch_all(&mut *tx).await?;
+                    tx.commit().await?;
 
                     for job in jobs {
                         yield {
-                            let (req, ctx) = job.req.take_parts();
-                            let req = C::decode(req)
-                                .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
-                            let mut req: Request<T, SqlContext> = Request::new_with_parts(req, ctx);
+                            let mut req = job.decode::<T, C>()?;
                             req.parts.namespace = Some(Namespace(self.config.namespace.clone()));
                             Some(req)
                         }
@@ -199,6 +208,26 @@ where
         }.boxed()
     }
 
+    /// Record that the worker is alive, as the poller does every `keep_alive` interval
+    pub async fn keep_alive(&mut self, worker_id: &WorkerId) -> Result<(), sqlx::Error> {
+        self.keep_alive_at::<AckLayer<Self, T, SqlContext, C>>(worker_id, Utc::now())
+            .await
+    }
+
+    /// Count the jobs of the namespace that are due and waiting to be fetched
+    ///
+    /// Jobs are fetched by their `run_at`, so scheduled jobs need no moving once due and workers
+    /// pick them up on their next `poll_interval`.
+    pub async fn count_due(&mut self) -> Result<usize, sqlx::Error> {
+        let query = "SELECT COUNT(*) FROM jobs
+            WHERE (status = 'Pending' OR (status = 'Failed' AND attempts < max_attempts)) AND run_at <= NOW() AND job_type = ?";
+        let due: i64 = sqlx::query_scalar(query)
+            .bind(&self.config.namespace)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(due.try_into().unwrap_or_default())
+    }
+
     async fn keep_alive_at<Service>(
         &mut self,
         worker_id: &WorkerId,
@@ -227,7 +256,8 @@ where
 impl<T, C> Storage for MysqlStorage<T, C>
 where
     T: Serialize + DeserializeOwned + Send + 'static + Unpin + Sync,
-    C: Codec<Compact = Value> + Send + Sync + 'static,
+    C: Codec + Send + Sync + 'static,
+    C::Compact: SqlCompact<MySql>,
     C::Error: std::error::Error + 'static + Send + Sync,
 {
     type Job = T;
@@ -236,7 +266,7 @@ where
 
     type Context = SqlContext;
 
-    type Compact = Value;
+    type Compact = C::Compact;
 
     async fn push_request(
         &mut self,
@@ -270,11 +300,9 @@ where
             "INSERT INTO jobs VALUES (?, ?, ?, 'Pending', 0, ?, now(), NULL, NULL, NULL, NULL, ?)";
         let pool = self.pool.clone();
 
-        let job = C::encode(args)
-            .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
         let job_type = self.config.namespace.clone();
         sqlx::query(query)
-            .bind(job)
+            .bind(args)
             .bind(parts.task_id.to_string())
             .bind(job_type.to_string())
             .bind(parts.context.max_attempts())
@@ -318,18 +346,18 @@ where
         let pool = self.pool.clone();
 
         let fetch_query = "SELECT * FROM jobs WHERE id = ?";
-        let res: Option<SqlRequest<serde_json::Value>> = sqlx::query_as(fetch_query)
+        let res: Option<SqlRequest<C::Compact>> = sqlx::query_as(fetch_query)
             .bind(job_id.to_string())
             .fetch_optional(&pool)
             .await?;
         match res {
             None => Ok(None),
             Some(job) => Ok(Some({
-                let (req, parts) = job.req.take_parts();
-                let req = C::decode(req)
-                    .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
-                let mut req = Request::new_with_parts(req, parts);
+                let mut req = job.decode::<T, C>()?;
                 req.parts.namespace = Some(Namespace(self.config.namespace.clone()));
+                req.parts
+                    .data
+                    .insert(Status(req.parts.context.status().clone()));
                 req
             })),
         }
@@ -405,10 +433,7 @@ where
     }
 
     async fn vacuum(&mut self) -> Result<usize, sqlx::Error> {
-        let pool = self.pool.clone();
-        let query = "Delete from jobs where status='Done'";
-        let record = sqlx::query(query).execute(&pool).await?;
-        Ok(record.rows_affected().try_into().unwrap_or_default())
+        MysqlStorage::vacuum_done(self).await
     }
 }
 
@@ -430,12 +455,17 @@ pub enum MysqlPollError {
     /// Error during re-enqueuing orphaned tasks.
     #[error("Encountered an error during ReenqueueOrphaned heartbeat: `{0}`")]
     ReenqueueOrphanedError(sqlx::Error),
+
+    /// Error while deleting finished jobs past the retention.
+    #[error("Encountered an error during Vacuum heartbeat: `{0}`")]
+    VacuumError(sqlx::Error),
 }
 
 impl<Req, C> Backend<Request<Req, SqlContext>> for MysqlStorage<Req, C>
 where
     Req: Serialize + DeserializeOwned + Sync + Send + 'static,
-    C: Codec<Compact = Value> + Send + 'static + Sync,
+    C: Codec + Send + 'static + Sync,
+    C::Compact: SqlCompact<MySql>,
     C::Error: std::error::Error + 'static + Send + Sync,
 {
     type Stream = BackendStream<RequestStream<Request<Req, SqlContext>>>;
@@ -469,7 +499,8 @@ where
                     let query = "UPDATE jobs SET status = ?, done_at = now(), last_error = ?, attempts = ? WHERE id = ? AND lock_by = ?";
                     let query = sqlx::query(query);
                     let last_result =
-                        C::encode(res.inner.as_ref().map_err(|e| e.to_string())).map_err(Box::new);
+                        serde_json::to_value(res.inner.as_ref().map_err(|e| e.to_string()))
+                            .map_err(|e| Box::new(e) as BoxDynError);
                     match (last_result, ctx.lock_by()) {
                         (Ok(val), Some(worker_id)) => {
                             let query = query
@@ -509,10 +540,14 @@ where
             }
 
             loop {
-                let now = Utc::now();
-                if let Err(e) = hb_storage.keep_alive_at::<Self::Layer>(w.id(), now).await {
+                if let Err(e) = hb_storage.keep_alive(w.id()).await {
                     w.emit(Event::Error(Box::new(MysqlPollError::KeepAliveError(e))));
                 }
+                if !config.done_retention.is_zero() {
+                    if let Err(e) = hb_storage.vacuum_done().await {
+                        w.emit(Event::Error(Box::new(MysqlPollError::VacuumError(e))));
+                    }
+                }
                 apalis_core::sleep(config.keep_alive).await;
             }
         };
@@ -553,16 +588,16 @@ impl<T, Res, C> Ack<T, Res, C> for MysqlStorage<T, C>
 where
     T: Sync + Send,
     Res: Serialize + Send + 'static + Sync,
-    C: Codec<Compact = Value> + Send,
-    C::Error: Debug,
+    C: Codec + Send,
 {
     type Context = SqlContext;
     type AckError = sqlx::Error;
     async fn ack(&mut self, ctx: &Self::Context, res: &Response<Res>) -> Result<(), sqlx::Error> {
+        // Results are kept as JSON in `last_error` whatever the codec of the jobs
         self.ack_notify
             .notify((
                 ctx.clone(),
-                res.map(|res| C::encode(res).expect("Could not encode result")),
+                res.map(|res| serde_json::to_value(res).expect("Could not encode result")),
             ))
             .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::BrokenPipe, e)))?;
 
@@ -605,6 +640,44 @@ impl<T, C: Codec> MysqlStorage<T, C> {
         Ok(())
     }
 
+    /// Cancel a job that is still pending, including scheduled jobs, by deleting it.
+    ///
+    /// Returns whether the job was deleted. Jobs that are running or finished are not affected.
+    pub async fn cancel(&mut self, job_id: &TaskId) -> Result<bool, sqlx::Error> {
+        let query = "DELETE FROM jobs WHERE id = ? AND status = 'Pending'";
+        let result = sqlx::query(query)
+            .bind(job_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete the jobs of this namespace that have been `Done` or `Killed` for longer than
+    /// [Config::set_done_retention], returning the number of deleted jobs
+    pub async fn vacuum_done(&self) -> Result<usize, sqlx::Error> {
+        let done_before = Utc::now()
+            - chrono::Duration::from_std(self.config.done_retention())
+                .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let query =
+            "DELETE FROM jobs WHERE job_type = ? AND status IN ('Done', 'Killed') AND done_at <= ?";
+        let record = sqlx::query(query)
+            .bind(self.config.namespace())
+            .bind(done_before)
+            .execute(&self.pool)
+            .await?;
+        Ok(record.rows_affected().try_into().unwrap_or_default())
+    }
+
+    /// Count the jobs of this namespace in each status with a single `GROUP BY` query
+    pub async fn queue_stats(&self) -> Result<QueueStats, sqlx::Error> {
+        let query = "SELECT status, COUNT(*) FROM jobs WHERE job_type = ? GROUP BY status";
+        let counts: Vec<(String, i64)> = sqlx::query_as(query)
+            .bind(self.config.namespace())
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(QueueStats::from_counts(counts))
+    }
+
     /// Readd jobs that are abandoned to the queue
     pub async fn reenqueue_orphaned(
         &self,
@@ -628,8 +701,11 @@ impl<T, C: Codec> MysqlStorage<T, C> {
     }
 }
 
-impl<J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync> BackendExpose<J>
-    for MysqlStorage<J>
+impl<J, C> BackendExpose<J> for MysqlStorage<J, C>
+where
+    J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync,
+    C: Codec + Send + Sync + 'static,
+    C::Compact: SqlCompact<MySql>,
 {
     type Request = Request<J, Parts<SqlContext>>;
     type Error = SqlError;
@@ -664,20 +740,18 @@ impl<J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync> BackendExp
     ) -> Result<Vec<Self::Request>, Self::Error> {
         let status = status.to_string();
         let fetch_query = "SELECT * FROM jobs WHERE status = ? AND job_type = ? ORDER BY done_at DESC, run_at DESC LIMIT 10 OFFSET ?";
-        let res: Vec<SqlRequest<serde_json::Value>> = sqlx::query_as(fetch_query)
+        let res: Vec<SqlRequest<C::Compact>> = sqlx::query_as(fetch_query)
             .bind(status)
             .bind(self.get_config().namespace())
             .bind(((page - 1) * 10).to_string())
             .fetch_all(self.pool())
             .await?;
-        Ok(res
-            .into_iter()
+        res.into_iter()
             .map(|j| {
-                let (req, ctx) = j.req.take_parts();
-                let req: J = MysqlCodec::decode(req).unwrap();
-                Request::new_with_ctx(req, ctx)
+                let (req, ctx) = j.decode::<J, C>()?.take_parts();
+                Ok(Request::new_with_ctx(req, ctx))
             })
-            .collect())
+            .collect()
     }
 
     async fn list_workers(&self) -> Result<Vec<Worker<WorkerState>>, Self::Error> {
@@ -779,7 +853,7 @@ mod tests {
         let wrk = Worker::new(worker_id, Context::default());
         wrk.start();
         storage
-            .keep_alive_at::<DummyService>(&wrk.id(), last_seen)
+            .keep_alive_at::<DummyService>(wrk.id(), last_seen)
             .await
             .expect("failed to register worker");
         wrk
@@ -823,6 +897,51 @@ mod tests {
         assert!(ctx.lock_at().is_some());
     }
 
+    #[tokio::test]
+    async fn test_consume_a_full_buffer() {
+        let mut storage = setup().await;
+        for _ in 0..3 {
+            push_email(&mut storage, example_email()).await;
+        }
+
+        let worker = register_worker(&mut storage).await;
+        let jobs: Vec<_> = storage
+            .clone()
+            .stream_jobs(&worker, std::time::Duration::from_secs(10), 3)
+            .take(3)
+            .collect()
+            .await;
+        assert_eq!(jobs.len(), 3);
+        for job in jobs {
+            let ctx = job
+                .expect("failed to poll job")
+                .expect("no job is pending")
+                .parts
+                .context;
+            assert_eq!(*ctx.status(), State::Running);
+            assert_eq!(*ctx.lock_by(), Some(worker.id().clone()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consume_failed_job_locked_by_its_last_worker() {
+        let mut storage = setup().await;
+        push_email(&mut storage, example_email()).await;
+        sqlx::query(
+            "UPDATE jobs SET status = 'Failed', lock_by = 'other-worker', attempts = 1 WHERE job_type = ?",
+        )
+        .bind(storage.config.namespace())
+        .execute(&storage.pool)
+        .await
+        .expect("failed to fail the job");
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, &worker).await;
+        let ctx = job.parts.context;
+        assert_eq!(*ctx.status(), State::Running);
+        assert_eq!(*ctx.lock_by(), Some(worker.id().clone()));
+    }
+
     #[tokio::test]
     async fn test_kill_job() {
         let mut storage = setup().await;
@@ -907,7 +1026,7 @@ mod tests {
         let (mut t, poller) = TestWrapper::new_with_service(storage.clone(), service);
         let four_minutes_ago = Utc::now() - Duration::from_secs(4 * 60);
         storage
-            .keep_alive_at::<Email>(&t.worker.id(), four_minutes_ago)
+            .keep_alive_at::<Email>(t.worker.id(), four_minutes_ago)
             .await
             .unwrap();
 
@@ -38,8 +38,8 @@
 //!          .await
 //!  }
 //! ```
-use crate::context::SqlContext;
-use crate::{calculate_status, Config, SqlError};
+use crate::context::{SqlContext, Status};
+use crate::{calculate_status, Config, QueueStats, SqlError};
 use apalis_core::backend::{BackendExpose, Stat, WorkerState};
 use apalis_core::codec::json::JsonCodec;
 use apalis_core::error::{BoxDynError, Error};
@@ -57,13 +57,12 @@ use apalis_core::worker::{Context, Event, Worker, WorkerId};
 use apalis_core::{backend::Backend, codec::Codec};
 use chrono::{DateTime, Utc};
 use futures::channel::mpsc;
-use futures::StreamExt;
-use futures::{select, stream, SinkExt};
-use log::error;
+use futures::{future, FutureExt, StreamExt};
+use futures::{select, SinkExt};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
-use sqlx::postgres::PgListener;
-use sqlx::{Pool, Postgres, Row};
+use sqlx::postgres::{PgConnection, PgHasArrayType, PgListener, PgNotification};
+use sqlx::{Connection, Pool, Postgres, Row};
 use std::any::type_name;
 use std::convert::TryInto;
 use std::fmt::Debug;
@@ -75,7 +74,7 @@ type Timestamp = i64;
 
 pub use sqlx::postgres::PgPool;
 
-use crate::from_row::SqlRequest;
+use crate::from_row::{SqlCompact, SqlRequest};
 
 /// Represents a [Storage] that persists to Postgres
 // #[derive(Debug)]
@@ -88,8 +87,19 @@ where
     codec: PhantomData<C>,
     config: Config,
     controller: Controller,
-    ack_notify: Notify<(SqlContext, Response<C::Compact>)>,
+    ack_notify: Notify<(SqlContext, Response<Value>)>,
     subscription: Option<PgSubscription>,
+    shared: Arc<Shared>,
+}
+
+/// The connections shared by a storage, its clones and the workers polling them
+#[derive(Debug, Default)]
+struct Shared {
+    /// Notified of jobs pushed to the namespace when no [PgListen] is shared
+    notify: Notify<()>,
+    /// The listening connection, driven by one worker at a time
+    listener: futures::lock::Mutex<Option<PgListener>>,
+    leader: futures::lock::Mutex<SchedulerLeader>,
 }
 
 impl<T, C: Codec> Clone for PostgresStorage<T, C> {
@@ -102,6 +112,7 @@ impl<T, C: Codec> Clone for PostgresStorage<T, C> {
             controller: self.controller.clone(),
             ack_notify: self.ack_notify.clone(),
             subscription: self.subscription.clone(),
+            shared: self.shared.clone(),
         }
     }
 }
@@ -134,6 +145,10 @@ pub enum PgPollError {
     #[error("Encountered an error during listening to PgNotification: {0}")]
     PgNotificationError(apalis_core::error::Error),
 
+    /// Error while subscribing to PostgreSQL notifications.
+    #[error("Encountered an error during listening to the notify channel: `{0}`")]
+    ListenError(sqlx::Error),
+
     /// Error during a keep-alive heartbeat.
     #[error("Encountered an error during KeepAlive heartbeat: `{0}`")]
     KeepAliveError(sqlx::Error),
@@ -145,12 +160,80 @@ pub enum PgPollError {
     /// Error during result encoding.
     #[error("Encountered an error during encoding the result: {0}")]
     CodecError(BoxDynError),
+
+    /// Error while deleting finished jobs past the retention.
+    #[error("Encountered an error during Vacuum heartbeat: `{0}`")]
+    VacuumError(sqlx::Error),
+
+    /// Error while electing the worker that runs the maintenance heartbeats.
+    #[error("Encountered an error during leader election: `{0}`")]
+    LeaderElectionError(sqlx::Error),
+
+    /// Error while announcing scheduled jobs that became due.
+    #[error("Encountered an error during EnqueueScheduled heartbeat: `{0}`")]
+    EnqueueScheduledError(sqlx::Error),
+}
+
+/// The session level advisory lock held by the storage whose workers run the maintenance
+/// heartbeats of a namespace, see [Config::set_scheduler_leader]
+///
+/// The lock lives on a connection detached from the pool, so it is released when the leader drops
+/// it or dies. A storage and its clones share one, so their workers elect once per storage.
+#[derive(Debug, Default)]
+struct SchedulerLeader {
+    conn: Option<PgConnection>,
+    leading: bool,
+}
+
+impl SchedulerLeader {
+    /// Check that the lock is still held, or try to take it, returning whether this worker leads
+    ///
+    /// Followers keep their connection to retry the lock on, instead of opening one on every
+    /// keep-alive.
+    async fn try_lead(&mut self, pool: &PgPool, config: &Config) -> Result<bool, sqlx::Error> {
+        if let Some(conn) = self.conn.as_mut() {
+            if conn.ping().await.is_err() {
+                // The lock, if held, went with the connection
+                self.conn = None;
+                self.leading = false;
+            } else if self.leading {
+                return Ok(true);
+            }
+        }
+        let conn = match self.conn.as_mut() {
+            Some(conn) => conn,
+            None => self.conn.insert(pool.acquire().await?.detach()),
+        };
+        self.leading = sqlx::query_scalar("SELECT pg_try_advisory_lock(hashtext($1))")
+            .bind(format!(
+                "apalis::leader::{}::{}",
+                config.jobs_table(),
+                config.namespace()
+            ))
+            .fetch_one(conn)
+            .await?;
+        Ok(self.leading)
+    }
+}
+
+/// Whether this worker runs the maintenance heartbeats until the next keep-alive, always when no
+/// leader is elected
+async fn elect(shared: &Shared, pool: &PgPool, config: &Config, worker: &Worker<Context>) -> bool {
+    if !config.scheduler_leader() {
+        return true;
+    }
+    let mut leader = shared.leader.lock().await;
+    leader.try_lead(pool, config).await.unwrap_or_else(|e| {
+        worker.emit(Event::Error(Box::new(PgPollError::LeaderElectionError(e))));
+        false
+    })
 }
 
 impl<T, C> Backend<Request<T, SqlContext>> for PostgresStorage<T, C>
 where
     T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
-    C: Codec<Compact = Value> + Send + 'static,
+    C: Codec + Send + Sync + 'static,
+    C::Compact: SqlCompact<Postgres>,
     C::Error: std::error::Error + 'static + Send + Sync,
 {
     type Stream = BackendStream<RequestStream<Request<T, SqlContext>>>;
@@ -167,6 +250,7 @@ where
         let (mut tx, rx) = mpsc::channel(self.config.buffer_size);
         let ack_notify = self.ack_notify.clone();
         let pool = self.pool.clone();
+        let shared = self.shared.clone();
         let worker = worker.clone();
         let heartbeat = async move {
             // Lets reenqueue any jobs that belonged to this worker in case of a death
@@ -182,23 +266,35 @@ where
             let mut keep_alive_stm = apalis_core::interval::interval(config.keep_alive).fuse();
             let mut reenqueue_orphaned_stm =
                 apalis_core::interval::interval(config.poll_interval).fuse();
+            let mut enqueue_scheduled_stm =
+                apalis_core::interval::interval(config.enqueue_scheduled).fuse();
 
             let mut ack_stream = ack_notify.clone().ready_chunks(config.buffer_size).fuse();
 
-            let mut poll_next_stm = apalis_core::interval::interval(config.poll_interval).fuse();
+            let mut leading = elect(&shared, &pool, &config, &worker).await;
 
-            let mut pg_notification = subscription
-                .map(|stm| stm.notify.boxed().fuse())
-                .unwrap_or(stream::iter(vec![]).boxed().fuse());
+            let mut poll_next_stm = apalis_core::interval::interval(config.poll_interval).fuse();
 
-            async fn fetch_next_batch<
-                T: Unpin + DeserializeOwned + Send + 'static,
-                C: Codec<Compact = Value>,
-            >(
+            // Without a shared [PgListen], the workers of this storage listen on one connection
+            // and rely on `poll_interval` if that fails
+            let (mut pg_notification, mut listen) = match subscription {
+                Some(sub) => (sub.notify.boxed().fuse(), future::pending().boxed().fuse()),
+                None => (
+                    shared.notify.clone().boxed().fuse(),
+                    PgListen::drive(shared.clone(), pool.clone(), config.clone())
+                        .boxed()
+                        .fuse(),
+                ),
+            };
+
+            async fn fetch_next_batch<T: Unpin + DeserializeOwned + Send + 'static, C: Codec>(
                 storage: &mut PostgresStorage<T, C>,
                 worker: &WorkerId,
                 tx: &mut mpsc::Sender<Result<Option<Request<T, SqlContext>>, Error>>,
-            ) -> Result<(), Error> {
+            ) -> Result<(), Error>
+            where
+                C::Compact: SqlCompact<Postgres>,
+            {
                 let res = storage
                     .fetch_next(worker)
                     .await
@@ -211,19 +307,22 @@ where
                 Ok(())
             }
 
-            if let Err(e) = self
-                .keep_alive_at::<Self::Layer>(worker.id(), Utc::now().timestamp())
-                .await
-            {
+            if let Err(e) = self.keep_alive(worker.id()).await {
                 worker.emit(Event::Error(Box::new(PgPollError::KeepAliveError(e))));
             }
 
             loop {
                 select! {
                     _ = keep_alive_stm.next() => {
-                        if let Err(e) = self.keep_alive_at::<Self::Layer>(worker.id(), Utc::now().timestamp()).await {
+                        if let Err(e) = self.keep_alive(worker.id()).await {
                             worker.emit(Event::Error(Box::new(PgPollError::KeepAliveError(e))));
                         }
+                        leading = elect(&shared, &pool, &config, &worker).await;
+                        if !config.done_retention.is_zero() && leading {
+                            if let Err(e) = self.vacuum_done().await {
+                                worker.emit(Event::Error(Box::new(PgPollError::VacuumError(e))));
+                            }
+                        }
                     }
                     ids = ack_stream.next() => {
 
@@ -231,8 +330,8 @@ where
                             let ack_ids: Vec<(String, String, String, String, u64)> = ids.iter().map(|(ctx, res)| {
                                 (res.task_id.to_string(), worker.id().to_string(), serde_json::to_string(&res.inner.as_ref().map_err(|e| e.to_string())).expect("Could not convert response to json"), calculate_status(ctx,res).to_string(), res.attempt.current() as u64)
                             }).collect();
-                            let query =
-                                "UPDATE apalis.jobs
+                            let query = format!(
+                                "UPDATE {table}
                                     SET status = Q.status, 
                                         done_at = now(), 
                                         lock_by = Q.worker_id, 
@@ -246,12 +345,14 @@ where
                                             (value->>4)::int as attempts 
                                         FROM json_array_elements($1::json)
                                     ) Q
-                                    WHERE apalis.jobs.id = Q.id;
-                                    ";
-                            let codec_res = C::encode(&ack_ids);
+                                    WHERE {table}.id = Q.id;
+                                    ",
+                                table = config.jobs_table()
+                            );
+                            let codec_res = serde_json::to_value(&ack_ids);
                             match codec_res {
                                 Ok(val) => {
-                                    if let Err(e) = sqlx::query(query)
+                                    if let Err(e) = sqlx::query(&query)
                                         .bind(val)
                                         .execute(&pool)
                                         .await
@@ -273,13 +374,29 @@ where
                             }
                         }
                     }
+                    res = listen => {
+                        if let Err(e) = res {
+                            worker.emit(Event::Error(Box::new(PgPollError::ListenError(e))));
+                        }
+                    }
                     _ = pg_notification.next() => {
                         if let Err(e) = fetch_next_batch(&mut self, worker.id(), &mut tx).await {
                             worker.emit(Event::Error(Box::new(PgPollError::PgNotificationError(e))));
 
                         }
                     }
+                    _ = enqueue_scheduled_stm.next() => {
+                        if !leading {
+                            continue;
+                        }
+                        if let Err(e) = self.enqueue_scheduled().await {
+                            worker.emit(Event::Error(Box::new(PgPollError::EnqueueScheduledError(e))));
+                        }
+                    }
                     _ = reenqueue_orphaned_stm.next() => {
+                        if !leading {
+                            continue;
+                        }
                         let dead_since = Utc::now()
                             - chrono::Duration::from_std(config.reenqueue_orphaned_after).expect("could not build dead_since");
                         if let Err(e) = self.reenqueue_orphaned((config.buffer_size * 10) as i32, dead_since).await {
@@ -308,6 +425,47 @@ impl PostgresStorage<()> {
         Self::migrations().run(pool).await?;
         Ok(())
     }
+
+    /// Do migrations for Postgres and create the jobs table configured with
+    /// [Config::set_schema_name] and [Config::set_table_name]
+    ///
+    /// A custom table is created with the same columns and indexes as `apalis.jobs`, and inserts
+    /// are announced on [Config::set_notify_channel]
+    #[cfg(feature = "migrate")]
+    pub async fn setup_with_config(
+        pool: &Pool<Postgres>,
+        config: &Config,
+    ) -> Result<(), sqlx::Error> {
+        Self::setup(pool).await?;
+        let table = config.jobs_table();
+        let channel = config.notify_channel();
+        if table == "apalis.jobs" && channel == "apalis::job" {
+            return Ok(());
+        }
+        let mut tx = pool.begin().await?;
+        if table != "apalis.jobs" {
+            sqlx::query(&format!(
+                "CREATE SCHEMA IF NOT EXISTS {}",
+                config.schema_name()
+            ))
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (LIKE apalis.jobs INCLUDING ALL)"
+            ))
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(&format!("DROP TRIGGER IF EXISTS notify_workers ON {table}"))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(&format!(
+            "CREATE TRIGGER notify_workers AFTER INSERT ON {table} REFERENCING NEW TABLE AS new_jobs FOR EACH STATEMENT EXECUTE PROCEDURE apalis.notify_new_job_types('{channel}')"
+        ))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
 }
 
 impl<T> PostgresStorage<T> {
@@ -317,7 +475,20 @@ impl<T> PostgresStorage<T> {
     }
     /// New Storage from [PgPool] and custom config
     pub fn new_with_config(pool: PgPool, config: Config) -> Self {
-        Self {
+        Self::new_with_codec(pool, config)
+    }
+
+    /// New Storage from [PgPool] and custom config that encodes jobs with the codec `K`
+    ///
+    /// The `job` column must hold the codec's compact type. The default `jsonb` column stores
+    /// `serde_json::Value`, binary codecs with a `Vec<u8>` compact need a `bytea` column, for
+    /// example a table created with [PostgresStorage::setup_with_config] and altered with
+    /// `ALTER TABLE ... ALTER COLUMN job TYPE bytea USING convert_to(job::text, 'UTF8')`.
+    pub fn new_with_codec<K>(pool: PgPool, config: Config) -> PostgresStorage<T, K>
+    where
+        K: Codec,
+    {
+        PostgresStorage {
             pool,
             job_type: PhantomData,
             codec: PhantomData,
@@ -325,9 +496,12 @@ impl<T> PostgresStorage<T> {
             controller: Controller::new(),
             ack_notify: Notify::new(),
             subscription: None,
+            shared: Arc::default(),
         }
     }
+}
 
+impl<T, C: Codec> PostgresStorage<T, C> {
     /// Expose the pool for other functionality, eg custom migrations
     pub fn pool(&self) -> &Pool<Postgres> {
         &self.pool
@@ -337,14 +511,46 @@ impl<T> PostgresStorage<T> {
     pub fn config(&self) -> &Config {
         &self.config
     }
-}
 
-impl<T, C: Codec> PostgresStorage<T, C> {
     /// Expose the codec
     pub fn codec(&self) -> &PhantomData<C> {
         &self.codec
     }
 
+    /// Record that the worker is alive, as the poller does every `keep_alive` interval
+    pub async fn keep_alive(&mut self, worker_id: &WorkerId) -> Result<(), sqlx::Error> {
+        self.keep_alive_at::<AckLayer<Self, T, SqlContext, C>>(worker_id, Utc::now().timestamp())
+            .await
+    }
+
+    /// Announce the jobs of the namespace that are due on the notify channel and return how many
+    /// there are
+    ///
+    /// Scheduled jobs are inserted ahead of their `run_at`, so the insert trigger announces them
+    /// too early. The poller calls this every `enqueue_scheduled` interval so that listening
+    /// workers fetch them once due, without waiting for their `poll_interval`.
+    pub async fn enqueue_scheduled(&mut self) -> Result<usize, sqlx::Error> {
+        let query = format!(
+            "SELECT COUNT(*) FROM {}
+                WHERE (status = 'Pending' OR (status = 'Failed' AND attempts < max_attempts))
+                    AND run_at < now()
+                    AND job_type = $1",
+            self.config.jobs_table()
+        );
+        let due: i64 = sqlx::query_scalar(&query)
+            .bind(&self.config.namespace)
+            .fetch_one(&self.pool)
+            .await?;
+        if due > 0 {
+            sqlx::query("SELECT pg_notify($1, $2)")
+                .bind(self.config.notify_channel())
+                .bind(&self.config.namespace)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(due.try_into().unwrap_or_default())
+    }
+
     async fn keep_alive_at<Service>(
         &mut self,
         worker_id: &WorkerId,
@@ -375,6 +581,7 @@ impl<T, C: Codec> PostgresStorage<T, C> {
 #[derive(Debug)]
 pub struct PgListen {
     listener: PgListener,
+    channels: Vec<String>,
     subscriptions: Vec<(String, PgSubscription)>,
 }
 
@@ -392,15 +599,40 @@ impl PgListen {
         let listener = PgListener::connect_with(&pool).await?;
         Ok(Self {
             listener,
+            channels: Vec::new(),
             subscriptions: Vec::new(),
         })
     }
 
+    /// Listen on the notify channel of `config` for jobs pushed to its namespace, notifying the
+    /// workers of a storage
+    ///
+    /// Every worker of the storage calls this, the first one to take the listener drives it and
+    /// the others wait to take over once it stops. Only returns if listening fails.
+    async fn drive(shared: Arc<Shared>, pool: PgPool, config: Config) -> Result<(), sqlx::Error> {
+        let mut listener = shared.listener.lock().await;
+        let listener = match listener.as_mut() {
+            Some(listener) => listener,
+            None => {
+                let mut connected = PgListener::connect_with(&pool).await?;
+                connected.listen(config.notify_channel()).await?;
+                listener.insert(connected)
+            }
+        };
+        loop {
+            let notification = listener.recv().await?;
+            if is_for(&notification, &config.namespace) {
+                let _ = shared.notify.notify(());
+            }
+        }
+    }
+
     /// Add a new subscription with a storage
     pub fn subscribe_with<T>(&mut self, storage: &mut PostgresStorage<T>) {
         let sub = PgSubscription {
             notify: Notify::new(),
         };
+        self.add_channel(storage.config.notify_channel());
         self.subscriptions
             .push((storage.config.namespace.to_owned(), sub.clone()));
         storage.subscription = Some(sub)
@@ -411,18 +643,26 @@ impl PgListen {
         let sub = PgSubscription {
             notify: Notify::new(),
         };
+        self.add_channel(Config::default().notify_channel());
         self.subscriptions.push((namespace.to_owned(), sub.clone()));
         sub
     }
+
+    fn add_channel(&mut self, channel: &str) {
+        if !self.channels.iter().any(|c| c == channel) {
+            self.channels.push(channel.to_owned());
+        }
+    }
     /// Start listening to jobs
     pub async fn listen(mut self) -> Result<(), sqlx::Error> {
-        self.listener.listen("apalis::job").await?;
+        let channels: Vec<&str> = self.channels.iter().map(String::as_str).collect();
+        self.listener.listen_all(channels).await?;
         let mut notification = self.listener.into_stream();
         while let Some(Ok(res)) = notification.next().await {
             let _: Vec<_> = self
                 .subscriptions
                 .iter()
-                .filter(|s| s.0 == res.payload())
+                .filter(|s| is_for(&res, &s.0))
                 .map(|s| s.1.notify.notify(()))
                 .collect();
         }
@@ -430,10 +670,18 @@ impl PgListen {
     }
 }
 
+/// Whether a notification announces jobs for `namespace`.
+///
+/// Tables set up before notifications carried the job type announce every insert as `insert`.
+fn is_for(notification: &PgNotification, namespace: &str) -> bool {
+    notification.payload() == namespace || notification.payload() == "insert"
+}
+
 impl<T, C> PostgresStorage<T, C>
 where
     T: DeserializeOwned + Send + Unpin + 'static,
-    C: Codec<Compact = Value>,
+    C: Codec,
+    C::Compact: SqlCompact<Postgres>,
 {
     async fn fetch_next(
         &mut self,
@@ -441,8 +689,21 @@ where
     ) -> Result<Vec<Request<T, SqlContext>>, sqlx::Error> {
         let config = &self.config;
         let job_type = &config.namespace;
-        let fetch_query = "Select * from apalis.get_jobs($1, $2, $3);";
-        let jobs: Vec<SqlRequest<serde_json::Value>> = sqlx::query_as(fetch_query)
+        let fetch_query = format!(
+            "UPDATE {table}
+                SET status = 'Running', lock_by = $1, lock_at = now()
+                WHERE id IN (
+                    SELECT id FROM {table}
+                    WHERE (status = 'Pending' OR (status = 'Failed' AND attempts < max_attempts))
+                        AND run_at < now()
+                        AND job_type = $2
+                    ORDER BY priority DESC, run_at ASC
+                    LIMIT $3 FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *",
+            table = config.jobs_table()
+        );
+        let jobs: Vec<SqlRequest<C::Compact>> = sqlx::query_as(&fetch_query)
             .bind(worker_id.to_string())
             .bind(job_type)
             // https://docs.rs/sqlx/latest/sqlx/postgres/types/index.html
@@ -452,26 +713,21 @@ where
             )
             .fetch_all(&self.pool)
             .await?;
-        let jobs: Vec<_> = jobs
-            .into_iter()
+        jobs.into_iter()
             .map(|job| {
-                let (req, parts) = job.req.take_parts();
-                let req = C::decode(req)
-                    .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
-                    .expect("Unable to decode");
-                let mut req = Request::new_with_parts(req, parts);
+                let mut req = job.decode::<T, C>()?;
                 req.parts.namespace = Some(Namespace(self.config.namespace.clone()));
-                req
+                Ok(req)
             })
-            .collect();
-        Ok(jobs)
+            .collect()
     }
 }
 
 impl<Req, C> Storage for PostgresStorage<Req, C>
 where
     Req: Serialize + DeserializeOwned + Send + 'static + Unpin + Sync,
-    C: Codec<Compact = Value> + Send + 'static,
+    C: Codec + Send + Sync + 'static,
+    C::Compact: SqlCompact<Postgres> + PgHasArrayType,
     C::Error: Send + std::error::Error + Sync + 'static,
 {
     type Job = Req;
@@ -480,7 +736,7 @@ where
 
     type Context = SqlContext;
 
-    type Compact = Value;
+    type Compact = C::Compact;
 
     /// Push a job to Postgres [Storage]
     ///
@@ -493,33 +749,22 @@ where
         &mut self,
         req: Request<Self::Job, SqlContext>,
     ) -> Result<Parts<SqlContext>, sqlx::Error> {
-        let query = "INSERT INTO apalis.jobs VALUES ($1, $2, $3, 'Pending', 0, $4, NOW() , NULL, NULL, NULL, NULL, $5)";
-
-        let args = C::encode(&req.args)
-            .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
-        let job_type = self.config.namespace.clone();
-        sqlx::query(query)
-            .bind(args)
-            .bind(req.parts.task_id.to_string())
-            .bind(&job_type)
-            .bind(req.parts.context.max_attempts())
-            .bind(req.parts.context.priority())
-            .execute(&self.pool)
-            .await?;
-        Ok(req.parts)
+        let mut parts = self.push_requests(vec![req]).await?;
+        Ok(parts.remove(0))
     }
 
     async fn push_raw_request(
         &mut self,
         req: Request<Self::Compact, SqlContext>,
     ) -> Result<Parts<SqlContext>, sqlx::Error> {
-        let query = "INSERT INTO apalis.jobs VALUES ($1, $2, $3, 'Pending', 0, $4, NOW() , NULL, NULL, NULL, NULL, $5)";
+        let query = format!(
+            "INSERT INTO {} VALUES ($1, $2, $3, 'Pending', 0, $4, NOW() , NULL, NULL, NULL, NULL, $5)",
+            self.config.jobs_table()
+        );
 
-        let args = C::encode(&req.args)
-            .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
         let job_type = self.config.namespace.clone();
-        sqlx::query(query)
-            .bind(args)
+        sqlx::query(&query)
+            .bind(req.args)
             .bind(req.parts.task_id.to_string())
             .bind(&job_type)
             .bind(req.parts.context.max_attempts())
@@ -534,15 +779,17 @@ where
         req: Request<Self::Job, SqlContext>,
         on: Timestamp,
     ) -> Result<Parts<Self::Context>, sqlx::Error> {
-        let query =
-            "INSERT INTO apalis.jobs VALUES ($1, $2, $3, 'Pending', 0, $4, $5, NULL, NULL, NULL, NULL, $6)";
+        let query = format!(
+            "INSERT INTO {} VALUES ($1, $2, $3, 'Pending', 0, $4, $5, NULL, NULL, NULL, NULL, $6)",
+            self.config.jobs_table()
+        );
         let task_id = req.parts.task_id.to_string();
         let parts = req.parts;
         let on = DateTime::from_timestamp(on, 0);
         let job = C::encode(&req.args)
             .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
         let job_type = self.config.namespace.clone();
-        sqlx::query(query)
+        sqlx::query(&query)
             .bind(job)
             .bind(task_id)
             .bind(job_type)
@@ -558,8 +805,11 @@ where
         &mut self,
         job_id: &TaskId,
     ) -> Result<Option<Request<Self::Job, SqlContext>>, sqlx::Error> {
-        let fetch_query = "SELECT * FROM apalis.jobs WHERE id = $1 LIMIT 1";
-        let res: Option<SqlRequest<serde_json::Value>> = sqlx::query_as(fetch_query)
+        let fetch_query = format!(
+            "SELECT * FROM {} WHERE id = $1 LIMIT 1",
+            self.config.jobs_table()
+        );
+        let res: Option<SqlRequest<C::Compact>> = sqlx::query_as(&fetch_query)
             .bind(job_id.to_string())
             .fetch_optional(&self.pool)
             .await?;
@@ -567,20 +817,22 @@ where
         match res {
             None => Ok(None),
             Some(job) => Ok(Some({
-                let (req, parts) = job.req.take_parts();
-                let args = C::decode(req)
-                    .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
-
-                let mut req: Request<Req, SqlContext> = Request::new_with_parts(args, parts);
+                let mut req = job.decode::<Req, C>()?;
                 req.parts.namespace = Some(Namespace(self.config.namespace.clone()));
+                req.parts
+                    .data
+                    .insert(Status(req.parts.context.status().clone()));
                 req
             })),
         }
     }
 
     async fn len(&mut self) -> Result<i64, sqlx::Error> {
-        let query = "Select Count(*) as count from apalis.jobs where status='Pending' OR (status = 'Failed' AND attempts < max_attempts)";
-        let record = sqlx::query(query).fetch_one(&self.pool).await?;
+        let query = format!(
+            "Select Count(*) as count from {} where status='Pending' OR (status = 'Failed' AND attempts < max_attempts)",
+            self.config.jobs_table()
+        );
+        let record = sqlx::query(&query).fetch_one(&self.pool).await?;
         record.try_get("count")
     }
 
@@ -592,10 +844,12 @@ where
         let job_id = job.parts.task_id;
         let on = Utc::now() + wait;
         let mut tx = self.pool.acquire().await?;
-        let query =
-                "UPDATE apalis.jobs SET status = 'Pending', done_at = NULL, lock_by = NULL, lock_at = NULL, run_at = $2 WHERE id = $1";
+        let query = format!(
+            "UPDATE {} SET status = 'Pending', done_at = NULL, lock_by = NULL, lock_at = NULL, run_at = $2 WHERE id = $1",
+            self.config.jobs_table()
+        );
 
-        sqlx::query(query)
+        sqlx::query(&query)
             .bind(job_id.to_string())
             .bind(on)
             .execute(&mut *tx)
@@ -620,9 +874,11 @@ where
         let priority = *ctx.priority();
 
         let mut tx = self.pool.acquire().await?;
-        let query =
-                "UPDATE apalis.jobs SET status = $1, attempts = $2, done_at = to_timestamp($3), lock_by = $4, lock_at = to_timestamp($5), last_error = $6, priority = $7 WHERE id = $8";
-        sqlx::query(query)
+        let query = format!(
+            "UPDATE {} SET status = $1, attempts = $2, done_at = to_timestamp($3), lock_by = $4, lock_at = to_timestamp($5), last_error = $6, priority = $7 WHERE id = $8",
+            self.config.jobs_table()
+        );
+        sqlx::query(&query)
             .bind(status.to_owned())
             .bind(attempts)
             .bind(done_at)
@@ -641,9 +897,7 @@ where
     }
 
     async fn vacuum(&mut self) -> Result<usize, sqlx::Error> {
-        let query = "Delete from apalis.jobs where status='Done'";
-        let record = sqlx::query(query).execute(&self.pool).await?;
-        Ok(record.rows_affected().try_into().unwrap_or_default())
+        PostgresStorage::vacuum_done(self).await
     }
 }
 
@@ -651,13 +905,14 @@ impl<T, Res, C> Ack<T, Res, C> for PostgresStorage<T, C>
 where
     T: Sync + Send,
     Res: Serialize + Sync + Clone,
-    C: Codec<Compact = Value> + Send,
+    C: Codec + Send,
 {
     type Context = SqlContext;
     type AckError = sqlx::Error;
     async fn ack(&mut self, ctx: &Self::Context, res: &Response<Res>) -> Result<(), sqlx::Error> {
+        // Results are kept as JSON in `last_error` whatever the codec of the jobs
         let res = res.clone().map(|r| {
-            C::encode(r)
+            serde_json::to_value(r)
                 .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::Interrupted, e)))
                 .expect("Could not encode result")
         });
@@ -670,6 +925,66 @@ where
     }
 }
 
+impl<T, C> PostgresStorage<T, C>
+where
+    T: Serialize,
+    C: Codec,
+    C::Compact: SqlCompact<Postgres> + PgHasArrayType,
+    C::Error: std::error::Error + Send + Sync + 'static,
+{
+    /// Push multiple jobs in as few round trips as possible.
+    ///
+    /// Jobs are inserted in statements of at most `buffer_size` rows within a single transaction.
+    /// The returned [TaskId]s are in the same order as the provided jobs.
+    pub async fn push_batch(&mut self, jobs: Vec<T>) -> Result<Vec<TaskId>, sqlx::Error> {
+        let requests = jobs.into_iter().map(Request::new).collect();
+        let parts = self.push_requests(requests).await?;
+        Ok(parts.into_iter().map(|parts| parts.task_id).collect())
+    }
+
+    async fn push_requests(
+        &mut self,
+        requests: Vec<Request<T, SqlContext>>,
+    ) -> Result<Vec<Parts<SqlContext>>, sqlx::Error> {
+        let query = format!(
+            "INSERT INTO {} (job, id, job_type, status, attempts, max_attempts, run_at, priority)
+            SELECT job, id, $3, 'Pending', 0, max_attempts, NOW(), priority
+            FROM UNNEST($1, $2::text[], $4::integer[], $5::integer[]) AS t(job, id, max_attempts, priority)",
+            self.config.jobs_table()
+        );
+        let job_type = self.config.namespace.clone();
+        let chunk_size = self.config.buffer_size.max(1);
+        let mut all_parts = Vec::with_capacity(requests.len());
+        let mut tx = self.pool.begin().await?;
+        let mut requests = requests.into_iter().peekable();
+        while requests.peek().is_some() {
+            let mut jobs = Vec::with_capacity(chunk_size);
+            let mut ids = Vec::with_capacity(chunk_size);
+            let mut max_attempts = Vec::with_capacity(chunk_size);
+            let mut priorities = Vec::with_capacity(chunk_size);
+            for req in requests.by_ref().take(chunk_size) {
+                let args = C::encode(&req.args)
+                    .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+                jobs.push(args);
+                ids.push(req.parts.task_id.to_string());
+                max_attempts.push(req.parts.context.max_attempts());
+                priorities.push(*req.parts.context.priority());
+                all_parts.push(req.parts);
+            }
+            sqlx::query(&query)
+                .bind(jobs)
+                .bind(ids)
+                .bind(&job_type)
+                .bind(max_attempts)
+                .bind(priorities)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(all_parts)
+    }
+}
+
 impl<T, C: Codec> PostgresStorage<T, C> {
     /// Kill a job
     pub async fn kill(
@@ -678,9 +993,11 @@ impl<T, C: Codec> PostgresStorage<T, C> {
         task_id: &TaskId,
     ) -> Result<(), sqlx::Error> {
         let mut tx = self.pool.acquire().await?;
-        let query =
-                "UPDATE apalis.jobs SET status = 'Killed', done_at = now() WHERE id = $1 AND lock_by = $2";
-        sqlx::query(query)
+        let query = format!(
+            "UPDATE {} SET status = 'Killed', done_at = now() WHERE id = $1 AND lock_by = $2",
+            self.config.jobs_table()
+        );
+        sqlx::query(&query)
             .bind(task_id.to_string())
             .bind(worker_id.to_string())
             .execute(&mut *tx)
@@ -696,9 +1013,11 @@ impl<T, C: Codec> PostgresStorage<T, C> {
         task_id: &TaskId,
     ) -> Result<(), sqlx::Error> {
         let mut tx = self.pool.acquire().await?;
-        let query =
-                "UPDATE apalis.jobs SET status = 'Pending', done_at = NULL, lock_by = NULL WHERE id = $1 AND lock_by = $2";
-        sqlx::query(query)
+        let query = format!(
+            "UPDATE {} SET status = 'Pending', done_at = NULL, lock_by = NULL WHERE id = $1 AND lock_by = $2",
+            self.config.jobs_table()
+        );
+        sqlx::query(&query)
             .bind(task_id.to_string())
             .bind(worker_id.to_string())
             .execute(&mut *tx)
@@ -706,6 +1025,52 @@ impl<T, C: Codec> PostgresStorage<T, C> {
         Ok(())
     }
 
+    /// Cancel a job that is still pending, including scheduled jobs, by deleting it.
+    ///
+    /// Returns whether the job was deleted. Jobs that are running or finished are not affected.
+    pub async fn cancel(&mut self, task_id: &TaskId) -> Result<bool, sqlx::Error> {
+        let query = format!(
+            "DELETE FROM {} WHERE id = $1 AND status = 'Pending'",
+            self.config.jobs_table()
+        );
+        let result = sqlx::query(&query)
+            .bind(task_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete the jobs of this namespace that have been `Done` or `Killed` for longer than
+    /// [Config::set_done_retention], returning the number of deleted jobs
+    pub async fn vacuum_done(&self) -> Result<usize, sqlx::Error> {
+        let done_before = Utc::now()
+            - chrono::Duration::from_std(self.config.done_retention())
+                .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let query = format!(
+            "DELETE FROM {} WHERE job_type = $1 AND status IN ('Done', 'Killed') AND done_at <= $2",
+            self.config.jobs_table()
+        );
+        let record = sqlx::query(&query)
+            .bind(self.config.namespace())
+            .bind(done_before)
+            .execute(&self.pool)
+            .await?;
+        Ok(record.rows_affected().try_into().unwrap_or_default())
+    }
+
+    /// Count the jobs of this namespace in each status with a single `GROUP BY` query
+    pub async fn queue_stats(&self) -> Result<QueueStats, sqlx::Error> {
+        let query = format!(
+            "SELECT status, COUNT(*) FROM {} WHERE job_type = $1 GROUP BY status",
+            self.config.jobs_table()
+        );
+        let counts: Vec<(String, i64)> = sqlx::query_as(&query)
+            .bind(self.config.namespace())
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(QueueStats::from_counts(counts))
+    }
+
     /// Reenqueue jobs that have been abandoned by their workers
     pub async fn reenqueue_orphaned(
         &mut self,
@@ -714,17 +1079,20 @@ impl<T, C: Codec> PostgresStorage<T, C> {
     ) -> Result<(), sqlx::Error> {
         let job_type = self.config.namespace.clone();
         let mut tx = self.pool.acquire().await?;
-        let query = "UPDATE apalis.jobs
+        let query = format!(
+            "UPDATE {table}
                             SET status = 'Pending', done_at = NULL, lock_by = NULL, lock_at = NULL, last_error = 'Job was abandoned'
                             WHERE id IN
-                                (SELECT jobs.id FROM apalis.jobs INNER JOIN apalis.workers ON lock_by = workers.id
+                                (SELECT jobs.id FROM {table} AS jobs INNER JOIN apalis.workers ON lock_by = workers.id
                                     WHERE status = 'Running' 
                                     AND workers.last_seen < ($3::timestamp)
                                     AND workers.worker_type = $1 
                                     ORDER BY lock_at ASC 
-                                    LIMIT $2);";
+                                    LIMIT $2);",
+            table = self.config.jobs_table()
+        );
 
-        sqlx::query(query)
+        sqlx::query(&query)
             .bind(job_type)
             .bind(count)
             .bind(dead_since)
@@ -734,22 +1102,28 @@ impl<T, C: Codec> PostgresStorage<T, C> {
     }
 }
 
-impl<J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync> BackendExpose<J>
-    for PostgresStorage<J>
+impl<J, C> BackendExpose<J> for PostgresStorage<J, C>
+where
+    J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync,
+    C: Codec + Send + Sync + 'static,
+    C::Compact: SqlCompact<Postgres>,
 {
     type Request = Request<J, Parts<SqlContext>>;
     type Error = SqlError;
     async fn stats(&self) -> Result<Stat, Self::Error> {
-        let fetch_query = "SELECT
+        let fetch_query = format!(
+            "SELECT
                             COUNT(1) FILTER (WHERE status = 'Pending') AS pending,
                             COUNT(1) FILTER (WHERE status = 'Running') AS running,
                             COUNT(1) FILTER (WHERE status = 'Done') AS done,
                             COUNT(1) FILTER (WHERE status = 'Retry') AS retry,
                             COUNT(1) FILTER (WHERE status = 'Failed') AS failed,
                             COUNT(1) FILTER (WHERE status = 'Killed') AS killed
-                        FROM apalis.jobs WHERE job_type = $1";
+                        FROM {} WHERE job_type = $1",
+            self.config.jobs_table()
+        );
 
-        let res: (i64, i64, i64, i64, i64, i64) = sqlx::query_as(fetch_query)
+        let res: (i64, i64, i64, i64, i64, i64) = sqlx::query_as(&fetch_query)
             .bind(self.config().namespace())
             .fetch_one(self.pool())
             .await?;
@@ -769,21 +1143,22 @@ impl<J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync> BackendExp
         page: i32,
     ) -> Result<Vec<Self::Request>, Self::Error> {
         let status = status.to_string();
-        let fetch_query = "SELECT * FROM apalis.jobs WHERE status = $1 AND job_type = $2 ORDER BY done_at DESC, run_at DESC LIMIT 10 OFFSET $3";
-        let res: Vec<SqlRequest<serde_json::Value>> = sqlx::query_as(fetch_query)
+        let fetch_query = format!(
+            "SELECT * FROM {} WHERE status = $1 AND job_type = $2 ORDER BY done_at DESC, run_at DESC LIMIT 10 OFFSET $3",
+            self.config.jobs_table()
+        );
+        let res: Vec<SqlRequest<C::Compact>> = sqlx::query_as(&fetch_query)
             .bind(status)
             .bind(self.config().namespace())
             .bind(((page - 1) * 10) as i64)
             .fetch_all(self.pool())
             .await?;
-        Ok(res
-            .into_iter()
+        res.into_iter()
             .map(|j| {
-                let (req, ctx) = j.req.take_parts();
-                let req = JsonCodec::<Value>::decode(req).unwrap();
-                Request::new_with_ctx(req, ctx)
+                let (req, ctx) = j.decode::<J, C>()?.take_parts();
+                Ok(Request::new_with_ctx(req, ctx))
             })
-            .collect())
+            .collect()
     }
 
     async fn list_workers(&self) -> Result<Vec<Worker<WorkerState>>, Self::Error> {
@@ -822,7 +1197,7 @@ mod tests {
     /// migrate DB and return a storage instance.
     async fn setup<T: Serialize + DeserializeOwned>() -> PostgresStorage<T> {
         let db_url = &std::env::var("DATABASE_URL").expect("No DATABASE_URL is specified");
-        let pool = PgPool::connect(&db_url).await.unwrap();
+        let pool = PgPool::connect(db_url).await.unwrap();
         // Because connections cannot be shared across async runtime
         // (different runtimes are created for each test),
         // we don't share the storage and tests must be run sequentially.
@@ -845,12 +1220,15 @@ mod tests {
             .acquire()
             .await
             .expect("failed to get connection");
-        sqlx::query("Delete from apalis.jobs where job_type = $1 OR lock_by = $2")
-            .bind(storage.config.namespace())
-            .bind(worker_id.to_string())
-            .execute(&mut *tx)
-            .await
-            .expect("failed to delete jobs");
+        sqlx::query(&format!(
+            "Delete from {} where job_type = $1 OR lock_by = $2",
+            storage.config.jobs_table()
+        ))
+        .bind(storage.config.namespace())
+        .bind(worker_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .expect("failed to delete jobs");
         sqlx::query("Delete from apalis.workers where id = $1")
             .bind(worker_id.to_string())
             .execute(&mut *tx)
@@ -921,7 +1299,7 @@ mod tests {
 
         let worker = register_worker(&mut storage).await;
 
-        let job = consume_one(&mut storage, &worker.id()).await;
+        let job = consume_one(&mut storage, worker.id()).await;
         let job_id = &job.parts.task_id;
 
         // Refresh our job
@@ -940,11 +1318,11 @@ mod tests {
 
         let worker = register_worker(&mut storage).await;
 
-        let job = consume_one(&mut storage, &worker.id()).await;
+        let job = consume_one(&mut storage, worker.id()).await;
         let job_id = &job.parts.task_id;
 
         storage
-            .kill(&worker.id(), job_id)
+            .kill(worker.id(), job_id)
             .await
             .expect("failed to kill job");
 
@@ -964,7 +1342,7 @@ mod tests {
 
         let worker = register_worker_at(&mut storage, six_minutes_ago.timestamp()).await;
 
-        let job = consume_one(&mut storage, &worker.id()).await;
+        let job = consume_one(&mut storage, worker.id()).await;
         storage
             .reenqueue_orphaned(1, five_minutes_ago)
             .await
@@ -992,7 +1370,7 @@ mod tests {
 
         let worker = register_worker_at(&mut storage, four_minutes_ago.timestamp()).await;
 
-        let job = consume_one(&mut storage, &worker.id()).await;
+        let job = consume_one(&mut storage, worker.id()).await;
         let ctx = &job.parts.context;
 
         assert_eq!(*ctx.status(), State::Running);
@@ -1046,6 +1424,125 @@ mod tests {
         assert_eq!(jobs.len(), 1, "Expected one job to be listed");
     }
 
+    #[tokio::test]
+    async fn test_custom_table() {
+        assert!(Config::default()
+            .set_table_name("jobs; DROP TABLE apalis.jobs")
+            .is_err());
+        assert!(Config::default().set_schema_name("Apalis").is_err());
+        assert!(Config::default().set_table_name("order").is_err());
+        assert!(Config::default().set_schema_name("user").is_err());
+
+        let db_url = &std::env::var("DATABASE_URL").expect("No DATABASE_URL is specified");
+        let pool = PgPool::connect(db_url).await.unwrap();
+        let config = Config::new("apalis-tests-custom-table")
+            .set_schema_name("apalis_custom")
+            .unwrap()
+            .set_table_name("custom_jobs")
+            .unwrap();
+        PostgresStorage::setup_with_config(&pool, &config)
+            .await
+            .unwrap();
+        let mut storage: PostgresStorage<Email> = PostgresStorage::new_with_config(pool, config);
+        cleanup(&mut storage, &WorkerId::new("test-worker")).await;
+
+        let task_id = push_email(&mut storage, example_email()).await;
+        let job = get_job(&mut storage, &task_id).await;
+        assert_eq!(*job.parts.context.status(), State::Pending);
+
+        let worker = register_worker(&mut storage).await;
+        let job = consume_one(&mut storage, worker.id()).await;
+        assert_eq!(job.parts.task_id, task_id);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM apalis.jobs WHERE id = $1")
+            .bind(task_id.to_string())
+            .fetch_one(storage.pool())
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_single_scheduler_leader() {
+        let storage = setup::<Email>().await;
+        let config = storage.config.clone().set_scheduler_leader(true);
+        let mut first = SchedulerLeader::default();
+        let mut second = SchedulerLeader::default();
+        assert!(first.try_lead(&storage.pool, &config).await.unwrap());
+        assert!(first.try_lead(&storage.pool, &config).await.unwrap());
+        assert!(!second.try_lead(&storage.pool, &config).await.unwrap());
+
+        // A follower retries the lock on the connection it already holds
+        async fn backend_pid(leader: &mut SchedulerLeader) -> i32 {
+            sqlx::query_scalar("SELECT pg_backend_pid()")
+                .fetch_one(
+                    leader
+                        .conn
+                        .as_mut()
+                        .expect("the follower keeps its connection"),
+                )
+                .await
+                .unwrap()
+        }
+        let pid = backend_pid(&mut second).await;
+        assert!(!second.try_lead(&storage.pool, &config).await.unwrap());
+        assert_eq!(backend_pid(&mut second).await, pid);
+
+        drop(first);
+        // The lock is released once the server notices the closed connection
+        apalis_core::sleep(Duration::from_millis(500)).await;
+        assert!(second.try_lead(&storage.pool, &config).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_push_notifies_namespace() {
+        assert!(Config::default()
+            .set_notify_channel("apalis'); DROP TABLE apalis.jobs; --")
+            .is_err());
+
+        let db_url = &std::env::var("DATABASE_URL").expect("No DATABASE_URL is specified");
+        let pool = PgPool::connect(db_url).await.unwrap();
+        let config = Config::new("apalis-tests-notify")
+            .set_schema_name("apalis_notify")
+            .unwrap()
+            .set_table_name("notify_jobs")
+            .unwrap()
+            .set_notify_channel("apalis::notify_tests")
+            .unwrap();
+        PostgresStorage::setup_with_config(&pool, &config)
+            .await
+            .unwrap();
+        let mut storage: PostgresStorage<Email> =
+            PostgresStorage::new_with_config(pool.clone(), config.clone());
+        cleanup(&mut storage, &WorkerId::new("test-worker")).await;
+
+        let mut notifications = storage.shared.notify.clone();
+        let listen = tokio::spawn(PgListen::drive(storage.shared.clone(), pool, config));
+        // Give the listener time to connect before pushing
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        push_email(&mut storage, example_email()).await;
+        tokio::time::timeout(Duration::from_secs(5), notifications.next())
+            .await
+            .expect("no notification for the pushed job");
+        listen.abort();
+    }
+
+    #[tokio::test]
+    async fn test_push_batch() {
+        // buffer_size is 1 so every job is inserted in its own statement
+        let mut storage = setup().await;
+        let task_ids = storage
+            .push_batch(vec![example_email(), example_email(), example_email()])
+            .await
+            .expect("failed to push jobs");
+        assert_eq!(task_ids.len(), 3);
+
+        for task_id in &task_ids {
+            let job = get_job(&mut storage, task_id).await;
+            assert_eq!(*job.parts.context.status(), State::Pending);
+        }
+    }
+
     // This test pushes a request using one job_type, then uses a worker with a different job_type
     // to fetch jobs and asserts that it returns nothing.
     #[tokio::test]
@@ -1,5 +1,5 @@
-use crate::context::SqlContext;
-use crate::{calculate_status, Config, SqlError};
+use crate::context::{SqlContext, Status};
+use crate::{calculate_status, Config, QueueStats, SqlError};
 use apalis_core::backend::{BackendExpose, Stat, WorkerState};
 use apalis_core::codec::json::JsonCodec;
 use apalis_core::error::Error;
@@ -17,17 +17,18 @@ use apalis_core::{backend::Backend, codec::Codec};
 use async_stream::try_stream;
 use chrono::{DateTime, Utc};
 use futures::{FutureExt, Stream, StreamExt, TryFutureExt, TryStreamExt};
-use log::error;
 use serde::{de::DeserializeOwned, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous};
 use sqlx::{Pool, Row, Sqlite};
 use std::any::type_name;
 use std::convert::TryInto;
 use std::fmt::Debug;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::{fmt, io};
 use std::{marker::PhantomData, time::Duration};
 
-use crate::from_row::SqlRequest;
+use crate::from_row::{SqlCompact, SqlRequest};
 
 pub use sqlx::sqlite::SqlitePool;
 
@@ -83,6 +84,52 @@ impl SqliteStorage<()> {
         Ok(())
     }
 
+    /// Perform migrations after tuning the database for concurrent workers
+    ///
+    /// Runs `PRAGMA journal_mode = WAL`, `PRAGMA synchronous = NORMAL` and
+    /// `PRAGMA busy_timeout` with [Config::busy_timeout]. WAL lets readers and a writer work at the
+    /// same time and the busy timeout makes a connection wait for a lock instead of failing with
+    /// `database is locked`, which reduces lock contention between workers.
+    ///
+    /// `busy_timeout` only applies to the connection it runs on, create the pools of workers with
+    /// [SqliteStorage::connect_options] so that every connection waits.
+    #[cfg(feature = "migrate")]
+    pub async fn setup_with_pragmas(
+        pool: &Pool<Sqlite>,
+        config: &Config,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "PRAGMA busy_timeout = {};",
+            config.busy_timeout().as_millis()
+        ))
+        .execute(pool)
+        .await?;
+        Self::setup(pool).await
+    }
+
+    /// Build the options of a pool for `url` whose every connection waits [Config::busy_timeout]
+    /// for a lock, in WAL mode with `synchronous = NORMAL`
+    ///
+    /// ```rust,no_run
+    /// # use apalis_sql::{sqlite::{SqlitePool, SqliteStorage}, Config};
+    /// # async fn example() -> Result<(), sqlx::Error> {
+    /// let config = Config::new("emails");
+    /// let options = SqliteStorage::connect_options("sqlite://jobs.db?mode=rwc", &config)?;
+    /// let pool = SqlitePool::connect_with(options).await?;
+    /// let storage = SqliteStorage::<String>::new_with_config(pool, config);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connect_options(
+        url: &str,
+        config: &Config,
+    ) -> Result<SqliteConnectOptions, sqlx::Error> {
+        Ok(SqliteConnectOptions::from_str(url)?
+            .busy_timeout(*config.busy_timeout())
+            .journal_mode(SqliteJournalMode::Wal)
+            .synchronous(SqliteSynchronous::Normal))
+    }
+
     /// Get sqlite migrations without running them
     #[cfg(feature = "migrate")]
     pub fn migrations() -> sqlx::migrate::Migrator {
@@ -104,7 +151,15 @@ impl<T> SqliteStorage<T> {
 
     /// Create a new instance with a custom config
     pub fn new_with_config(pool: SqlitePool, config: Config) -> Self {
-        Self {
+        Self::new_with_codec(pool, config)
+    }
+
+    /// Create a new instance with a custom config that encodes jobs with the codec `K`
+    ///
+    /// The `job` column accepts both text and blobs, so binary codecs with a `Vec<u8>` compact
+    /// such as `MessagePackCodec` can share the default `Jobs` table.
+    pub fn new_with_codec<K>(pool: SqlitePool, config: Config) -> SqliteStorage<T, K> {
+        SqliteStorage {
             pool,
             job_type: PhantomData,
             controller: Controller::new(),
@@ -119,6 +174,23 @@ impl<T, C> SqliteStorage<T, C> {
         &mut self,
         worker: &Worker<Context>,
         last_seen: i64,
+    ) -> Result<(), sqlx::Error> {
+        self.register_worker(worker.id(), worker.get_service(), last_seen)
+            .await
+    }
+
+    /// Record that the worker is alive, as the poller does every `keep_alive` interval
+    pub async fn keep_alive(&mut self, worker_id: &WorkerId) -> Result<(), sqlx::Error> {
+        let layers = std::any::type_name::<AckLayer<Self, T, SqlContext, C>>();
+        self.register_worker(worker_id, layers, Utc::now().timestamp())
+            .await
+    }
+
+    async fn register_worker(
+        &mut self,
+        worker_id: &WorkerId,
+        layers: &str,
+        last_seen: i64,
     ) -> Result<(), sqlx::Error> {
         let worker_type = self.config.namespace.clone();
         let storage_name = std::any::type_name::<Self>();
@@ -127,16 +199,31 @@ impl<T, C> SqliteStorage<T, C> {
                 ON CONFLICT (id) DO
                    UPDATE SET last_seen = EXCLUDED.last_seen";
         sqlx::query(query)
-            .bind(worker.id().to_string())
+            .bind(worker_id.to_string())
             .bind(worker_type)
             .bind(storage_name)
-            .bind(worker.get_service())
+            .bind(layers)
             .bind(last_seen)
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
+    /// Count the jobs of the namespace that are due and waiting to be fetched
+    ///
+    /// Jobs are fetched by their `run_at`, so scheduled jobs need no moving once due and workers
+    /// pick them up on their next `poll_interval`.
+    pub async fn count_due(&mut self) -> Result<usize, sqlx::Error> {
+        let query = "SELECT COUNT(*) FROM Jobs
+            WHERE (status = 'Pending' OR (status = 'Failed' AND attempts < max_attempts)) AND run_at < ?1 AND job_type = ?2";
+        let due: i64 = sqlx::query_scalar(query)
+            .bind(Utc::now().timestamp())
+            .bind(&self.config.namespace)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(due.try_into().unwrap_or_default())
+    }
+
     /// Expose the pool for other functionality, eg custom migrations
     pub fn pool(&self) -> &Pool<Sqlite> {
         &self.pool
@@ -155,15 +242,15 @@ impl<T, C> SqliteStorage<T, C> {
     }
 }
 
-async fn fetch_next(
+async fn fetch_next<Compact: SqlCompact<Sqlite>>(
     pool: &Pool<Sqlite>,
     worker_id: &WorkerId,
     id: String,
     config: &Config,
-) -> Result<Option<SqlRequest<String>>, sqlx::Error> {
+) -> Result<Option<SqlRequest<Compact>>, sqlx::Error> {
     let now: i64 = Utc::now().timestamp();
     let update_query = "UPDATE Jobs SET status = 'Running', lock_by = ?2, lock_at = ?3 WHERE id = ?1 AND job_type = ?4 AND status = 'Pending' AND lock_by IS NULL RETURNING *";
-    let job: Option<SqlRequest<String>> = sqlx::query_as(update_query)
+    let job: Option<SqlRequest<Compact>> = sqlx::query_as(update_query)
         .bind(id.to_string())
         .bind(worker_id.to_string())
         .bind(now)
@@ -173,7 +260,7 @@ async fn fetch_next(
 
     if job.is_none() {
         // Retry path.
-        let job: Option<SqlRequest<String>> =
+        let job: Option<SqlRequest<Compact>> =
             sqlx::query_as("SELECT * FROM Jobs WHERE id = ?1 AND lock_by = ?2 AND job_type = ?3")
                 .bind(id.to_string())
                 .bind(worker_id.to_string())
@@ -190,7 +277,8 @@ async fn fetch_next(
 impl<T, C> SqliteStorage<T, C>
 where
     T: DeserializeOwned + Send + Unpin,
-    C: Codec<Compact = String>,
+    C: Codec,
+    C::Compact: SqlCompact<Sqlite>,
 {
     fn stream_jobs(
         &self,
@@ -224,14 +312,11 @@ where
                     yield None::<Request<T, SqlContext>>;
                 } else {
                     for id in ids {
-                        let res = fetch_next(&pool, worker_id, id.0, &config).await?;
+                        let res = fetch_next::<C::Compact>(&pool, worker_id, id.0, &config).await?;
                         yield match res {
                             None => None::<Request<T, SqlContext>>,
                             Some(job) => {
-                                let (req, parts) = job.req.take_parts();
-                                let args = C::decode(req)
-                                    .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
-                                let mut req = Request::new_with_parts(args, parts);
+                                let mut req = job.decode::<T, C>()?;
                                 req.parts.namespace = Some(namespace.clone());
                                 Some(req)
                             }
@@ -246,7 +331,8 @@ where
 impl<T, C> Storage for SqliteStorage<T, C>
 where
     T: Serialize + DeserializeOwned + Send + 'static + Unpin + Sync,
-    C: Codec<Compact = String> + Send + 'static + Sync,
+    C: Codec + Send + 'static + Sync,
+    C::Compact: SqlCompact<Sqlite>,
     C::Error: std::error::Error + Send + Sync + 'static,
 {
     type Job = T;
@@ -255,7 +341,7 @@ where
 
     type Context = SqlContext;
 
-    type Compact = String;
+    type Compact = C::Compact;
 
     async fn push_request(
         &mut self,
@@ -282,9 +368,7 @@ where
         job: Request<Self::Compact, SqlContext>,
     ) -> Result<Parts<SqlContext>, Self::Error> {
         let query = "INSERT INTO Jobs VALUES (?1, ?2, ?3, 'Pending', 0, ?4, strftime('%s','now'), NULL, NULL, NULL, NULL, ?5)";
-        let (task, parts) = job.take_parts();
-        let raw = C::encode(&task)
-            .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+        let (raw, parts) = job.take_parts();
         let job_type = self.config.namespace.clone();
         sqlx::query(query)
             .bind(raw)
@@ -325,19 +409,18 @@ where
         job_id: &TaskId,
     ) -> Result<Option<Request<Self::Job, SqlContext>>, Self::Error> {
         let fetch_query = "SELECT * FROM Jobs WHERE id = ?1";
-        let res: Option<SqlRequest<String>> = sqlx::query_as(fetch_query)
+        let res: Option<SqlRequest<C::Compact>> = sqlx::query_as(fetch_query)
             .bind(job_id.to_string())
             .fetch_optional(&self.pool)
             .await?;
         match res {
             None => Ok(None),
             Some(job) => Ok(Some({
-                let (req, parts) = job.req.take_parts();
-                let args = C::decode(req)
-                    .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
-
-                let mut req: Request<T, SqlContext> = Request::new_with_parts(args, parts);
+                let mut req = job.decode::<T, C>()?;
                 req.parts.namespace = Some(Namespace(self.config.namespace.clone()));
+                req.parts
+                    .data
+                    .insert(Status(req.parts.context.status().clone()));
                 req
             })),
         }
@@ -362,7 +445,7 @@ where
             .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))?;
 
         let query =
-                "UPDATE Jobs SET status = 'Failed', done_at = NULL, lock_by = NULL, lock_at = NULL, run_at = ?2 WHERE id = ?1";
+                "UPDATE Jobs SET status = 'Pending', done_at = NULL, lock_by = NULL, lock_at = NULL, run_at = ?2 WHERE id = ?1";
         let now: i64 = Utc::now().timestamp();
         let wait_until = now + wait;
 
@@ -410,9 +493,7 @@ where
     }
 
     async fn vacuum(&mut self) -> Result<usize, sqlx::Error> {
-        let query = "Delete from Jobs where status='Done'";
-        let record = sqlx::query(query).execute(&self.pool).await?;
-        Ok(record.rows_affected().try_into().unwrap_or_default())
+        SqliteStorage::vacuum_done(self).await
     }
 }
 
@@ -446,6 +527,46 @@ impl<T, C> SqliteStorage<T, C> {
         Ok(())
     }
 
+    /// Cancel a job that is still pending, including scheduled jobs, by deleting it.
+    ///
+    /// Returns whether the job was deleted. Jobs that are running or finished are not affected.
+    pub async fn cancel(&mut self, job_id: &TaskId) -> Result<bool, sqlx::Error> {
+        let query = "DELETE FROM Jobs WHERE id = ?1 AND status = 'Pending'";
+        let result = sqlx::query(query)
+            .bind(job_id.to_string())
+            .execute(self.pool())
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Delete the jobs of this namespace that have been `Done` or `Killed` for longer than
+    /// [Config::set_done_retention], returning the number of deleted jobs
+    pub async fn vacuum_done(&self) -> Result<usize, sqlx::Error> {
+        let retention: i64 = self
+            .config
+            .done_retention()
+            .as_secs()
+            .try_into()
+            .map_err(|e| sqlx::Error::Io(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let query = "DELETE FROM Jobs WHERE job_type = ?1 AND status IN ('Done', 'Killed') AND done_at <= ?2";
+        let record = sqlx::query(query)
+            .bind(self.config.namespace())
+            .bind(Utc::now().timestamp() - retention)
+            .execute(self.pool())
+            .await?;
+        Ok(record.rows_affected().try_into().unwrap_or_default())
+    }
+
+    /// Count the jobs of this namespace in each status with a single `GROUP BY` query
+    pub async fn queue_stats(&self) -> Result<QueueStats, sqlx::Error> {
+        let query = "SELECT status, COUNT(*) FROM Jobs WHERE job_type = ?1 GROUP BY status";
+        let counts: Vec<(String, i64)> = sqlx::query_as(query)
+            .bind(self.config.namespace())
+            .fetch_all(self.pool())
+            .await?;
+        Ok(QueueStats::from_counts(counts))
+    }
+
     /// Add jobs that workers have disappeared to the queue
     pub async fn reenqueue_orphaned(
         &self,
@@ -480,18 +601,23 @@ pub enum SqlitePollError {
     /// Error during re-enqueuing orphaned tasks.
     #[error("Encountered an error during ReenqueueOrphaned heartbeat: `{0}`")]
     ReenqueueOrphanedError(sqlx::Error),
+
+    /// Error while deleting finished jobs past the retention.
+    #[error("Encountered an error during Vacuum heartbeat: `{0}`")]
+    VacuumError(sqlx::Error),
 }
 
 impl<T, C> Backend<Request<T, SqlContext>> for SqliteStorage<T, C>
 where
-    C: Codec<Compact = String> + Send + 'static + Sync,
+    C: Codec + Send + 'static + Sync,
+    C::Compact: SqlCompact<Sqlite>,
     C::Error: std::error::Error + 'static + Send + Sync,
     T: Serialize + DeserializeOwned + Sync + Send + Unpin + 'static,
 {
     type Stream = BackendStream<RequestStream<Request<T, SqlContext>>>;
     type Layer = AckLayer<SqliteStorage<T, C>, T, SqlContext, C>;
 
-    type Codec = JsonCodec<String>;
+    type Codec = C;
 
     fn poll(mut self, worker: &Worker<Context>) -> Poller<Self::Stream, Self::Layer> {
         let layer = AckLayer::new(self.clone());
@@ -514,11 +640,15 @@ where
                 )));
             }
             loop {
-                let now: i64 = Utc::now().timestamp();
-                if let Err(e) = self.keep_alive_at(&w, now).await {
+                if let Err(e) = self.keep_alive_at(&w, Utc::now().timestamp()).await {
                     w.emit(Event::Error(Box::new(SqlitePollError::KeepAliveError(e))));
                 }
-                apalis_core::sleep(Duration::from_secs(30)).await;
+                if !config.done_retention.is_zero() {
+                    if let Err(e) = self.vacuum_done().await {
+                        w.emit(Event::Error(Box::new(SqlitePollError::VacuumError(e))));
+                    }
+                }
+                apalis_core::sleep(config.keep_alive).await;
             }
         }
         .boxed();
@@ -580,8 +710,11 @@ impl<T: Sync + Send, C: Send, Res: Serialize + Sync> Ack<T, Res, C> for SqliteSt
     }
 }
 
-impl<J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync> BackendExpose<J>
-    for SqliteStorage<J, JsonCodec<String>>
+impl<J, C> BackendExpose<J> for SqliteStorage<J, C>
+where
+    J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync,
+    C: Codec + Send + Sync + 'static,
+    C::Compact: SqlCompact<Sqlite>,
 {
     type Request = Request<J, Parts<SqlContext>>;
     type Error = SqlError;
@@ -615,20 +748,18 @@ impl<J: 'static + Serialize + DeserializeOwned + Unpin + Send + Sync> BackendExp
     ) -> Result<Vec<Self::Request>, Self::Error> {
         let status = status.to_string();
         let fetch_query = "SELECT * FROM Jobs WHERE status = ? AND job_type = ? ORDER BY done_at DESC, run_at DESC LIMIT 10 OFFSET ?";
-        let res: Vec<SqlRequest<String>> = sqlx::query_as(fetch_query)
+        let res: Vec<SqlRequest<C::Compact>> = sqlx::query_as(fetch_query)
             .bind(status)
             .bind(self.get_config().namespace())
             .bind(((page - 1) * 10).to_string())
             .fetch_all(self.pool())
             .await?;
-        Ok(res
-            .into_iter()
+        res.into_iter()
             .map(|j| {
-                let (req, ctx) = j.req.take_parts();
-                let req = JsonCodec::<String>::decode(req).unwrap();
-                Request::new_with_ctx(req, ctx)
+                let (req, ctx) = j.decode::<J, C>()?.take_parts();
+                Ok(Request::new_with_ctx(req, ctx))
             })
-            .collect())
+            .collect()
     }
 
     async fn list_workers(&self) -> Result<Vec<Worker<WorkerState>>, Self::Error> {
@@ -675,9 +806,8 @@ mod tests {
             .await
             .expect("failed to migrate DB");
         let config = Config::new("apalis::test");
-        let storage = SqliteStorage::<T>::new_with_config(pool, config);
 
-        storage
+        SqliteStorage::<T>::new_with_config(pool, config)
     }
 
     #[tokio::test]
@@ -744,6 +874,210 @@ mod tests {
             .expect("no job found by id")
     }
 
+    #[tokio::test]
+    async fn test_concurrent_fetch_with_pragmas() {
+        let path = std::env::temp_dir().join(format!("apalis-{}.db", TaskId::new()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        let config = Config::new("apalis::test::pragmas").set_busy_timeout(Duration::from_secs(10));
+        let connect = |url: String, config: Config| async move {
+            let options = SqliteStorage::connect_options(&url, &config).unwrap();
+            let pool = SqlitePool::connect_with(options).await.unwrap();
+            let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(busy_timeout, 10_000);
+            pool
+        };
+        let pool = connect(url.clone(), config.clone()).await;
+        SqliteStorage::setup_with_pragmas(&pool, &config)
+            .await
+            .expect("failed to migrate DB");
+        let mut storage = SqliteStorage::<Email>::new_with_config(pool, config.clone());
+        for _ in 0..20 {
+            push_email(&mut storage, example_good_email()).await;
+        }
+
+        // Jobs become available once `run_at` is in the past, so keep polling until both workers
+        // have fetched all of them between them
+        let fetched = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let consume = |name: &'static str| {
+            let url = url.clone();
+            let config = config.clone();
+            let fetched = fetched.clone();
+            async move {
+                let pool = connect(url, config.clone()).await;
+                let mut storage = SqliteStorage::<Email>::new_with_config(pool, config);
+                let worker = Worker::new(WorkerId::new(name), Context::default());
+                storage
+                    .keep_alive_at(&worker, Utc::now().timestamp())
+                    .await
+                    .expect("failed to register worker");
+                worker.start();
+                let mut stream = storage
+                    .stream_jobs(&worker, Duration::from_millis(1), 5)
+                    .boxed();
+                while fetched.load(std::sync::atomic::Ordering::SeqCst) < 20 {
+                    let job = stream
+                        .next()
+                        .await
+                        .expect("stream is empty")
+                        .expect("failed to poll job");
+                    if job.is_some() {
+                        fetched.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    }
+                }
+            }
+        };
+        let (first, second) = tokio::time::timeout(Duration::from_secs(10), async {
+            tokio::join!(
+                tokio::spawn(consume("worker-1")),
+                tokio::spawn(consume("worker-2"))
+            )
+        })
+        .await
+        .expect("workers did not fetch every job");
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(fetched.load(std::sync::atomic::Ordering::SeqCst), 20);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_codec() {
+        use apalis_core::codec::msgpack::MessagePackCodec;
+
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        SqliteStorage::setup(&pool)
+            .await
+            .expect("failed to migrate DB");
+        let mut storage: SqliteStorage<Email, MessagePackCodec> =
+            SqliteStorage::new_with_codec(pool, Config::new("apalis::test::msgpack"));
+
+        let email = example_good_email();
+        let parts = storage.push(email.clone()).await.expect("failed to push");
+        let (payload_type,): (String,) =
+            sqlx::query_as("SELECT typeof(job) FROM Jobs WHERE id = ?1")
+                .bind(parts.task_id.to_string())
+                .fetch_one(storage.pool())
+                .await
+                .unwrap();
+        assert_eq!(payload_type, "blob");
+
+        let job = storage
+            .fetch_by_id(&parts.task_id)
+            .await
+            .expect("failed to fetch job by id")
+            .expect("no job found by id");
+        assert_eq!(job.args.to, email.to);
+
+        let listed = storage
+            .list_jobs(&State::Pending, 1)
+            .await
+            .expect("failed to list jobs");
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].args.subject, email.subject);
+
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        storage
+            .keep_alive_at(&worker, Utc::now().timestamp())
+            .await
+            .expect("failed to register worker");
+        worker.start();
+        // Jobs become available once `run_at` is in the past
+        let mut stream = storage
+            .stream_jobs(&worker, Duration::from_millis(100), 1)
+            .boxed();
+        let job = loop {
+            let job = stream
+                .next()
+                .await
+                .expect("stream is empty")
+                .expect("failed to poll job");
+            if let Some(job) = job {
+                break job;
+            }
+        };
+        assert_eq!(job.parts.task_id, parts.task_id);
+        assert_eq!(job.args.text, email.text);
+    }
+
+    #[tokio::test]
+    async fn test_count_due_counts_due_jobs() {
+        let mut storage = setup::<Email>().await;
+        let now = Utc::now().timestamp();
+        storage
+            .schedule(example_good_email(), now - 60)
+            .await
+            .expect("failed to schedule a job");
+        storage
+            .schedule(example_good_email(), now + 300)
+            .await
+            .expect("failed to schedule a job");
+
+        let due = storage.count_due().await.expect("failed to count due jobs");
+        assert_eq!(due, 1);
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_registers_worker() {
+        let mut storage = setup::<Email>().await;
+        let worker = Worker::new(WorkerId::new("test-worker"), Context::default());
+        storage
+            .keep_alive(worker.id())
+            .await
+            .expect("failed to register worker");
+
+        let (last_seen,): (i64,) = sqlx::query_as("SELECT last_seen FROM Workers WHERE id = ?1")
+            .bind(worker.id().to_string())
+            .fetch_one(storage.pool())
+            .await
+            .unwrap();
+        assert!(Utc::now().timestamp() - last_seen < 5);
+    }
+
+    #[tokio::test]
+    async fn test_vacuum_keeps_jobs_within_retention() {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        SqliteStorage::setup(&pool)
+            .await
+            .expect("failed to migrate DB");
+        let config = Config::new("apalis::test").set_done_retention(Duration::from_secs(3600));
+        let mut storage = SqliteStorage::<Email>::new_with_config(pool, config);
+
+        let worker = register_worker(&mut storage).await;
+        push_email(&mut storage, example_good_email()).await;
+        push_email(&mut storage, example_good_email()).await;
+        let old = consume_one(&mut storage, &worker).await;
+        let recent = consume_one(&mut storage, &worker).await;
+        storage
+            .kill(worker.id(), &old.parts.task_id)
+            .await
+            .expect("failed to kill job");
+        storage
+            .kill(worker.id(), &recent.parts.task_id)
+            .await
+            .expect("failed to kill job");
+        sqlx::query("UPDATE Jobs SET done_at = ?1 WHERE id = ?2")
+            .bind(Utc::now().timestamp() - 7200)
+            .bind(old.parts.task_id.to_string())
+            .execute(storage.pool())
+            .await
+            .unwrap();
+
+        assert_eq!(storage.queue_stats().await.unwrap().killed, 2);
+        assert_eq!(storage.vacuum_done().await.expect("failed to vacuum"), 1);
+        assert!(storage
+            .fetch_by_id(&old.parts.task_id)
+            .await
+            .unwrap()
+            .is_none());
+        let stats = storage.queue_stats().await.unwrap();
+        assert_eq!(stats.killed, 1);
+        assert_eq!(stats.pending, 0);
+    }
+
     #[tokio::test]
     async fn test_consume_last_pushed_job() {
         let mut storage = setup().await;
@@ -796,7 +1130,7 @@ mod tests {
         let job_id = &job.parts.task_id;
 
         storage
-            .kill(&worker.id(), job_id)
+            .kill(worker.id(), job_id)
             .await
             .expect("failed to kill job");
 
@@ -845,7 +1179,7 @@ mod tests {
             .unwrap();
         //end simulate worker
 
-        let job = get_job(&mut storage, &job_id).await;
+        let job = get_job(&mut storage, job_id).await;
         let ctx = &job.parts.context;
         assert_eq!(*ctx.status(), State::Done);
         assert_eq!(*ctx.lock_by(), Some(worker.id().clone()));
@@ -186,6 +186,7 @@ mod tests {
                 Poll::Ready(Ok(()))
             }
 
+            #[allow(clippy::unnecessary_literal_unwrap)]
             fn call(&mut self, _req: Request<TestJob, ()>) -> Self::Future {
                 Box::pin(async { None.unwrap() })
             }
@@ -48,24 +48,24 @@ where
                 // so don't retry...
                 None
             }
-            Err(Err::Abort(_)) => return None,
+            Err(Err::Abort(_)) => None,
             Err(err) => {
                 if self.retries == 0 {
                     *err = Err::Abort(Arc::new(Box::new(RetryPolicyError::ZeroRetries(
                         err.clone(),
                     ))));
-                    return None;
+                    None
                 } else if self.retries >= attempt {
                     let counter = req.parts.attempt.clone();
-                    return Some(Box::pin(self.backoff.next_backoff().map(move |_| {
+                    Some(Box::pin(self.backoff.next_backoff().map(move |_| {
                         counter.increment();
-                    })));
+                    })))
                 } else {
                     *err = Err::Abort(Arc::new(Box::new(RetryPolicyError::OutOfRetries {
                         current_attempt: attempt,
                         inner: err.clone(),
                     })));
-                    return None;
+                    None
                 }
             }
         }
@@ -123,22 +123,22 @@ where
                 // so don't retry...
                 None
             }
-            Err(Err::Abort(_)) => return None,
+            Err(Err::Abort(_)) => None,
             Err(err) => {
                 if self.retries == 0 {
                     *err = Err::Abort(Arc::new(Box::new(RetryPolicyError::ZeroRetries(
                         err.clone(),
                     ))));
-                    return None;
+                    None
                 } else if self.retries >= attempt {
                     req.parts.attempt.increment();
-                    return Some(std::future::ready(()));
+                    Some(std::future::ready(()))
                 } else {
                     *err = Err::Abort(Arc::new(Box::new(RetryPolicyError::OutOfRetries {
                         current_attempt: attempt,
                         inner: err.clone(),
                     })));
-                    return None;
+                    None
                 }
             }
         }
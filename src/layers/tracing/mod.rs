@@ -101,7 +101,6 @@ impl<MakeSpan, OnRequest, OnResponse, OnFailure>
     /// Customize what to do when a response has been produced.
     ///
     /// `NewOnResponse` is expected to implement [`OnResponse`].
-
     pub fn on_response<NewOnResponse>(
         self,
         new_on_response: NewOnResponse,